@@ -0,0 +1,4 @@
+-- Capture the modules attached to a page alongside each revision
+-- so revisions can be diffed and merged module by module.
+-- JSON array of modules rows (TEXT, like full_snapshot). NULL for legacy revisions.
+ALTER TABLE page_revisions ADD COLUMN IF NOT EXISTS modules_snapshot TEXT;
@@ -0,0 +1,4 @@
+-- Per-row version for optimistic concurrency control on modules.
+-- Bumped on every update; exposed to clients as the module ETag.
+-- (Pages use the existing current_revision counter.)
+ALTER TABLE modules ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1 @@
+ALTER TABLE page_revisions DROP COLUMN IF EXISTS categories_snapshot;
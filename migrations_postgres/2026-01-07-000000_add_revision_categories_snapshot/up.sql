@@ -0,0 +1,4 @@
+-- Capture the module categories (array fields) of a page alongside each revision
+-- so rollback can restore modules together with the categories they belong to.
+-- JSON array of module_category rows. NULL for revisions taken before this column existed.
+ALTER TABLE page_revisions ADD COLUMN IF NOT EXISTS categories_snapshot TEXT;
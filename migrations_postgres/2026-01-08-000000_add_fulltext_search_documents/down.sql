@@ -0,0 +1,35 @@
+DROP TRIGGER IF EXISTS trg_search_languages ON languages;
+DROP TRIGGER IF EXISTS trg_search_media ON media;
+DROP TRIGGER IF EXISTS trg_search_module_translations ON module_translations;
+DROP TRIGGER IF EXISTS trg_search_modules ON modules;
+DROP TRIGGER IF EXISTS trg_search_page_translations ON page_translations;
+DROP TRIGGER IF EXISTS trg_search_pages ON pages;
+
+DROP FUNCTION IF EXISTS search_languages_trigger();
+DROP FUNCTION IF EXISTS search_media_trigger();
+DROP FUNCTION IF EXISTS search_module_translations_trigger();
+DROP FUNCTION IF EXISTS search_modules_trigger();
+DROP FUNCTION IF EXISTS search_page_translations_trigger();
+DROP FUNCTION IF EXISTS search_pages_trigger();
+DROP FUNCTION IF EXISTS search_reindex_all();
+DROP FUNCTION IF EXISTS search_index_media(VARCHAR);
+DROP FUNCTION IF EXISTS search_index_page(VARCHAR);
+DROP FUNCTION IF EXISTS search_index_module(VARCHAR);
+DROP FUNCTION IF EXISTS search_upsert_document(VARCHAR, VARCHAR, INTEGER, INTEGER, VARCHAR, TEXT, TEXT, TEXT, VARCHAR, TSVECTOR);
+DROP FUNCTION IF EXISTS search_strip_html(TEXT);
+DROP FUNCTION IF EXISTS search_config_for_language(INTEGER);
+
+DROP TABLE IF EXISTS search_documents;
+
+ALTER TABLE languages DROP COLUMN IF EXISTS search_config;
+
+CREATE INDEX IF NOT EXISTS idx_pages_fulltext ON pages USING GIN (to_tsvector('english',
+    coalesce(page_title,'') || ' ' ||
+    coalesce(page_name,'') || ' ' ||
+    coalesce(meta_title,'') || ' ' ||
+    coalesce(meta_description,'')
+));
+CREATE INDEX IF NOT EXISTS idx_modules_fulltext ON modules USING GIN (to_tsvector('english',
+    coalesce(title,'') || ' ' ||
+    coalesce(content,'')
+));
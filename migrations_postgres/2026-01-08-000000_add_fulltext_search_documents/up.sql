@@ -0,0 +1,284 @@
+-- Full-text search index backed by tsvector
+--
+-- Every searchable resource (pages, modules, media) gets one row in
+-- search_documents per language: the source row is indexed with the default
+-- language (language_id NULL), page/module translations with their own
+-- language. The text search configuration comes from languages.search_config,
+-- so stemming follows the language of the content.
+
+ALTER TABLE languages ADD COLUMN IF NOT EXISTS search_config VARCHAR(64) NOT NULL DEFAULT 'simple';
+
+UPDATE languages SET search_config = CASE split_part(lower(code), '-', 1)
+    WHEN 'ar' THEN 'arabic'
+    WHEN 'da' THEN 'danish'
+    WHEN 'de' THEN 'german'
+    WHEN 'el' THEN 'greek'
+    WHEN 'en' THEN 'english'
+    WHEN 'es' THEN 'spanish'
+    WHEN 'fi' THEN 'finnish'
+    WHEN 'fr' THEN 'french'
+    WHEN 'hu' THEN 'hungarian'
+    WHEN 'id' THEN 'indonesian'
+    WHEN 'it' THEN 'italian'
+    WHEN 'nl' THEN 'dutch'
+    WHEN 'no' THEN 'norwegian'
+    WHEN 'nb' THEN 'norwegian'
+    WHEN 'pt' THEN 'portuguese'
+    WHEN 'ro' THEN 'romanian'
+    WHEN 'ru' THEN 'russian'
+    WHEN 'sv' THEN 'swedish'
+    WHEN 'tr' THEN 'turkish'
+    ELSE 'simple'
+END;
+
+CREATE TABLE search_documents (
+    id BIGSERIAL PRIMARY KEY,
+    resource_type VARCHAR(20) NOT NULL, -- 'pages', 'modules', 'media'
+    resource_id VARCHAR(255) NOT NULL,  -- uuid of the indexed row
+    language_id INTEGER REFERENCES languages(id) ON DELETE CASCADE,
+    language_key INTEGER GENERATED ALWAYS AS (COALESCE(language_id, 0)) STORED,
+    tenant_id INTEGER,
+    page_uuid VARCHAR(255),
+    title TEXT NOT NULL DEFAULT '',
+    body TEXT NOT NULL DEFAULT '',
+    url TEXT,
+    search_config VARCHAR(64) NOT NULL DEFAULT 'simple',
+    document TSVECTOR NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT uk_search_document UNIQUE (resource_type, resource_id, language_key)
+);
+
+CREATE INDEX idx_search_documents_document ON search_documents USING GIN (document);
+CREATE INDEX idx_search_documents_tenant ON search_documents (tenant_id, resource_type);
+
+-- Superseded by search_documents
+DROP INDEX IF EXISTS idx_pages_fulltext;
+DROP INDEX IF EXISTS idx_modules_fulltext;
+DROP INDEX IF EXISTS idx_media_fulltext;
+
+-- Text search configuration for a language (default language when NULL).
+-- Unknown configuration names fall back to 'simple' rather than failing writes.
+CREATE OR REPLACE FUNCTION search_config_for_language(lang_id INTEGER) RETURNS VARCHAR AS $$
+    SELECT COALESCE(
+        (SELECT cfg.cfgname::VARCHAR
+           FROM languages l
+           JOIN pg_ts_config cfg ON cfg.cfgname = l.search_config
+          WHERE (lang_id IS NOT NULL AND l.id = lang_id)
+             OR (lang_id IS NULL AND l.is_default)
+          ORDER BY l.id
+          LIMIT 1),
+        'simple')
+$$ LANGUAGE sql STABLE;
+
+CREATE OR REPLACE FUNCTION search_strip_html(html TEXT) RETURNS TEXT AS $$
+    SELECT regexp_replace(COALESCE(html, ''), '<[^>]*>', ' ', 'g')
+$$ LANGUAGE sql IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION search_upsert_document(
+    r_type VARCHAR, r_id VARCHAR, lang_id INTEGER, r_tenant INTEGER, r_page VARCHAR,
+    r_title TEXT, r_body TEXT, r_url TEXT, cfg VARCHAR, doc TSVECTOR
+) RETURNS void AS $$
+    INSERT INTO search_documents
+        (resource_type, resource_id, language_id, tenant_id, page_uuid, title, body, url, search_config, document, updated_at)
+    VALUES (r_type, r_id, lang_id, r_tenant, r_page, COALESCE(r_title, ''), COALESCE(r_body, ''), r_url, cfg, doc, NOW())
+    ON CONFLICT (resource_type, resource_id, language_key) DO UPDATE SET
+        tenant_id = EXCLUDED.tenant_id,
+        page_uuid = EXCLUDED.page_uuid,
+        title = EXCLUDED.title,
+        body = EXCLUDED.body,
+        url = EXCLUDED.url,
+        search_config = EXCLUDED.search_config,
+        document = EXCLUDED.document,
+        updated_at = NOW()
+$$ LANGUAGE sql;
+
+CREATE OR REPLACE FUNCTION search_index_module(m_uuid VARCHAR) RETURNS void AS $$
+DECLARE
+    m RECORD;
+    t RECORD;
+    cfg VARCHAR;
+BEGIN
+    SELECT mo.uuid, mo.title, mo.content, mo.page_uuid, p.tenant_id, p.page_url
+      INTO m
+      FROM modules mo JOIN pages p ON p.uuid = mo.page_uuid
+     WHERE mo.uuid = m_uuid;
+    IF NOT FOUND THEN
+        DELETE FROM search_documents WHERE resource_type = 'modules' AND resource_id = m_uuid;
+        RETURN;
+    END IF;
+
+    cfg := search_config_for_language(NULL);
+    PERFORM search_upsert_document('modules', m.uuid, NULL, m.tenant_id, m.page_uuid,
+        m.title, search_strip_html(m.content), m.page_url, cfg,
+        setweight(to_tsvector(cfg::regconfig, COALESCE(m.title, '')), 'A') ||
+        setweight(to_tsvector(cfg::regconfig, search_strip_html(m.content)), 'C'));
+
+    FOR t IN SELECT * FROM module_translations WHERE module_id = m_uuid LOOP
+        cfg := search_config_for_language(t.language_id);
+        PERFORM search_upsert_document('modules', m.uuid, t.language_id, m.tenant_id, m.page_uuid,
+            COALESCE(t.title, m.title), search_strip_html(COALESCE(t.content, m.content)), m.page_url, cfg,
+            setweight(to_tsvector(cfg::regconfig, COALESCE(t.title, m.title, '')), 'A') ||
+            setweight(to_tsvector(cfg::regconfig, search_strip_html(COALESCE(t.content, m.content))), 'C'));
+    END LOOP;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Pages and media are read through to_jsonb() so optional columns
+-- (pages.content, media.title/description/tags) simply index as empty when a
+-- database predates them.
+CREATE OR REPLACE FUNCTION search_index_page(p_uuid VARCHAR) RETURNS void AS $$
+DECLARE
+    p JSONB;
+    t RECORD;
+    cfg VARCHAR;
+BEGIN
+    SELECT to_jsonb(pg) INTO p FROM pages pg WHERE pg.uuid = p_uuid;
+    IF p IS NULL THEN
+        DELETE FROM search_documents WHERE resource_type = 'pages' AND resource_id = p_uuid;
+        RETURN;
+    END IF;
+
+    cfg := search_config_for_language(NULL);
+    PERFORM search_upsert_document('pages', p_uuid, NULL, (p->>'tenant_id')::INTEGER, p_uuid,
+        p->>'page_title', search_strip_html(concat_ws(' ', p->>'meta_description', p->>'content')), p->>'page_url', cfg,
+        setweight(to_tsvector(cfg::regconfig, COALESCE(p->>'page_title', '')), 'A') ||
+        setweight(to_tsvector(cfg::regconfig, concat_ws(' ', p->>'page_name', p->>'meta_title', p->>'meta_keywords')), 'B') ||
+        setweight(to_tsvector(cfg::regconfig, COALESCE(p->>'meta_description', '')), 'C') ||
+        setweight(to_tsvector(cfg::regconfig, search_strip_html(p->>'content')), 'D'));
+
+    FOR t IN SELECT * FROM page_translations WHERE page_id = p_uuid LOOP
+        cfg := search_config_for_language(t.language_id);
+        PERFORM search_upsert_document('pages', p_uuid, t.language_id, (p->>'tenant_id')::INTEGER, p_uuid,
+            COALESCE(t.page_title, p->>'page_title'),
+            search_strip_html(concat_ws(' ', t.meta_description, t.page_content)),
+            COALESCE(t.page_url, p->>'page_url'), cfg,
+            setweight(to_tsvector(cfg::regconfig, COALESCE(t.page_title, p->>'page_title', '')), 'A') ||
+            setweight(to_tsvector(cfg::regconfig, COALESCE(t.meta_title, '')), 'B') ||
+            setweight(to_tsvector(cfg::regconfig, COALESCE(t.meta_description, '')), 'C') ||
+            setweight(to_tsvector(cfg::regconfig, search_strip_html(t.page_content)), 'D'));
+    END LOOP;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION search_index_media(m_uuid VARCHAR) RETURNS void AS $$
+DECLARE
+    m JSONB;
+    tags TEXT;
+    cfg VARCHAR;
+BEGIN
+    SELECT to_jsonb(md) INTO m FROM media md WHERE md.uuid = m_uuid;
+    IF m IS NULL THEN
+        DELETE FROM search_documents WHERE resource_type = 'media' AND resource_id = m_uuid;
+        RETURN;
+    END IF;
+
+    IF jsonb_typeof(m->'tags') = 'array' THEN
+        SELECT string_agg(tag, ' ') INTO tags FROM jsonb_array_elements_text(m->'tags') AS tag;
+    END IF;
+
+    cfg := search_config_for_language(NULL);
+    PERFORM search_upsert_document('media', m_uuid, NULL, (m->>'tenant_id')::INTEGER, NULL,
+        COALESCE(m->>'title', m->>'original_filename'),
+        concat_ws(' ', m->>'alt_text', m->>'description', m->>'caption'),
+        COALESCE(m->>'file_path', m->>'storage_path'), cfg,
+        setweight(to_tsvector(cfg::regconfig, concat_ws(' ', m->>'title', m->>'original_filename')), 'A') ||
+        setweight(to_tsvector(cfg::regconfig, concat_ws(' ', m->>'alt_text', tags)), 'B') ||
+        setweight(to_tsvector(cfg::regconfig, concat_ws(' ', m->>'description', m->>'caption')), 'C') ||
+        setweight(to_tsvector(cfg::regconfig, COALESCE(m->>'filename', '')), 'D'));
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION search_reindex_all() RETURNS void AS $$
+BEGIN
+    PERFORM search_index_page(uuid) FROM pages;
+    PERFORM search_index_module(uuid) FROM modules;
+    PERFORM search_index_media(uuid) FROM media;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Triggers keep search_documents in sync with the source tables
+
+CREATE OR REPLACE FUNCTION search_pages_trigger() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        DELETE FROM search_documents WHERE resource_type = 'pages' AND resource_id = OLD.uuid;
+        RETURN OLD;
+    END IF;
+    PERFORM search_index_page(NEW.uuid);
+    -- Module documents carry the page's tenant and URL
+    IF TG_OP = 'UPDATE' AND (NEW.page_url IS DISTINCT FROM OLD.page_url OR NEW.tenant_id IS DISTINCT FROM OLD.tenant_id) THEN
+        PERFORM search_index_module(uuid) FROM modules WHERE page_uuid = NEW.uuid;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION search_page_translations_trigger() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        DELETE FROM search_documents
+         WHERE resource_type = 'pages' AND resource_id = OLD.page_id AND language_id = OLD.language_id;
+        RETURN OLD;
+    END IF;
+    PERFORM search_index_page(NEW.page_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION search_modules_trigger() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        DELETE FROM search_documents WHERE resource_type = 'modules' AND resource_id = OLD.uuid;
+        RETURN OLD;
+    END IF;
+    PERFORM search_index_module(NEW.uuid);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION search_module_translations_trigger() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        DELETE FROM search_documents
+         WHERE resource_type = 'modules' AND resource_id = OLD.module_id AND language_id = OLD.language_id;
+        RETURN OLD;
+    END IF;
+    PERFORM search_index_module(NEW.module_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION search_media_trigger() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        DELETE FROM search_documents WHERE resource_type = 'media' AND resource_id = OLD.uuid;
+        RETURN OLD;
+    END IF;
+    PERFORM search_index_media(NEW.uuid);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION search_languages_trigger() RETURNS trigger AS $$
+BEGIN
+    PERFORM search_reindex_all();
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_search_pages AFTER INSERT OR UPDATE OR DELETE ON pages
+    FOR EACH ROW EXECUTE FUNCTION search_pages_trigger();
+CREATE TRIGGER trg_search_page_translations AFTER INSERT OR UPDATE OR DELETE ON page_translations
+    FOR EACH ROW EXECUTE FUNCTION search_page_translations_trigger();
+CREATE TRIGGER trg_search_modules AFTER INSERT OR UPDATE OR DELETE ON modules
+    FOR EACH ROW EXECUTE FUNCTION search_modules_trigger();
+CREATE TRIGGER trg_search_module_translations AFTER INSERT OR UPDATE OR DELETE ON module_translations
+    FOR EACH ROW EXECUTE FUNCTION search_module_translations_trigger();
+CREATE TRIGGER trg_search_media AFTER INSERT OR UPDATE OR DELETE ON media
+    FOR EACH ROW EXECUTE FUNCTION search_media_trigger();
+-- Changing a language's configuration (or the default language) re-stems everything
+CREATE TRIGGER trg_search_languages AFTER UPDATE OF search_config, is_default ON languages
+    FOR EACH STATEMENT EXECUTE FUNCTION search_languages_trigger();
+
+-- Backfill existing content
+SELECT search_reindex_all();
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS search_clicks;
+DROP INDEX IF EXISTS idx_search_history_tenant;
+ALTER TABLE search_history DROP COLUMN IF EXISTS weights;
+ALTER TABLE search_history DROP COLUMN IF EXISTS results;
+ALTER TABLE search_history DROP COLUMN IF EXISTS tenant_id;
@@ -0,0 +1,21 @@
+-- Hybrid search logging and click feedback
+-- search_history rows now record the tenant, the ranked result list and the
+-- fusion weights in effect, so clicks can be related back to positions.
+
+ALTER TABLE search_history ADD COLUMN IF NOT EXISTS tenant_id INTEGER REFERENCES tenants(id) ON DELETE CASCADE;
+ALTER TABLE search_history ADD COLUMN IF NOT EXISTS results JSONB;
+ALTER TABLE search_history ADD COLUMN IF NOT EXISTS weights JSONB;
+
+CREATE INDEX IF NOT EXISTS idx_search_history_tenant ON search_history(tenant_id, created_at);
+
+CREATE TABLE search_clicks (
+    id BIGSERIAL PRIMARY KEY,
+    search_id BIGINT NOT NULL REFERENCES search_history(id) ON DELETE CASCADE,
+    resource_type VARCHAR(20) NOT NULL,
+    resource_id VARCHAR(255) NOT NULL,
+    position INTEGER, -- 1-based position in the result list, NULL when unknown
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_search_clicks_search ON search_clicks(search_id);
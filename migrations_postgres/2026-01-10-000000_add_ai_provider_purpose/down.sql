@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_ai_provider_purpose;
+ALTER TABLE ai_provider_configs DROP COLUMN IF EXISTS purpose;
+-- `config` is left in place: it belongs to the original table definition
@@ -0,0 +1,8 @@
+-- Let ai_provider_configs rows configure embedding backends as well as completion providers.
+-- `config` is part of the original table definition but missing on some databases.
+ALTER TABLE ai_provider_configs ADD COLUMN IF NOT EXISTS config JSONB NOT NULL DEFAULT '{}';
+
+-- 'completion' or 'embedding'
+ALTER TABLE ai_provider_configs ADD COLUMN IF NOT EXISTS purpose VARCHAR(20) NOT NULL DEFAULT 'completion';
+
+CREATE INDEX IF NOT EXISTS idx_ai_provider_purpose ON ai_provider_configs(purpose, is_active);
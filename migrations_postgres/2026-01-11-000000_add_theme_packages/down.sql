@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_themes_one_active_per_tenant;
+ALTER TABLE themes DROP COLUMN IF EXISTS manifest;
@@ -0,0 +1,16 @@
+-- Theme packages: parsed theme.json and one active theme per tenant
+ALTER TABLE themes ADD COLUMN IF NOT EXISTS manifest JSONB;
+
+-- Keep only the most recently updated active theme per tenant
+UPDATE themes SET is_active = FALSE
+WHERE is_active = TRUE
+  AND tenant_id IS NOT NULL
+  AND id NOT IN (
+    SELECT DISTINCT ON (tenant_id) id
+    FROM themes
+    WHERE is_active = TRUE AND tenant_id IS NOT NULL
+    ORDER BY tenant_id, updated_at DESC NULLS LAST, id DESC
+  );
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_themes_one_active_per_tenant
+    ON themes(tenant_id) WHERE is_active = TRUE AND tenant_id IS NOT NULL;
@@ -0,0 +1,19 @@
+-- Per-tenant redirects, checked before a public page 404s
+CREATE TABLE redirects (
+    id SERIAL PRIMARY KEY,
+    tenant_id INTEGER NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    -- Path ("/old"), path prefix ("/blog/") or regex ("^/p/(\d+)$")
+    source VARCHAR(1000) NOT NULL,
+    -- Path or absolute URL; regex targets may use $1 captures
+    target VARCHAR(1000) NOT NULL,
+    match_type VARCHAR(10) NOT NULL DEFAULT 'exact' CHECK (match_type IN ('exact', 'prefix', 'regex')),
+    status_code INTEGER NOT NULL DEFAULT 301 CHECK (status_code IN (301, 302)),
+    hit_count BIGINT NOT NULL DEFAULT 0,
+    last_hit_at TIMESTAMP,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (tenant_id, match_type, source)
+);
+
+CREATE INDEX idx_redirects_tenant_active ON redirects(tenant_id) WHERE is_active;
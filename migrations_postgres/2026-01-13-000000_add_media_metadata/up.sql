@@ -0,0 +1,2 @@
+-- Per-type metadata extracted on upload (EXIF, PDF page count, duration)
+ALTER TABLE media ADD COLUMN IF NOT EXISTS metadata JSONB;
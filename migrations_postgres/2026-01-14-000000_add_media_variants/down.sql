@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_media_variants_media_name;
+CREATE INDEX IF NOT EXISTS idx_media_id_variant ON media_variants (media_id, variant_name);
+ALTER TABLE media_variants DROP COLUMN IF EXISTS format;
@@ -0,0 +1,16 @@
+-- Responsive derivatives generated for uploaded images
+CREATE TABLE IF NOT EXISTS media_variants (
+    id SERIAL PRIMARY KEY,
+    media_id INTEGER NOT NULL REFERENCES media(id) ON DELETE CASCADE,
+    variant_name VARCHAR(50) NOT NULL,
+    file_path VARCHAR(500) NOT NULL,
+    width INTEGER,
+    height INTEGER,
+    file_size BIGINT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE media_variants ADD COLUMN IF NOT EXISTS format VARCHAR(10) NOT NULL DEFAULT 'jpeg';
+
+DROP INDEX IF EXISTS idx_media_id_variant;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_media_variants_media_name ON media_variants (media_id, variant_name);
@@ -0,0 +1,3 @@
+-- Point to keep in view when cropping, as fractions of width and height (NULL = centre)
+ALTER TABLE media ADD COLUMN IF NOT EXISTS focal_x REAL CHECK (focal_x BETWEEN 0 AND 1);
+ALTER TABLE media ADD COLUMN IF NOT EXISTS focal_y REAL CHECK (focal_y BETWEEN 0 AND 1);
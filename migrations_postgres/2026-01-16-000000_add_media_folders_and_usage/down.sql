@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS media_usages;
+DROP INDEX IF EXISTS idx_media_tags;
+DROP INDEX IF EXISTS idx_media_folder_id;
+ALTER TABLE media DROP COLUMN IF EXISTS folder_id;
+DROP TABLE IF EXISTS media_folders;
@@ -0,0 +1,32 @@
+-- Hierarchical media folders. `path` is the full slash-separated path
+-- ("/products/shoes"), kept in sync on rename and move.
+CREATE TABLE media_folders (
+    id SERIAL PRIMARY KEY,
+    tenant_id INTEGER NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    parent_id INTEGER REFERENCES media_folders(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    path TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (tenant_id, path)
+);
+
+CREATE INDEX idx_media_folders_parent ON media_folders(parent_id);
+
+ALTER TABLE media ADD COLUMN IF NOT EXISTS folder_id INTEGER REFERENCES media_folders(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_media_folder_id ON media(folder_id);
+
+ALTER TABLE media ADD COLUMN IF NOT EXISTS tags TEXT[];
+CREATE INDEX IF NOT EXISTS idx_media_tags ON media USING GIN (tags);
+
+-- Which pages and modules reference each media item, rebuilt whenever one is saved
+CREATE TABLE media_usages (
+    id SERIAL PRIMARY KEY,
+    media_id INTEGER NOT NULL REFERENCES media(id) ON DELETE CASCADE,
+    source_type VARCHAR(20) NOT NULL,
+    source_uuid VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (media_id, source_type, source_uuid)
+);
+
+CREATE INDEX idx_media_usages_source ON media_usages(source_type, source_uuid);
@@ -0,0 +1,19 @@
+-- Runs of the tool that moves media files between local storage and S3.
+-- Media are processed in id order, so `last_media_id` is where a paused or
+-- interrupted run resumes.
+CREATE TABLE storage_migrations (
+    id SERIAL PRIMARY KEY,
+    direction VARCHAR(20) NOT NULL CHECK (direction IN ('local_to_s3', 's3_to_local')),
+    status VARCHAR(20) NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'paused', 'completed', 'failed')),
+    delete_source BOOLEAN NOT NULL DEFAULT FALSE,
+    last_media_id INTEGER NOT NULL DEFAULT 0,
+    total INTEGER NOT NULL DEFAULT 0,
+    migrated INTEGER NOT NULL DEFAULT 0,
+    skipped INTEGER NOT NULL DEFAULT 0,
+    failed INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    started_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP
+);
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS backup_policies;
+DROP INDEX IF EXISTS idx_backups_tenant;
+ALTER TABLE backups DROP COLUMN IF EXISTS checksum;
+ALTER TABLE backups DROP COLUMN IF EXISTS tenant_id;
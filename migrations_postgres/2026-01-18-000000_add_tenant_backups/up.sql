@@ -0,0 +1,18 @@
+-- Per-tenant logical backups. Tenant backups outlive their tenant, so the
+-- reference is cleared rather than cascaded.
+ALTER TABLE backups ADD COLUMN tenant_id INTEGER REFERENCES tenants(id) ON DELETE SET NULL;
+ALTER TABLE backups ADD COLUMN checksum VARCHAR(64);
+CREATE INDEX idx_backups_tenant ON backups (tenant_id, created_at);
+
+-- Scheduled backups and how many to keep, one policy per tenant
+CREATE TABLE backup_policies (
+    id SERIAL PRIMARY KEY,
+    tenant_id INTEGER NOT NULL UNIQUE REFERENCES tenants(id) ON DELETE CASCADE,
+    interval_hours INTEGER NOT NULL DEFAULT 24 CHECK (interval_hours > 0),
+    keep_last INTEGER NOT NULL DEFAULT 7 CHECK (keep_last > 0),
+    keep_days INTEGER CHECK (keep_days > 0),
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    last_run_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
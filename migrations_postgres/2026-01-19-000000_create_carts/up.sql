@@ -0,0 +1,61 @@
+-- Persistent shopping carts. A cart belongs to a signed-in user, or to a
+-- guest holding its `guest_token`; a guest cart is merged into the user's
+-- cart at login. `last_activity_at` drives abandonment tracking.
+CREATE TABLE carts (
+    id SERIAL PRIMARY KEY,
+    uuid VARCHAR(36) NOT NULL UNIQUE,
+    tenant_id INTEGER REFERENCES tenants(id) ON DELETE CASCADE,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    guest_token VARCHAR(64) UNIQUE,
+    status VARCHAR(20) NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'converted', 'merged')),
+    currency VARCHAR(3) NOT NULL DEFAULT 'usd',
+    -- Contact for guests, collected at checkout; used for recovery emails
+    email VARCHAR(255),
+    last_activity_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    abandoned_at TIMESTAMP,
+    recovery_sent_at TIMESTAMP,
+    -- Set when a cart that was marked abandoned is checked out after all
+    recovered_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (user_id IS NOT NULL OR guest_token IS NOT NULL)
+);
+
+-- One open cart per user and site
+CREATE UNIQUE INDEX idx_carts_active_user ON carts (COALESCE(tenant_id, 0), user_id) WHERE status = 'active' AND user_id IS NOT NULL;
+CREATE INDEX idx_carts_activity ON carts (status, last_activity_at);
+
+CREATE TABLE cart_items (
+    id SERIAL PRIMARY KEY,
+    cart_id INTEGER NOT NULL REFERENCES carts(id) ON DELETE CASCADE,
+    product_id BIGINT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    variant_id INTEGER REFERENCES product_variants(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX idx_cart_items_line ON cart_items (cart_id, product_id, COALESCE(variant_id, 0));
+
+-- A cart at checkout: prices are locked into `line_items` until the session
+-- expires, and completing it creates the order and its payment intent.
+CREATE TABLE checkout_sessions (
+    id SERIAL PRIMARY KEY,
+    uuid VARCHAR(36) NOT NULL UNIQUE,
+    cart_id INTEGER NOT NULL REFERENCES carts(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'completed', 'expired')),
+    currency VARCHAR(3) NOT NULL,
+    line_items JSONB NOT NULL,
+    subtotal_cents BIGINT NOT NULL,
+    total_cents BIGINT NOT NULL,
+    email VARCHAR(255),
+    shipping_address JSONB,
+    billing_address JSONB,
+    order_id BIGINT REFERENCES orders(id) ON DELETE SET NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    completed_at TIMESTAMP
+);
+
+CREATE INDEX idx_checkout_sessions_cart ON checkout_sessions (cart_id);
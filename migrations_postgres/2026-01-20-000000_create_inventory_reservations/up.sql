@@ -0,0 +1,19 @@
+-- Stock held for unpaid orders. A reservation counts against available stock
+-- until it expires; it is committed (stock decremented) when the payment
+-- succeeds and released when the payment is cancelled or fails.
+CREATE TABLE inventory_reservations (
+    id SERIAL PRIMARY KEY,
+    order_id BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    product_id BIGINT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    -- Stock is held on the variant when set, otherwise on the product
+    variant_id INTEGER REFERENCES product_variants(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    status VARCHAR(20) NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'committed', 'released', 'expired')),
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_inventory_reservations_order ON inventory_reservations (order_id);
+CREATE INDEX idx_inventory_reservations_active ON inventory_reservations (product_id, variant_id) WHERE status = 'active';
+CREATE INDEX idx_inventory_reservations_expiry ON inventory_reservations (expires_at) WHERE status = 'active';
@@ -0,0 +1,10 @@
+DROP INDEX IF EXISTS idx_order_items_variant;
+
+ALTER TABLE order_items
+    DROP COLUMN IF EXISTS discount_cents,
+    DROP COLUMN IF EXISTS tax_cents,
+    DROP COLUMN IF EXISTS variant_attributes,
+    DROP COLUMN IF EXISTS variant_name,
+    DROP COLUMN IF EXISTS sku,
+    DROP COLUMN IF EXISTS product_name,
+    DROP COLUMN IF EXISTS variant_id;
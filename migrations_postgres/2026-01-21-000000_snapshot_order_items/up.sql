@@ -0,0 +1,21 @@
+-- Order lines keep a copy of what was bought, so order history and invoices
+-- don't change with the catalogue. `price_cents` is the unit price paid;
+-- `tax_cents` and `discount_cents` are for the whole line.
+ALTER TABLE order_items
+    ADD COLUMN variant_id INTEGER REFERENCES product_variants(id) ON DELETE SET NULL,
+    ADD COLUMN product_name VARCHAR(255),
+    ADD COLUMN sku VARCHAR(100),
+    ADD COLUMN variant_name VARCHAR(255),
+    ADD COLUMN variant_attributes JSONB,
+    ADD COLUMN tax_cents BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN discount_cents BIGINT NOT NULL DEFAULT 0;
+
+-- Best effort for existing orders: the catalogue as it is now
+UPDATE order_items
+SET product_name = products.name, sku = products.sku
+FROM products
+WHERE products.id = order_items.product_id;
+
+ALTER TABLE order_items ALTER COLUMN product_name SET NOT NULL;
+
+CREATE INDEX idx_order_items_variant ON order_items(variant_id);
@@ -0,0 +1,31 @@
+-- Order lifecycle: statuses are limited to the states of the order state
+-- machine, and every transition is recorded with who made it and why.
+
+-- Earlier free-form statuses: "processing" meant a payment was linked,
+-- "completed" that the order was done
+UPDATE orders SET status = CASE
+    WHEN status = 'processing' AND payment_status = 'succeeded' THEN 'paid'
+    WHEN status = 'processing' THEN 'pending'
+    WHEN status = 'completed' THEN 'delivered'
+    ELSE status
+END;
+UPDATE orders SET status = 'pending'
+WHERE status NOT IN ('pending', 'paid', 'fulfilling', 'shipped', 'delivered', 'cancelled', 'refunded', 'partially_refunded');
+
+ALTER TABLE orders ADD CONSTRAINT orders_status_check
+    CHECK (status IN ('pending', 'paid', 'fulfilling', 'shipped', 'delivered', 'cancelled', 'refunded', 'partially_refunded'));
+
+CREATE TABLE order_status_history (
+    id SERIAL PRIMARY KEY,
+    order_id BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    -- NULL for the order's creation
+    from_status VARCHAR(50),
+    to_status VARCHAR(50) NOT NULL,
+    -- "customer", "staff", or the system that made the change, e.g. "stripe"
+    actor VARCHAR(100) NOT NULL,
+    actor_user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    reason TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_order_status_history_order ON order_status_history (order_id, created_at);
@@ -0,0 +1,22 @@
+-- Refunds of order payments. Each refund is saved before the payment
+-- provider is called and is keyed by the client's idempotency key, so a
+-- retried request never refunds twice.
+CREATE TABLE order_refunds (
+    id SERIAL PRIMARY KEY,
+    -- Sent to the provider as its idempotency key
+    uuid VARCHAR(36) NOT NULL UNIQUE,
+    order_id BIGINT NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    idempotency_key VARCHAR(255) NOT NULL,
+    provider VARCHAR(50) NOT NULL,
+    provider_refund_id VARCHAR(255),
+    amount_cents BIGINT NOT NULL CHECK (amount_cents > 0),
+    currency VARCHAR(3) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'succeeded', 'failed', 'canceled')),
+    reason TEXT,
+    failure_reason TEXT,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (order_id, idempotency_key)
+);
@@ -0,0 +1,2 @@
+-- Backfilled rows can't be told apart from ones written on save, so they stay
+SELECT 1;
@@ -0,0 +1,28 @@
+-- Index the media referenced by content saved before media_usages existed,
+-- the same way media_usage_service does on save: every UUID in a page's
+-- content and images, or a module's content and field config, that names
+-- media of the same tenant
+INSERT INTO media_usages (media_id, source_type, source_uuid)
+SELECT DISTINCT m.id, 'page', p.uuid
+FROM pages p
+CROSS JOIN LATERAL regexp_matches(
+    concat_ws(' ', p.content, p.og_image, p.featured_image),
+    '\y[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\y',
+    'gi'
+) AS found(uuid)
+JOIN media m ON m.uuid = lower(found.uuid[1])
+WHERE p.tenant_id IS NULL OR m.tenant_id = p.tenant_id
+ON CONFLICT DO NOTHING;
+
+INSERT INTO media_usages (media_id, source_type, source_uuid)
+SELECT DISTINCT m.id, 'module', mo.uuid
+FROM modules mo
+LEFT JOIN pages p ON p.uuid = mo.page_uuid
+CROSS JOIN LATERAL regexp_matches(
+    concat_ws(' ', mo.content, mo.field_config),
+    '\y[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\y',
+    'gi'
+) AS found(uuid)
+JOIN media m ON m.uuid = lower(found.uuid[1])
+WHERE COALESCE(mo.tenant_id, p.tenant_id) IS NULL OR m.tenant_id = COALESCE(mo.tenant_id, p.tenant_id)
+ON CONFLICT DO NOTHING;
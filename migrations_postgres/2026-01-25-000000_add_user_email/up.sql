@@ -0,0 +1,5 @@
+-- Contact address of a user, separate from the login name
+ALTER TABLE users ADD COLUMN email VARCHAR(255);
+
+-- Most usernames are email addresses
+UPDATE users SET email = username WHERE username ~ '^[^@\s]+@[^@\s]+$';
@@ -0,0 +1,13 @@
+-- The theme each site renders with. Shared themes (themes.tenant_id NULL)
+-- are one row used by many sites, so activation can't live on the theme.
+CREATE TABLE tenant_themes (
+    tenant_id INTEGER PRIMARY KEY REFERENCES tenants(id) ON DELETE CASCADE,
+    theme_id INTEGER NOT NULL REFERENCES themes(id) ON DELETE CASCADE,
+    activated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO tenant_themes (tenant_id, theme_id)
+SELECT DISTINCT ON (tenant_id) tenant_id, id
+FROM themes
+WHERE is_active AND tenant_id IS NOT NULL
+ORDER BY tenant_id, updated_at DESC NULLS LAST;
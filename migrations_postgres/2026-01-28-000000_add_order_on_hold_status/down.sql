@@ -0,0 +1,4 @@
+UPDATE orders SET status = 'pending' WHERE status = 'on_hold';
+ALTER TABLE orders DROP CONSTRAINT orders_status_check;
+ALTER TABLE orders ADD CONSTRAINT orders_status_check
+    CHECK (status IN ('pending', 'paid', 'fulfilling', 'shipped', 'delivered', 'cancelled', 'refunded', 'partially_refunded'));
@@ -0,0 +1,6 @@
+-- Orders whose payment succeeded but that couldn't be marked paid (sold out
+-- after their stock reservation expired, or cancelled first) are put on hold
+-- until staff refund or fulfil them
+ALTER TABLE orders DROP CONSTRAINT orders_status_check;
+ALTER TABLE orders ADD CONSTRAINT orders_status_check
+    CHECK (status IN ('pending', 'paid', 'fulfilling', 'shipped', 'delivered', 'cancelled', 'refunded', 'partially_refunded', 'on_hold'));
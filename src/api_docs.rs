@@ -97,7 +97,21 @@ use utoipa::OpenApi;
         crate::controllers::order_controller::get_order,
         crate::controllers::order_controller::create_order,
         crate::controllers::order_controller::update_order_status,
+        crate::controllers::order_controller::get_order_history,
+        crate::controllers::order_controller::refund_order,
+        crate::controllers::order_controller::list_order_refunds,
         crate::controllers::order_controller::link_payment_to_order,
+        // Commerce - Cart
+        crate::controllers::cart_controller::get_cart,
+        crate::controllers::cart_controller::add_cart_item,
+        crate::controllers::cart_controller::update_cart_item,
+        crate::controllers::cart_controller::remove_cart_item,
+        crate::controllers::cart_controller::merge_cart,
+        crate::controllers::cart_controller::start_checkout,
+        crate::controllers::cart_controller::get_checkout,
+        crate::controllers::cart_controller::update_checkout,
+        crate::controllers::cart_controller::complete_checkout,
+        crate::controllers::cart_controller::confirm_checkout,
         
         // Content - Modules
         crate::controllers::module_controllers::create_module,
@@ -185,6 +199,24 @@ use utoipa::OpenApi;
         crate::controllers::media_controller::list_media,
         crate::controllers::media_controller::get_media,
         crate::controllers::media_controller::delete_media,
+        crate::controllers::media_controller::get_media_policy,
+        crate::controllers::media_controller::set_focal_point,
+        crate::controllers::media_controller::create_transform_url,
+        crate::controllers::media_controller::transform_media,
+        crate::controllers::media_controller::get_media_usage,
+        crate::controllers::media_controller::get_download_url,
+        crate::controllers::media_controller::create_upload_url,
+        crate::controllers::media_controller::complete_upload,
+        crate::controllers::media_folder_controller::list_folders,
+        crate::controllers::media_folder_controller::create_folder,
+        crate::controllers::media_folder_controller::rename_folder,
+        crate::controllers::media_folder_controller::move_folder,
+        crate::controllers::media_folder_controller::delete_folder,
+        crate::controllers::media_folder_controller::move_media,
+        crate::controllers::storage_migration_controller::start_storage_migration,
+        crate::controllers::storage_migration_controller::list_storage_migrations,
+        crate::controllers::storage_migration_controller::get_storage_migration,
+        crate::controllers::storage_migration_controller::pause_storage_migration,
         
         // Internal - Dashboard
         crate::controllers::dashboard_controller::dashboard_summary,
@@ -199,6 +231,13 @@ use utoipa::OpenApi;
         // Internal - Backup
         crate::controllers::backup_controller::list_backups,
         crate::controllers::backup_controller::create_backup,
+        crate::controllers::backup_controller::create_tenant_backup,
+        crate::controllers::backup_controller::list_tenant_backups,
+        crate::controllers::backup_controller::download_tenant_backup,
+        crate::controllers::backup_controller::restore_tenant_backup,
+        crate::controllers::backup_controller::delete_tenant_backup,
+        crate::controllers::backup_controller::get_tenant_backup_policy,
+        crate::controllers::backup_controller::update_tenant_backup_policy,
         
         // Internal - SEO
         crate::controllers::seo_controller::audit_url,
@@ -210,6 +249,7 @@ use utoipa::OpenApi;
         
         // Content - Search
         crate::controllers::search_controller::search_content,
+        crate::controllers::search_controller::record_search_click,
         
         // Content - AI
         crate::services::ai_content_service::generate_content,
@@ -255,6 +295,15 @@ use utoipa::OpenApi;
         crate::controllers::revision_controller::list_revisions,
         crate::controllers::revision_controller::get_revision,
         crate::controllers::revision_controller::rollback_revision,
+        crate::controllers::revision_controller::diff_revisions,
+        crate::controllers::revision_controller::merge_revision,
+        
+        // Content - Redirects
+        crate::controllers::redirect_controller::list_redirects,
+        crate::controllers::redirect_controller::create_redirect,
+        crate::controllers::redirect_controller::update_redirect,
+        crate::controllers::redirect_controller::delete_redirect,
+        crate::controllers::redirect_controller::import_redirects,
         
         // Customer - CRM (AI)
         crate::services::recommendation_service::get_related_content,
@@ -361,6 +410,17 @@ use utoipa::OpenApi;
         crate::models::commerce_models::NewOrder,
         crate::models::commerce_models::OrderItem,
         crate::models::commerce_models::NewOrderItem,
+        crate::models::commerce_models::OrderStatusChange,
+        crate::models::commerce_models::OrderRefund,
+        crate::services::order_service::OrderStatus,
+        crate::models::cart_models::Cart,
+        crate::models::cart_models::CartItem,
+        crate::models::cart_models::CheckoutSession,
+        crate::models::cart_models::CheckoutLine,
+        crate::services::cart_service::CartView,
+        crate::services::cart_service::CartLine,
+        crate::services::cart_service::CheckoutDetails,
+        crate::services::cart_service::CheckoutResult,
         
         // Content - Module models
         crate::models::module_models::Module,
@@ -401,13 +461,41 @@ use utoipa::OpenApi;
         crate::controllers::order_controller::OrderItemInput,
         crate::controllers::order_controller::OrderResponse,
         crate::controllers::order_controller::UpdateOrderStatusRequest,
+        crate::controllers::order_controller::RefundOrderRequest,
         crate::controllers::order_controller::LinkPaymentRequest,
+        crate::controllers::cart_controller::AddCartItemRequest,
+        crate::controllers::cart_controller::UpdateCartItemRequest,
+        crate::controllers::cart_controller::CompleteCheckoutRequest,
         crate::controllers::marketplace_plugin_controller::InstallPluginRequest,
         crate::controllers::tenant_controller::NewTenantRequest,
         crate::controllers::tenant_controller::InviteMemberRequest,
         crate::controllers::tenant_sso_controller::UpdateSsoRequest,
         crate::controllers::billing_controller::SubscribeRequest,
         crate::controllers::seo_controller::AuditRequest,
+        crate::controllers::redirect_controller::CreateRedirectRequest,
+        crate::controllers::redirect_controller::UpdateRedirectRequest,
+        crate::controllers::redirect_controller::ImportRedirectsResponse,
+        crate::models::redirect_models::Redirect,
+        crate::services::redirect_service::CsvRowError,
+        crate::services::media_policy_service::MediaPolicy,
+        crate::services::media_inspection_service::MediaKind,
+        crate::controllers::media_controller::FocalPointRequest,
+        crate::controllers::media_controller::TransformUrlRequest,
+        crate::controllers::media_controller::TransformUrlResponse,
+        crate::controllers::media_controller::DownloadUrlResponse,
+        crate::controllers::media_controller::UploadUrlRequest,
+        crate::controllers::media_controller::UploadUrlResponse,
+        crate::controllers::media_controller::UploadCompleteRequest,
+        crate::models::media_models::MediaFolder,
+        crate::models::media_models::MediaUsage,
+        crate::controllers::media_folder_controller::CreateFolderRequest,
+        crate::controllers::media_folder_controller::RenameFolderRequest,
+        crate::controllers::media_folder_controller::MoveFolderRequest,
+        crate::controllers::media_folder_controller::MoveMediaRequest,
+        crate::controllers::media_folder_controller::MoveMediaResponse,
+        crate::controllers::storage_migration_controller::StartStorageMigrationRequest,
+        crate::models::storage_migration_models::StorageMigration,
+        crate::services::storage_migration_service::Direction,
         crate::controllers::site_controller::ValidateCnameRequest,
         crate::controllers::survey_controller::CreateSurveyRequest,
         crate::controllers::survey_controller::AddQuestionRequest,
@@ -415,6 +503,12 @@ use utoipa::OpenApi;
         crate::controllers::webhook_controller::CreateWebhookRequest,
         crate::controllers::backup_controller::BackupResponse,
         crate::controllers::backup_controller::BackupList,
+        crate::controllers::backup_controller::BackupPolicyRequest,
+        crate::controllers::backup_controller::RestoreBackupRequest,
+        crate::models::backup_models::Backup,
+        crate::models::backup_models::BackupPolicy,
+        crate::services::tenant_backup_service::NewTenantTarget,
+        crate::services::tenant_backup_service::RestoreReport,
         crate::controllers::dashboard_controller::DashboardSummary,
         crate::controllers::dashboard_controller::AnalyticsSummary,
         crate::controllers::dashboard_controller::SEOHealthCheck,
@@ -494,6 +588,10 @@ use utoipa::OpenApi;
             name = "Commerce - Orders", 
             description = "🛒 Order processing: order lifecycle, fulfillment, and status tracking"
         ),
+        (
+            name = "Commerce - Cart",
+            description = "🧺 Shopping carts for guests and signed-in users, and checkout sessions that place orders"
+        ),
         (
             name = "Commerce - Payments", 
             description = "💳 Payment processing: multi-provider support (Stripe, PayPal, Square), payment intents, and webhooks"
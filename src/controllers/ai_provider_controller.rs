@@ -118,6 +118,8 @@ pub async fn create_ai_provider(
         daily_token_limit: None,
         monthly_budget_cents: None,
         created_by: None,
+        config: None,
+        purpose: None,
     };
     
     let config = web::block(move || -> Result<AIProviderConfig, diesel::result::Error> {
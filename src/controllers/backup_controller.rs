@@ -1,6 +1,15 @@
-use actix_web::{get, post, HttpResponse, Responder};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
+use crate::helpers::tenant_helper::{get_tenant_role, resolve_tenant_id};
+use crate::middleware::auth_middleware::get_user_context;
+use crate::models::backup_models::{Backup, NewBackupPolicy};
+use crate::models::rbac::{has_permission, Permission};
+use crate::models::{pool_handler, DatabasePool, PooledDatabaseConnection};
 use crate::services::backup_service::BackupService;
-use serde::Serialize;
+use crate::services::cache_service_v2::CacheServiceV2;
+use crate::services::errors_service::CustomHttpError;
+use crate::services::storage_service::{storage_filename, StorageBackend};
+use crate::services::tenant_backup_service::{self, NewTenantTarget, RestoreTarget};
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 #[derive(Serialize, ToSchema)]
@@ -82,3 +91,307 @@ pub async fn create_backup() -> impl Responder {
         }),
     }
 }
+
+#[derive(Deserialize, ToSchema)]
+pub struct BackupPolicyRequest {
+    /// Hours between scheduled backups (1-720, default 24)
+    #[serde(default = "default_interval_hours")]
+    pub interval_hours: i32,
+    /// Completed backups always kept (1-365, default 7)
+    #[serde(default = "default_keep_last")]
+    pub keep_last: i32,
+    /// Delete backups beyond `keep_last` once they are this many days old;
+    /// without it they are deleted straight away
+    pub keep_days: Option<i32>,
+    #[serde(default = "default_is_active")]
+    pub is_active: bool,
+}
+
+fn default_interval_hours() -> i32 {
+    24
+}
+
+fn default_keep_last() -> i32 {
+    7
+}
+
+fn default_is_active() -> bool {
+    true
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RestoreBackupRequest {
+    /// Restore into a new tenant owned by the caller (platform admins only)
+    pub new_tenant: Option<NewTenantTarget>,
+    /// Delete the current tenant's content before restoring (default false)
+    #[serde(default)]
+    pub replace_existing: bool,
+}
+
+/// Caller's user ID, the current tenant, and whether the caller is a platform admin
+fn authorize_tenant(
+    req: &HttpRequest,
+    pool: web::Data<DatabasePool>,
+) -> Result<(i32, i32, bool, PooledDatabaseConnection), CustomHttpError> {
+    let user_ctx = get_user_context(req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
+    let tenant_id = resolve_tenant_id(req, &pool).map_err(CustomHttpError::BadRequest)?;
+    let mut conn = pool_handler(pool)?;
+
+    if !user_ctx.is_platform_admin() {
+        let role = get_tenant_role(tenant_id, user_ctx.user_id, &mut conn)
+            .map_err(|_| CustomHttpError::Forbidden("Access denied".to_string()))?;
+        if !has_permission(&role, Permission::ManageSettings) {
+            return Err(CustomHttpError::Forbidden("Insufficient permissions".to_string()));
+        }
+    }
+    Ok((user_ctx.user_id, tenant_id, user_ctx.is_platform_admin(), conn))
+}
+
+/// A backup of the current tenant; platform admins may use any tenant's
+fn find_tenant_backup(
+    uuid: &str,
+    tenant_id: i32,
+    is_platform_admin: bool,
+    conn: &mut PooledDatabaseConnection,
+) -> Result<Backup, CustomHttpError> {
+    let backup = tenant_backup_service::find_backup(uuid, conn)?;
+    if backup.tenant_id != Some(tenant_id) && !is_platform_admin {
+        return Err(CustomHttpError::NotFound("Backup not found".to_string()));
+    }
+    Ok(backup)
+}
+
+/// Back up the current tenant
+///
+/// Exports pages, modules, categories, translations, media files, themes, CRM
+/// and commerce rows into a versioned archive with a checksum per entry.
+#[utoipa::path(
+    post,
+    path = "/admin/backups/tenant",
+    tag = "Internal - Backup",
+    responses(
+        (status = 201, description = "Backup created", body = Backup),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 500, description = "Backup failed; the failed backup is listed with its error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[post("/admin/backups/tenant")]
+pub async fn create_tenant_backup(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    storage: web::Data<StorageBackend>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (_, tenant_id, _, mut conn) = authorize_tenant(&req, pool)?;
+    let backup = tenant_backup_service::create_backup(tenant_id, &storage, &mut conn).await?;
+    Ok(HttpResponse::Created().json(backup))
+}
+
+/// List the current tenant's backups, newest first
+#[utoipa::path(
+    get,
+    path = "/admin/backups/tenant",
+    tag = "Internal - Backup",
+    responses(
+        (status = 200, description = "Tenant backups", body = Vec<Backup>),
+        (status = 403, description = "Insufficient permissions")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[get("/admin/backups/tenant")]
+pub async fn list_tenant_backups(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (_, tenant_id, _, mut conn) = authorize_tenant(&req, pool)?;
+    Ok(HttpResponse::Ok().json(tenant_backup_service::list_backups(tenant_id, &mut conn)?))
+}
+
+/// Download a tenant backup archive
+#[utoipa::path(
+    get,
+    path = "/admin/backups/tenant/{uuid}/download",
+    tag = "Internal - Backup",
+    params(
+        ("uuid" = String, Path, description = "Backup UUID")
+    ),
+    responses(
+        (status = 200, description = "The zip archive", content_type = "application/zip"),
+        (status = 404, description = "Backup or its archive not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[get("/admin/backups/tenant/{uuid}/download")]
+pub async fn download_tenant_backup(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    uuid: web::Path<String>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (_, tenant_id, is_platform_admin, mut conn) = authorize_tenant(&req, pool)?;
+    let backup = find_tenant_backup(&uuid, tenant_id, is_platform_admin, &mut conn)?;
+    let not_found = || CustomHttpError::NotFound("Backup archive not found".to_string());
+    let file_path = backup.file_path.as_deref().ok_or_else(not_found)?;
+
+    let path = file_path.to_string();
+    let data = web::block(move || std::fs::read(path))
+        .await
+        .map_err(|e| CustomHttpError::InternalServerError(e.to_string()))?
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => not_found(),
+            _ => CustomHttpError::InternalServerError(e.to_string()),
+        })?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", storage_filename(file_path)),
+        ))
+        .body(data))
+}
+
+/// Restore a tenant backup
+///
+/// Restores into the current tenant, or into a new one. Rows get new IDs and
+/// references between them are remapped; outside a same-tenant replace, pages,
+/// modules, media and products also get new UUIDs. Rows that conflict with
+/// existing ones are skipped and counted in the report.
+#[utoipa::path(
+    post,
+    path = "/admin/backups/tenant/{uuid}/restore",
+    tag = "Internal - Backup",
+    params(
+        ("uuid" = String, Path, description = "Backup UUID")
+    ),
+    request_body = RestoreBackupRequest,
+    responses(
+        (status = 200, description = "Backup restored", body = RestoreReport),
+        (status = 400, description = "Backup is not restorable, the archive is corrupt, or the subdomain is taken"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "Backup not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[post("/admin/backups/tenant/{uuid}/restore")]
+pub async fn restore_tenant_backup(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    storage: web::Data<StorageBackend>,
+    cache: web::Data<CacheServiceV2>,
+    uuid: web::Path<String>,
+    payload: web::Json<RestoreBackupRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (user_id, tenant_id, is_platform_admin, mut conn) = authorize_tenant(&req, pool)?;
+    let backup = find_tenant_backup(&uuid, tenant_id, is_platform_admin, &mut conn)?;
+
+    let payload = payload.into_inner();
+    let target = match payload.new_tenant {
+        Some(target) => {
+            if !is_platform_admin {
+                return Err(CustomHttpError::Forbidden("Platform admin access required".to_string()));
+            }
+            RestoreTarget::New { target, owner_id: user_id }
+        }
+        None => RestoreTarget::Existing { tenant_id, replace_existing: payload.replace_existing },
+    };
+
+    let report = tenant_backup_service::restore_backup(&backup, target, &storage, &cache, &mut conn).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Delete a tenant backup and its archive
+#[utoipa::path(
+    delete,
+    path = "/admin/backups/tenant/{uuid}",
+    tag = "Internal - Backup",
+    params(
+        ("uuid" = String, Path, description = "Backup UUID")
+    ),
+    responses(
+        (status = 204, description = "Backup deleted"),
+        (status = 404, description = "Backup not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[delete("/admin/backups/tenant/{uuid}")]
+pub async fn delete_tenant_backup(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    uuid: web::Path<String>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (_, tenant_id, is_platform_admin, mut conn) = authorize_tenant(&req, pool)?;
+    let backup = find_tenant_backup(&uuid, tenant_id, is_platform_admin, &mut conn)?;
+    tenant_backup_service::delete_backup(&backup, &mut conn)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The current tenant's backup schedule and retention policy
+#[utoipa::path(
+    get,
+    path = "/admin/backups/tenant/policy",
+    tag = "Internal - Backup",
+    responses(
+        (status = 200, description = "Backup policy", body = BackupPolicy),
+        (status = 404, description = "No backup policy set")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[get("/admin/backups/tenant/policy")]
+pub async fn get_tenant_backup_policy(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (_, tenant_id, _, mut conn) = authorize_tenant(&req, pool)?;
+    let policy = tenant_backup_service::policy_for(tenant_id, &mut conn)?
+        .ok_or(CustomHttpError::NotFound("No backup policy set".to_string()))?;
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+/// Set the current tenant's backup schedule and retention policy
+///
+/// Scheduled backups run in the background every `interval_hours`; after each
+/// one, backups the policy no longer keeps are deleted.
+#[utoipa::path(
+    put,
+    path = "/admin/backups/tenant/policy",
+    tag = "Internal - Backup",
+    request_body = BackupPolicyRequest,
+    responses(
+        (status = 200, description = "Backup policy saved", body = BackupPolicy),
+        (status = 400, description = "Values out of range"),
+        (status = 403, description = "Insufficient permissions")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[put("/admin/backups/tenant/policy")]
+pub async fn update_tenant_backup_policy(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    payload: web::Json<BackupPolicyRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (_, tenant_id, _, mut conn) = authorize_tenant(&req, pool)?;
+    let policy = tenant_backup_service::save_policy(
+        &NewBackupPolicy {
+            tenant_id,
+            interval_hours: payload.interval_hours,
+            keep_last: payload.keep_last,
+            keep_days: payload.keep_days,
+            is_active: payload.is_active,
+        },
+        &mut conn,
+    )?;
+    Ok(HttpResponse::Ok().json(policy))
+}
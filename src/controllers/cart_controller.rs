@@ -0,0 +1,408 @@
+// Cart Controller
+// Shopping cart and checkout for signed-in users and guests. Guests get a
+// cart token with their first item and send it back in `X-Cart-Token`.
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::helpers::tenant_helper::resolve_tenant_id;
+use crate::middleware::auth_middleware::get_user_context;
+use crate::models::cart_models::Cart;
+use crate::models::{pool_handler, DatabasePool};
+use crate::services::cart_service::{self, CartOwner, CheckoutDetails, CART_TOKEN_HEADER};
+use crate::services::email_service::EmailService;
+use crate::services::errors_service::CustomHttpError;
+use crate::services::order_service;
+use crate::services::payment_service::{PaymentHandlerRegistry, PaymentStatus};
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddCartItemRequest {
+    pub product_id: i64,
+    pub variant_id: Option<i32>,
+    /// Default 1
+    #[serde(default = "default_quantity")]
+    pub quantity: i32,
+}
+
+fn default_quantity() -> i32 {
+    1
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateCartItemRequest {
+    /// 0 removes the item
+    pub quantity: i32,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CompleteCheckoutRequest {
+    /// Payment provider, e.g. "stripe"
+    pub provider: String,
+}
+
+/// The signed-in user, or the guest holding a cart token
+fn cart_owner(req: &HttpRequest) -> Option<CartOwner> {
+    if let Some(user_ctx) = get_user_context(req) {
+        return Some(CartOwner::User(user_ctx.user_id));
+    }
+    req.headers()
+        .get(CART_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .map(CartOwner::Guest)
+}
+
+fn existing_cart(
+    req: &HttpRequest,
+    pool: &web::Data<DatabasePool>,
+    conn: &mut diesel::PgConnection,
+) -> Result<Cart, CustomHttpError> {
+    let owner = cart_owner(req).ok_or(CustomHttpError::NotFound("Cart not found".to_string()))?;
+    let tenant_id = resolve_tenant_id(req, pool).map_err(CustomHttpError::BadRequest)?;
+    cart_service::find_active_cart(&owner, tenant_id, conn)?
+        .ok_or(CustomHttpError::NotFound("Cart not found".to_string()))
+}
+
+/// Get the current cart
+///
+/// Lines are priced from the current catalogue. Without a cart, returns an
+/// empty one.
+#[utoipa::path(
+    get,
+    path = "/v1/cart",
+    tag = "Commerce - Cart",
+    params(
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart token")
+    ),
+    responses(
+        (status = 200, description = "The cart with priced lines", body = CartView),
+        (status = 400, description = "No site resolved")
+    )
+)]
+pub async fn get_cart(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let mut conn = pool_handler(pool.clone())?;
+    let tenant_id = resolve_tenant_id(&req, &pool).map_err(CustomHttpError::BadRequest)?;
+    let cart = match cart_owner(&req) {
+        Some(owner) => cart_service::find_active_cart(&owner, tenant_id, &mut conn)?,
+        None => None,
+    };
+    match cart {
+        Some(cart) => Ok(HttpResponse::Ok().json(cart_service::view(cart, &mut conn)?)),
+        None => Ok(HttpResponse::Ok().json(serde_json::json!({ "cart": null, "items": [], "subtotal_cents": 0 }))),
+    }
+}
+
+/// Add a product or variant to the cart
+///
+/// Creates the cart if needed; a new guest cart comes with its `guest_token`.
+#[utoipa::path(
+    post,
+    path = "/v1/cart/items",
+    tag = "Commerce - Cart",
+    params(
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart token")
+    ),
+    request_body = AddCartItemRequest,
+    responses(
+        (status = 200, description = "The updated cart", body = CartView),
+        (status = 400, description = "Invalid quantity, or no site resolved"),
+        (status = 404, description = "Product or variant not found on this site")
+    )
+)]
+pub async fn add_cart_item(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    payload: web::Json<AddCartItemRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let mut conn = pool_handler(pool.clone())?;
+    let tenant_id = resolve_tenant_id(&req, &pool).map_err(CustomHttpError::BadRequest)?;
+    let cart = cart_service::get_or_create_cart(cart_owner(&req).as_ref(), tenant_id, &mut conn)?;
+    cart_service::add_item(&cart, payload.product_id, payload.variant_id, payload.quantity, &mut conn)?;
+    Ok(HttpResponse::Ok().json(cart_service::view(cart, &mut conn)?))
+}
+
+/// Change the quantity of a cart line
+#[utoipa::path(
+    put,
+    path = "/v1/cart/items/{id}",
+    tag = "Commerce - Cart",
+    params(
+        ("id" = i32, Path, description = "Cart item ID"),
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart token")
+    ),
+    request_body = UpdateCartItemRequest,
+    responses(
+        (status = 200, description = "The updated cart", body = CartView),
+        (status = 400, description = "Invalid quantity"),
+        (status = 404, description = "Cart or item not found")
+    )
+)]
+pub async fn update_cart_item(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    id: web::Path<i32>,
+    payload: web::Json<UpdateCartItemRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let mut conn = pool_handler(pool.clone())?;
+    let cart = existing_cart(&req, &pool, &mut conn)?;
+    cart_service::update_item(&cart, id.into_inner(), payload.quantity, &mut conn)?;
+    Ok(HttpResponse::Ok().json(cart_service::view(cart, &mut conn)?))
+}
+
+/// Remove a line from the cart
+#[utoipa::path(
+    delete,
+    path = "/v1/cart/items/{id}",
+    tag = "Commerce - Cart",
+    params(
+        ("id" = i32, Path, description = "Cart item ID"),
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart token")
+    ),
+    responses(
+        (status = 200, description = "The updated cart", body = CartView),
+        (status = 404, description = "Cart or item not found")
+    )
+)]
+pub async fn remove_cart_item(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let mut conn = pool_handler(pool.clone())?;
+    let cart = existing_cart(&req, &pool, &mut conn)?;
+    cart_service::remove_item(&cart, id.into_inner(), &mut conn)?;
+    Ok(HttpResponse::Ok().json(cart_service::view(cart, &mut conn)?))
+}
+
+/// Merge a guest cart into the signed-in user's cart
+///
+/// Password logins that send `X-Cart-Token` merge automatically; call this
+/// after other sign-in flows.
+#[utoipa::path(
+    post,
+    path = "/v1/cart/merge",
+    tag = "Commerce - Cart",
+    params(
+        ("X-Cart-Token" = String, Header, description = "Guest cart token")
+    ),
+    responses(
+        (status = 200, description = "The user's cart", body = CartView),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "No open guest cart for the token")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn merge_cart(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let user_ctx = get_user_context(&req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
+    let token = req
+        .headers()
+        .get(CART_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or(CustomHttpError::BadRequest(format!("{} header is required", CART_TOKEN_HEADER)))?;
+    let mut conn = pool_handler(pool)?;
+    let cart = cart_service::merge_guest_cart(token.trim(), user_ctx.user_id, &mut conn)?
+        .ok_or(CustomHttpError::NotFound("Cart not found".to_string()))?;
+    Ok(HttpResponse::Ok().json(cart_service::view(cart, &mut conn)?))
+}
+
+/// Start checkout
+///
+/// Locks the cart's current prices for 30 minutes. Starting again replaces
+/// any earlier open session.
+#[utoipa::path(
+    post,
+    path = "/v1/cart/checkout",
+    tag = "Commerce - Cart",
+    params(
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart token")
+    ),
+    request_body = CheckoutDetails,
+    responses(
+        (status = 201, description = "Checkout session", body = CheckoutSession),
+        (status = 400, description = "Cart is empty or has unavailable items"),
+        (status = 404, description = "Cart not found")
+    )
+)]
+pub async fn start_checkout(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    payload: web::Json<CheckoutDetails>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let mut conn = pool_handler(pool.clone())?;
+    let cart = existing_cart(&req, &pool, &mut conn)?;
+    let session = cart_service::start_checkout(&cart, payload.into_inner(), &mut conn)?;
+    Ok(HttpResponse::Created().json(session))
+}
+
+/// Get a checkout session
+#[utoipa::path(
+    get,
+    path = "/v1/checkout/{uuid}",
+    tag = "Commerce - Cart",
+    params(
+        ("uuid" = String, Path, description = "Checkout session UUID"),
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart token")
+    ),
+    responses(
+        (status = 200, description = "Checkout session", body = CheckoutSession),
+        (status = 404, description = "Checkout session not found")
+    )
+)]
+pub async fn get_checkout(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    uuid: web::Path<String>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let owner = cart_owner(&req).ok_or(CustomHttpError::NotFound("Checkout session not found".to_string()))?;
+    let mut conn = pool_handler(pool)?;
+    let (session, _) = cart_service::find_checkout(&uuid, &owner, &mut conn)?;
+    Ok(HttpResponse::Ok().json(session))
+}
+
+/// Update the contact email and addresses of a checkout session
+#[utoipa::path(
+    put,
+    path = "/v1/checkout/{uuid}",
+    tag = "Commerce - Cart",
+    params(
+        ("uuid" = String, Path, description = "Checkout session UUID"),
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart token")
+    ),
+    request_body = CheckoutDetails,
+    responses(
+        (status = 200, description = "Checkout session", body = CheckoutSession),
+        (status = 400, description = "Session expired or completed, or invalid email"),
+        (status = 404, description = "Checkout session not found")
+    )
+)]
+pub async fn update_checkout(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    uuid: web::Path<String>,
+    payload: web::Json<CheckoutDetails>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let owner = cart_owner(&req).ok_or(CustomHttpError::NotFound("Checkout session not found".to_string()))?;
+    let mut conn = pool_handler(pool)?;
+    let (session, _) = cart_service::find_checkout(&uuid, &owner, &mut conn)?;
+    let session = cart_service::update_checkout(&session, payload.into_inner(), &mut conn)?;
+    Ok(HttpResponse::Ok().json(session))
+}
+
+/// Complete checkout
+///
+/// Places the order at the session's locked prices and creates its payment
+/// intent with the provider. The order stays pending until the payment
+/// succeeds; providers that charge right away (Square) mark it paid here,
+/// others through their webhook or `/v1/checkout/{uuid}/confirm`.
+#[utoipa::path(
+    post,
+    path = "/v1/checkout/{uuid}/complete",
+    tag = "Commerce - Cart",
+    params(
+        ("uuid" = String, Path, description = "Checkout session UUID"),
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart token")
+    ),
+    request_body = CompleteCheckoutRequest,
+    responses(
+        (status = 201, description = "Order and payment intent", body = CheckoutResult),
+        (status = 400, description = "Unknown provider, session expired or completed, or guest email missing"),
+        (status = 404, description = "Checkout session not found"),
+        (status = 500, description = "Payment provider error")
+    )
+)]
+pub async fn complete_checkout(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    registry: web::Data<PaymentHandlerRegistry>,
+    email_service: web::Data<EmailService>,
+    uuid: web::Path<String>,
+    payload: web::Json<CompleteCheckoutRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let owner = cart_owner(&req).ok_or(CustomHttpError::NotFound("Checkout session not found".to_string()))?;
+    let handler = registry
+        .get(&payload.provider)
+        .ok_or(CustomHttpError::BadRequest("Payment handler not found".to_string()))?;
+    let mut conn = pool_handler(pool.clone())?;
+    let (session, cart) = cart_service::find_checkout(&uuid, &owner, &mut conn)?;
+    let mut result = cart_service::complete_checkout(&session, &cart, handler, &mut conn).await?;
+    drop(conn);
+
+    if result.payment_intent.status == PaymentStatus::Succeeded {
+        let settled = order_service::settle_payment(&pool, &email_service, &result.order.uuid, "succeeded", handler.provider_name())
+            .await
+            .map_err(CustomHttpError::InternalServerError)?;
+        if let Some(order) = settled {
+            result.order = order;
+        }
+    }
+    Ok(HttpResponse::Created().json(result))
+}
+
+/// Confirm the payment of a completed checkout
+///
+/// Captures an approved payment with its provider (PayPal) or picks up its
+/// current status, and marks the order paid once it succeeded. Safe to call
+/// again.
+#[utoipa::path(
+    post,
+    path = "/v1/checkout/{uuid}/confirm",
+    tag = "Commerce - Cart",
+    params(
+        ("uuid" = String, Path, description = "Checkout session UUID"),
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart token")
+    ),
+    responses(
+        (status = 200, description = "The order", body = Order),
+        (status = 400, description = "Checkout session is not completed"),
+        (status = 404, description = "Checkout session not found"),
+        (status = 500, description = "Payment provider error")
+    )
+)]
+pub async fn confirm_checkout(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    registry: web::Data<PaymentHandlerRegistry>,
+    email_service: web::Data<EmailService>,
+    uuid: web::Path<String>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let owner = cart_owner(&req).ok_or(CustomHttpError::NotFound("Checkout session not found".to_string()))?;
+    let mut conn = pool_handler(pool.clone())?;
+    let (session, _) = cart_service::find_checkout(&uuid, &owner, &mut conn)?;
+    let order = cart_service::checkout_order(&session, &mut conn)?;
+    drop(conn);
+    let (Some(provider), Some(intent_id)) = (order.payment_provider.as_deref(), order.payment_intent_id.as_deref()) else {
+        return Err(CustomHttpError::BadRequest("Order has no payment".to_string()));
+    };
+    let handler = registry
+        .get(provider)
+        .ok_or(CustomHttpError::BadRequest("Payment handler not found".to_string()))?;
+
+    let provider_error = |e: String| {
+        log::error!("Payment confirmation for order {} failed: {}", order.uuid, e);
+        CustomHttpError::InternalServerError("Payment confirmation failed".to_string())
+    };
+    let mut intent = handler.get_payment_intent(intent_id).await.map_err(provider_error)?;
+    if intent.status == PaymentStatus::Pending {
+        intent = handler.confirm_payment_intent(intent_id).await.map_err(provider_error)?;
+    }
+    let settled = order_service::settle_payment(
+        &pool,
+        &email_service,
+        &order.uuid,
+        cart_service::payment_status_name(&intent.status),
+        handler.provider_name(),
+    )
+    .await
+    .map_err(CustomHttpError::InternalServerError)?;
+    Ok(HttpResponse::Ok().json(settled.unwrap_or(order)))
+}
@@ -3,6 +3,10 @@
 
 use actix_web::{get, post, web, HttpResponse, Result};
 use crate::graphql::AppSchema;
+use crate::graphql::context::RequestContext;
+use crate::helpers::tenant_helper::resolve_tenant_id;
+use crate::middleware::auth_middleware::get_user_context;
+use crate::models::DatabasePool;
 use serde::Deserialize;
 
 #[derive(Deserialize)]
@@ -20,6 +24,7 @@ pub async fn graphql_handler(
     schema: web::Data<AppSchema>,
     req: web::Json<GraphQLRequest>,
     http_req: actix_web::HttpRequest,
+    pool: web::Data<DatabasePool>,
 ) -> Result<HttpResponse> {
     // Extract and verify JWT token from Authorization header
     let auth_header = http_req
@@ -88,6 +93,12 @@ pub async fn graphql_handler(
     if let Some(operation_name) = &req.operation_name {
         request = request.operation_name(operation_name);
     }
+
+    // Resolvers enforce tenant scoping + RBAC from this context
+    request = request.data(RequestContext {
+        tenant_id: resolve_tenant_id(&http_req, &pool).ok(),
+        user: get_user_context(&http_req),
+    });
     
     let response = schema.execute(request).await;
     Ok(HttpResponse::Ok().json(response))
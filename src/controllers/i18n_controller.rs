@@ -11,6 +11,8 @@ pub struct CreateLanguageInput {
     pub name: String,
     pub native_name: Option<String>,
     pub is_rtl: Option<bool>,
+    /// Postgres text search configuration; derived from `code` when omitted
+    pub search_config: Option<String>,
 }
 
 /// List all languages
@@ -63,6 +65,10 @@ pub async fn create_language(
         is_default: Some(false),
         is_rtl: input.is_rtl,
         enabled: Some(true),
+        search_config: Some(
+            input.search_config.clone()
+                .unwrap_or_else(|| LanguageService::search_config_for_code(&input.code).to_string())
+        ),
     };
     
     match LanguageService::create_language(&mut conn, new_lang) {
@@ -1,43 +1,131 @@
 use actix_multipart::Multipart;
-use actix_web::{web, HttpResponse, Responder};
-use utoipa::ToSchema;
+use actix_web::{web, HttpRequest, HttpResponse, Responder, ResponseError};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use diesel::prelude::*;
 use futures_util::StreamExt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 
-use crate::models::media_models::{Media, NewMedia};
-use crate::schema::media;
+use crate::helpers::tenant_helper::{get_tenant_role, resolve_tenant_id};
+use crate::middleware::auth_middleware::get_user_context;
+use crate::models::db_connection::DatabasePool;
+use crate::models::media_models::{Media, MediaVariant, NewMedia, NewMediaVariant};
+use crate::models::rbac::{has_permission, Permission};
+use crate::schema::{media, media_variants};
 use crate::services::database_service;
-use crate::services::image_service;
+use crate::services::errors_service::CustomHttpError;
+use crate::services::image_service::{self, ImageSource, VariantFormat};
+use crate::services::image_transform_service::{self, TransformParams, TransformQuery, TRANSFORM_VARIANT_PREFIX};
+use crate::services::media_folder_service;
+use crate::services::media_inspection_service::{self, MediaKind, SNIFF_BYTES};
+use crate::services::media_policy_service::MediaPolicy;
+use crate::services::media_usage_service;
+use crate::services::media_variant_service;
 use crate::services::storage_service::{StorageService, StorageBackend};
 
-const MAX_FILE_SIZE: usize = 10 * 1024 * 1024; // 10MB
+#[derive(Deserialize, IntoParams)]
+pub struct ListMediaQuery {
+    /// Only media in this folder
+    pub folder_id: Option<i32>,
+    /// With `folder_id`, include media in its subfolders
+    pub recursive: Option<bool>,
+    /// Comma-separated tags; media must have all of them
+    pub tags: Option<String>,
+}
 
 /// List all media files
 #[utoipa::path(
     get,
     path = "/api/media",
     tag = "Content - Media",
+    params(ListMediaQuery),
     responses(
-        (status = 200, description = "List of media files (max 100)")
+        (status = 200, description = "List of the site's media files (max 100)"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "Folder not found")
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-pub async fn list_media() -> impl Responder {
-    use crate::schema::media::dsl::*;
-    
-    let mut conn = database_service::establish_connection();
-    
-    match media
-        .order(created_at.desc())
+pub async fn list_media(
+    req: HttpRequest,
+    query: web::Query<ListMediaQuery>,
+    pool: web::Data<DatabasePool>,
+) -> impl Responder {
+    let (tenant_id, _) = match authorize_media(&req, &pool, Permission::ViewContent) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(_) => return HttpResponse::InternalServerError().json("Database connection failed"),
+    };
+
+    let mut items = media::table
+        .filter(media::tenant_id.eq(tenant_id))
+        .order(media::created_at.desc())
         .limit(100)
-        .load::<Media>(&mut conn)
-    {
+        .into_boxed();
+    if let Some(folder_id) = query.folder_id {
+        let folder = match media_folder_service::find(tenant_id, folder_id, &mut conn) {
+            Ok(folder) => folder,
+            Err(e) => return e.error_response(),
+        };
+        let folder_ids = if query.recursive.unwrap_or(false) {
+            match media_folder_service::subtree_ids(&folder, &mut conn) {
+                Ok(ids) => ids,
+                Err(_) => return HttpResponse::InternalServerError().json("Failed to fetch folders"),
+            }
+        } else {
+            vec![folder.id]
+        };
+        items = items.filter(media::folder_id.eq_any(folder_ids));
+    }
+    let tags: Vec<Option<String>> = query
+        .tags
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| Some(tag.to_string()))
+        .collect();
+    if !tags.is_empty() {
+        items = items.filter(media::tags.contains(tags));
+    }
+
+    match items.load::<Media>(&mut conn) {
         Ok(items) => HttpResponse::Ok().json(items),
         Err(_) => HttpResponse::InternalServerError().json("Failed to fetch media"),
     }
 }
 
+#[derive(Serialize)]
+pub struct MediaVariantResponse {
+    pub name: String,
+    pub format: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub file_size: Option<i64>,
+    pub url: String,
+}
+
+#[derive(Serialize)]
+pub struct MediaDetailsResponse {
+    #[serde(flatten)]
+    pub media: Media,
+    pub url: String,
+    pub variants: Vec<MediaVariantResponse>,
+    /// srcset attributes by format ("jpeg", "webp"), built from stored variants
+    pub srcset: std::collections::HashMap<String, String>,
+    pub picture_html: Option<String>,
+}
+
 /// Get single media file
 #[utoipa::path(
     get,
@@ -47,99 +135,307 @@ pub async fn list_media() -> impl Responder {
         ("uuid" = String, Path, description = "Media UUID")
     ),
     responses(
-        (status = 200, description = "Media file details"),
+        (status = 200, description = "Media file details with its responsive variants"),
+        (status = 403, description = "Insufficient permissions"),
         (status = 404, description = "Media not found")
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-pub async fn get_media(media_uuid: web::Path<String>) -> impl Responder {
-    use crate::schema::media::dsl::*;
-    
-    let mut conn = database_service::establish_connection();
-    
-    match media
-        .filter(uuid.eq(media_uuid.as_str()))
-        .first::<Media>(&mut conn)
-    {
-        Ok(item) => HttpResponse::Ok().json(item),
-        Err(_) => HttpResponse::NotFound().json("Media not found"),
+pub async fn get_media(
+    req: HttpRequest,
+    media_uuid: web::Path<String>,
+    pool: web::Data<DatabasePool>,
+    storage: web::Data<StorageBackend>,
+) -> impl Responder {
+    let (tenant_id, _) = match authorize_media(&req, &pool, Permission::ViewContent) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(_) => return HttpResponse::InternalServerError().json("Database connection failed"),
+    };
+    let item = match find_tenant_media(&media_uuid, tenant_id, &mut conn) {
+        Ok(item) => item,
+        Err(response) => return response,
+    };
+    let variants = match media_variant_service::responsive_variants_for(item.id, &mut conn) {
+        Ok(variants) => variants,
+        Err(_) => return HttpResponse::InternalServerError().json("Failed to fetch media variants"),
+    };
+
+    let url = storage.get_url(&item.file_path);
+    let mut sources = media_variant_service::image_sources(&variants, &storage);
+    if let (Some(w), "image/jpeg") = (item.width, item.mime_type.as_str()) {
+        sources.push(ImageSource { url: url.clone(), width: w as u32, format: VariantFormat::Jpeg });
     }
+    let srcset = [VariantFormat::Jpeg, VariantFormat::WebP]
+        .iter()
+        .map(|format| (format.as_str().to_string(), image_service::generate_srcset(&sources, *format)))
+        .filter(|(_, srcset)| !srcset.is_empty())
+        .collect();
+    let picture_html = item.mime_type.starts_with("image/").then(|| {
+        image_service::generate_picture_html(
+            &url,
+            &sources,
+            item.alt_text.as_deref().unwrap_or(""),
+            item.title.as_deref(),
+            true,
+        )
+    });
+
+    let variants = variants
+        .into_iter()
+        .map(|v| MediaVariantResponse {
+            url: storage.get_url(&v.file_path),
+            name: v.variant_name,
+            format: v.format,
+            width: v.width,
+            height: v.height,
+            file_size: v.file_size,
+        })
+        .collect();
+
+    HttpResponse::Ok().json(MediaDetailsResponse { media: item, url, variants, srcset, picture_html })
+}
+
+/// Pages and modules that use a media file
+#[utoipa::path(
+    get,
+    path = "/api/media/{uuid}/usage",
+    tag = "Content - Media",
+    params(
+        ("uuid" = String, Path, description = "Media UUID")
+    ),
+    responses(
+        (status = 200, description = "Pages and modules referencing the media", body = Vec<MediaUsage>),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "Media not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_media_usage(
+    req: HttpRequest,
+    media_uuid: web::Path<String>,
+    pool: web::Data<DatabasePool>,
+) -> impl Responder {
+    let (tenant_id, _) = match authorize_media(&req, &pool, Permission::ViewContent) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(_) => return HttpResponse::InternalServerError().json("Database connection failed"),
+    };
+    let item = match find_tenant_media(&media_uuid, tenant_id, &mut conn) {
+        Ok(item) => item,
+        Err(response) => return response,
+    };
+    match media_usage_service::usages_of(item.id, &mut conn) {
+        Ok(usages) => HttpResponse::Ok().json(usages),
+        Err(_) => HttpResponse::InternalServerError().json("Failed to fetch media usage"),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct DeleteMediaQuery {
+    /// Delete even if pages or modules still reference the media
+    pub force: Option<bool>,
 }
 
 /// Delete media file
+///
+/// Refused with 409 while pages or modules reference the media, listing
+/// them, unless `force=true`.
 #[utoipa::path(
     delete,
     path = "/api/media/{uuid}",
     tag = "Content - Media",
     params(
-        ("uuid" = String, Path, description = "Media UUID")
+        ("uuid" = String, Path, description = "Media UUID"),
+        DeleteMediaQuery
     ),
     responses(
         (status = 200, description = "Media deleted"),
-        (status = 404, description = "Media not found")
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "Media not found"),
+        (status = 409, description = "Media is still in use; body lists where")
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
 pub async fn delete_media(
+    req: HttpRequest,
     media_uuid: web::Path<String>,
+    query: web::Query<DeleteMediaQuery>,
+    pool: web::Data<DatabasePool>,
     storage: web::Data<StorageBackend>,
 ) -> impl Responder {
-    use crate::schema::media::dsl::*;
-    
-    let mut conn = database_service::establish_connection();
+    let (tenant_id, _) = match authorize_media(&req, &pool, Permission::DeleteContent) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(_) => return HttpResponse::InternalServerError().json("Database connection failed"),
+    };
     
     // Get media to find file path
-    let media_item = match media
-        .filter(uuid.eq(media_uuid.as_str()))
-        .first::<Media>(&mut conn)
-    {
+    let media_item = match find_tenant_media(&media_uuid, tenant_id, &mut conn) {
         Ok(item) => item,
-        Err(_) => return HttpResponse::NotFound().json("Media not found"),
+        Err(response) => return response,
     };
+
+    if !query.force.unwrap_or(false) {
+        match media_usage_service::usages_of(media_item.id, &mut conn) {
+            Ok(usages) if !usages.is_empty() => {
+                return HttpResponse::Conflict().json(serde_json::json!({
+                    "message": "Media is still used by pages or modules; delete with force=true to remove it anyway",
+                    "usages": usages,
+                }));
+            }
+            Ok(_) => {}
+            Err(_) => return HttpResponse::InternalServerError().json("Failed to check media usage"),
+        }
+    }
+
+    // Variant rows go with the media row; their files are removed below
+    let variants = media_variant_service::variants_for(media_item.id, &mut conn).unwrap_or_default();
     
     // Delete database record
-    match diesel::delete(media.filter(uuid.eq(media_uuid.as_str())))
+    match diesel::delete(media::table.find(media_item.id))
         .execute(&mut conn)
     {
         Ok(_) => {
-            // Delete file from storage
-            // Extract filename from file_path or store simple filename in DB? 
-            // Currently file_path stores "uploads/filename". S3 implementation expects "filename".
-            // We should ideally store the key/filename in the DB.
-            // For now, let's assume file_path might be a full path or just filename.
-            // But verify: upload_media stores `storage_path`.
-            
-            // FIXME: The DB stores `file_path`. If it was "uploads/uuid.ext", 
-            // we need to extract "uuid.ext" for S3.
-            let target_filename = std::path::Path::new(&media_item.file_path)
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("unknown");
-                
-            let _ = storage.delete(target_filename).await;
+            // Delete files from storage
+            let _ = storage.delete(&media_item.file_path).await;
+            for variant in &variants {
+                let _ = storage.delete(&variant.file_path).await;
+            }
             HttpResponse::Ok().json("Media deleted")
         }
         Err(_) => HttpResponse::InternalServerError().json("Failed to delete media"),
     }
 }
 
+/// Where an upload is written while it arrives, removed once handled
+struct SpooledUpload {
+    path: PathBuf,
+    file: tokio::fs::File,
+    size: u64,
+}
+
+impl SpooledUpload {
+    async fn create() -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("media-upload-{}", Uuid::new_v4()));
+        let file = tokio::fs::File::create(&path).await?;
+        Ok(Self { path, file, size: 0 })
+    }
+
+    async fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.file.write_all(data).await?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+}
+
+impl Drop for SpooledUpload {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Tenant and user of the request, if the user has `permission` on the site
+fn authorize_media(req: &HttpRequest, pool: &DatabasePool, permission: Permission) -> Result<(i32, i32), HttpResponse> {
+    let user_ctx = get_user_context(req).ok_or_else(|| HttpResponse::Unauthorized().json("User not authenticated"))?;
+    let tenant_id = resolve_tenant_id(req, pool).map_err(|e| HttpResponse::BadRequest().json(e))?;
+    let mut conn = pool.get().map_err(|_| HttpResponse::InternalServerError().json("Database connection failed"))?;
+    let role = get_tenant_role(tenant_id, user_ctx.user_id, &mut conn).map_err(|e| HttpResponse::Forbidden().json(e))?;
+    if !has_permission(&role, permission) {
+        return Err(HttpResponse::Forbidden().json("Insufficient permissions"));
+    }
+    Ok((tenant_id, user_ctx.user_id))
+}
+
+/// Tenant and user of the request and the tenant's media policy, if the user may add content to the site
+fn authorize_upload(req: &HttpRequest, pool: &DatabasePool) -> Result<(i32, i32, MediaPolicy), HttpResponse> {
+    let (tenant_id, user_id) = authorize_media(req, pool, Permission::EditContent)?;
+    let mut conn = pool.get().map_err(|_| HttpResponse::InternalServerError().json("Database connection failed"))?;
+    let policy = MediaPolicy::for_tenant(tenant_id, &mut conn)
+        .map_err(|_| HttpResponse::InternalServerError().json("Failed to load media policy"))?;
+    Ok((tenant_id, user_id, policy))
+}
+
+/// A media item of the tenant
+fn find_tenant_media(media_uuid: &str, tenant_id: i32, conn: &mut PgConnection) -> Result<Media, HttpResponse> {
+    media::table
+        .filter(media::uuid.eq(media_uuid))
+        .filter(media::tenant_id.eq(tenant_id))
+        .first::<Media>(conn)
+        .optional()
+        .map_err(|_| HttpResponse::InternalServerError().json("Failed to fetch media"))?
+        .ok_or_else(|| HttpResponse::NotFound().json("Media not found"))
+}
+
+/// Media upload policy for the current site
+#[utoipa::path(
+    get,
+    path = "/api/media/policy",
+    tag = "Content - Media",
+    responses(
+        (status = 200, description = "Allowed kinds and size limits", body = MediaPolicy),
+        (status = 403, description = "Insufficient permissions")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_media_policy(req: HttpRequest, pool: web::Data<DatabasePool>) -> impl Responder {
+    match authorize_upload(&req, &pool) {
+        Ok((_, _, policy)) => HttpResponse::Ok().json(policy),
+        Err(response) => response,
+    }
+}
+
 /// Upload media file (multipart form data)
+///
+/// Accepts images, SVG (sanitized), PDF, video, audio and office documents,
+/// subject to the site's media policy. The file is streamed to disk as it
+/// arrives, never held in memory whole (except images, which are optimized),
+/// then handed to storage; large files go to S3 as a multipart upload.
 #[utoipa::path(
     post,
     path = "/api/media/upload",
     tag = "Content - Media",
-    request_body(content = String, description = "Multipart form with file, alt_text, caption, folder", content_type = "multipart/form-data"),
+    request_body(content = String, description = "Multipart form with file, alt_text, caption, folder (folder ID or path)", content_type = "multipart/form-data"),
     responses(
         (status = 201, description = "File uploaded successfully"),
         (status = 400, description = "Invalid file or upload failed"),
-        (status = 413, description = "File too large")
+        (status = 413, description = "File too large for the site's media policy"),
+        (status = 415, description = "File type not allowed by the site's media policy")
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
 pub async fn upload_media(
-    req: actix_web::HttpRequest, 
+    req: HttpRequest,
     mut payload: Multipart,
+    pool: web::Data<DatabasePool>,
     storage: web::Data<StorageBackend>,
 ) -> impl Responder {
+    let (tenant_id, user_id, policy) = match authorize_upload(&req, &pool) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+
     let mut filename = String::new();
-    let mut file_data: Vec<u8> = Vec::new();
+    let mut upload: Option<(SpooledUpload, String, MediaKind)> = None;
     let mut alt_text = None;
     let mut caption = None;
     let mut folder = None;
@@ -162,22 +458,53 @@ pub async fn upload_media(
                     Some(name) => name.to_string(),
                     None => "unknown".to_string(),
                 };
-                
-                // Stream file data
+
+                // Read enough to identify the type before accepting the rest
+                let mut head: Vec<u8> = Vec::new();
+                while head.len() < SNIFF_BYTES {
+                    match field.next().await {
+                        Some(Ok(data)) => head.extend_from_slice(&data),
+                        Some(Err(_)) => return HttpResponse::BadRequest().json("Failed to read file"),
+                        None => break,
+                    }
+                }
+
+                let mime_type = media_inspection_service::detect_mime(&head, &filename);
+                let (kind, max_bytes) = match policy.check(&mime_type) {
+                    Ok(allowed) => allowed,
+                    Err(reason) => return HttpResponse::UnsupportedMediaType().json(reason),
+                };
+                let too_large = || HttpResponse::PayloadTooLarge()
+                    .json(format!("File too large (max {}MB for {})", max_bytes / 1024 / 1024, kind.as_str()));
+
+                let mut spool = match SpooledUpload::create().await {
+                    Ok(spool) => spool,
+                    Err(e) => return HttpResponse::InternalServerError().json(format!("Failed to buffer upload: {}", e)),
+                };
+                if spool.write(&head).await.is_err() {
+                    return HttpResponse::InternalServerError().json("Failed to buffer upload");
+                }
+
+                // Stream the rest to disk, enforcing the limit as it arrives
                 while let Some(chunk) = field.next().await {
                     let data = match chunk {
                         Ok(data) => data,
                         Err(_) => return HttpResponse::BadRequest().json("Failed to read file"),
                     };
-                    
-                    file_data.extend_from_slice(&data);
-                    
-                    // Check file size limit
-                    if file_data.len() > MAX_FILE_SIZE {
-                        return HttpResponse::PayloadTooLarge()
-                            .json(format!("File too large (max {}MB)", MAX_FILE_SIZE / 1024 / 1024));
+                    if spool.size + data.len() as u64 > max_bytes {
+                        return too_large();
                     }
+                    if spool.write(&data).await.is_err() {
+                        return HttpResponse::InternalServerError().json("Failed to buffer upload");
+                    }
+                }
+                if spool.size > max_bytes {
+                    return too_large();
                 }
+                if spool.file.flush().await.is_err() {
+                    return HttpResponse::InternalServerError().json("Failed to buffer upload");
+                }
+                upload = Some((spool, mime_type, kind));
             }
             Some("alt_text") => {
                 let mut value = String::new();
@@ -217,65 +544,102 @@ pub async fn upload_media(
     }
     
     // Validate file was provided
-    if file_data.is_empty() {
-        return HttpResponse::BadRequest().json("No file provided");
-    }
-    
-    // Detect MIME type
-    let mime_type = match infer::get(&file_data) {
-        Some(kind) => kind.mime_type().to_string(),
-        None => "application/octet-stream".to_string(),
+    let (spool, mut mime_type, kind) = match upload {
+        Some(upload) if upload.0.size > 0 => upload,
+        _ => return HttpResponse::BadRequest().json("No file provided"),
     };
-    
-    // Validate image type (for MVP only allow images)
-    if !mime_type.starts_with("image/") {
-        return HttpResponse::BadRequest()
-            .json(format!("Invalid file type. Only images allowed. Got: {}", mime_type));
-    }
-    
-    // Extract image dimensions
-    let (width, height) = match image::load_from_memory(&file_data) {
-        Ok(img) => (Some(img.width() as i32), Some(img.height() as i32)),
-        Err(_) => (None, None),  // Not an image or unreadable
+
+    // `folder` is a folder ID or path; it must exist
+    let folder_id = match folder {
+        Some(reference) => {
+            let found = pool
+                .get()
+                .map_err(|_| CustomHttpError::InternalServerError("Database connection failed".to_string()))
+                .and_then(|mut conn| media_folder_service::resolve(tenant_id, &reference, &mut conn));
+            match found {
+                Ok(found) => Some(found.id),
+                Err(e) => return e.error_response(),
+            }
+        }
+        None => None,
     };
-    
-    // Generate unique filename with original extension
+
     let filename_path = PathBuf::from(&filename);
-    let ext = filename_path
+    let mut ext = filename_path
         .extension()
         .and_then(|s| s.to_str())
-        .unwrap_or("bin");
+        .unwrap_or("bin")
+        .to_lowercase();
     let new_uuid = Uuid::new_v4().to_string();
-    let new_filename = format!("{}.{}", new_uuid, ext);
-    
-    // Optimize images (resize, compress, generate WebP)
-    let (optimized_data, webp_data, opt_width, opt_height) = if mime_type.starts_with("image/") {
-        match image_service::optimize_image(&file_data, 1920, true) {
-            Ok((jpeg_bytes, webp_bytes, w, h)) => {
-                log::info!("Image optimized: {}x{}, original: {} bytes, optimized: {} bytes", 
-                    w, h, file_data.len(), jpeg_bytes.len());
-                (jpeg_bytes, webp_bytes, Some(w as i32), Some(h as i32))
+    let original_size = spool.size;
+
+    let stored = match kind {
+        MediaKind::Image => {
+            let path = spool.path.clone();
+            let processed = web::block(move || std::fs::read(&path).map(|data| process_image(&data))).await;
+            match processed {
+                Ok(Ok(Ok(image))) => {
+                    mime_type = image.mime_type.to_string();
+                    ext = image.extension.to_string();
+                    StoredUpload::Image(image)
+                }
+                Ok(Ok(Err(e))) => return HttpResponse::BadRequest().json(e),
+                _ => return HttpResponse::InternalServerError().json("Failed to read upload"),
             }
-            Err(e) => {
-                log::warn!("Image optimization failed: {}, using original", e);
-                (file_data.clone(), None, width, height)
+        }
+        MediaKind::Svg => {
+            let path = spool.path.clone();
+            let sanitized = web::block(move || {
+                std::fs::read_to_string(&path)
+                    .map_err(|_| "SVG must be UTF-8 text".to_string())
+                    .and_then(|svg| media_inspection_service::sanitize_svg(&svg))
+            })
+            .await;
+            match sanitized {
+                Ok(Ok(svg)) => {
+                    ext = "svg".to_string();
+                    StoredUpload::Bytes(svg.into_bytes(), serde_json::json!({"kind": kind.as_str()}))
+                }
+                Ok(Err(e)) => return HttpResponse::BadRequest().json(e),
+                Err(_) => return HttpResponse::InternalServerError().json("Failed to read upload"),
             }
         }
-    } else {
-        (file_data.clone(), None, width, height)
+        _ => {
+            let (path, mime) = (spool.path.clone(), mime_type.clone());
+            let metadata = web::block(move || media_inspection_service::extract_metadata(kind, &mime, &path))
+                .await
+                .unwrap_or_else(|_| serde_json::json!({"kind": kind.as_str()}));
+            StoredUpload::File(metadata)
+        }
     };
-    
-    // Save optimized file to storage
-    let storage_path = match storage.save(&new_filename, &optimized_data, &mime_type).await {
+
+    let new_filename = format!("{}.{}", new_uuid, ext);
+
+    // Save file to storage
+    let saved = match &stored {
+        StoredUpload::Image(image) => storage.save(&new_filename, &image.data, &mime_type).await,
+        StoredUpload::Bytes(data, _) => storage.save(&new_filename, data, &mime_type).await,
+        StoredUpload::File(_) => storage.save_file(&new_filename, &spool.path, &mime_type).await,
+    };
+    let storage_path = match saved {
         Ok(path) => path, // For S3 this is the key, for Local it is uploads/filename
         Err(e) => return HttpResponse::InternalServerError().json(format!("Failed to save file: {}", e)),
     };
-    
+    drop(spool);
+
+    let (file_size, width, height, metadata, webp_data, variant_source) = match stored {
+        StoredUpload::Image(image) => {
+            (image.data.len() as u64, image.width, image.height, image.metadata, image.webp, Some(image.data))
+        }
+        StoredUpload::Bytes(data, metadata) => (data.len() as u64, None, None, metadata, None, None),
+        StoredUpload::File(metadata) => (original_size, None, None, metadata, None, None),
+    };
+
     // Save WebP variant if generated
-    let webp_path = match webp_data {
+    let webp_path = match &webp_data {
         Some(webp_bytes) => {
             let webp_filename = format!("{}.webp", new_uuid);
-            match storage.save(&webp_filename, &webp_bytes, "image/webp").await {
+            match storage.save(&webp_filename, webp_bytes, "image/webp").await {
                 Ok(path) => {
                     log::info!("WebP variant saved: {}", webp_filename);
                     Some(path)
@@ -293,47 +657,733 @@ pub async fn upload_media(
         original_filename: filename.clone(),
         file_path: storage_path.clone(),
         mime_type: mime_type.clone(),
-        file_size: optimized_data.len() as i64,  // Size of optimized file
-        width: opt_width,
-        height: opt_height,
+        file_size: file_size as i64,
+        width,
+        height,
         alt_text,
         title: None,
         description: None,
         tags: None,
-        uploaded_by: crate::middleware::auth_middleware::get_user_context(&req)
-            .map(|ctx| Some(ctx.user_id))
-            .unwrap_or(None),
+        uploaded_by: Some(user_id),
+        tenant_id: Some(tenant_id),
+        metadata: Some(metadata.clone()),
+        folder_id,
     };
     
     let mut conn = database_service::establish_connection();
     
-    match diesel::insert_into(media::table)
+    let inserted = diesel::insert_into(media::table)
         .values(&new_media)
-        .execute(&mut conn)
-    {
+        .get_result::<Media>(&mut conn);
+
+    if let Ok(created) = &inserted {
+        // The full-size WebP is the largest WebP source
+        if let (Some(path), Some(w)) = (&webp_path, width) {
+            let full_size = NewMediaVariant {
+                media_id: created.id,
+                variant_name: media_variant_service::variant_name(w as u32, VariantFormat::WebP),
+                file_path: path.clone(),
+                width,
+                height,
+                file_size: webp_data.as_ref().map(|data| data.len() as i64),
+                format: VariantFormat::WebP.as_str().to_string(),
+            };
+            if let Err(e) = diesel::insert_into(media_variants::table).values(&full_size).execute(&mut conn) {
+                log::warn!("Failed to record WebP variant of {}: {}", new_uuid, e);
+            }
+        }
+
+        // Smaller sizes are generated after responding
+        if let Some(source) = variant_source {
+            let (media_id, media_uuid) = (created.id, new_uuid.clone());
+            let (storage, pool) = (storage.clone(), pool.get_ref().clone());
+            actix_web::rt::spawn(async move {
+                match media_variant_service::generate_for_media(media_id, media_uuid.clone(), source, storage, pool).await {
+                    Ok(count) => log::info!("Generated {} responsive variants for {}", count, media_uuid),
+                    Err(e) => log::warn!("Responsive variants for {} failed: {}", media_uuid, e),
+                }
+            });
+        }
+    }
+
+    match inserted {
         Ok(_) => HttpResponse::Created().json(serde_json::json!({
             "uuid": new_uuid,
             "filename": new_filename,
             "original_filename": filename,
             "mime_type": mime_type,
-            "file_size": optimized_data.len(),
-            "original_size": file_data.len(),
-            "width": opt_width,
-            "height": opt_height,
+            "kind": kind,
+            "file_size": file_size,
+            "original_size": original_size,
+            "width": width,
+            "height": height,
+            "metadata": metadata,
             "storage_path": storage_path,
             "webp_generated": webp_path.is_some(),
             "webp_path": webp_path,
-            "message": "File uploaded and optimized successfully"
+            "message": "File uploaded successfully"
         })),
         Err(e) => {
             // Delete files if database insert fails
-            let _ = storage.delete(&new_filename).await;
-            if let Some(_) = webp_path {
-                 let webp_filename = format!("{}.webp", new_uuid);
-                let _ = storage.delete(&webp_filename).await;
+            let _ = storage.delete(&storage_path).await;
+            if let Some(path) = &webp_path {
+                let _ = storage.delete(path).await;
             }
             HttpResponse::InternalServerError()
                 .json(format!("Failed to save media metadata: {}", e))
         }
     }
 }
+
+#[derive(Deserialize, ToSchema)]
+pub struct FocalPointRequest {
+    /// 0 (left) to 1 (right); null with `y` null resets to the centre
+    pub x: Option<f32>,
+    /// 0 (top) to 1 (bottom)
+    pub y: Option<f32>,
+}
+
+/// Set the point image crops keep in view
+///
+/// Previously transformed copies are discarded so they are re-cropped on
+/// next request.
+#[utoipa::path(
+    put,
+    path = "/api/media/{uuid}/focal-point",
+    tag = "Content - Media",
+    params(
+        ("uuid" = String, Path, description = "Media UUID")
+    ),
+    request_body = FocalPointRequest,
+    responses(
+        (status = 200, description = "Focal point saved, with the updated media"),
+        (status = 400, description = "Coordinates outside 0-1, or only one given"),
+        (status = 404, description = "Media not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn set_focal_point(
+    req: HttpRequest,
+    media_uuid: web::Path<String>,
+    payload: web::Json<FocalPointRequest>,
+    pool: web::Data<DatabasePool>,
+    storage: web::Data<StorageBackend>,
+) -> impl Responder {
+    let (tenant_id, _) = match authorize_media(&req, &pool, Permission::EditContent) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let (x, y) = (payload.x, payload.y);
+    if x.is_some() != y.is_some() || [x, y].iter().flatten().any(|v| !(0.0..=1.0).contains(v)) {
+        return HttpResponse::BadRequest().json("x and y must both be given, between 0 and 1, or both be null");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(_) => return HttpResponse::InternalServerError().json("Database connection failed"),
+    };
+    let item = match find_tenant_media(&media_uuid, tenant_id, &mut conn) {
+        Ok(item) => item,
+        Err(response) => return response,
+    };
+
+    let updated = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let updated = diesel::update(media::table.find(item.id))
+            .set((
+                media::focal_x.eq(x),
+                media::focal_y.eq(y),
+                media::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .get_result::<Media>(conn)?;
+        let transforms = diesel::delete(
+            media_variants::table
+                .filter(media_variants::media_id.eq(item.id))
+                .filter(media_variants::variant_name.like(format!("{}%", TRANSFORM_VARIANT_PREFIX))),
+        )
+        .get_results::<MediaVariant>(conn)?;
+        Ok((updated, transforms))
+    });
+
+    match updated {
+        Ok((updated, transforms)) => {
+            for variant in &transforms {
+                let _ = storage.delete(&variant.file_path).await;
+            }
+            HttpResponse::Ok().json(updated)
+        }
+        Err(_) => HttpResponse::InternalServerError().json("Failed to save focal point"),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TransformUrlRequest {
+    pub w: Option<u32>,
+    pub h: Option<u32>,
+    /// "cover" (default), "contain" or "fill"
+    pub fit: Option<String>,
+    /// "jpeg" (default), "webp" or "png"
+    pub fmt: Option<String>,
+    /// JPEG quality, 1-100 (default 80)
+    pub q: Option<u8>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TransformUrlResponse {
+    /// Path of the signed transform, relative to the site
+    pub url: String,
+}
+
+/// Sign a transform URL for an image
+#[utoipa::path(
+    post,
+    path = "/api/media/{uuid}/transform-url",
+    tag = "Content - Media",
+    params(
+        ("uuid" = String, Path, description = "Media UUID")
+    ),
+    request_body = TransformUrlRequest,
+    responses(
+        (status = 200, description = "Signed URL", body = TransformUrlResponse),
+        (status = 400, description = "Invalid parameters or not a raster image"),
+        (status = 404, description = "Media not found"),
+        (status = 503, description = "MEDIA_SIGNING_KEY is not configured")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_transform_url(
+    req: HttpRequest,
+    media_uuid: web::Path<String>,
+    payload: web::Json<TransformUrlRequest>,
+    pool: web::Data<DatabasePool>,
+) -> impl Responder {
+    let (tenant_id, _) = match authorize_media(&req, &pool, Permission::ViewContent) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let key = match image_transform_service::signing_key() {
+        Some(key) => key,
+        None => return HttpResponse::ServiceUnavailable().json("Image transforms are not configured"),
+    };
+    let payload = payload.into_inner();
+    let params = match TransformParams::from_query(&TransformQuery {
+        w: payload.w,
+        h: payload.h,
+        fit: payload.fit,
+        fmt: payload.fmt,
+        q: payload.q,
+        s: None,
+    }) {
+        Ok(params) => params,
+        Err(e) => return HttpResponse::BadRequest().json(e),
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(_) => return HttpResponse::InternalServerError().json("Database connection failed"),
+    };
+    let item = match find_tenant_media(&media_uuid, tenant_id, &mut conn) {
+        Ok(item) => item,
+        Err(response) => return response,
+    };
+    if MediaKind::of_mime(&item.mime_type) != Some(MediaKind::Image) {
+        return HttpResponse::BadRequest().json("Only raster images can be transformed");
+    }
+
+    HttpResponse::Ok().json(TransformUrlResponse {
+        url: image_transform_service::signed_path(&key, &item.uuid, &params),
+    })
+}
+
+/// Resized, cropped or re-encoded image
+///
+/// Public; parameters must carry a signature from
+/// `POST /api/media/{uuid}/transform-url`. Each distinct transform is
+/// generated once and stored as a media variant.
+#[utoipa::path(
+    get,
+    path = "/media/{uuid}",
+    tag = "Content - Media",
+    params(
+        ("uuid" = String, Path, description = "Media UUID"),
+        TransformQuery
+    ),
+    responses(
+        (status = 200, description = "Transformed image"),
+        (status = 400, description = "Invalid parameters or not a raster image"),
+        (status = 403, description = "Missing or invalid signature"),
+        (status = 404, description = "Media not found")
+    )
+)]
+pub async fn transform_media(
+    media_uuid: web::Path<String>,
+    query: web::Query<TransformQuery>,
+    pool: web::Data<DatabasePool>,
+    storage: web::Data<StorageBackend>,
+) -> impl Responder {
+    let params = match TransformParams::from_query(&query) {
+        Ok(params) => params,
+        Err(e) => return HttpResponse::BadRequest().json(e),
+    };
+    let signed = match (image_transform_service::signing_key(), query.s.as_deref()) {
+        (Some(key), Some(signature)) => image_transform_service::verify(&key, &media_uuid, &params, signature),
+        _ => false,
+    };
+    if !signed {
+        return HttpResponse::Forbidden().json("Invalid signature");
+    }
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(_) => return HttpResponse::InternalServerError().json("Database connection failed"),
+    };
+    let item = match media::table.filter(media::uuid.eq(media_uuid.as_str())).first::<Media>(&mut conn) {
+        Ok(item) => item,
+        Err(_) => return HttpResponse::NotFound().json("Media not found"),
+    };
+    if MediaKind::of_mime(&item.mime_type) != Some(MediaKind::Image) {
+        return HttpResponse::BadRequest().json("Only raster images can be transformed");
+    }
+
+    let focal = (item.focal_x.unwrap_or(0.5), item.focal_y.unwrap_or(0.5));
+    let name = params.variant_name(&item.uuid, focal);
+    let transformed = |data: Vec<u8>| {
+        HttpResponse::Ok()
+            .content_type(params.format.mime_type())
+            .insert_header(("Cache-Control", "public, max-age=3600"))
+            .body(data)
+    };
+
+    // Served from storage if this transform was made before
+    let cached = media_variants::table
+        .filter(media_variants::media_id.eq(item.id))
+        .filter(media_variants::variant_name.eq(&name))
+        .first::<MediaVariant>(&mut conn)
+        .optional();
+    if let Ok(Some(variant)) = cached {
+        if let Ok(Some(data)) = storage.load(&variant.file_path).await {
+            return transformed(data);
+        }
+    }
+
+    let original = match storage.load(&item.file_path).await {
+        Ok(Some(data)) => data,
+        Ok(None) => return HttpResponse::NotFound().json("Media file not found"),
+        Err(e) => return HttpResponse::InternalServerError().json(format!("Failed to read media: {}", e)),
+    };
+    let job = params.clone();
+    let (data, width, height) = match web::block(move || image_transform_service::transform(&original, &job, focal)).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return HttpResponse::UnprocessableEntity().json(e),
+        Err(_) => return HttpResponse::InternalServerError().json("Image transform failed"),
+    };
+
+    let filename = format!("{}-{}.{}", item.uuid, name, params.format.extension());
+    match storage.save(&filename, &data, params.format.mime_type()).await {
+        Ok(file_path) => {
+            let inserted = diesel::insert_into(media_variants::table)
+                .values(&NewMediaVariant {
+                    media_id: item.id,
+                    variant_name: name,
+                    file_path,
+                    width: Some(width as i32),
+                    height: Some(height as i32),
+                    file_size: Some(data.len() as i64),
+                    format: params.format.as_str().to_string(),
+                })
+                .on_conflict((media_variants::media_id, media_variants::variant_name))
+                .do_update()
+                .set(media_variants::file_size.eq(data.len() as i64))
+                .execute(&mut conn);
+            if let Err(e) = inserted {
+                log::warn!("Failed to record transform of media {}: {}", item.uuid, e);
+            }
+        }
+        Err(e) => log::warn!("Failed to store transform of media {}: {}", item.uuid, e),
+    }
+
+    transformed(data)
+}
+
+/// Presigned downloads last an hour unless asked otherwise, and a week at most
+const DEFAULT_DOWNLOAD_URL_SECS: u64 = 3600;
+const MAX_DOWNLOAD_URL_SECS: u64 = 7 * 24 * 3600;
+const UPLOAD_URL_SECS: u64 = 15 * 60;
+
+#[derive(Deserialize, IntoParams)]
+pub struct DownloadUrlQuery {
+    /// Seconds the URL stays valid (default 3600, max 604800)
+    pub expires_in: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DownloadUrlResponse {
+    pub url: String,
+    pub expires_in: u64,
+}
+
+/// Time-limited download URL for a media file
+///
+/// For buckets that aren't public. With local storage the plain file URL is
+/// returned.
+#[utoipa::path(
+    get,
+    path = "/api/media/{uuid}/download-url",
+    tag = "Content - Media",
+    params(
+        ("uuid" = String, Path, description = "Media UUID"),
+        DownloadUrlQuery
+    ),
+    responses(
+        (status = 200, description = "Presigned URL", body = DownloadUrlResponse),
+        (status = 404, description = "Media not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_download_url(
+    req: HttpRequest,
+    media_uuid: web::Path<String>,
+    query: web::Query<DownloadUrlQuery>,
+    pool: web::Data<DatabasePool>,
+    storage: web::Data<StorageBackend>,
+) -> impl Responder {
+    let (tenant_id, _) = match authorize_media(&req, &pool, Permission::ViewContent) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(_) => return HttpResponse::InternalServerError().json("Database connection failed"),
+    };
+    let item = match find_tenant_media(&media_uuid, tenant_id, &mut conn) {
+        Ok(item) => item,
+        Err(response) => return response,
+    };
+
+    let expires_in = query.expires_in.unwrap_or(DEFAULT_DOWNLOAD_URL_SECS).clamp(1, MAX_DOWNLOAD_URL_SECS);
+    match storage
+        .presigned_get_url(&item.file_path, std::time::Duration::from_secs(expires_in))
+        .await
+    {
+        Ok(url) => HttpResponse::Ok().json(DownloadUrlResponse { url, expires_in }),
+        Err(e) => HttpResponse::InternalServerError().json(format!("Failed to sign URL: {}", e)),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UploadUrlRequest {
+    pub filename: String,
+    pub mime_type: String,
+    /// Size in bytes, checked against the site's media policy
+    pub size: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UploadUrlResponse {
+    /// UUID the media will get; pass it to `upload-complete`
+    pub uuid: String,
+    /// PUT the file here with the same Content-Type
+    pub upload_url: String,
+    pub expires_in: u64,
+    /// Signs the UUID, filename and type; pass it to `upload-complete`
+    pub upload_token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UploadCompleteRequest {
+    pub uuid: String,
+    /// Must match the filename the upload URL was issued for
+    pub filename: String,
+    /// Must match the type the upload URL was issued for
+    pub mime_type: String,
+    /// `upload_token` from `upload-url`
+    pub upload_token: String,
+    pub alt_text: Option<String>,
+    pub folder_id: Option<i32>,
+}
+
+/// Storage key of a direct upload
+fn direct_upload_filename(media_uuid: &str, filename: &str) -> String {
+    let ext = PathBuf::from(filename)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("bin")
+        .to_lowercase();
+    format!("{}.{}", media_uuid, ext)
+}
+
+/// Token binding a direct upload's UUID, tenant, filename and type, so
+/// `upload-complete` registers the file as it was checked in `upload-url`
+fn upload_token_mac(key: &[u8], media_uuid: &str, tenant_id: i32, filename: &str, mime_type: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(format!("upload\n{}\n{}\n{}\n{}", media_uuid, tenant_id, filename, mime_type).as_bytes());
+    mac
+}
+
+fn sign_upload(key: &[u8], media_uuid: &str, tenant_id: i32, filename: &str, mime_type: &str) -> String {
+    hex::encode(upload_token_mac(key, media_uuid, tenant_id, filename, mime_type).finalize().into_bytes())
+}
+
+fn verify_upload(key: &[u8], media_uuid: &str, tenant_id: i32, filename: &str, mime_type: &str, token: &str) -> bool {
+    match hex::decode(token) {
+        Ok(token) => upload_token_mac(key, media_uuid, tenant_id, filename, mime_type).verify_slice(&token).is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn upload_signing_key() -> Result<Vec<u8>, HttpResponse> {
+    image_transform_service::signing_key()
+        .ok_or_else(|| HttpResponse::ServiceUnavailable().json("Direct uploads need MEDIA_SIGNING_KEY"))
+}
+
+/// Checks a direct upload against the policy. Images and SVGs are processed on
+/// upload, so they must go through `/api/media/upload`.
+fn check_direct_upload(policy: &MediaPolicy, mime_type: &str) -> Result<(MediaKind, u64), HttpResponse> {
+    let (kind, max_bytes) = policy.check(mime_type).map_err(|reason| HttpResponse::UnsupportedMediaType().json(reason))?;
+    if matches!(kind, MediaKind::Image | MediaKind::Svg) {
+        return Err(HttpResponse::BadRequest().json("Images must be uploaded through /api/media/upload"));
+    }
+    Ok((kind, max_bytes))
+}
+
+/// Presigned URL for uploading a large file straight to S3
+///
+/// The client PUTs the file to `upload_url`, then calls
+/// `POST /api/media/upload-complete` to register it. Only available with S3
+/// storage, and not for images or SVGs.
+#[utoipa::path(
+    post,
+    path = "/api/media/upload-url",
+    tag = "Content - Media",
+    request_body = UploadUrlRequest,
+    responses(
+        (status = 200, description = "Upload URL", body = UploadUrlResponse),
+        (status = 400, description = "Images and SVGs can't be uploaded directly, or storage is local"),
+        (status = 413, description = "File too large for the site's media policy"),
+        (status = 415, description = "File type not allowed by the site's media policy"),
+        (status = 503, description = "MEDIA_SIGNING_KEY is not set")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_upload_url(
+    req: HttpRequest,
+    payload: web::Json<UploadUrlRequest>,
+    pool: web::Data<DatabasePool>,
+    storage: web::Data<StorageBackend>,
+) -> impl Responder {
+    let (tenant_id, _, policy) = match authorize_upload(&req, &pool) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let signing_key = match upload_signing_key() {
+        Ok(key) => key,
+        Err(response) => return response,
+    };
+    let (kind, max_bytes) = match check_direct_upload(&policy, &payload.mime_type) {
+        Ok(allowed) => allowed,
+        Err(response) => return response,
+    };
+    if payload.size > max_bytes {
+        return HttpResponse::PayloadTooLarge()
+            .json(format!("File too large (max {}MB for {})", max_bytes / 1024 / 1024, kind.as_str()));
+    }
+
+    let media_uuid = Uuid::new_v4().to_string();
+    let key = direct_upload_filename(&media_uuid, &payload.filename);
+    match storage
+        .presigned_put_url(&key, &payload.mime_type, std::time::Duration::from_secs(UPLOAD_URL_SECS))
+        .await
+    {
+        Ok(upload_url) => HttpResponse::Ok().json(UploadUrlResponse {
+            upload_token: sign_upload(&signing_key, &media_uuid, tenant_id, &payload.filename, &payload.mime_type),
+            uuid: media_uuid,
+            upload_url,
+            expires_in: UPLOAD_URL_SECS,
+        }),
+        Err(e) => HttpResponse::BadRequest().json(e),
+    }
+}
+
+/// Register a file uploaded through a presigned URL
+#[utoipa::path(
+    post,
+    path = "/api/media/upload-complete",
+    tag = "Content - Media",
+    request_body = UploadCompleteRequest,
+    responses(
+        (status = 201, description = "Media created"),
+        (status = 400, description = "Nothing was uploaded for this UUID, or the details don't match the upload token"),
+        (status = 409, description = "Media already registered"),
+        (status = 413, description = "File too large for the site's media policy; it is deleted"),
+        (status = 503, description = "MEDIA_SIGNING_KEY is not set")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn complete_upload(
+    req: HttpRequest,
+    payload: web::Json<UploadCompleteRequest>,
+    pool: web::Data<DatabasePool>,
+    storage: web::Data<StorageBackend>,
+) -> impl Responder {
+    let (tenant_id, user_id, policy) = match authorize_upload(&req, &pool) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    let signing_key = match upload_signing_key() {
+        Ok(key) => key,
+        Err(response) => return response,
+    };
+    if Uuid::parse_str(&payload.uuid).is_err() {
+        return HttpResponse::BadRequest().json("Invalid UUID");
+    }
+    let media_uuid = payload.uuid.to_lowercase();
+    if !verify_upload(&signing_key, &media_uuid, tenant_id, &payload.filename, &payload.mime_type, &payload.upload_token) {
+        return HttpResponse::BadRequest().json("Upload details don't match the upload token");
+    }
+    let (kind, max_bytes) = match check_direct_upload(&policy, &payload.mime_type) {
+        Ok(allowed) => allowed,
+        Err(response) => return response,
+    };
+
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(_) => return HttpResponse::InternalServerError().json("Database connection failed"),
+    };
+    let exists = diesel::select(diesel::dsl::exists(media::table.filter(media::uuid.eq(&media_uuid))))
+        .get_result::<bool>(&mut conn);
+    match exists {
+        Ok(false) => {}
+        Ok(true) => return HttpResponse::Conflict().json("Media already registered"),
+        Err(_) => return HttpResponse::InternalServerError().json("Failed to fetch media"),
+    }
+    let folder_id = match payload.folder_id {
+        Some(id) => match media_folder_service::find(tenant_id, id, &mut conn) {
+            Ok(folder) => Some(folder.id),
+            Err(e) => return e.error_response(),
+        },
+        None => None,
+    };
+
+    let key = direct_upload_filename(&media_uuid, &payload.filename);
+    let file_size = match storage.head(&key).await {
+        Ok(Some(size)) => size,
+        Ok(None) => return HttpResponse::BadRequest().json("No file was uploaded for this UUID"),
+        Err(e) => return HttpResponse::InternalServerError().json(format!("Failed to check upload: {}", e)),
+    };
+    if file_size > max_bytes {
+        let _ = storage.delete(&key).await;
+        return HttpResponse::PayloadTooLarge()
+            .json(format!("File too large (max {}MB for {})", max_bytes / 1024 / 1024, kind.as_str()));
+    }
+
+    let new_media = NewMedia {
+        uuid: media_uuid,
+        filename: key.clone(),
+        original_filename: payload.filename.clone(),
+        // S3 stores under the bare key, as `save` returns it
+        file_path: key.clone(),
+        mime_type: payload.mime_type.clone(),
+        file_size: file_size as i64,
+        width: None,
+        height: None,
+        alt_text: payload.alt_text.clone(),
+        title: None,
+        description: None,
+        tags: None,
+        uploaded_by: Some(user_id),
+        tenant_id: Some(tenant_id),
+        metadata: Some(serde_json::json!({"kind": kind.as_str(), "direct_upload": true})),
+        folder_id,
+    };
+    match diesel::insert_into(media::table).values(&new_media).get_result::<Media>(&mut conn) {
+        Ok(created) => HttpResponse::Created().json(created),
+        Err(e) => HttpResponse::InternalServerError().json(format!("Failed to save media metadata: {}", e)),
+    }
+}
+
+/// What gets stored for an upload
+enum StoredUpload {
+    /// An optimized raster image
+    Image(ProcessedImage),
+    /// Rewritten content, e.g. a sanitized SVG
+    Bytes(Vec<u8>, serde_json::Value),
+    /// The spooled file as uploaded
+    File(serde_json::Value),
+}
+
+struct ProcessedImage {
+    data: Vec<u8>,
+    webp: Option<Vec<u8>>,
+    mime_type: &'static str,
+    extension: &'static str,
+    width: Option<i32>,
+    height: Option<i32>,
+    metadata: serde_json::Value,
+}
+
+/// Resizes and re-encodes an image, which also drops its EXIF block. The
+/// camera details are kept in the metadata, the location never is. If the
+/// image can't be optimized the original is stored with its JPEG metadata
+/// segments stripped; any other image that can't be re-encoded is rejected,
+/// as its metadata can't be removed.
+fn process_image(file_data: &[u8]) -> Result<ProcessedImage, String> {
+    let (exif, had_gps) = media_inspection_service::exif_metadata(file_data);
+    let mut metadata = serde_json::Map::new();
+    metadata.insert("kind".to_string(), serde_json::json!(MediaKind::Image.as_str()));
+    if !exif.is_empty() {
+        metadata.insert("exif".to_string(), serde_json::Value::Object(exif));
+    }
+
+    // Optimize images (resize, compress, generate WebP)
+    let mut image = match image_service::optimize_image(file_data, 1920, true) {
+        Ok((jpeg_bytes, webp_bytes, w, h)) => {
+            log::info!("Image optimized: {}x{}, original: {} bytes, optimized: {} bytes",
+                w, h, file_data.len(), jpeg_bytes.len());
+            ProcessedImage {
+                data: jpeg_bytes,
+                webp: webp_bytes,
+                mime_type: "image/jpeg",
+                extension: "jpg",
+                width: Some(w as i32),
+                height: Some(h as i32),
+                metadata: serde_json::Value::Object(metadata),
+            }
+        }
+        Err(e) => {
+            log::warn!("Image optimization failed: {}, using original", e);
+            let data = media_inspection_service::strip_jpeg_metadata(file_data).ok_or_else(|| {
+                format!("Image could not be processed ({}), so its metadata can't be removed", e)
+            })?;
+            let (width, height) = match image::load_from_memory(&data) {
+                Ok(img) => (Some(img.width() as i32), Some(img.height() as i32)),
+                Err(_) => (None, None),  // Unreadable
+            };
+            ProcessedImage {
+                data,
+                webp: None,
+                mime_type: "image/jpeg",
+                extension: "jpg",
+                width,
+                height,
+                metadata: serde_json::Value::Object(metadata),
+            }
+        }
+    };
+
+    // Only now is the location known to be gone
+    if had_gps {
+        if let serde_json::Value::Object(metadata) = &mut image.metadata {
+            metadata.insert("gps_stripped".to_string(), serde_json::json!(true));
+        }
+    }
+    Ok(image)
+}
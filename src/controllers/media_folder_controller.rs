@@ -0,0 +1,233 @@
+// Media Folder Controller
+// Organise a site's media library into folders
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::helpers::tenant_helper::{get_tenant_role, resolve_tenant_id};
+use crate::middleware::auth_middleware::get_user_context;
+use crate::models::rbac::{has_permission, Permission};
+use crate::models::{pool_handler, DatabasePool, PooledDatabaseConnection};
+use crate::schema::media;
+use crate::services::errors_service::CustomHttpError;
+use crate::services::media_folder_service;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateFolderRequest {
+    pub name: String,
+    /// Parent folder; top level if omitted
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RenameFolderRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MoveFolderRequest {
+    /// New parent folder; null moves the folder to the top level
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MoveMediaRequest {
+    pub media_uuids: Vec<String>,
+    /// Destination folder; null moves the media out of any folder
+    pub folder_id: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MoveMediaResponse {
+    pub moved: usize,
+}
+
+fn authorize(
+    req: &HttpRequest,
+    pool: web::Data<DatabasePool>,
+    permission: Permission,
+) -> Result<(i32, PooledDatabaseConnection), CustomHttpError> {
+    let user_ctx = get_user_context(req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
+    let tenant_id = resolve_tenant_id(req, &pool).map_err(CustomHttpError::BadRequest)?;
+    let mut conn = pool_handler(pool)?;
+
+    let role = get_tenant_role(tenant_id, user_ctx.user_id, &mut conn)
+        .map_err(|_| CustomHttpError::Forbidden("Access denied".to_string()))?;
+    if !has_permission(&role, permission) {
+        return Err(CustomHttpError::Forbidden("Insufficient permissions".to_string()));
+    }
+    Ok((tenant_id, conn))
+}
+
+/// List the site's media folders
+#[utoipa::path(
+    get,
+    path = "/v1/api/media/folders",
+    tag = "Content - Media",
+    responses(
+        (status = 200, description = "All folders, ordered by path", body = Vec<MediaFolder>),
+        (status = 403, description = "Insufficient permissions")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_folders(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::ViewContent)?;
+    let folders = media_folder_service::list(tenant_id, &mut conn)?;
+    Ok(HttpResponse::Ok().json(folders))
+}
+
+/// Create a media folder
+#[utoipa::path(
+    post,
+    path = "/v1/api/media/folders",
+    tag = "Content - Media",
+    request_body = CreateFolderRequest,
+    responses(
+        (status = 201, description = "Folder created", body = MediaFolder),
+        (status = 400, description = "Invalid name or a folder already exists at that path"),
+        (status = 404, description = "Parent folder not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_folder(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    payload: web::Json<CreateFolderRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::EditContent)?;
+    let folder = media_folder_service::create(tenant_id, payload.parent_id, &payload.name, &mut conn)?;
+    Ok(HttpResponse::Created().json(folder))
+}
+
+/// Rename a media folder
+#[utoipa::path(
+    put,
+    path = "/v1/api/media/folders/{id}",
+    tag = "Content - Media",
+    params(
+        ("id" = i32, Path, description = "Folder ID")
+    ),
+    request_body = RenameFolderRequest,
+    responses(
+        (status = 200, description = "Folder renamed; paths of its subfolders follow", body = MediaFolder),
+        (status = 400, description = "Invalid name or a folder already exists at that path"),
+        (status = 404, description = "Folder not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn rename_folder(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    id: web::Path<i32>,
+    payload: web::Json<RenameFolderRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::EditContent)?;
+    let folder = media_folder_service::find(tenant_id, id.into_inner(), &mut conn)?;
+    let renamed = media_folder_service::rename(&folder, &payload.name, &mut conn)?;
+    Ok(HttpResponse::Ok().json(renamed))
+}
+
+/// Move a media folder, with everything in it, under another folder
+#[utoipa::path(
+    post,
+    path = "/v1/api/media/folders/{id}/move",
+    tag = "Content - Media",
+    params(
+        ("id" = i32, Path, description = "Folder ID")
+    ),
+    request_body = MoveFolderRequest,
+    responses(
+        (status = 200, description = "Folder moved", body = MediaFolder),
+        (status = 400, description = "Destination is inside the folder or already has a folder of that name"),
+        (status = 404, description = "Folder not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn move_folder(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    id: web::Path<i32>,
+    payload: web::Json<MoveFolderRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::EditContent)?;
+    let folder = media_folder_service::find(tenant_id, id.into_inner(), &mut conn)?;
+    let moved = media_folder_service::move_to(&folder, payload.parent_id, &mut conn)?;
+    Ok(HttpResponse::Ok().json(moved))
+}
+
+/// Delete an empty media folder
+#[utoipa::path(
+    delete,
+    path = "/v1/api/media/folders/{id}",
+    tag = "Content - Media",
+    params(
+        ("id" = i32, Path, description = "Folder ID")
+    ),
+    responses(
+        (status = 204, description = "Folder deleted"),
+        (status = 400, description = "Folder still has subfolders or media"),
+        (status = 404, description = "Folder not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_folder(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::DeleteContent)?;
+    let folder = media_folder_service::find(tenant_id, id.into_inner(), &mut conn)?;
+    media_folder_service::delete(&folder, &mut conn)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Move media items into a folder
+#[utoipa::path(
+    post,
+    path = "/v1/api/media/move",
+    tag = "Content - Media",
+    request_body = MoveMediaRequest,
+    responses(
+        (status = 200, description = "Number of media items moved", body = MoveMediaResponse),
+        (status = 404, description = "Folder not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn move_media(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    payload: web::Json<MoveMediaRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::EditContent)?;
+    let folder_id = match payload.folder_id {
+        Some(id) => Some(media_folder_service::find(tenant_id, id, &mut conn)?.id),
+        None => None,
+    };
+
+    let moved = diesel::update(
+        media::table
+            .filter(media::tenant_id.eq(tenant_id))
+            .filter(media::uuid.eq_any(&payload.media_uuids)),
+    )
+    .set((media::folder_id.eq(folder_id), media::updated_at.eq(chrono::Utc::now().naive_utc())))
+    .execute(&mut conn)?;
+
+    Ok(HttpResponse::Ok().json(MoveMediaResponse { moved }))
+}
@@ -8,6 +8,7 @@ pub mod marketplace_submission_controller;
 pub mod category_controllers;
 pub mod product_controller;
 pub mod order_controller;
+pub mod cart_controller; // Shopping carts and checkout
 pub mod crm_controller;
 pub mod crm_controller_public;
 pub mod theme_controller;
@@ -28,6 +29,7 @@ pub mod tenant_sso_controller;
 pub mod verification_settings_controller;
 pub mod billing_controller;
 pub mod media_controller;
+pub mod media_folder_controller; // Folders in the media library
 pub mod dashboard_controller;
 pub mod metrics_controller;
 pub mod backup_controller;
@@ -37,5 +39,7 @@ pub mod search_controller;
 pub mod i18n_controller;
 pub mod relationship_controller;
 pub mod revision_controller;
+pub mod redirect_controller;
+pub mod storage_migration_controller; // Move media between local storage and S3
 pub mod graphql_controller;
 pub mod mcp_custom_tool_controller; // Phase 2: Custom MCP tools + marketplace
\ No newline at end of file
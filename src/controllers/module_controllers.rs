@@ -1,11 +1,18 @@
-use actix_web::{web, HttpResponse};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use diesel::Connection;
+use serde_json::json;
 use uuid::Uuid;
 
 use crate::models::{Model, DatabasePool, pool_handler};
 use crate::models::module_models::{Module, ModuleCategory, MutModule};
 
+use crate::helpers::etag_helper::{if_match_satisfied, module_etag};
 use crate::services::auth_service::Claims;
+use crate::services::cache_service_v2::{module_tag, page_tag, CacheServiceV2};
+use crate::services::embedding_refresh_service;
 use crate::services::errors_service::CustomHttpError;
+use crate::services::plugin_service::PluginRegistry;
 
 /// Create a new content module
 #[utoipa::path(
@@ -24,6 +31,8 @@ use crate::services::errors_service::CustomHttpError;
 pub async fn create_module(
     new: web::Json<MutModule>,
     pool: web::Data<DatabasePool>,
+    cache: web::Data<CacheServiceV2>,
+    plugins: web::Data<PluginRegistry>,
     _: Claims
 ) -> Result<HttpResponse, CustomHttpError> {
     let mut mysql_pool = pool_handler(pool)?;
@@ -32,6 +41,10 @@ pub async fn create_module(
     uuid_new.uuid = Some(Uuid::new_v4().to_string());
 
     Module::create(&uuid_new, &mut mysql_pool)?;
+    let _ = cache.purge_tags(&[page_tag(&uuid_new.page_uuid)]).await;
+    if let Some(module_uuid) = &uuid_new.uuid {
+        plugins.module_saved(module_uuid, &uuid_new.page_uuid).await;
+    }
 
     Ok(HttpResponse::Created().json(uuid_new))
 }
@@ -73,7 +86,9 @@ pub async fn get_module(
 
     let module = Module::read_one(id.clone(), &mut mysql_pool)?;
 
-    Ok(HttpResponse::Created().json(module))
+    Ok(HttpResponse::Created()
+        .insert_header((header::ETAG, module_etag(module.version)))
+        .json(module))
 }
 
 /// Update an existing module
@@ -82,29 +97,61 @@ pub async fn get_module(
     path = "/v1/modules/{id}",
     tag = "Content - Modules",
     params(
-        ("id" = String, Path, description = "Module UUID to update", example = "123e4567-e89b-12d3-a456-426614174000")
+        ("id" = String, Path, description = "Module UUID to update", example = "123e4567-e89b-12d3-a456-426614174000"),
+        ("If-Match" = Option<String>, Header, description = "ETag from a previous read; the update is rejected if the module changed since")
     ),
     request_body = MutModule,
     responses(
         (status = 200, description = "Module updated successfully", body = MutModule),
         (status = 401, description = "Not authenticated"),
-        (status = 404, description = "Module not found")
+        (status = 404, description = "Module not found"),
+        (status = 409, description = "Module was modified since the If-Match ETag; body holds the current module", body = Module)
     ),
     security(
         ("bearer_auth" = [])
     )
 )]
 pub async fn update_module(
+    req: HttpRequest,
     updated_module: web::Json<MutModule>,
     id: web::Path<String>,
     pool: web::Data<DatabasePool>,
+    cache: web::Data<CacheServiceV2>,
+    plugins: web::Data<PluginRegistry>,
     _: Claims
 ) -> Result<HttpResponse, CustomHttpError> {
     let mut mysql_pool = pool_handler(pool)?;
 
-    Module::update(id.clone(), &updated_module, &mut mysql_pool)?;
-
-    Ok(HttpResponse::Created().json(updated_module.0))
+    // Optimistic concurrency: compare If-Match with the locked row's version
+    let outcome = mysql_pool.transaction::<Result<i32, Module>, CustomHttpError, _>(|conn| {
+        let locked = Module::read_one_for_update(id.clone(), conn)?;
+        if !if_match_satisfied(&req, &module_etag(locked.version)) {
+            return Ok(Err(locked));
+        }
+
+        Module::update(id.clone(), &updated_module, conn)?;
+        Ok(Ok(locked.version + 1))
+    })?;
+
+    if outcome.is_ok() {
+        // The module may have moved pages, so purge pages that rendered it as well as its current page
+        let _ = cache.purge_tags(&[module_tag(&id), page_tag(&updated_module.page_uuid)]).await;
+        plugins.module_saved(&id, &updated_module.page_uuid).await;
+    }
+
+    match outcome {
+        Ok(new_version) => Ok(HttpResponse::Created()
+            .insert_header((header::ETAG, module_etag(new_version)))
+            .json(updated_module.0)),
+        Err(current) => Ok(HttpResponse::Conflict()
+            .insert_header((header::ETAG, module_etag(current.version)))
+            .json(json!({
+                "code": 409,
+                "error": "Conflict",
+                "message": "Module was modified by someone else. Reload before saving.",
+                "current": current,
+            }))),
+    }
 }
 
 /// Delete a module
@@ -127,11 +174,15 @@ pub async fn update_module(
 pub async fn delete_module(
     id: web::Path<String>,
     pool: web::Data<DatabasePool>,
+    cache: web::Data<CacheServiceV2>,
     _: Claims
 ) -> Result<HttpResponse, CustomHttpError> {
     let mut mysql_pool = pool_handler(pool)?;
 
+    let module = Module::read_one(id.clone(), &mut mysql_pool)?;
     let res = Module::delete(id.clone(), &mut mysql_pool)?;
+    let _ = cache.purge_tags(&[module_tag(&id), page_tag(&module.page_uuid)]).await;
+    embedding_refresh_service::enqueue_page(&module.page_uuid);
 
     Ok(HttpResponse::Created().json(res))
 }
@@ -166,6 +166,7 @@ pub async fn google_callback(
                 token: None,
                 two_factor_secret: None,
                 two_factor_enabled: Some(false),
+                email: Some(user_email.clone()),
             };
             
             diesel::insert_into(users::table)
@@ -381,6 +382,7 @@ pub async fn github_callback(
                 token: None,
                 two_factor_secret: None,
                 two_factor_enabled: Some(false),
+                email: Some(user_email.clone()),
             };
             
             diesel::insert_into(users::table)
@@ -1,14 +1,24 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use bigdecimal::BigDecimal;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::models::{pool_handler, DatabasePool};
-use crate::models::commerce_models::{Order, NewOrder, NewOrderItem, Product};
+use crate::helpers::tenant_helper::{get_tenant_role, resolve_tenant_id};
+use crate::models::{pool_handler, DatabasePool, PooledDatabaseConnection};
+use crate::models::commerce_models::{Order, NewOrder, OrderItem, Product};
+use crate::models::rbac::{has_permission, Permission};
 use crate::services::errors_service::CustomHttpError;
 use crate::services::auth_service::Claims;
-use crate::schema::{orders, order_items, products};
+use crate::services::cart_service::price_to_cents;
+use crate::services::email_service::EmailService;
+use crate::services::order_service::{self, Actor, OrderLine, OrderStatus};
+use crate::services::payment_service::PaymentHandlerRegistry;
+use crate::services::refund_service::{self, RefundOrder};
+use crate::services::stock_reservation_service;
+use crate::schema::{orders, order_items, product_variants, products};
 
 #[derive(Deserialize, ToSchema)]
 pub struct CreateOrderRequest {
@@ -19,6 +29,7 @@ pub struct CreateOrderRequest {
 #[derive(Deserialize, ToSchema)]
 pub struct OrderItemInput {
     pub product_id: i64,
+    pub variant_id: Option<i32>,
     pub quantity: i32,
 }
 
@@ -33,9 +44,17 @@ pub struct OrderResponse {
 pub struct OrderItemWithProduct {
     pub id: i64,
     pub product_id: i64,
+    pub variant_id: Option<i32>,
+    /// Name, SKU and variant as they were when the order was placed
     pub product_name: String,
+    pub sku: Option<String>,
+    pub variant_name: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub variant_attributes: Option<serde_json::Value>,
     pub quantity: i32,
     pub price_cents: i64,
+    pub tax_cents: i64,
+    pub discount_cents: i64,
     pub subtotal_amount_cents: i64,
 }
 
@@ -131,35 +150,26 @@ pub async fn get_order(
         // return Err(CustomHttpError::Forbidden("Access denied".to_string()));
     }
     
-    // Fetch order items (without join since joinables are commented)
-    let items_data = order_items::table
+    // Lines carry their own snapshot of the product
+    let itms = order_items::table
         .filter(order_items::order_id.eq(ord.id))
-        .select((
-            order_items::id,
-            order_items::product_id,
-            order_items::quantity,
-            order_items::price_cents,
-        ))
-        .load::<(i64, i64, i32, i64)>(&mut conn)?;
-    
-    // Fetch product names separately
-    let itms = items_data
+        .order(order_items::id)
+        .select(OrderItem::as_select())
+        .load::<OrderItem>(&mut conn)?
         .into_iter()
-        .map(|(id, product_id, quantity, price_cents)| {
-            let product_name = products::table
-                .find(product_id)
-                .select(products::name)
-                .first::<String>(&mut conn)
-                .unwrap_or_else(|_| "Unknown Product".to_string());
-                
-            OrderItemWithProduct {
-                id,
-                product_id,
-                product_name,
-                quantity,
-                price_cents,
-                subtotal_amount_cents: price_cents * quantity as i64,
-            }
+        .map(|item| OrderItemWithProduct {
+            id: item.id,
+            product_id: item.product_id,
+            variant_id: item.variant_id,
+            subtotal_amount_cents: item.line_total_cents(),
+            product_name: item.product_name,
+            sku: item.sku,
+            variant_name: item.variant_name,
+            variant_attributes: item.variant_attributes,
+            quantity: item.quantity,
+            price_cents: item.price_cents,
+            tax_cents: item.tax_cents,
+            discount_cents: item.discount_cents,
         })
         .collect();
     
@@ -181,8 +191,8 @@ pub async fn get_order(
     tag = "Commerce - Orders",
     request_body = CreateOrderRequest,
     responses(
-        (status = 201, description = "Order created successfully with calculated total"),
-        (status = 400, description = "Invalid items or products not found"),
+        (status = 201, description = "Order created with calculated total; stock is reserved until `reserved_until`"),
+        (status = 400, description = "Invalid items, products not found on this site or not enough stock"),
         (status = 401, description = "Not authenticated")
     ),
     security(
@@ -190,37 +200,74 @@ pub async fn get_order(
     )
 )]
 pub async fn create_order(
+    req: HttpRequest,
     body: web::Json<CreateOrderRequest>,
     pool: web::Data<DatabasePool>,
     claim: Claims,
 ) -> Result<HttpResponse, CustomHttpError> {
+    let tenant_id = resolve_tenant_id(&req, &pool).map_err(CustomHttpError::BadRequest)?;
     let mut conn = pool_handler(pool)?;
     
     // Validate all products exist and calculate total
-    let product_ids: Vec<i64> = body.items.iter().map(|item| item.product_id).collect();
+    let mut product_ids: Vec<i64> = body.items.iter().map(|item| item.product_id).collect();
+    product_ids.sort_unstable();
+    product_ids.dedup();
     let products_list = products::table
         .filter(products::id.eq_any(&product_ids))
+        .filter(products::tenant_id.eq(tenant_id))
         .filter(products::is_active.eq(true))
         .load::<Product>(&mut conn)?;
     
     if products_list.len() != product_ids.len() {
         return Err(CustomHttpError::BadRequest("Some products not found".to_string()));
     }
+
+    // Variants override the product price when they have one
+    let variant_ids: Vec<i32> = body.items.iter().filter_map(|item| item.variant_id).collect();
+    let variant_prices: HashMap<i32, (i64, Option<BigDecimal>)> = product_variants::table
+        .filter(product_variants::id.eq_any(&variant_ids))
+        .filter(product_variants::is_active.eq(true))
+        .select((product_variants::id, product_variants::product_id, product_variants::price))
+        .load::<(i32, i64, Option<BigDecimal>)>(&mut conn)?
+        .into_iter()
+        .map(|(id, product_id, price)| (id, (product_id, price)))
+        .collect();
     
     // Calculate total
+    if body.items.iter().any(|item| item.quantity <= 0) {
+        return Err(CustomHttpError::BadRequest("Quantity must be at least 1".to_string()));
+    }
     let mut total_amount_cents: i64 = 0;
-    let mut order_item_data = Vec::new();
+    let mut order_lines = Vec::new();
     
     for item_input in &body.items {
         let product = products_list
             .iter()
             .find(|p| p.id == item_input.product_id)
             .ok_or(CustomHttpError::BadRequest("Product not found".to_string()))?;
+
+        let unit_price_cents = match item_input.variant_id {
+            Some(variant_id) => {
+                let (_, price) = variant_prices
+                    .get(&variant_id)
+                    .filter(|(owner, _)| *owner == product.id)
+                    .ok_or(CustomHttpError::BadRequest(format!("Variant {} not found", variant_id)))?;
+                price.as_ref().and_then(price_to_cents).unwrap_or(product.price_cents)
+            }
+            None => product.price_cents,
+        };
         
-        let subtotal = product.price_cents * item_input.quantity as i64;
+        let subtotal = unit_price_cents * item_input.quantity as i64;
         total_amount_cents += subtotal;
         
-        order_item_data.push((item_input.product_id, item_input.quantity, product.price_cents));
+        order_lines.push(OrderLine {
+            product_id: item_input.product_id,
+            variant_id: item_input.variant_id,
+            quantity: item_input.quantity,
+            unit_price_cents,
+            tax_cents: 0,
+            discount_cents: 0,
+        });
     }
 
     // Determine target user
@@ -231,60 +278,46 @@ pub async fn create_order(
         claim.sub.clone()
     };
     
-    // Create order
+    let creator = match claim.to_user_context() {
+        Ok(user_ctx) if body.target_user_uuid.is_some() => Actor::user("staff", user_ctx.user_id),
+        Ok(user_ctx) => Actor::user("customer", user_ctx.user_id),
+        Err(_) => Actor::system("api"),
+    };
+
+    // Create order, its items and stock reservations together
     let new_order = NewOrder {
         uuid: Uuid::new_v4().to_string(),
-        user_uuid: user_uuid,
+        user_uuid,
         status: "pending".to_string(),
         total_amount_cents,
         payment_status: Some("pending".to_string()),
         payment_provider: None,
         payment_intent_id: None,
     };
-    
-    diesel::insert_into(orders::table)
-        .values(&new_order)
-        .execute(&mut conn)?;
-    
-    let order_id = orders::table
-        .select((
-            orders::id,
-            orders::uuid,
-            orders::user_uuid,
-            orders::status,
-            orders::total_amount_cents,
-            orders::payment_status,
-            orders::payment_provider,
-            orders::payment_intent_id,
-            orders::metadata,
-            orders::created_at,
-            orders::updated_at,
-            orders::tenant_id,
-        ))
-        .order(orders::id.desc())
-        .first::<Order>(&mut conn)?
-        .id;
-    
-    // Create order items
-    for (product_id, quantity, price_cents) in order_item_data {
-        let new_item = NewOrderItem {
-            order_id,
-            product_id,
-            quantity,
-            price_cents,
-        };
-        
-        diesel::insert_into(order_items::table)
-            .values(&new_item)
-            .execute(&mut conn)?;
-    }
-    
-    let (order_id, total_amount_cents) = (order_id, total_amount_cents);
-    
+
+    let (order_id, reservations) = conn.transaction::<_, CustomHttpError, _>(|conn| {
+        let order_id = diesel::insert_into(orders::table)
+            .values(&new_order)
+            .returning(orders::id)
+            .get_result::<i64>(conn)?;
+        diesel::update(orders::table.find(order_id)).set(orders::tenant_id.eq(tenant_id)).execute(conn)?;
+
+        order_service::record_created(order_id, &creator, conn)?;
+        order_service::add_items(order_id, &order_lines, conn)?;
+
+        let held: Vec<(i64, Option<i32>, i32)> = order_lines
+            .iter()
+            .map(|line| (line.product_id, line.variant_id, line.quantity))
+            .collect();
+        let reservations = stock_reservation_service::reserve(order_id, &held, conn)?;
+        Ok((order_id, reservations))
+    })?;
+
     Ok(HttpResponse::Created().json(serde_json::json!({
         "order_id": order_id,
         "total_amount_cents": total_amount_cents,
         "status": "pending",
+        "reserved_until": reservations.iter().map(|r| r.expires_at).min(),
         "message": "Order created successfully"
     })))
 }
@@ -292,10 +325,49 @@ pub async fn create_order(
 // Update order status
 #[derive(Deserialize, ToSchema)]
 pub struct UpdateOrderStatusRequest {
-    pub status: String,
+    pub status: OrderStatus,
+    /// Recorded in the order history and shown to the customer
+    pub reason: Option<String>,
+}
+
+/// Staff of the order's site and platform admins may make any change the
+/// lifecycle allows; the customer who placed the order may only cancel it
+fn order_actor(order: &Order, claim: &Claims, conn: &mut PooledDatabaseConnection) -> Result<Actor, CustomHttpError> {
+    let user_ctx = claim.to_user_context().map_err(CustomHttpError::Unauthorized)?;
+    let is_staff = user_ctx.is_platform_admin()
+        || order.tenant_id.is_some_and(|tenant_id| {
+            get_tenant_role(tenant_id, user_ctx.user_id, conn)
+                .is_ok_and(|role| has_permission(&role, Permission::ManageSettings))
+        });
+    if is_staff {
+        Ok(Actor::user("staff", user_ctx.user_id))
+    } else if order.user_uuid == claim.sub {
+        Ok(Actor::user("customer", user_ctx.user_id))
+    } else {
+        Err(CustomHttpError::NotFound("Order not found".to_string()))
+    }
+}
+
+fn find_order(id: i64, conn: &mut PooledDatabaseConnection) -> Result<Order, CustomHttpError> {
+    orders::table
+        .find(id)
+        .select(Order::as_select())
+        .first::<Order>(conn)
+        .optional()?
+        .ok_or(CustomHttpError::NotFound("Order not found".to_string()))
 }
 
 /// Update order status
+///
+/// Moves the order through its lifecycle: pending → paid → fulfilling →
+/// shipped → delivered, with cancelled for unpaid orders. Orders paid for
+/// after they sold out or were cancelled are on_hold until refunded or
+/// marked paid. Refunds go through `POST /v1/orders/{id}/refunds`, which
+/// moves the order to refunded or partially_refunded. Cancelling a pending
+/// order cancels its payment too, and is rejected while the payment is being
+/// processed. Other changes are rejected. The change is recorded in the
+/// order history and announced via `order.<status>` webhooks and a customer
+/// email.
 #[utoipa::path(
     put,
     path = "/v1/orders/{id}/status",
@@ -305,8 +377,9 @@ pub struct UpdateOrderStatusRequest {
     ),
     request_body = UpdateOrderStatusRequest,
     responses(
-        (status = 200, description = "Order status updated"),
-        (status = 400, description = "Invalid status (must be: pending, processing, completed, cancelled)"),
+        (status = 200, description = "Order status updated", body = OrderStatusChange),
+        (status = 400, description = "The order can't move to this status from its current one, or the status is set by refunds"),
+        (status = 403, description = "Customers can only cancel their orders"),
         (status = 404, description = "Order not found"),
         (status = 401, description = "Not authenticated")
     ),
@@ -318,32 +391,163 @@ pub async fn update_order_status(
     id: web::Path<i64>,
     body: web::Json<UpdateOrderStatusRequest>,
     pool: web::Data<DatabasePool>,
+    email_service: web::Data<EmailService>,
+    registry: web::Data<PaymentHandlerRegistry>,
+    claim: Claims,
+) -> Result<HttpResponse, CustomHttpError> {
+    let mut conn = pool_handler(pool.clone())?;
+    let order = find_order(*id, &mut conn)?;
+    let actor = order_actor(&order, &claim, &mut conn)?;
+    if actor.name == "customer" && body.status != OrderStatus::Cancelled {
+        return Err(CustomHttpError::Forbidden("Customers can only cancel their orders".to_string()));
+    }
+    // Only a refund made with the provider may mark an order refunded
+    if matches!(body.status, OrderStatus::Refunded | OrderStatus::PartiallyRefunded) {
+        return Err(CustomHttpError::BadRequest(format!(
+            "Refund the order with POST /v1/orders/{}/refunds instead",
+            order.id
+        )));
+    }
+    drop(conn);
+
+    let body = body.into_inner();
+    let transition =
+        order_service::change_status(order.id, body.status, &actor, body.reason, &registry, &pool, &email_service).await?;
+
+    Ok(HttpResponse::Ok().json(transition.change))
+}
+
+/// Get the status history of an order, oldest first
+#[utoipa::path(
+    get,
+    path = "/v1/orders/{id}/history",
+    tag = "Commerce - Orders",
+    params(
+        ("id" = i64, Path, description = "Order ID", example = 456)
+    ),
+    responses(
+        (status = 200, description = "Status changes with actor and reason", body = Vec<OrderStatusChange>),
+        (status = 404, description = "Order not found"),
+        (status = 401, description = "Not authenticated")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_order_history(
+    id: web::Path<i64>,
+    pool: web::Data<DatabasePool>,
     claim: Claims,
 ) -> Result<HttpResponse, CustomHttpError> {
     let mut conn = pool_handler(pool)?;
-    
-    // Valid statuses
-    let valid_statuses = vec!["pending", "processing", "completed", "cancelled"];
-    if !valid_statuses.contains(&body.status.as_str()) {
-        return Err(CustomHttpError::BadRequest("Missing product information".to_string()));
+    let order = find_order(*id, &mut conn)?;
+    order_actor(&order, &claim, &mut conn)?;
+
+    Ok(HttpResponse::Ok().json(order_service::history(order.id, &mut conn)?))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RefundOrderRequest {
+    /// Cents to give back; everything not yet refunded when omitted
+    pub amount_cents: Option<i64>,
+    /// Recorded with the refund and in the order history
+    pub reason: Option<String>,
+}
+
+/// Refund an order
+///
+/// Refunds the order's payment in full or in part through the provider that
+/// took it, then marks the order refunded or partially refunded. Requests
+/// must carry an `Idempotency-Key`; repeating a key returns the refund
+/// already made instead of refunding again.
+#[utoipa::path(
+    post,
+    path = "/v1/orders/{id}/refunds",
+    tag = "Commerce - Orders",
+    params(
+        ("id" = i64, Path, description = "Order ID", example = 456),
+        ("Idempotency-Key" = String, Header, description = "Unique per refund; reuse it to retry the same refund")
+    ),
+    request_body = RefundOrderRequest,
+    responses(
+        (status = 200, description = "Refund made, or the refund already made with this key", body = OrderRefund),
+        (status = 400, description = "Missing idempotency key, invalid amount, or the order can't be refunded"),
+        (status = 403, description = "Only staff can refund orders"),
+        (status = 404, description = "Order not found"),
+        (status = 500, description = "The payment provider failed; retry with the same key"),
+        (status = 401, description = "Not authenticated")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn refund_order(
+    req: HttpRequest,
+    id: web::Path<i64>,
+    body: web::Json<RefundOrderRequest>,
+    pool: web::Data<DatabasePool>,
+    registry: web::Data<PaymentHandlerRegistry>,
+    email_service: web::Data<EmailService>,
+    claim: Claims,
+) -> Result<HttpResponse, CustomHttpError> {
+    let idempotency_key = req
+        .headers()
+        .get("Idempotency-Key")
+        .and_then(|v| v.to_str().ok())
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty())
+        .ok_or(CustomHttpError::BadRequest("Idempotency-Key header is required".to_string()))?;
+
+    let mut conn = pool_handler(pool.clone())?;
+    let order = find_order(*id, &mut conn)?;
+    let actor = order_actor(&order, &claim, &mut conn)?;
+    if actor.name != "staff" {
+        return Err(CustomHttpError::Forbidden("Only staff can refund orders".to_string()));
     }
-    
-    let updated = diesel::update(
-            orders::table
-                .find(*id)
-                .filter(orders::user_uuid.eq(&claim.sub))
-        )
-        .set(orders::status.eq(&body.status))
-        .execute(&mut conn)?;
-    
-    if updated == 0 {
-        return Err(CustomHttpError::NotFound("Product not found".to_string()));
+    drop(conn);
+
+    let body = body.into_inner();
+    let request = RefundOrder {
+        order_id: order.id,
+        amount_cents: body.amount_cents,
+        idempotency_key,
+        reason: body.reason,
+    };
+    let outcome = refund_service::refund_order(request, &actor, &registry, &pool).await?;
+    if let Some(transition) = &outcome.transition {
+        order_service::notify(transition, &pool, &email_service).await;
     }
-    
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "message": "Order status updated",
-        "status": body.status
-    })))
+
+    Ok(HttpResponse::Ok().json(outcome.refund))
+}
+
+/// List an order's refunds, oldest first
+#[utoipa::path(
+    get,
+    path = "/v1/orders/{id}/refunds",
+    tag = "Commerce - Orders",
+    params(
+        ("id" = i64, Path, description = "Order ID", example = 456)
+    ),
+    responses(
+        (status = 200, description = "Refunds with amount, status and provider reference", body = Vec<OrderRefund>),
+        (status = 404, description = "Order not found"),
+        (status = 401, description = "Not authenticated")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_order_refunds(
+    id: web::Path<i64>,
+    pool: web::Data<DatabasePool>,
+    claim: Claims,
+) -> Result<HttpResponse, CustomHttpError> {
+    let mut conn = pool_handler(pool)?;
+    let order = find_order(*id, &mut conn)?;
+    order_actor(&order, &claim, &mut conn)?;
+
+    Ok(HttpResponse::Ok().json(refund_service::refunds(order.id, &mut conn)?))
 }
 
 // Link payment to order
@@ -363,7 +567,7 @@ pub struct LinkPaymentRequest {
     ),
     request_body = LinkPaymentRequest,
     responses(
-        (status = 200, description = "Payment linked, order status set to processing"),
+        (status = 200, description = "Payment linked; the order is marked paid when the payment succeeds"),
         (status = 404, description = "Order not found"),
         (status = 401, description = "Not authenticated")
     ),
@@ -387,7 +591,6 @@ pub async fn link_payment_to_order(
         .set((
             orders::payment_provider.eq(&body.payment_provider),
             orders::payment_intent_id.eq(&body.payment_intent_id),
-            orders::status.eq("processing"),
         ))
         .execute(&mut conn)?;
     
@@ -397,6 +600,6 @@ pub async fn link_payment_to_order(
     
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Payment linked to order",
-        "status": "processing"
+        "status": "pending"
     })))
 }
@@ -1,8 +1,6 @@
-use std::sync::Mutex;
 use diesel::prelude::*;
 
-use actix_web::{web, HttpRequest, HttpResponse};
-use handlebars::Handlebars;
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
 use uuid::Uuid;
 use serde_json::json;
 
@@ -10,15 +8,22 @@ use crate::models::{pool_handler, Model, DatabasePool, ReadDatabasePool};
 
 use crate::models::module_models::{FieldsDTO};
 use crate::models::page_models::{PageModuleDTO, MutPage, Page, PageDTO};
+use crate::models::status_enum::PageStatus;
 use crate::models::tenant_models::Tenant;
 
+use crate::services::cache_service_v2::{module_tag, page_tag, template_tag, tenant_tag, CacheServiceV2, ALL_PAGES_TAG};
+use crate::services::embedding_refresh_service;
+use crate::services::redirect_service;
 use crate::services::errors_service::CustomHttpError;
+use crate::services::plugin_service::PluginRegistry;
+use crate::services::template_service::TemplateService;
 use crate::helpers::tenant_helper::{resolve_tenant_id, get_tenant_role};
+use crate::helpers::etag_helper::{if_match_satisfied, page_etag};
 use crate::models::rbac::{has_permission, Permission};
 use crate::middleware::auth_middleware::get_user_context;
 
 /// Validates SEO fields to ensure they meet requirements
-fn validate_seo_fields(page: &MutPage) -> Result<(), CustomHttpError> {
+pub fn validate_seo_fields(page: &MutPage) -> Result<(), CustomHttpError> {
     // Validate meta_title length (max 70 chars)
     if let Some(title) = &page.meta_title {
         if title.len() > 70 {
@@ -93,12 +98,38 @@ fn parse_page(page: (Page, FieldsDTO)) -> Result<PageModuleDTO, CustomHttpError>
     Ok(res)
 }
 
+/// Cache tags for a rendered page: the page, each module on it, its tenant and template
+fn page_cache_tags(tenant_id: i32, page: &PageModuleDTO) -> Vec<String> {
+    let mut tags = vec![
+        ALL_PAGES_TAG.to_string(),
+        page_tag(&page.uuid),
+        tenant_tag(tenant_id),
+        template_tag(&page.page_name),
+    ];
+    tags.extend(
+        page.fields.values()
+            .chain(page.array_fields.values().flatten())
+            .map(|module| module_tag(&module.uuid)),
+    );
+    tags
+}
+
+/// Error page with a real status code: the theme's "410" template (falling
+/// back to "404") for archived pages, "404" for missing ones
+fn error_page(templates: &TemplateService, tenant_id: i32, status: StatusCode) -> HttpResponse {
+    let body = templates
+        .render_for_tenant(tenant_id, status.as_str(), &json!({}))
+        .or_else(|_| templates.render_for_tenant(tenant_id, "404", &json!({})))
+        .unwrap_or_else(|_| status.canonical_reason().unwrap_or("Not Found").to_string());
+    HttpResponse::build(status).content_type("text/html").body(body)
+}
+
 pub async fn display_page(
     req: HttpRequest,
     pool: web::Data<DatabasePool>,
     read_pool: web::Data<ReadDatabasePool>,
-    cache: web::Data<crate::services::cache_service_v2::CacheServiceV2>,
-    hb: web::Data<Mutex<Handlebars<'_>>>,
+    cache: web::Data<CacheServiceV2>,
+    templates: web::Data<TemplateService>,
 ) -> Result<HttpResponse, CustomHttpError> {
     let tenant_id = resolve_tenant_id(&req, &pool).unwrap_or(0); 
     let path = req.path();
@@ -114,16 +145,38 @@ pub async fn display_page(
         }
     }
 
+    // Renders with the theme the tenant activated, even if another instance activated it
+    if let Ok(mut conn) = pool.get() {
+        templates.sync_active_theme(tenant_id, &cache, &mut conn).await;
+    }
+
     // Use Read Replica for page display high-traffic endpoint
     let mut mysql_pool = read_pool.0.get().or(Err(CustomHttpError::InternalServerError("Read Pool connection failed".to_string())))?;
 
     // Use tenant-aware read
     let pagemodule = match Page::read_one_by_tenant_and_url(tenant_id, path.to_string(), &mut mysql_pool) {
+        Ok((page, _)) if page.status == Some(PageStatus::Archived) => {
+            return Ok(error_page(&templates, tenant_id, StatusCode::GONE));
+        }
         Ok(t) => parse_page(t)?,
-        Err(_) => {
-            let s = hb.lock().unwrap().render("404", &String::from("")).unwrap();
-            return Ok(HttpResponse::Ok().content_type("text/html").body(s));
+        Err(diesel::result::Error::NotFound) => {
+            // Redirects are only consulted for paths without a page
+            if let Some((redirect, mut location)) = redirect_service::find_redirect(tenant_id, path, &mut mysql_pool)? {
+                if let Some(query) = Some(req.query_string()).filter(|q| !q.is_empty() && !location.contains('?')) {
+                    location = format!("{}?{}", location, query);
+                }
+                // Hit counts go to the primary; a failed count must not break the redirect
+                if let Ok(mut conn) = pool.get() {
+                    let _ = redirect_service::record_hit(redirect.id, &mut conn);
+                }
+                let status = StatusCode::from_u16(redirect.status_code as u16).unwrap_or(StatusCode::MOVED_PERMANENTLY);
+                return Ok(HttpResponse::build(status)
+                    .insert_header((actix_web::http::header::LOCATION, location))
+                    .finish());
+            }
+            return Ok(error_page(&templates, tenant_id, StatusCode::NOT_FOUND));
         }
+        Err(e) => return Err(e.into()),
     };
 
     // Inject User Context data into the template
@@ -232,15 +285,14 @@ pub async fn display_page(
         }
     }
 
-    let s = hb
-        .lock()
-        .unwrap()
-        .render(&pagemodule.page_name, &context_data)
-        .unwrap();
+    // Tenants with an active theme render with its templates
+    let s = templates
+        .render_for_tenant(tenant_id, &pagemodule.page_name, &context_data)
+        .map_err(CustomHttpError::InternalServerError)?;
 
-    // 2. Store in Cache (Only static pages)
+    // 2. Store in Cache (Only static pages), tagged so edits can purge it
     if !is_dynamic_route {
-        let _ = cache.set(&cache_key, &s, None).await;
+        let _ = cache.set_with_tags(&cache_key, &s, None, &page_cache_tags(tenant_id, &pagemodule)).await;
     }
 
     Ok(HttpResponse::Ok().content_type("text/html").body(s))
@@ -266,6 +318,7 @@ pub async fn create_page(
     req: HttpRequest,
     new: web::Json<MutPage>,
     pool: web::Data<DatabasePool>,
+    plugins: web::Data<PluginRegistry>,
 ) -> Result<HttpResponse, CustomHttpError> {
     let user_ctx = get_user_context(&req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
     let tenant_id = resolve_tenant_id(&req, &pool).map_err(|e| CustomHttpError::BadRequest(e))?;
@@ -287,6 +340,9 @@ pub async fn create_page(
     uuid_new.tenant_id = Some(tenant_id);
 
     Page::create(&uuid_new, &mut mysql_pool)?;
+    if let Some(page_uuid) = &uuid_new.uuid {
+        plugins.page_saved(page_uuid).await;
+    }
 
     Ok(HttpResponse::Ok().json(uuid_new))
 }
@@ -359,7 +415,9 @@ pub async fn get_page(
         return Err(CustomHttpError::NotFound("Page not found".to_string()));
     }
 
-    Ok(HttpResponse::Ok().json(page))
+    Ok(HttpResponse::Ok()
+        .insert_header((actix_web::http::header::ETAG, page_etag(page.current_revision)))
+        .json(page))
 }
 
 /// Get page with associated modules
@@ -414,7 +472,8 @@ pub async fn get_page_join_modules(
     path = "/v1/pages/{id}",
     tag = "Content - Pages",
     params(
-        ("id" = String, Path, description = "Page UUID to update", example = "123e4567-e89b-12d3-a456-426614174000")
+        ("id" = String, Path, description = "Page UUID to update", example = "123e4567-e89b-12d3-a456-426614174000"),
+        ("If-Match" = Option<String>, Header, description = "ETag from a previous read; the update is rejected if the page changed since")
     ),
     request_body = MutPage,
     responses(
@@ -422,7 +481,8 @@ pub async fn get_page_join_modules(
         (status = 400, description = "Validation failed"),
         (status = 401, description = "Not authenticated"),
         (status = 403, description = "Insufficient permissions"),
-        (status = 404, description = "Page not found")
+        (status = 404, description = "Page not found"),
+        (status = 409, description = "Page was modified since the If-Match ETag; body holds the current page", body = PageDTO)
     ),
     security(
         ("bearer_auth" = [])
@@ -433,7 +493,8 @@ pub async fn update_page(
     updated_page: web::Json<MutPage>,
     id: web::Path<String>,
     pool: web::Data<DatabasePool>,
-    cache: web::Data<crate::services::cache_service_v2::CacheServiceV2>,
+    cache: web::Data<CacheServiceV2>,
+    plugins: web::Data<PluginRegistry>,
 ) -> Result<HttpResponse, CustomHttpError> {
     let user_ctx = get_user_context(&req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
     let tenant_id = resolve_tenant_id(&req, &pool).map_err(|e| CustomHttpError::BadRequest(e))?;
@@ -461,34 +522,56 @@ pub async fn update_page(
          return Err(CustomHttpError::NotFound("Page not found (tenant mismatch)".to_string()));
     }
 
-    // Invalidate Cache
-    let cache_key = format!("page:{}:{}:html", tenant_id, existing_page.page_url);
-    let _ = cache.delete(&cache_key).await;
-
-    // Create revision BEFORE updating the page
+    let mut final_page = updated_page.into_inner();
+    final_page.tenant_id = Some(tenant_id); // Enforce tenant persistence
     let user_id = Some(user_ctx.user_id);
-    match crate::services::revision_service::create_page_revision(
-        &id,
-        user_id,
-        Some("Page updated".to_string()),
-        &mut mysql_pool
-    ) {
-        Ok(rev_num) => {
-            log::info!("Created revision {} for page {}", rev_num, id);
-        }
-        Err(e) => {
-            log::warn!("Failed to create revision: {}", e);
-            // Continue with update even if revision fails
+
+    // Optimistic concurrency: lock the row, compare If-Match with current_revision,
+    // then snapshot + update in the same transaction so no save can slip in between.
+    let outcome = mysql_pool.transaction::<Result<(), Page>, CustomHttpError, _>(|conn| {
+        let locked = Page::read_one_for_update(id.clone(), conn)?;
+        if !if_match_satisfied(&req, &page_etag(locked.current_revision)) {
+            return Ok(Err(locked));
         }
+
+        // Create revision BEFORE updating the page. A failed snapshot fails the
+        // save: updating without it would change the content but not the ETag.
+        let rev_num = crate::services::revision_service::create_page_revision(
+            &id,
+            user_id,
+            Some("Page updated".to_string()),
+            conn
+        )?;
+        log::info!("Created revision {} for page {}", rev_num, id);
+        final_page.current_revision = Some(rev_num);
+        final_page.last_modified_by = user_id;
+
+        // Update the page, keeping links to a previous URL working
+        Page::update(id.clone(), &final_page, conn)?;
+        redirect_service::redirect_on_url_change(tenant_id, &locked.page_url, &final_page.page_url, conn)?;
+        Ok(Ok(()))
+    })?;
+
+    if let Err(current) = outcome {
+        let current_etag = page_etag(current.current_revision);
+        return Ok(HttpResponse::Conflict()
+            .insert_header((actix_web::http::header::ETAG, current_etag))
+            .json(json!({
+                "code": 409,
+                "error": "Conflict",
+                "message": "Page was modified by someone else. Reload or merge before saving.",
+                "current": PageDTO::from(current),
+            })));
     }
 
-    // Update the page
-    let mut final_page = updated_page.into_inner();
-    final_page.tenant_id = Some(tenant_id); // Enforce tenant persistence
-    
-    Page::update(id.clone(), &final_page, &mut mysql_pool)?;
+    // Purge after the update so a concurrent render cannot re-cache the old HTML
+    let _ = cache.purge_tags(&[page_tag(&id)]).await;
+    plugins.page_saved(&id).await;
 
-    Ok(HttpResponse::Ok().json(final_page))
+    let etag = page_etag(final_page.current_revision);
+    Ok(HttpResponse::Ok()
+        .insert_header((actix_web::http::header::ETAG, etag))
+        .json(final_page))
 }
 
 /// Delete a page
@@ -513,7 +596,7 @@ pub async fn delete_page(
     req: HttpRequest,
     id: web::Path<String>,
     pool: web::Data<DatabasePool>,
-    cache: web::Data<crate::services::cache_service_v2::CacheServiceV2>,
+    cache: web::Data<CacheServiceV2>,
 ) -> Result<HttpResponse, CustomHttpError> {
     let user_ctx = get_user_context(&req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
     let tenant_id = resolve_tenant_id(&req, &pool).map_err(|e| CustomHttpError::BadRequest(e))?;
@@ -533,11 +616,9 @@ pub async fn delete_page(
          return Err(CustomHttpError::NotFound("Page not found (tenant mismatch)".to_string()));
     }
 
-    // Invalidate Cache
-    let cache_key = format!("page:{}:{}:html", tenant_id, existing_page.page_url);
-    let _ = cache.delete(&cache_key).await;
-
+    embedding_refresh_service::delete_page_embeddings(&id, &mut mysql_pool)?;
     let res = Page::delete(id.clone(), &mut mysql_pool)?;
+    let _ = cache.purge_tags(&[page_tag(&id)]).await;
 
     Ok(HttpResponse::Ok().json(res))
 }
@@ -0,0 +1,240 @@
+// Redirect Controller
+// Manage per-tenant redirects for public pages
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::helpers::tenant_helper::{get_tenant_role, resolve_tenant_id};
+use crate::middleware::auth_middleware::get_user_context;
+use crate::models::rbac::{has_permission, Permission};
+use crate::models::redirect_models::{NewRedirect, Redirect};
+use crate::models::{pool_handler, DatabasePool, PooledDatabaseConnection};
+use crate::schema::redirects;
+use crate::services::errors_service::CustomHttpError;
+use crate::services::redirect_service::{self, CsvRowError};
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateRedirectRequest {
+    pub source: String,
+    pub target: String,
+    /// "exact" (default), "prefix" or "regex"
+    pub match_type: Option<String>,
+    /// 301 (default) or 302
+    pub status_code: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateRedirectRequest {
+    pub target: Option<String>,
+    pub status_code: Option<i32>,
+    pub is_active: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportRedirectsResponse {
+    pub imported: usize,
+    pub errors: Vec<CsvRowError>,
+}
+
+fn authorize(
+    req: &HttpRequest,
+    pool: web::Data<DatabasePool>,
+    permission: Permission,
+) -> Result<(i32, PooledDatabaseConnection), CustomHttpError> {
+    let user_ctx = get_user_context(req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
+    let tenant_id = resolve_tenant_id(req, &pool).map_err(CustomHttpError::BadRequest)?;
+    let mut conn = pool_handler(pool)?;
+
+    let role = get_tenant_role(tenant_id, user_ctx.user_id, &mut conn)
+        .map_err(|_| CustomHttpError::Forbidden("Access denied".to_string()))?;
+    if !has_permission(&role, permission) {
+        return Err(CustomHttpError::Forbidden("Insufficient permissions".to_string()));
+    }
+    Ok((tenant_id, conn))
+}
+
+fn find_tenant_redirect(id: i32, tenant_id: i32, conn: &mut PooledDatabaseConnection) -> Result<Redirect, CustomHttpError> {
+    redirects::table
+        .find(id)
+        .filter(redirects::tenant_id.eq(tenant_id))
+        .first::<Redirect>(conn)
+        .optional()?
+        .ok_or(CustomHttpError::NotFound("Redirect not found".to_string()))
+}
+
+/// List the site's redirects
+#[utoipa::path(
+    get,
+    path = "/v1/api/redirects",
+    tag = "Content - SEO",
+    responses(
+        (status = 200, description = "Redirects with hit counts", body = Vec<Redirect>),
+        (status = 403, description = "Insufficient permissions")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_redirects(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::ViewContent)?;
+
+    let rules = redirects::table
+        .filter(redirects::tenant_id.eq(tenant_id))
+        .order((redirects::match_type.asc(), redirects::source.asc()))
+        .load::<Redirect>(&mut conn)?;
+
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+/// Create a redirect, replacing an existing one with the same source
+#[utoipa::path(
+    post,
+    path = "/v1/api/redirects",
+    tag = "Content - SEO",
+    request_body = CreateRedirectRequest,
+    responses(
+        (status = 201, description = "Redirect saved", body = Redirect),
+        (status = 400, description = "Invalid redirect"),
+        (status = 403, description = "Insufficient permissions")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn create_redirect(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    payload: web::Json<CreateRedirectRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::EditContent)?;
+    let payload = payload.into_inner();
+
+    let rule = NewRedirect {
+        tenant_id,
+        source: payload.source.trim().to_string(),
+        target: payload.target.trim().to_string(),
+        match_type: payload.match_type.unwrap_or_else(|| "exact".to_string()).to_lowercase(),
+        status_code: payload.status_code.unwrap_or(301),
+    };
+    redirect_service::validate(&rule).map_err(CustomHttpError::BadRequest)?;
+
+    let saved = redirect_service::upsert(&rule, &mut conn)?;
+    Ok(HttpResponse::Created().json(saved))
+}
+
+/// Update a redirect's target, status or active flag
+#[utoipa::path(
+    put,
+    path = "/v1/api/redirects/{id}",
+    tag = "Content - SEO",
+    params(
+        ("id" = i32, Path, description = "Redirect ID")
+    ),
+    request_body = UpdateRedirectRequest,
+    responses(
+        (status = 200, description = "Redirect updated", body = Redirect),
+        (status = 400, description = "Invalid redirect"),
+        (status = 404, description = "Redirect not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_redirect(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    id: web::Path<i32>,
+    payload: web::Json<UpdateRedirectRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::EditContent)?;
+    let existing = find_tenant_redirect(id.into_inner(), tenant_id, &mut conn)?;
+
+    let rule = NewRedirect {
+        tenant_id,
+        source: existing.source.clone(),
+        target: payload.target.as_deref().map(str::trim).unwrap_or(&existing.target).to_string(),
+        match_type: existing.match_type.clone(),
+        status_code: payload.status_code.unwrap_or(existing.status_code),
+    };
+    redirect_service::validate(&rule).map_err(CustomHttpError::BadRequest)?;
+
+    let updated = diesel::update(redirects::table.find(existing.id))
+        .set((
+            redirects::target.eq(&rule.target),
+            redirects::status_code.eq(rule.status_code),
+            redirects::is_active.eq(payload.is_active.unwrap_or(existing.is_active)),
+            redirects::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .get_result::<Redirect>(&mut conn)?;
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// Delete a redirect
+#[utoipa::path(
+    delete,
+    path = "/v1/api/redirects/{id}",
+    tag = "Content - SEO",
+    params(
+        ("id" = i32, Path, description = "Redirect ID")
+    ),
+    responses(
+        (status = 204, description = "Redirect deleted"),
+        (status = 404, description = "Redirect not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_redirect(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::EditContent)?;
+    let existing = find_tenant_redirect(id.into_inner(), tenant_id, &mut conn)?;
+
+    diesel::delete(redirects::table.find(existing.id)).execute(&mut conn)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Import redirects from CSV
+///
+/// Body is `text/csv` with rows of `source,target[,status_code[,match_type]]`
+/// and an optional header. Valid rows are saved (replacing rules with the same
+/// source); invalid rows are reported by line number.
+#[utoipa::path(
+    post,
+    path = "/v1/api/redirects/import",
+    tag = "Content - SEO",
+    request_body(content = String, description = "CSV rows: source,target[,status_code[,match_type]]", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Import summary", body = ImportRedirectsResponse),
+        (status = 403, description = "Insufficient permissions")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn import_redirects(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    body: String,
+) -> Result<HttpResponse, CustomHttpError> {
+    let (tenant_id, mut conn) = authorize(&req, pool, Permission::EditContent)?;
+    let (rules, errors) = redirect_service::parse_csv(tenant_id, &body);
+
+    let imported = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        for rule in &rules {
+            redirect_service::upsert(rule, conn)?;
+        }
+        Ok(rules.len())
+    })?;
+
+    Ok(HttpResponse::Ok().json(ImportRedirectsResponse { imported, errors }))
+}
@@ -1,12 +1,26 @@
 // Simplified Revision Controller
 // Iteration 4, Task 2 - Basic implementation
 
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse};
 use diesel::prelude::*;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
 use utoipa::ToSchema;
 
-use crate::services::database_service;
 use crate::models::revision_models::{PageRevision, RevisionSummary};
+use crate::helpers::tenant_helper::{resolve_tenant_id, get_tenant_role};
+use crate::middleware::auth_middleware::get_user_context;
+use crate::models::{pool_handler, DatabasePool, Model, PooledDatabaseConnection};
+use crate::models::module_models::{Module, MutModule};
+use crate::models::page_models::{MutPage, Page};
+use crate::models::rbac::{has_permission, Permission};
+use crate::services::cache_service_v2::{module_tag, page_tag, CacheServiceV2};
+use crate::services::embedding_refresh_service;
+use crate::services::errors_service::CustomHttpError;
+use crate::services::plugin_service::PluginRegistry;
+use crate::services::revision_diff_service::{self, MergeConflict, IGNORED_PAGE_FIELDS};
+use crate::services::revision_service::{self, RevisionState};
 
 /// List all revisions for a page
 #[utoipa::path(
@@ -18,36 +32,40 @@ use crate::models::revision_models::{PageRevision, RevisionSummary};
     ),
     responses(
         (status = 200, description = "List of page revisions"),
+        (status = 404, description = "Page not found"),
         (status = 500, description = "Failed to fetch revisions")
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-pub async fn list_revisions(page_uuid_param: web::Path<String>) -> impl Responder {
+pub async fn list_revisions(
+    req: HttpRequest,
+    page_uuid_param: web::Path<String>,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
     use crate::schema::page_revisions::dsl::*;
-    
-    let mut conn = database_service::establish_connection();
+
     let uuid_str = page_uuid_param.into_inner();
-    
-    match page_revisions
+    let (_, mut conn) = authorize_page(&req, pool, &uuid_str, Permission::ViewContent)?;
+
+    let revisions = page_revisions
         .filter(page_uuid.eq(uuid_str))
         .order(revision_number.desc())
-        .load::<PageRevision>(&mut conn)
-    {
-        Ok(revisions) => {
-            let summaries: Vec<RevisionSummary> = revisions
-                .iter()
-                .map(|r| RevisionSummary {
-                    id: r.id,
-                    revision_number: r.revision_number,
-                    change_summary: r.change_summary.clone(),
-                    created_at: r.created_at,
-                    changed_by_user_id: r.changed_by_user_id,
-                })
-                .collect();
-            
-            HttpResponse::Ok().json(summaries)
-        }
-        Err(_) => HttpResponse::InternalServerError().json("Failed to fetch revisions"),
-    }
+        .load::<PageRevision>(&mut conn)?;
+
+    let summaries: Vec<RevisionSummary> = revisions
+        .iter()
+        .map(|r| RevisionSummary {
+            id: r.id,
+            revision_number: r.revision_number,
+            change_summary: r.change_summary.clone(),
+            created_at: r.created_at,
+            changed_by_user_id: r.changed_by_user_id,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(summaries))
 }
 
 /// Get specific revision
@@ -62,25 +80,35 @@ pub async fn list_revisions(page_uuid_param: web::Path<String>) -> impl Responde
     responses(
         (status = 200, description = "Page revision details"),
         (status = 404, description = "Revision not found")
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-pub async fn get_revision(path: web::Path<(String, i32)>) -> impl Responder {
+pub async fn get_revision(
+    req: HttpRequest,
+    path: web::Path<(String, i32)>,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
     use crate::schema::page_revisions::dsl::*;
-    
+
     let (uuid, rev_num) = path.into_inner();
-    let mut conn = database_service::establish_connection();
-    
-    match page_revisions
+    let (_, mut conn) = authorize_page(&req, pool, &uuid, Permission::ViewContent)?;
+
+    let revision = page_revisions
         .filter(page_uuid.eq(uuid))
         .filter(revision_number.eq(rev_num))
         .first::<PageRevision>(&mut conn)
-    {
-        Ok(revision) => HttpResponse::Ok().json(revision),
-        Err(_) => HttpResponse::NotFound().json("Revision not found"),
-    }
+        .optional()?
+        .ok_or(CustomHttpError::NotFound("Revision not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(revision))
 }
 
 /// Rollback to a specific revision
+///
+/// Restores the page together with its modules and categories as captured by the
+/// revision. The state being replaced is saved as a new revision first.
 #[utoipa::path(
     post,
     path = "/api/pages/{page_uuid}/rollback/{rev_number}",
@@ -93,71 +121,388 @@ pub async fn get_revision(path: web::Path<(String, i32)>) -> impl Responder {
         (status = 200, description = "Page rolled back successfully"),
         (status = 404, description = "Revision not found"),
         (status = 500, description = "Rollback failed")
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-pub async fn rollback_revision(path: web::Path<(String, i32)>) -> impl Responder {
-    use crate::schema::page_revisions::dsl::*;
-    use crate::schema::pages;
-    
+pub async fn rollback_revision(
+    req: HttpRequest,
+    path: web::Path<(String, i32)>,
+    pool: web::Data<DatabasePool>,
+    cache: web::Data<CacheServiceV2>,
+) -> Result<HttpResponse, CustomHttpError> {
     let (uuid, rev_num) = path.into_inner();
-    let mut conn = database_service::establish_connection();
-    
-    // 1. Load the revision
-    let revision = match page_revisions
-        .filter(page_uuid.eq(&uuid))
-        .filter(revision_number.eq(rev_num))
-        .first::<PageRevision>(&mut conn)
-    {
-        Ok(rev) => rev,
-        Err(_) => return HttpResponse::NotFound().json("Revision not found"),
+    let (user_id, mut conn) = authorize_page(&req, pool, &uuid, Permission::EditContent)?;
+
+    let target = revision_service::load_revision_state(&uuid, rev_num, &mut conn)?
+        .ok_or(CustomHttpError::NotFound("Revision not found".to_string()))?;
+    let previous = revision_service::load_current_state(&uuid, &mut conn)?;
+
+    let new_revision = revision_service::restore_state(
+        &uuid,
+        &target,
+        Some(user_id),
+        Some(format!("Rolled back to revision {}", rev_num)),
+        &mut conn,
+    )?;
+    embedding_refresh_service::enqueue_page(&uuid);
+
+    let restored = revision_service::load_current_state(&uuid, &mut conn)?;
+    // Modules the rollback removed are purged as well as those it restored
+    let mut tags = vec![page_tag(&uuid)];
+    tags.extend(
+        previous.modules.iter().chain(restored.modules.iter()).flatten().map(|m| module_tag(&m.uuid)),
+    );
+    let _ = cache.purge_tags(&tags).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Page rolled back successfully",
+        "page_uuid": uuid,
+        "rollback_to_revision": rev_num,
+        "revision": new_revision,
+        "modules_restored": target.modules.is_some(),
+        "categories_restored": target.categories.is_some(),
+        "page": restored.page,
+        "modules": restored.modules,
+        "categories": restored.categories,
+    })))
+}
+
+/// Page fields the editor cannot change through a merge
+const PROTECTED_PAGE_FIELDS: &[&str] = &["uuid", "tenant_id", "time_created", "current_revision", "last_modified_by"];
+
+/// Checks membership + permission and that the page belongs to the caller's tenant
+fn authorize_page(
+    req: &HttpRequest,
+    pool: web::Data<DatabasePool>,
+    page_uuid: &str,
+    permission: Permission,
+) -> Result<(i32, PooledDatabaseConnection), CustomHttpError> {
+    let user_ctx = get_user_context(req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
+    let tenant_id = resolve_tenant_id(req, &pool).map_err(CustomHttpError::BadRequest)?;
+    let mut conn = pool_handler(pool)?;
+
+    let role = get_tenant_role(tenant_id, user_ctx.user_id, &mut conn)
+        .map_err(|_| CustomHttpError::Forbidden("Access denied".to_string()))?;
+    if !has_permission(&role, permission) {
+        return Err(CustomHttpError::Forbidden("Insufficient permissions".to_string()));
+    }
+
+    let page = Page::read_one(page_uuid.to_string(), &mut conn)?;
+    if page.tenant_id.unwrap_or(0) != tenant_id {
+        return Err(CustomHttpError::NotFound("Page not found (tenant mismatch)".to_string()));
+    }
+
+    Ok((user_ctx.user_id, conn))
+}
+
+fn page_json(state: &RevisionState) -> Value {
+    serde_json::to_value(&state.page).unwrap_or(Value::Null)
+}
+
+fn modules_json(modules: &[Module]) -> Vec<Value> {
+    modules.iter().filter_map(|m| serde_json::to_value(m).ok()).collect()
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    pub from: i32,
+    /// Omit to compare against the live page
+    pub to: Option<i32>,
+}
+
+/// Field-by-field diff between two revisions (or a revision and the live page)
+#[utoipa::path(
+    get,
+    path = "/api/pages/{page_uuid}/revisions/diff",
+    tag = "Content - Revisions",
+    params(
+        ("page_uuid" = String, Path, description = "Page UUID"),
+        ("from" = i32, Query, description = "Revision number to diff from"),
+        ("to" = Option<i32>, Query, description = "Revision number to diff to (defaults to the live page)")
+    ),
+    responses(
+        (status = 200, description = "Structured diff of page fields and modules"),
+        (status = 404, description = "Revision not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn diff_revisions(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<DiffQuery>,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let page_uuid = path.into_inner();
+    let (_, mut conn) = authorize_page(&req, pool, &page_uuid, Permission::ViewContent)?;
+
+    let from_state = revision_service::load_revision_state(&page_uuid, query.from, &mut conn)?
+        .ok_or(CustomHttpError::NotFound(format!("Revision {} not found", query.from)))?;
+    let to_state = match query.to {
+        Some(rev) => revision_service::load_revision_state(&page_uuid, rev, &mut conn)?
+            .ok_or(CustomHttpError::NotFound(format!("Revision {} not found", rev)))?,
+        None => revision_service::load_current_state(&page_uuid, &mut conn)?,
     };
-    
-    // 2. Deserialize the full_snapshot to get page state
-    let restored_page: crate::models::page_models::Page = match serde_json::from_str(&revision.full_snapshot) {
-        Ok(page) => page,
-        Err(e) =>  {
-            return HttpResponse::InternalServerError()
-                .json(format!("Failed to deserialize snapshot: {}", e));
-        }
+
+    let fields = revision_diff_service::diff_fields(&page_json(&from_state), &page_json(&to_state), IGNORED_PAGE_FIELDS);
+
+    // Legacy revisions carry no module snapshot, so modules can't be compared
+    let modules = match (&from_state.modules, &to_state.modules) {
+        (Some(before), Some(after)) => Some(revision_diff_service::diff_modules(&modules_json(before), &modules_json(after))),
+        _ => None,
     };
-    
-    // 3. Update the page with revision data
-    // We'll use Diesel's update directly
-    match diesel::update(pages::table.filter(pages::uuid.eq(&uuid)))
-        .set((
-            pages::page_title.eq(&restored_page.page_title),
-            pages::page_url.eq(&restored_page.page_url),
-            pages::page_name.eq(&restored_page.page_name),
-            pages::meta_title.eq(&restored_page.meta_title),
-            pages::meta_description.eq(&restored_page.meta_description),
-            pages::meta_keywords.eq(&restored_page.meta_keywords),
-            pages::canonical_url.eq(&restored_page.canonical_url),
-            pages::og_title.eq(&restored_page.og_title),
-            pages::og_description.eq(&restored_page.og_description),
-            pages::og_image.eq(&restored_page.og_image),
-            pages::twitter_card.eq(&restored_page.twitter_card),
-            pages::twitter_title.eq(&restored_page.twitter_title),
-            pages::twitter_description.eq(&restored_page.twitter_description),
-            pages::author.eq(&restored_page.author),
-            pages::article_type.eq(&restored_page.article_type),
-            pages::featured_image.eq(&restored_page.featured_image),
-            pages::word_count.eq(&restored_page.word_count),
-            pages::reading_time.eq(&restored_page.reading_time),
-        ))
-        .execute(&mut conn)
-    {
-        Ok(_) => {
-            // Successfully rolled back - return success without creating new revision
-            // (Creating revision requires complex connection type handling)
-            HttpResponse::Ok().json(serde_json::json!({
-                "message": "Page rolled back successfully",
-                "page_uuid": uuid,
-                "rollback_to_revision": rev_num
-            }))
+
+    Ok(HttpResponse::Ok().json(json!({
+        "page_uuid": page_uuid,
+        "from": query.from,
+        "to": query.to.map(|r| json!(r)).unwrap_or(json!("current")),
+        "fields": fields,
+        "modules_compared": modules.is_some(),
+        "modules": modules.unwrap_or_default(),
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    /// `current_revision` of the page when the editor started editing
+    pub base_revision: i32,
+    /// Editor's page fields; omitted fields are treated as unchanged
+    pub page: Value,
+    /// Editor's full module list; omit to leave modules untouched
+    pub modules: Option<Vec<Value>>,
+    /// Conflict resolutions keyed by conflict path
+    #[serde(default)]
+    pub resolutions: HashMap<String, Value>,
+    /// Persist the merged result when it is conflict-free
+    #[serde(default)]
+    pub apply: bool,
+}
+
+/// Applies client-chosen values to conflicts and returns the unresolved ones
+fn apply_resolutions(
+    page: &mut Value,
+    modules: &mut Vec<Value>,
+    conflicts: Vec<MergeConflict>,
+    resolutions: &HashMap<String, Value>,
+) -> Vec<MergeConflict> {
+    let mut unresolved = Vec::new();
+
+    for conflict in conflicts {
+        let resolution = match resolutions.get(&conflict.path) {
+            Some(value) => value.clone(),
+            None => {
+                unresolved.push(conflict);
+                continue;
+            }
+        };
+
+        match conflict.path.strip_prefix("modules.") {
+            None => {
+                if let Some(obj) = page.as_object_mut() {
+                    obj.insert(conflict.path.clone(), resolution);
+                }
+            }
+            Some(rest) => {
+                let (module_uuid, field) = match rest.split_once('.') {
+                    Some((uuid, field)) => (uuid, Some(field)),
+                    None => (rest, None),
+                };
+                let position = modules.iter().position(|m| m["uuid"] == json!(module_uuid));
+                match (field, position) {
+                    (Some(field), Some(idx)) => {
+                        if let Some(obj) = modules[idx].as_object_mut() {
+                            obj.insert(field.to_string(), resolution);
+                        }
+                    }
+                    // Whole-module conflicts: null deletes, an object keeps that version
+                    (None, Some(idx)) if resolution.is_null() => { modules.remove(idx); }
+                    (None, Some(idx)) => modules[idx] = resolution,
+                    (None, None) if !resolution.is_null() => modules.push(resolution),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    unresolved
+}
+
+/// Writes the merged page (and modules, when provided) in one transaction.
+/// Returns `Err` with the page's revision if a save landed after `current` was read.
+fn persist_merge(
+    page_uuid: &str,
+    user_id: i32,
+    current: &RevisionState,
+    merged_page: &Value,
+    merged_modules: Option<&Vec<Value>>,
+    conn: &mut PooledDatabaseConnection,
+) -> Result<Result<i32, Option<i32>>, CustomHttpError> {
+    let merged: Page = serde_json::from_value(merged_page.clone())
+        .map_err(|e| CustomHttpError::BadRequest(format!("Invalid merged page: {}", e)))?;
+    let mut changes: MutPage = merged.into();
+    changes.uuid = Some(current.page.uuid.clone());
+    changes.tenant_id = current.page.tenant_id;
+    crate::controllers::page_controllers::validate_seo_fields(&changes)?;
+
+    let current_modules = current.modules.clone().unwrap_or_default();
+
+    conn.transaction::<_, CustomHttpError, _>(|conn| {
+        // The merge was computed against `current`; a save since then would be overwritten
+        let locked = Page::read_one_for_update(page_uuid.to_string(), conn)?;
+        if locked.current_revision != current.page.current_revision {
+            return Ok(Err(locked.current_revision));
         }
-        Err(e) => {
-            HttpResponse::InternalServerError()
-                .json(format!("Failed to rollback page: {}", e))
+
+        let rev_num = revision_service::create_page_revision(
+            page_uuid,
+            Some(user_id),
+            Some("Merged concurrent edit".to_string()),
+            conn,
+        )?;
+        changes.current_revision = Some(rev_num);
+        changes.last_modified_by = Some(user_id);
+        Page::update(page_uuid.to_string(), &changes, conn)?;
+
+        if let Some(modules) = merged_modules {
+            let mut kept: Vec<String> = Vec::new();
+            for module in modules {
+                let mut module = module.clone();
+                if let Some(obj) = module.as_object_mut() {
+                    obj.insert("page_uuid".to_string(), json!(page_uuid));
+                }
+                let mut mut_module: MutModule = serde_json::from_value(module)
+                    .map_err(|e| CustomHttpError::BadRequest(format!("Invalid module: {}", e)))?;
+
+                match mut_module.uuid.clone() {
+                    Some(module_uuid) if current_modules.iter().any(|m| m.uuid == module_uuid) => {
+                        Module::update(module_uuid.clone(), &mut_module, conn)?;
+                        kept.push(module_uuid);
+                    }
+                    existing_uuid => {
+                        let module_uuid = existing_uuid.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+                        mut_module.uuid = Some(module_uuid.clone());
+                        Module::create(&mut_module, conn)?;
+                        kept.push(module_uuid);
+                    }
+                }
+            }
+            for module in current_modules.iter().filter(|m| !kept.contains(&m.uuid)) {
+                Module::delete(module.uuid.clone(), conn)?;
+            }
         }
+
+        Ok(Ok(rev_num))
+    })
+}
+
+/// Three-way merge of an editor's changes with edits saved since their base revision
+#[utoipa::path(
+    post,
+    path = "/api/pages/{page_uuid}/revisions/merge",
+    tag = "Content - Revisions",
+    params(
+        ("page_uuid" = String, Path, description = "Page UUID")
+    ),
+    responses(
+        (status = 200, description = "Merged result (persisted when `apply` is true)"),
+        (status = 404, description = "Base revision not found"),
+        (status = 409, description = "Merge conflicts that need resolving")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn merge_revision(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<MergeRequest>,
+    pool: web::Data<DatabasePool>,
+    cache: web::Data<CacheServiceV2>,
+    plugins: web::Data<PluginRegistry>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let page_uuid = path.into_inner();
+    let (user_id, mut conn) = authorize_page(&req, pool, &page_uuid, Permission::EditContent)?;
+    let request = body.into_inner();
+
+    let base = revision_service::load_state_at_version(&page_uuid, request.base_revision, &mut conn)?
+        .ok_or(CustomHttpError::NotFound(format!("Base revision {} not found", request.base_revision)))?;
+    let current = revision_service::load_current_state(&page_uuid, &mut conn)?;
+
+    // Ours = base overlaid with the editor's fields
+    let base_page = page_json(&base);
+    let mut ours_page = base_page.clone();
+    if let (Some(ours), Some(edits)) = (ours_page.as_object_mut(), request.page.as_object()) {
+        for (key, value) in edits {
+            if !PROTECTED_PAGE_FIELDS.contains(&key.as_str()) {
+                ours.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    let page_merge = revision_diff_service::merge_fields(
+        &base_page,
+        &ours_page,
+        &page_json(&current),
+        "",
+        PROTECTED_PAGE_FIELDS,
+    );
+    let mut merged_page = page_merge.merged;
+    let mut conflicts = page_merge.conflicts;
+
+    let mut merged_modules: Vec<Value> = Vec::new();
+    if let Some(ours_modules) = &request.modules {
+        let base_modules = base.modules.as_deref().map(modules_json).unwrap_or_default();
+        let theirs_modules = modules_json(current.modules.as_deref().unwrap_or(&[]));
+        let (merged, module_conflicts) = revision_diff_service::merge_modules(&base_modules, ours_modules, &theirs_modules);
+        conflicts.extend(module_conflicts);
+        merged_modules = merged;
     }
+
+    let conflicts = apply_resolutions(&mut merged_page, &mut merged_modules, conflicts, &request.resolutions);
+    // Modules are only touched when the editor sent them
+    let merged_modules = request.modules.as_ref().map(|_| merged_modules);
+
+    if !conflicts.is_empty() {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "message": "Merge conflicts must be resolved",
+            "base_revision": request.base_revision,
+            "current_revision": current.page.current_revision,
+            "conflicts": conflicts,
+            "merged": { "page": merged_page, "modules": merged_modules },
+        })));
+    }
+
+    let revision = if request.apply {
+        let revision = match persist_merge(&page_uuid, user_id, &current, &merged_page, merged_modules.as_ref(), &mut conn)? {
+            Ok(revision) => revision,
+            Err(current_revision) => {
+                return Ok(HttpResponse::Conflict().json(json!({
+                    "message": "Page was modified during the merge. Merge again against the current revision.",
+                    "base_revision": request.base_revision,
+                    "current_revision": current_revision,
+                    "conflicts": [],
+                })));
+            }
+        };
+        embedding_refresh_service::enqueue_page(&page_uuid);
+
+        // Purge after the write so a concurrent render cannot re-cache the old HTML
+        let mut tags = vec![page_tag(&page_uuid)];
+        tags.extend(current.modules.iter().flatten().map(|m| module_tag(&m.uuid)));
+        let _ = cache.purge_tags(&tags).await;
+        plugins.page_saved(&page_uuid).await;
+        Some(revision)
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "applied": revision.is_some(),
+        "revision": revision,
+        "page": merged_page,
+        "modules": merged_modules,
+    })))
 }
@@ -1,7 +1,36 @@
-// Search Controller - Temporarily disabled
-use actix_web::{web, HttpResponse, Responder, get};
-use utoipa::ToSchema;
-use crate::models::DatabasePool;
+// Search Controller - full-text search over pages, modules and media
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+
+use crate::helpers::tenant_helper::{get_tenant_role, resolve_tenant_id};
+use crate::middleware::auth_middleware::get_user_context;
+use crate::models::rbac::{has_permission, Permission};
+use crate::models::{pool_handler, DatabasePool};
+use crate::services::errors_service::CustomHttpError;
+use crate::services::search_service::{self, SearchMode, SearchQuery};
+use crate::services::semantic_search_service;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    /// Search terms: `"exact phrase"`, `prefix*`, `-excluded`, `a OR b`
+    pub q: String,
+    /// Comma-separated resource types (pages, modules, media)
+    pub resources: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Language code; defaults to the site's default language
+    pub lang: Option<String>,
+    /// keyword (default), semantic or hybrid
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchClick {
+    pub resource_type: String,
+    pub resource_id: String,
+    /// 1-based position of the result in the list the user saw
+    pub position: Option<i32>,
+}
 
 /// Search across pages, modules, and media
 #[utoipa::path(
@@ -9,21 +38,127 @@ use crate::models::DatabasePool;
     path = "/search",
     tag = "Content - Search",
     params(
-        ("q" = String, Query, description = "Search query"),
-        ("resources" = Option<String>, Query, description = "Resources to search"),
-        ("page" = Option<i32>, Query, description = "Page number"),
-        ("per_page" = Option<i32>, Query, description = "Results per page")
+        ("q" = String, Query, description = "Search terms: \"exact phrase\", prefix*, -excluded, a OR b"),
+        ("resources" = Option<String>, Query, description = "Comma-separated resource types (pages, modules, media)"),
+        ("page" = Option<i64>, Query, description = "Page number"),
+        ("per_page" = Option<i64>, Query, description = "Results per page"),
+        ("lang" = Option<String>, Query, description = "Language code (defaults to the default language)"),
+        ("mode" = Option<String>, Query, description = "keyword (default), semantic or hybrid")
     ),
     responses(
-        (status = 501, description = "Search temporarily disabled")
+        (status = 200, description = "Ranked results with highlighted snippets, per-resource facet counts and a search_id for click feedback"),
+        (status = 400, description = "Unknown search mode"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Insufficient permissions")
+    ),
+    security(
+        ("bearer_auth" = [])
     )
 )]
-#[get("/search")]
 pub async fn search_content(
-    _query: web::Query<serde_json::Value>,
-    _pool: web::Data<DatabasePool>
-) -> impl Responder {
-    HttpResponse::NotImplemented().json(serde_json::json!({
-   "error": "Search functionality temporarily disabled"
-    }))
+    req: HttpRequest,
+    params: web::Query<SearchParams>,
+    pool: web::Data<DatabasePool>
+) -> Result<HttpResponse, CustomHttpError> {
+    let user_ctx = get_user_context(&req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
+    let tenant_id = resolve_tenant_id(&req, &pool).map_err(CustomHttpError::BadRequest)?;
+    let mut conn = pool_handler(pool)?;
+
+    let role = get_tenant_role(tenant_id, user_ctx.user_id, &mut conn)
+        .map_err(|_| CustomHttpError::Forbidden("Access denied".to_string()))?;
+    if !has_permission(&role, Permission::ViewContent) {
+        return Err(CustomHttpError::Forbidden("Insufficient permissions".to_string()));
+    }
+
+    let params = params.into_inner();
+    let mode = match params.mode.as_deref() {
+        None => SearchMode::Keyword,
+        Some(mode) => SearchMode::from_str(mode)
+            .ok_or(CustomHttpError::BadRequest(format!("Unknown search mode: {}", mode)))?,
+    };
+    let query = SearchQuery {
+        q: params.q,
+        resources: params.resources.map(|list| {
+            list.split(',')
+                .map(|r| r.trim().to_string())
+                .filter(|r| !r.is_empty())
+                .collect()
+        }),
+        page: params.page,
+        per_page: params.per_page,
+        lang: params.lang,
+    };
+    let weights = search_service::load_weights(tenant_id, &mut conn)?;
+
+    let mut response = match mode {
+        SearchMode::Keyword => search_service::search(&query, Some(tenant_id), &mut conn)?,
+        SearchMode::Semantic | SearchMode::Hybrid => {
+            // Fall back to keyword ranking when no embedding can be produced
+            let provider = semantic_search_service::load_embedding_provider(&mut conn)?;
+            let embedding = match semantic_search_service::generate_embedding_vector(provider.as_ref(), &query.q).await {
+                Ok(embedding) => Some(embedding),
+                Err(e) => {
+                    log::warn!("Query embedding failed, using keyword ranking only: {}", e);
+                    None
+                }
+            };
+            let embedding = embedding.as_deref().map(|vector| (vector, provider.model()));
+            search_service::hybrid_search(&query, Some(tenant_id), mode, embedding, &weights, &mut conn)?
+        }
+    };
+
+    match search_service::log_search(tenant_id, Some(user_ctx.user_id), &query, mode, &response, &weights, &mut conn) {
+        Ok(search_id) => response.search_id = Some(search_id),
+        Err(e) => log::warn!("Failed to log search: {}", e),
+    }
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Record a click on a search result, for relevance tuning
+#[utoipa::path(
+    post,
+    path = "/search/{search_id}/click",
+    tag = "Content - Search",
+    params(
+        ("search_id" = i64, Path, description = "search_id returned by /search")
+    ),
+    responses(
+        (status = 204, description = "Click recorded"),
+        (status = 404, description = "Search not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn record_search_click(
+    req: HttpRequest,
+    path: web::Path<i64>,
+    body: web::Json<SearchClick>,
+    pool: web::Data<DatabasePool>
+) -> Result<HttpResponse, CustomHttpError> {
+    let user_ctx = get_user_context(&req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
+    let tenant_id = resolve_tenant_id(&req, &pool).map_err(CustomHttpError::BadRequest)?;
+    let mut conn = pool_handler(pool)?;
+
+    let role = get_tenant_role(tenant_id, user_ctx.user_id, &mut conn)
+        .map_err(|_| CustomHttpError::Forbidden("Access denied".to_string()))?;
+    if !has_permission(&role, Permission::ViewContent) {
+        return Err(CustomHttpError::Forbidden("Insufficient permissions".to_string()));
+    }
+
+    let recorded = search_service::record_click(
+        tenant_id,
+        path.into_inner(),
+        Some(user_ctx.user_id),
+        &body.resource_type,
+        &body.resource_id,
+        body.position,
+        &mut conn,
+    )?;
+    if !recorded {
+        return Err(CustomHttpError::NotFound("Search not found".to_string()));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
 }
@@ -207,7 +207,10 @@ pub async fn update_site(
         let res = diesel::update(tenants::table.find(site_id))
             .set(tenants::custom_domain.eq(Some(custom_domain.clone())))
             .execute(&mut conn);
-        if res.is_ok() { updated = true; }
+        if res.is_ok() {
+            updated = true;
+            crate::helpers::tenant_helper::invalidate_host_cache();
+        }
     }
 
     if updated {
@@ -0,0 +1,151 @@
+// Storage Migration Controller
+// Platform-admin tool for moving all media between local storage and S3
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::middleware::auth_middleware::get_user_context;
+use crate::models::config_models::LocalConfig;
+use crate::models::{pool_handler, DatabasePool};
+use crate::services::errors_service::CustomHttpError;
+use crate::services::storage_migration_service::{self, Direction};
+use crate::services::storage_service::StorageBackend;
+
+#[derive(Deserialize, ToSchema)]
+pub struct StartStorageMigrationRequest {
+    pub direction: Direction,
+    /// Remove each file from the old storage once it is copied (default false)
+    #[serde(default)]
+    pub delete_source: bool,
+}
+
+/// The user ID of a platform admin
+fn require_platform_admin(req: &HttpRequest) -> Result<i32, CustomHttpError> {
+    let user_ctx = get_user_context(req).ok_or(CustomHttpError::Unauthorized("Not authenticated".to_string()))?;
+    if !user_ctx.is_platform_admin() {
+        return Err(CustomHttpError::Forbidden("Platform admin access required".to_string()));
+    }
+    Ok(user_ctx.user_id)
+}
+
+/// Start or resume a storage migration
+///
+/// Copies every media file and variant still in the source storage to the
+/// target and rewrites its path. Runs in the background; poll
+/// `GET /v1/admin/storage/migrations/{id}` for progress. Starting the
+/// direction of a paused run resumes it. Media are served from wherever
+/// their path points meanwhile; set `APP_STORAGE_BACKEND` to the target so
+/// new uploads go there too.
+#[utoipa::path(
+    post,
+    path = "/v1/admin/storage/migrations",
+    tag = "Internal - System",
+    request_body = StartStorageMigrationRequest,
+    responses(
+        (status = 202, description = "Migration started or resumed", body = StorageMigration),
+        (status = 400, description = "S3 is not configured, or another migration is running or unfinished"),
+        (status = 403, description = "Platform admin access required")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn start_storage_migration(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    config: web::Data<LocalConfig>,
+    payload: web::Json<StartStorageMigrationRequest>,
+) -> Result<HttpResponse, CustomHttpError> {
+    let user_id = require_platform_admin(&req)?;
+    let s3 = StorageBackend::s3(&config)
+        .await
+        .ok_or(CustomHttpError::BadRequest("S3 storage is not configured".to_string()))?;
+    let local = StorageBackend::local(&config);
+    let (source, target) = match payload.direction {
+        Direction::LocalToS3 => (local, s3),
+        Direction::S3ToLocal => (s3, local),
+    };
+
+    let mut conn = pool_handler(pool.clone())?;
+    let migration = storage_migration_service::start(payload.direction, payload.delete_source, Some(user_id), &mut conn)?;
+    drop(conn);
+
+    actix_web::rt::spawn(storage_migration_service::run(migration.id, source, target, pool.get_ref().clone()));
+    Ok(HttpResponse::Accepted().json(migration))
+}
+
+/// List storage migrations, newest first
+#[utoipa::path(
+    get,
+    path = "/v1/admin/storage/migrations",
+    tag = "Internal - System",
+    responses(
+        (status = 200, description = "Storage migrations", body = Vec<StorageMigration>),
+        (status = 403, description = "Platform admin access required")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_storage_migrations(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+) -> Result<HttpResponse, CustomHttpError> {
+    require_platform_admin(&req)?;
+    let mut conn = pool_handler(pool)?;
+    Ok(HttpResponse::Ok().json(storage_migration_service::list(&mut conn)?))
+}
+
+/// Progress of a storage migration
+#[utoipa::path(
+    get,
+    path = "/v1/admin/storage/migrations/{id}",
+    tag = "Internal - System",
+    params(
+        ("id" = i32, Path, description = "Migration ID")
+    ),
+    responses(
+        (status = 200, description = "Storage migration", body = StorageMigration),
+        (status = 404, description = "Migration not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_storage_migration(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, CustomHttpError> {
+    require_platform_admin(&req)?;
+    let mut conn = pool_handler(pool)?;
+    Ok(HttpResponse::Ok().json(storage_migration_service::find(id.into_inner(), &mut conn)?))
+}
+
+/// Pause a running storage migration after the media item in progress
+#[utoipa::path(
+    post,
+    path = "/v1/admin/storage/migrations/{id}/pause",
+    tag = "Internal - System",
+    params(
+        ("id" = i32, Path, description = "Migration ID")
+    ),
+    responses(
+        (status = 200, description = "Migration paused", body = StorageMigration),
+        (status = 400, description = "Migration is not running"),
+        (status = 404, description = "Migration not found")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn pause_storage_migration(
+    req: HttpRequest,
+    pool: web::Data<DatabasePool>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, CustomHttpError> {
+    require_platform_admin(&req)?;
+    let mut conn = pool_handler(pool)?;
+    Ok(HttpResponse::Ok().json(storage_migration_service::pause(id.into_inner(), &mut conn)?))
+}
@@ -4,6 +4,8 @@ use crate::services::billing_service::BillingService;
 use crate::services::payment_service::stripe::StripePaymentHandler;
 use crate::services::payment_service::PaymentHandler;
 use crate::services::cache_service_v2::CacheServiceV2;
+use crate::services::email_service::EmailService;
+use crate::services::order_service;
 
 /// Handle Stripe Webhook
 #[utoipa::path(
@@ -23,6 +25,7 @@ pub async fn handle_webhook(
     body: web::Bytes,
     pool: web::Data<DatabasePool>,
     cache: web::Data<CacheServiceV2>,
+    email_service: web::Data<EmailService>,
 ) -> impl Responder {
     // 1. Get header
     let signature = match req.headers().get("Stripe-Signature") {
@@ -53,6 +56,25 @@ pub async fn handle_webhook(
 
     let event_type = event["type"].as_str().unwrap_or("");
 
+    // Checkout orders carry their UUID in the intent metadata
+    if let Some(order_uuid) = event["data"]["object"]["metadata"]["order_uuid"].as_str() {
+        let outcome = match event_type {
+            "payment_intent.succeeded" => Some("succeeded"),
+            "payment_intent.canceled" => Some("canceled"),
+            "payment_intent.payment_failed" => Some("failed"),
+            _ => None,
+        };
+        if let Some(payment_status) = outcome {
+            return match order_service::settle_payment(&pool, &email_service, order_uuid, payment_status, "stripe").await {
+                Ok(_) => HttpResponse::Ok().body("Order Updated"),
+                Err(e) => {
+                    log::error!("Webhook for order {} failed: {}", order_uuid, e);
+                    HttpResponse::InternalServerError().body("Failed to update order")
+                }
+            };
+        }
+    }
+
     if event_type == "payment_intent.succeeded" {
         if let Some(data) = event["data"]["object"].as_object() {
             if let Some(metadata) = data["metadata"].as_object() {
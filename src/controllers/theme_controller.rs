@@ -3,47 +3,88 @@ use actix_multipart::Multipart;
 use futures::StreamExt;
 use std::io::Write;
 use std::fs;
+use std::path::Path;
 use uuid::Uuid;
 use crate::models::theme_models::{NewTheme, Theme};
 use crate::models::db_connection;
+use crate::models::rbac::{has_permission, Permission};
 use crate::middleware::auth_middleware::get_user_context;
+use crate::helpers::tenant_helper::{get_tenant_role, resolve_tenant_id};
+use crate::services::cache_service_v2::{tenant_tag, CacheServiceV2};
+use crate::services::template_service::TemplateService;
+use crate::services::theme_package_service::{self, MAX_ARCHIVE_BYTES, THEMES_DIR};
 use diesel::prelude::*;
 
-/// List all available themes
+/// Tenant of the request, if the user may manage its settings
+fn authorize_settings(req: &HttpRequest, pool: &db_connection::DatabasePool) -> Result<i32, HttpResponse> {
+    let user_ctx = get_user_context(req).ok_or_else(|| HttpResponse::Unauthorized().json("User not authenticated"))?;
+    let tenant_id = resolve_tenant_id(req, pool).map_err(|e| HttpResponse::BadRequest().json(e))?;
+    let mut conn = pool.get().map_err(|_| HttpResponse::InternalServerError().json("Database connection failed"))?;
+    let role = get_tenant_role(tenant_id, user_ctx.user_id, &mut conn).map_err(|e| HttpResponse::Forbidden().json(e))?;
+    if !has_permission(&role, Permission::ManageSettings) {
+        return Err(HttpResponse::Forbidden().json("Insufficient permissions"));
+    }
+    Ok(tenant_id)
+}
+
+/// List the site's themes and the shared ones
 #[utoipa::path(
     get,
     path = "/v1/themes",
     tag = "Marketplace - Themes",
     responses(
-        (status = 200, description = "List of all themes", body = Vec<Theme>)
+        (status = 200, description = "Themes available to the current site", body = Vec<Theme>)
     )
 )]
 pub async fn list_themes(
-    _req: HttpRequest,
+    req: HttpRequest,
     pool: web::Data<db_connection::DatabasePool>
 ) -> impl Responder {
+    let tenant_id = resolve_tenant_id(&req, &pool).ok();
     let mut conn = pool.get().expect("couldn't get db connection from pool");
     use crate::schema::themes;
 
-    let results = themes::table
-        .load::<Theme>(&mut conn);
+    use crate::schema::tenant_themes;
+
+    let mut query = themes::table.filter(themes::tenant_id.is_null()).into_boxed();
+    if let Some(tid) = tenant_id {
+        query = query.or_filter(themes::tenant_id.eq(tid));
+    }
+    let results = query.order(themes::id.asc()).load::<Theme>(&mut conn);
+    // Shared themes are active per site, so `is_active` reflects the current one
+    let active = tenant_id.and_then(|tid| {
+        tenant_themes::table
+            .find(tid)
+            .select(tenant_themes::theme_id)
+            .first::<i32>(&mut conn)
+            .ok()
+    });
 
     match results {
-        Ok(t) => HttpResponse::Ok().json(t),
+        Ok(t) => HttpResponse::Ok().json(
+            t.into_iter()
+                .map(|theme| Theme { is_active: Some(active == Some(theme.id)), ..theme })
+                .collect::<Vec<_>>(),
+        ),
         Err(e) => HttpResponse::InternalServerError().json(format!("Error listing themes: {}", e)),
     }
 }
 
-/// Upload a new theme
+/// Upload a theme package
+///
+/// The zip must contain a `theme.json` manifest, `templates/` and optionally
+/// `assets/`. It is unpacked into its own directory and validated before the
+/// theme is saved; invalid packages are rejected and nothing is kept.
 #[utoipa::path(
     post,
     path = "/v1/themes/upload",
     tag = "Marketplace - Themes",
-    request_body(content = String, description = "Multipart form with theme file, name, version", content_type = "multipart/form-data"),
+    request_body(content = String, description = "Multipart form with theme_file (zip) and optional name", content_type = "multipart/form-data"),
     responses(
-        (status = 201, description = "Theme uploaded successfully"),
-        (status = 400, description = "Missing required fields"),
-        (status = 401, description = "Not authenticated")
+        (status = 201, description = "Theme uploaded successfully", body = Theme),
+        (status = 400, description = "Missing file or invalid theme package"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Insufficient permissions")
     ),
     security(
         ("bearer_auth" = [])
@@ -54,15 +95,14 @@ pub async fn upload_theme(
     pool: web::Data<db_connection::DatabasePool>,
     mut payload: Multipart,
 ) -> impl Responder {
-    let _user_ctx = match get_user_context(&req) {
-        Some(ctx) => ctx,
-        None => return HttpResponse::Unauthorized().json("User not authenticated"),
+    let tenant_id = match authorize_settings(&req, &pool) {
+        Ok(tid) => tid,
+        Err(resp) => return resp,
     };
 
     // Prepare variables to collect form data
     let mut theme_name = String::new();
-    let mut theme_version = String::new();
-    let mut file_path = String::new();
+    let mut archive_path: Option<String> = None;
     
     // Iterate over multipart stream
     while let Some(item) = payload.next().await {
@@ -78,27 +118,32 @@ pub async fn upload_theme(
         let field_name = content_disposition.get_name().unwrap_or("").to_string();
 
         if field_name == "theme_file" {
-             let filename = content_disposition
-                .get_filename()
-                .map(|f| {
-                    let ext = std::path::Path::new(f).extension().and_then(|s| s.to_str()).unwrap_or("zip");
-                    format!("{}.{}", Uuid::new_v4(), ext)
-                })
-                .unwrap_or_else(|| Uuid::new_v4().to_string() + ".zip");
-            
-            let upload_dir = "./uploads/themes";
-            fs::create_dir_all(upload_dir).ok();
-            let filepath = format!("{}/{}", upload_dir, filename);
-            let mut f = fs::File::create(&filepath).expect("Failed to create file");
+            fs::create_dir_all(THEMES_DIR).ok();
+            let filepath = format!("{}/.{}.zip", THEMES_DIR, Uuid::new_v4());
+            let mut f = match fs::File::create(&filepath) {
+                Ok(f) => f,
+                Err(e) => return HttpResponse::InternalServerError().json(format!("Error storing upload: {}", e)),
+            };
+            archive_path = Some(filepath.clone());
 
             // Field is a stream of bytes
+            let mut written = 0;
             while let Some(chunk) = field.next().await {
-                let data = chunk.unwrap();
-                f.write_all(&data).unwrap();
+                let data = match chunk {
+                    Ok(d) => d,
+                    Err(_) => {
+                        let _ = fs::remove_file(&filepath);
+                        return HttpResponse::BadRequest().json("Error reading theme_file");
+                    }
+                };
+                written += data.len();
+                if written > MAX_ARCHIVE_BYTES || f.write_all(&data).is_err() {
+                    let _ = fs::remove_file(&filepath);
+                    return HttpResponse::BadRequest().json(format!("Theme archive must be at most {} bytes", MAX_ARCHIVE_BYTES));
+                }
             }
-            file_path = filepath;
         } else {
-             // Read text fields (name, version)
+             // Read text fields (name)
              let mut value_bytes = Vec::new();
              while let Some(chunk) = field.next().await {
                  let data = chunk.unwrap();
@@ -106,40 +151,62 @@ pub async fn upload_theme(
              }
              let value = String::from_utf8(value_bytes).unwrap_or_default();
              
-             match field_name.as_str() {
-                 "name" => theme_name = value,
-                 "version" => theme_version = value,
-                 _ => {}
+             if field_name == "name" {
+                 theme_name = value;
              }
         }
     }
 
-    if theme_name.is_empty() || file_path.is_empty() {
-        return HttpResponse::BadRequest().json("Name and theme_file are required");
-    }
+    let Some(archive_path) = archive_path else {
+        return HttpResponse::BadRequest().json("theme_file is required");
+    };
+
+    let installed = web::block(move || {
+        let result = fs::File::open(&archive_path)
+            .map_err(|e| theme_package_service::ThemePackageError::Io(e.to_string()))
+            .and_then(|file| theme_package_service::install_package(file, Path::new(THEMES_DIR)));
+        let _ = fs::remove_file(&archive_path);
+        result
+    })
+    .await;
+
+    let installed = match installed {
+        Ok(Ok(installed)) => installed,
+        Ok(Err(e)) => return HttpResponse::BadRequest().json(e.to_string()),
+        Err(e) => return HttpResponse::InternalServerError().json(format!("Error unpacking theme: {}", e)),
+    };
 
     // Insert into DB
     let mut conn = pool.get().expect("couldn't get db connection from pool");
     use crate::schema::themes;
 
+    let manifest = installed.manifest;
     let new_theme = NewTheme {
-        name: theme_name,
-        description: None,
-        version: if theme_version.is_empty() { "1.0.0".to_string() } else { theme_version },
-        file_path: file_path,
+        name: if theme_name.trim().is_empty() { manifest.name.clone() } else { theme_name },
+        description: manifest.description.clone(),
+        version: manifest.version.clone(),
+        file_path: installed.dir.to_string_lossy().to_string(),
         thumbnail_url: None,
         is_active: Some(false),
         is_default: Some(false),
-        tenant_id: None, 
+        tenant_id: Some(tenant_id),
+        manifest: serde_json::to_value(&manifest).ok(),
     };
 
-    match diesel::insert_into(themes::table).values(&new_theme).execute(&mut conn) {
-        Ok(_) => HttpResponse::Created().json("Theme uploaded successfully"),
-        Err(e) => HttpResponse::InternalServerError().json(format!("Error saving theme: {}", e)),
+    match diesel::insert_into(themes::table).values(&new_theme).get_result::<Theme>(&mut conn) {
+        Ok(theme) => HttpResponse::Created().json(theme),
+        Err(e) => {
+            let _ = fs::remove_dir_all(&installed.dir);
+            HttpResponse::InternalServerError().json(format!("Error saving theme: {}", e))
+        }
     }
 }
 
-/// Activate a theme
+/// Activate a theme for the current site
+///
+/// Replaces the site's previously active theme. The theme may be the site's
+/// own or a shared one. Pages render with the new theme's templates and
+/// assets immediately, on every instance.
 #[utoipa::path(
     post,
     path = "/v1/themes/{id}/activate",
@@ -149,7 +216,10 @@ pub async fn upload_theme(
     ),
     responses(
         (status = 200, description = "Theme activated successfully"),
-        (status = 401, description = "Not authenticated")
+        (status = 400, description = "Theme package failed to load"),
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Insufficient permissions"),
+        (status = 404, description = "Theme not found for this site")
     ),
     security(
         ("bearer_auth" = [])
@@ -158,29 +228,74 @@ pub async fn upload_theme(
 pub async fn activate_theme(
     req: HttpRequest,
     pool: web::Data<db_connection::DatabasePool>,
+    cache: web::Data<CacheServiceV2>,
+    templates: web::Data<TemplateService>,
     path: web::Path<i32>
 ) -> impl Responder {
-     let _user_ctx = match get_user_context(&req) {
-        Some(ctx) => ctx,
-        None => return HttpResponse::Unauthorized().json("User not authenticated"),
+    let tenant_id = match authorize_settings(&req, &pool) {
+        Ok(tid) => tid,
+        Err(resp) => return resp,
     };
     
     let theme_id = path.into_inner();
     let mut conn = pool.get().expect("couldn't get db connection from pool");
-    use crate::schema::themes;
+    use crate::schema::{tenant_themes, themes};
 
-    // Logic: Deactivate all others (if tenant scoped), activate this one.
-    // Simpler: Just set is_active = true for this one.
+    let theme = match themes::table
+        .find(theme_id)
+        .filter(themes::tenant_id.eq(tenant_id).or(themes::tenant_id.is_null()))
+        .first::<Theme>(&mut conn)
+    {
+        Ok(t) => t,
+        Err(_) => return HttpResponse::NotFound().json("Theme not found"),
+    };
 
-    // 1. Deactivate others (optional, depending on one-theme-per-site rule)
-    // diesel::update(themes::table).set(themes::is_active.eq(false)).execute(&mut conn)?;
+    // Compile before touching the database so a broken package never goes live
+    let registry = match templates.compile_theme(&theme) {
+        Ok(r) => r,
+        Err(e) => return HttpResponse::BadRequest().json(format!("Theme failed to load: {}", e)),
+    };
 
-    let res = diesel::update(themes::table.find(theme_id))
-        .set(themes::is_active.eq(true))
+    // One active theme per site
+    let now = chrono::Utc::now().naive_utc();
+    let res = diesel::insert_into(tenant_themes::table)
+        .values((
+            tenant_themes::tenant_id.eq(tenant_id),
+            tenant_themes::theme_id.eq(theme_id),
+            tenant_themes::activated_at.eq(now),
+        ))
+        .on_conflict(tenant_themes::tenant_id)
+        .do_update()
+        .set((tenant_themes::theme_id.eq(theme_id), tenant_themes::activated_at.eq(now)))
         .execute(&mut conn);
 
     match res {
-        Ok(_) => HttpResponse::Ok().json("Theme activated"),
+        Ok(_) => {
+            templates.set_active_theme(tenant_id, registry);
+            // Also drops the cached theme id, so other instances load the new theme
+            let _ = cache.purge_tags(&[tenant_tag(tenant_id)]).await;
+            HttpResponse::Ok().json("Theme activated")
+        },
         Err(e) => HttpResponse::InternalServerError().json(format!("Error activating theme: {}", e)),
     }
 }
+
+/// Serves a static asset from the current site's active theme
+pub async fn theme_asset(
+    req: HttpRequest,
+    pool: web::Data<db_connection::DatabasePool>,
+    cache: web::Data<CacheServiceV2>,
+    templates: web::Data<TemplateService>,
+    path: web::Path<String>,
+) -> actix_web::Result<actix_files::NamedFile> {
+    let tenant_id = resolve_tenant_id(&req, &pool).map_err(actix_web::error::ErrorNotFound)?;
+    if let Ok(mut conn) = pool.get() {
+        templates.sync_active_theme(tenant_id, &cache, &mut conn).await;
+    }
+    let asset = templates.active_theme(tenant_id).and_then(|theme| theme.asset(&path));
+
+    match asset {
+        Some(file) => Ok(actix_files::NamedFile::open_async(file).await?),
+        None => Err(actix_web::error::ErrorNotFound("Asset not found")),
+    }
+}
@@ -7,6 +7,7 @@ use uuid::Uuid;
 use crate::models::user_models::{MutUser, User, LoginRequest, Enable2faRequest};
 use crate::models::{pool_handler, Model, DatabasePool};
 use crate::services::totp_service::TotpService;
+use crate::services::cart_service::{self, CART_TOKEN_HEADER};
 use crate::services::auth_service::{authenticate, encrypt, encrypt_password, Claims};
 use crate::services::errors_service::CustomHttpError;
 use serde_json;
@@ -210,6 +211,9 @@ pub async fn delete_user(
     path = "/v1/auth/login",
     tag = "Customer - Authentication",
     request_body = LoginRequest,
+    params(
+        ("X-Cart-Token" = Option<String>, Header, description = "Guest cart to merge into the user's cart")
+    ),
     responses(
         (status = 200, description = "Login successful, auth cookie set"),
         (status = 401, description = "Invalid credentials or 2FA code"),
@@ -217,6 +221,7 @@ pub async fn delete_user(
     )
 )]
 pub async fn login(
+    req: HttpRequest,
     user: web::Json<LoginRequest>,
     pool: web::Data<DatabasePool>,
 ) -> Result<HttpResponse, CustomHttpError> {
@@ -241,6 +246,7 @@ pub async fn login(
             token: None,
             two_factor_secret: None,
             two_factor_enabled: None,
+            email: None,
         };
         
         let cookie = login_res(&read_user)?; // Pass actual user with ID
@@ -280,6 +286,7 @@ pub async fn login(
                 token: None,
                 two_factor_secret: None,
                 two_factor_enabled: None,
+                email: None,
             };
 
             let cookie = login_res(&read_user)?; // Pass actual user with ID
@@ -288,6 +295,13 @@ pub async fn login(
             new_user.token = Some(cookie.value().to_string());
             User::update_with_token(&new_user, &mut mysql_pool)?;
 
+            // Carry the guest's cart over to the account; never fail the login over it
+            if let Some(token) = req.headers().get(CART_TOKEN_HEADER).and_then(|v| v.to_str().ok()) {
+                if let Err(e) = cart_service::merge_guest_cart(token.trim(), read_user.id, &mut mysql_pool) {
+                    log::warn!("Failed to merge guest cart for user {}: {}", read_user.id, e);
+                }
+            }
+
             Ok(cookie_response)
         }
         _ => Ok(HttpResponse::Unauthorized().json("Failed to authenticate.")),
@@ -441,6 +455,7 @@ pub async fn enable_2fa(
          token: None,
          two_factor_secret: Some(body.secret.clone()),
          two_factor_enabled: Some(true),
+         email: None,
     };
     
     User::update(path.clone(), &update_user, &mut mysql_pool)?;
@@ -0,0 +1,54 @@
+// GraphQL Request Context
+// Per-request tenant and user information resolved from the HTTP request
+
+use async_graphql::{Context, Error, Result};
+
+use crate::helpers::tenant_helper::get_tenant_role;
+use crate::middleware::auth_middleware::UserContext;
+use crate::models::rbac::{has_permission, Permission};
+use crate::models::{DatabasePool, PooledDatabaseConnection, ReadDatabasePool};
+
+/// Data injected into every GraphQL request by `graphql_handler`.
+/// Resolvers never see the `HttpRequest`, so tenant resolution happens up front.
+#[derive(Clone)]
+pub struct RequestContext {
+    pub tenant_id: Option<i32>,
+    pub user: Option<UserContext>,
+}
+
+impl RequestContext {
+    /// Resolves the tenant and checks that the current user holds `permission` in it.
+    /// Mirrors the membership + RBAC checks done in `page_controllers`.
+    pub fn authorize(&self, permission: Permission, conn: &mut PooledDatabaseConnection) -> Result<i32> {
+        let user = self.user.as_ref().ok_or_else(|| Error::new("Not authenticated"))?;
+        let tenant_id = self.tenant_id.ok_or_else(|| Error::new("Tenant not found"))?;
+
+        let role = get_tenant_role(tenant_id, user.user_id, conn)
+            .map_err(|_| Error::new("Access denied"))?;
+
+        if !has_permission(&role, permission) {
+            return Err(Error::new("Insufficient permissions"));
+        }
+
+        Ok(tenant_id)
+    }
+}
+
+/// Primary (read/write) connection from the schema data
+pub fn write_conn(ctx: &Context<'_>) -> Result<PooledDatabaseConnection> {
+    ctx.data::<DatabasePool>()?
+        .get()
+        .map_err(|_| Error::new("Pool connection failed"))
+}
+
+/// Read replica connection from the schema data
+pub fn read_conn(ctx: &Context<'_>) -> Result<PooledDatabaseConnection> {
+    ctx.data::<ReadDatabasePool>()?
+        .0
+        .get()
+        .map_err(|_| Error::new("Read Pool connection failed"))
+}
+
+pub fn request_context<'a>(ctx: &Context<'a>) -> Result<&'a RequestContext> {
+    ctx.data::<RequestContext>()
+}
@@ -1,16 +1,34 @@
 // GraphQL Module
 
 pub mod types;
+pub mod context;
 pub mod query;
 pub mod mutation;
 
+use std::sync::Arc;
+
 use async_graphql::{Schema, EmptySubscription};
 use query::QueryRoot;
 use mutation::MutationRoot;
 
+use crate::models::{DatabasePool, ReadDatabasePool};
+use crate::services::cache_service_v2::CacheServiceV2;
+use crate::services::plugin_service::PluginRegistry;
+
 pub type AppSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
 
-pub fn create_schema() -> AppSchema {
+/// Builds the schema with the shared pools, cache and plugins as schema-level data.
+/// Per-request tenant/user data is attached by `graphql_handler`.
+pub fn create_schema(
+    pool: DatabasePool,
+    read_pool: ReadDatabasePool,
+    cache: CacheServiceV2,
+    plugins: Arc<PluginRegistry>,
+) -> AppSchema {
     Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(pool)
+        .data(read_pool)
+        .data(cache)
+        .data(plugins)
         .finish()
 }
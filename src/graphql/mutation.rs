@@ -1,7 +1,68 @@
 // GraphQL Mutation Root
 
+use std::sync::Arc;
+
 use async_graphql::*;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::controllers::page_controllers::validate_seo_fields;
+use crate::graphql::context::{request_context, write_conn};
+use crate::graphql::query::load_tenant_page;
 use crate::graphql::types::*;
+use crate::models::media_models::Media;
+use crate::models::module_models::{Module, ModuleCategory, MutCategory, MutModule};
+use crate::models::page_models::{MutPage, Page};
+use crate::models::rbac::Permission;
+use crate::models::status_enum::PageStatus;
+use crate::models::{Model, PooledDatabaseConnection};
+use crate::schema::{media, module_category, modules, pages};
+use crate::services::cache_service_v2::{module_tag, page_tag, CacheServiceV2};
+use crate::services::embedding_refresh_service;
+use crate::services::plugin_service::PluginRegistry;
+use crate::services::redirect_service;
+
+fn parse_status(status: &str) -> Result<PageStatus> {
+    PageStatus::from_str(status).ok_or_else(|| Error::new(format!("Invalid page status: {}", status)))
+}
+
+/// Drops the rendered HTML cached by `display_page` under any of `tags`,
+/// whatever URL or locale it was cached for
+async fn invalidate_page_cache(ctx: &Context<'_>, tags: &[String]) {
+    if let Ok(cache) = ctx.data::<CacheServiceV2>() {
+        let _ = cache.purge_tags(tags).await;
+    }
+}
+
+/// Runs the plugins' save hooks for a created or updated page
+async fn page_saved(ctx: &Context<'_>, page_uuid: &str) {
+    if let Ok(plugins) = ctx.data::<Arc<PluginRegistry>>() {
+        plugins.page_saved(page_uuid).await;
+    }
+}
+
+/// Runs the plugins' save hooks for a created or updated module
+async fn module_saved(ctx: &Context<'_>, module_uuid: &str, page_uuid: &str) {
+    if let Ok(plugins) = ctx.data::<Arc<PluginRegistry>>() {
+        plugins.module_saved(module_uuid, page_uuid).await;
+    }
+}
+
+/// Loads a module and verifies its parent page belongs to the tenant
+fn load_tenant_module(
+    module_uuid: &str,
+    tenant_id: i32,
+    conn: &mut PooledDatabaseConnection,
+) -> Result<(Module, Page)> {
+    modules::table
+        .inner_join(pages::table)
+        .filter(modules::uuid.eq(module_uuid))
+        .filter(pages::tenant_id.eq(tenant_id))
+        .select((Module::as_select(), Page::as_select()))
+        .first::<(Module, Page)>(conn)
+        .optional()?
+        .ok_or_else(|| Error::new("Module not found"))
+}
 
 pub struct MutationRoot;
 
@@ -10,53 +71,383 @@ impl MutationRoot {
     /// Create a new page
     async fn create_page(
         &self,
-        _ctx: &Context<'_>,
+        ctx: &Context<'_>,
         input: CreatePageInput
     ) -> Result<GqlPage> {
-        // Mock implementation
-        Ok(GqlPage {
-            uuid: "new-page-uuid".to_string(),
-            page_title: input.page_title,
+        let mut conn = write_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::PublishContent, &mut conn)?;
+
+        let new_uuid = Uuid::new_v4().to_string();
+        let new_page = MutPage {
+            uuid: Some(new_uuid.clone()),
+            page_name: input.page_name.unwrap_or_else(|| "index".to_string()),
             page_url: input.page_url,
-            page_name: input.page_name,
-            content: input.content,
+            page_title: input.page_title,
             meta_title: input.meta_title,
             meta_description: input.meta_description,
-            status: "draft".to_string(),
-            created_at: Some("2025-12-24T00:00:00Z".to_string()),
-            updated_at: Some("2025-12-24T00:00:00Z".to_string()),
-        })
+            meta_keywords: None,
+            canonical_url: None,
+            og_title: None,
+            og_description: None,
+            og_image: None,
+            twitter_card: None,
+            twitter_title: None,
+            twitter_description: None,
+            author: None,
+            article_type: None,
+            featured_image: None,
+            word_count: None,
+            reading_time: None,
+            current_revision: None,
+            last_modified_by: request_context(ctx)?.user.as_ref().map(|u| u.user_id),
+            status: Some(PageStatus::Draft),
+            publish_at: None,
+            unpublish_at: None,
+            tenant_id: Some(tenant_id),
+            content: input.content,
+        };
+
+        validate_seo_fields(&new_page).map_err(|e| Error::new(e.to_string()))?;
+
+        Page::create(&new_page, &mut conn)?;
+        page_saved(ctx, &new_uuid).await;
+
+        let page = load_tenant_page(&new_uuid, tenant_id, &mut conn)?
+            .ok_or_else(|| Error::new("Page could not be created"))?;
+
+        Ok(page.into())
     }
-    
+
     /// Update an existing page
     async fn update_page(
         &self,
-        _ctx: &Context<'_>,
+        ctx: &Context<'_>,
         uuid: String,
-        input: UpdatePageInput
+        input: UpdatePageInput,
+        #[graphql(desc = "Reject the update if the page's current revision differs")]
+        expected_revision: Option<i32>
     ) -> Result<GqlPage> {
-        // Mock implementation
-        Ok(GqlPage {
-            uuid,
-            page_title: input.page_title.unwrap_or_else(|| "Updated Page".to_string()),
-            page_url: input.page_url.unwrap_or_else(|| "/updated".to_string()),
-            page_name: Some("updated-page".to_string()),
-            content: input.content,
-            meta_title: input.meta_title,
-            meta_description: input.meta_description,
-            status: input.status.unwrap_or_else(|| "draft".to_string()),
-            created_at: Some("2025-12-24T00:00:00Z".to_string()),
-            updated_at: Some("2025-12-24T00:00:00Z".to_string()),
-        })
+        let mut conn = write_conn(ctx)?;
+        let request = request_context(ctx)?;
+        let tenant_id = request.authorize(Permission::EditContent, &mut conn)?;
+
+        let user_id = request.user.as_ref().map(|u| u.user_id);
+
+        // Lock the page so the revision check, snapshot, update and redirect can't
+        // interleave with another save (same flow as page_controllers::update_page)
+        conn.transaction::<_, Error, _>(|conn| {
+            let existing = pages::table
+                .filter(pages::uuid.eq(&uuid))
+                .filter(pages::tenant_id.eq(tenant_id))
+                .select(Page::as_select())
+                .for_update()
+                .first::<Page>(conn)
+                .optional()?
+                .ok_or_else(|| Error::new("Page not found"))?;
+            if let Some(expected) = expected_revision {
+                let current = existing.current_revision.unwrap_or(0);
+                if current != expected {
+                    return Err(Error::new("Page was modified by someone else")
+                        .extend_with(|_, e| e.set("code", "CONFLICT"))
+                        .extend_with(|_, e| e.set("current_revision", current)));
+                }
+            }
+            let old_url = existing.page_url.clone();
+
+            let mut changes: MutPage = existing.into();
+            if let Some(title) = input.page_title { changes.page_title = title; }
+            if let Some(url) = input.page_url { changes.page_url = url; }
+            if input.content.is_some() { changes.content = input.content; }
+            if input.meta_title.is_some() { changes.meta_title = input.meta_title; }
+            if input.meta_description.is_some() { changes.meta_description = input.meta_description; }
+            if let Some(status) = input.status { changes.status = Some(parse_status(&status)?); }
+
+            validate_seo_fields(&changes).map_err(|e| Error::new(e.to_string()))?;
+
+            // Create revision BEFORE updating the page; without it the revision wouldn't move
+            let rev_num = crate::services::revision_service::create_page_revision(
+                &uuid,
+                user_id,
+                Some("Page updated via GraphQL".to_string()),
+                conn
+            )?;
+            changes.current_revision = Some(rev_num);
+            changes.last_modified_by = user_id;
+
+            Page::update(uuid.clone(), &changes, conn)?;
+            redirect_service::redirect_on_url_change(tenant_id, &old_url, &changes.page_url, conn)?;
+            Ok(())
+        })?;
+        page_saved(ctx, &uuid).await;
+
+        invalidate_page_cache(ctx, &[page_tag(&uuid)]).await;
+
+        let page = load_tenant_page(&uuid, tenant_id, &mut conn)?
+            .ok_or_else(|| Error::new("Page not found"))?;
+
+        Ok(page.into())
     }
-    
+
     /// Delete a page
     async fn delete_page(
         &self,
-        _ctx: &Context<'_>,
-        _uuid: String
+        ctx: &Context<'_>,
+        uuid: String
     ) -> Result<bool> {
-        // Mock implementation
-        Ok(true)
+        let mut conn = write_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::DeleteContent, &mut conn)?;
+
+        let existing = match load_tenant_page(&uuid, tenant_id, &mut conn)? {
+            Some(page) => page,
+            None => return Ok(false),
+        };
+
+        embedding_refresh_service::delete_page_embeddings(&uuid, &mut conn)?;
+        let deleted = Page::delete(uuid, &mut conn)?;
+        invalidate_page_cache(ctx, &[page_tag(&existing.uuid)]).await;
+
+        Ok(deleted > 0)
+    }
+
+    /// Create a module on a page
+    async fn create_module(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateModuleInput
+    ) -> Result<GqlModule> {
+        let mut conn = write_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::EditContent, &mut conn)?;
+
+        let page = load_tenant_page(&input.page_uuid, tenant_id, &mut conn)?
+            .ok_or_else(|| Error::new("Page not found"))?;
+
+        if let Some(category_uuid) = &input.category_uuid {
+            ModuleCategory::belonging_to(&page)
+                .filter(module_category::uuid.eq(category_uuid))
+                .select(ModuleCategory::as_select())
+                .first::<ModuleCategory>(&mut conn)
+                .optional()?
+                .ok_or_else(|| Error::new("Category not found on this page"))?;
+        }
+
+        let new_uuid = Uuid::new_v4().to_string();
+        let new_module = MutModule {
+            uuid: Some(new_uuid.clone()),
+            title: input.title,
+            page_uuid: page.uuid.clone(),
+            category_uuid: input.category_uuid,
+            content: input.content,
+            field_type: input.field_type,
+            field_config: None,
+            validation_rules: None,
+        };
+
+        Module::create(&new_module, &mut conn)?;
+        module_saved(ctx, &new_uuid, &page.uuid).await;
+        invalidate_page_cache(ctx, &[page_tag(&page.uuid)]).await;
+
+        Ok(Module::read_one(new_uuid, &mut conn)?.into())
+    }
+
+    /// Update a module
+    async fn update_module(
+        &self,
+        ctx: &Context<'_>,
+        uuid: String,
+        input: UpdateModuleInput,
+        #[graphql(desc = "Reject the update if the module's version differs")]
+        expected_version: Option<i32>
+    ) -> Result<GqlModule> {
+        let mut conn = write_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::EditContent, &mut conn)?;
+
+        let (_, page) = load_tenant_module(&uuid, tenant_id, &mut conn)?;
+
+        if let Some(category_uuid) = &input.category_uuid {
+            ModuleCategory::belonging_to(&page)
+                .filter(module_category::uuid.eq(category_uuid))
+                .select(ModuleCategory::as_select())
+                .first::<ModuleCategory>(&mut conn)
+                .optional()?
+                .ok_or_else(|| Error::new("Category not found on this page"))?;
+        }
+
+        // Compare the version on the locked row so concurrent saves can't both pass
+        conn.transaction::<_, Error, _>(|conn| {
+            let existing = Module::read_one_for_update(uuid.clone(), conn)?;
+            if let Some(expected) = expected_version {
+                if existing.version != expected {
+                    return Err(Error::new("Module was modified by someone else")
+                        .extend_with(|_, e| e.set("code", "CONFLICT"))
+                        .extend_with(|_, e| e.set("current_version", existing.version)));
+                }
+            }
+
+            let changes = MutModule {
+                uuid: Some(existing.uuid.clone()),
+                title: input.title.unwrap_or(existing.title),
+                page_uuid: existing.page_uuid,
+                category_uuid: input.category_uuid.or(existing.category_uuid),
+                content: input.content.unwrap_or(existing.content),
+                field_type: input.field_type.or(existing.field_type),
+                field_config: existing.field_config,
+                validation_rules: existing.validation_rules,
+            };
+
+            Module::update(uuid.clone(), &changes, conn)?;
+            Ok(())
+        })?;
+        module_saved(ctx, &uuid, &page.uuid).await;
+        invalidate_page_cache(ctx, &[module_tag(&uuid), page_tag(&page.uuid)]).await;
+
+        Ok(Module::read_one(uuid, &mut conn)?.into())
+    }
+
+    /// Delete a module
+    async fn delete_module(
+        &self,
+        ctx: &Context<'_>,
+        uuid: String
+    ) -> Result<bool> {
+        let mut conn = write_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::DeleteContent, &mut conn)?;
+
+        let (_, page) = match load_tenant_module(&uuid, tenant_id, &mut conn) {
+            Ok(found) => found,
+            Err(_) => return Ok(false),
+        };
+
+        let deleted = Module::delete(uuid.clone(), &mut conn)?;
+        embedding_refresh_service::enqueue_page(&page.uuid);
+        invalidate_page_cache(ctx, &[module_tag(&uuid), page_tag(&page.uuid)]).await;
+
+        Ok(deleted > 0)
+    }
+
+    /// Create a module category (array field) on a page
+    async fn create_category(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateCategoryInput
+    ) -> Result<GqlCategory> {
+        let mut conn = write_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::EditContent, &mut conn)?;
+
+        let page = load_tenant_page(&input.page_uuid, tenant_id, &mut conn)?
+            .ok_or_else(|| Error::new("Page not found"))?;
+
+        let new_uuid = Uuid::new_v4().to_string();
+        let new_category = MutCategory {
+            title: input.title,
+            page_uuid: page.uuid,
+            uuid: Some(new_uuid.clone()),
+        };
+
+        ModuleCategory::create(&new_category, &mut conn)?;
+
+        Ok((ModuleCategory::read_one(new_uuid, &mut conn)?, vec![]).into())
+    }
+
+    /// Rename a module category
+    async fn update_category(
+        &self,
+        ctx: &Context<'_>,
+        uuid: String,
+        title: String
+    ) -> Result<GqlCategory> {
+        let mut conn = write_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::EditContent, &mut conn)?;
+
+        let (category, page) = module_category::table
+            .inner_join(pages::table)
+            .filter(module_category::uuid.eq(&uuid))
+            .filter(pages::tenant_id.eq(tenant_id))
+            .select((ModuleCategory::as_select(), Page::as_select()))
+            .first::<(ModuleCategory, Page)>(&mut conn)
+            .optional()?
+            .ok_or_else(|| Error::new("Category not found"))?;
+
+        let changes = MutCategory {
+            title,
+            page_uuid: category.page_uuid,
+            uuid: Some(category.uuid),
+        };
+        ModuleCategory::update(uuid.clone(), &changes, &mut conn)?;
+        invalidate_page_cache(ctx, &[page_tag(&page.uuid)]).await;
+
+        let category = ModuleCategory::read_one(uuid, &mut conn)?;
+        let category_modules = Module::belonging_to(&category)
+            .select(Module::as_select())
+            .load::<Module>(&mut conn)?;
+
+        Ok((category, category_modules).into())
+    }
+
+    /// Delete a module category
+    async fn delete_category(
+        &self,
+        ctx: &Context<'_>,
+        uuid: String
+    ) -> Result<bool> {
+        let mut conn = write_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::DeleteContent, &mut conn)?;
+
+        let page = module_category::table
+            .inner_join(pages::table)
+            .filter(module_category::uuid.eq(&uuid))
+            .filter(pages::tenant_id.eq(tenant_id))
+            .select(Page::as_select())
+            .first::<Page>(&mut conn)
+            .optional()?;
+
+        let page = match page {
+            Some(page) => page,
+            None => return Ok(false),
+        };
+
+        let deleted = ModuleCategory::delete(uuid, &mut conn)?;
+        invalidate_page_cache(ctx, &[page_tag(&page.uuid)]).await;
+
+        Ok(deleted > 0)
+    }
+
+    /// Update media metadata (alt text, title, description, tags)
+    async fn update_media(
+        &self,
+        ctx: &Context<'_>,
+        uuid: String,
+        input: UpdateMediaInput
+    ) -> Result<GqlMedia> {
+        let mut conn = write_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::EditContent, &mut conn)?;
+
+        let existing = media::table
+            .filter(media::uuid.eq(&uuid))
+            .filter(media::tenant_id.eq(tenant_id))
+            .select(Media::as_select())
+            .first::<Media>(&mut conn)
+            .optional()?
+            .ok_or_else(|| Error::new("Media not found"))?;
+
+        let tags = input.tags
+            .map(|t| t.into_iter().map(Some).collect::<Vec<_>>())
+            .or(existing.tags);
+
+        diesel::update(media::table.filter(media::id.eq(existing.id)))
+            .set((
+                media::alt_text.eq(input.alt_text.or(existing.alt_text)),
+                media::title.eq(input.title.or(existing.title)),
+                media::description.eq(input.description.or(existing.description)),
+                media::tags.eq(tags),
+                media::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(&mut conn)?;
+
+        let updated = media::table
+            .filter(media::id.eq(existing.id))
+            .select(Media::as_select())
+            .first::<Media>(&mut conn)?;
+
+        Ok(updated.into())
     }
 }
@@ -1,116 +1,276 @@
 // GraphQL Query Root
 
 use async_graphql::*;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD as BASE64};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::graphql::context::{read_conn, request_context};
 use crate::graphql::types::*;
+use crate::models::media_models::Media;
+use crate::models::module_models::{Module, ModuleCategory};
+use crate::models::page_models::Page;
+use crate::models::rbac::Permission;
+use crate::models::PooledDatabaseConnection;
+use crate::schema::{media, module_category, modules, pages};
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Cursors are opaque to clients: base64 of "<time_created micros>|<uuid>",
+/// matching the (time_created DESC, uuid DESC) ordering used for page listing.
+pub fn encode_cursor(page: &Page) -> String {
+    BASE64.encode(format!("{}|{}", page.time_created.and_utc().timestamp_micros(), page.uuid))
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<(NaiveDateTime, String)> {
+    let invalid = || Error::new("Invalid cursor");
+    let raw = BASE64.decode(cursor).map_err(|_| invalid())?;
+    let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+    let (micros, uuid) = raw.split_once('|').ok_or_else(invalid)?;
+    let micros: i64 = micros.parse().map_err(|_| invalid())?;
+    let time = chrono::DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?.naive_utc();
+    Ok((time, uuid.to_string()))
+}
+
+/// Loads a page and verifies it belongs to the tenant
+pub(crate) fn load_tenant_page(
+    page_uuid: &str,
+    tenant_id: i32,
+    conn: &mut PooledDatabaseConnection,
+) -> Result<Option<Page>> {
+    Ok(pages::table
+        .filter(pages::uuid.eq(page_uuid))
+        .filter(pages::tenant_id.eq(tenant_id))
+        .select(Page::as_select())
+        .first::<Page>(conn)
+        .optional()?)
+}
 
 pub struct QueryRoot;
 
 #[Object]
 impl QueryRoot {
     /// Get a single page by UUID
-    async fn page(&self, _ctx: &Context<'_>, uuid: String) -> Result<Option<GqlPage>> {
-        // Mock implementation - replace with actual database query
-        Ok(Some(GqlPage {
-            uuid: uuid.clone(),
-            page_title: "Sample Page".to_string(),
-            page_url: "/sample".to_string(),
-            page_name: Some("sample-page".to_string()),
-            content: Some("<p>Sample content</p>".to_string()),
-            meta_title: Some("Sample Page Title".to_string()),
-            meta_description: Some("Sample description".to_string()),
-            status: "published".to_string(),
-            created_at: Some("2025-12-24T00:00:00Z".to_string()),
-            updated_at: Some("2025-12-24T00:00:00Z".to_string()),
-        }))
+    async fn page(&self, ctx: &Context<'_>, uuid: String) -> Result<Option<GqlPage>> {
+        let mut conn = read_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::ViewContent, &mut conn)?;
+
+        Ok(load_tenant_page(&uuid, tenant_id, &mut conn)?.map(GqlPage::from))
+    }
+
+    /// Get a single page by its URL path
+    async fn page_by_url(&self, ctx: &Context<'_>, page_url: String) -> Result<Option<GqlPage>> {
+        let mut conn = read_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::ViewContent, &mut conn)?;
+
+        let page = pages::table
+            .filter(pages::page_url.eq(page_url))
+            .filter(pages::tenant_id.eq(tenant_id))
+            .select(Page::as_select())
+            .first::<Page>(&mut conn)
+            .optional()?;
+
+        Ok(page.map(GqlPage::from))
     }
-    
-    /// List all pages with pagination
+
+    /// List pages of the current tenant, newest first
     async fn pages(
         &self,
-        _ctx: &Context<'_>,
+        ctx: &Context<'_>,
         pagination: Option<PaginationInput>
     ) -> Result<PageConnection> {
-        let _page = pagination.as_ref().and_then(|p| p.page).unwrap_or(1);
-        let _per_page = pagination.as_ref().and_then(|p| p.per_page).unwrap_or(20);
-        
-        // Mock implementation
+        let mut conn = read_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::ViewContent, &mut conn)?;
+
+        let pagination = pagination.unwrap_or(PaginationInput {
+            page: None,
+            per_page: None,
+            first: None,
+            after: None,
+        });
+        let limit = pagination.first
+            .or(pagination.per_page)
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+
+        let total_count: i64 = pages::table
+            .filter(pages::tenant_id.eq(tenant_id))
+            .count()
+            .get_result(&mut conn)?;
+
+        let mut query = pages::table
+            .filter(pages::tenant_id.eq(tenant_id))
+            .select(Page::as_select())
+            .order((pages::time_created.desc(), pages::uuid.desc()))
+            .into_boxed();
+
+        let has_previous_page = match &pagination.after {
+            Some(cursor) => {
+                let (after_time, after_uuid) = decode_cursor(cursor)?;
+                query = query.filter(
+                    pages::time_created.lt(after_time)
+                        .or(pages::time_created.eq(after_time).and(pages::uuid.lt(after_uuid)))
+                );
+                true
+            }
+            None => {
+                let offset = (pagination.page.unwrap_or(1).max(1) - 1) * limit;
+                query = query.offset(offset);
+                offset > 0
+            }
+        };
+
+        // Fetch one extra row to know whether another page exists
+        let mut rows: Vec<Page> = query.limit(limit + 1).load(&mut conn)?;
+        let has_next_page = rows.len() as i64 > limit;
+        rows.truncate(limit as usize);
+
+        let edges: Vec<PageEdge> = rows
+            .into_iter()
+            .map(|page| PageEdge {
+                cursor: encode_cursor(&page),
+                node: page.into(),
+            })
+            .collect();
+
         Ok(PageConnection {
-            nodes: vec![
-                GqlPage {
-                    uuid: "page-1".to_string(),
-                    page_title: "Home".to_string(),
-                    page_url: "/".to_string(),
-                    page_name: Some("home".to_string()),
-                    content: Some("<h1>Welcome</h1>".to_string()),
-                    meta_title: Some("Home Page".to_string()),
-                    meta_description: None,
-                    status: "published".to_string(),
-                    created_at: Some("2025-12-24T00:00:00Z".to_string()),
-                    updated_at: Some("2025-12-24T00:00:00Z".to_string()),
-                }
-            ],
-            total_count: 1,
             page_info: PageInfo {
-                has_next_page: false,
-                has_previous_page: false,
+                has_next_page,
+                has_previous_page,
+                start_cursor: edges.first().map(|e| e.cursor.clone()),
+                end_cursor: edges.last().map(|e| e.cursor.clone()),
             },
+            nodes: edges.iter().map(|e| e.node.clone()).collect(),
+            edges,
+            total_count,
         })
     }
-    
+
     /// Search across resources
     async fn search(
         &self,
-        _ctx: &Context<'_>,
+        ctx: &Context<'_>,
         query: String,
-        _resources: Option<Vec<String>>
+        resources: Option<Vec<String>>,
+        #[graphql(desc = "Language code; defaults to the site's default language")]
+        lang: Option<String>
     ) -> Result<Vec<GqlSearchResult>> {
-        // Mock implementation
-        Ok(vec![
-            GqlSearchResult {
-                resource_type: "pages".to_string(),
-                id: "page-1".to_string(),
-                title: "Matching Page".to_string(),
-                snippet: format!("Found: {}", query),
-            }
-        ])
+        let mut conn = read_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::ViewContent, &mut conn)?;
+
+        let search_query = crate::services::search_service::SearchQuery {
+            q: query,
+            resources,
+            page: Some(1),
+            per_page: Some(MAX_PAGE_SIZE),
+            lang,
+        };
+
+        let response = crate::services::search_service::search(&search_query, Some(tenant_id), &mut conn)?;
+
+        Ok(response.results
+            .into_iter()
+            .map(|r| GqlSearchResult {
+                resource_type: r.resource_type,
+                id: r.id,
+                title: r.title,
+                snippet: r.snippet,
+            })
+            .collect())
     }
-    
+
+    /// Get a single module by UUID
+    async fn module(&self, ctx: &Context<'_>, uuid: String) -> Result<Option<GqlModule>> {
+        let mut conn = read_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::ViewContent, &mut conn)?;
+
+        let module = modules::table
+            .inner_join(pages::table)
+            .filter(modules::uuid.eq(uuid))
+            .filter(pages::tenant_id.eq(tenant_id))
+            .select(Module::as_select())
+            .first::<Module>(&mut conn)
+            .optional()?;
+
+        Ok(module.map(GqlModule::from))
+    }
+
     /// Get modules for a page
     async fn modules(
         &self,
-        _ctx: &Context<'_>,
+        ctx: &Context<'_>,
         page_uuid: String
     ) -> Result<Vec<GqlModule>> {
-        // Mock implementation
-        Ok(vec![
-            GqlModule {
-                uuid: "module-1".to_string(),
-                page_uuid: page_uuid.clone(),
-                title: "Hero Section".to_string(),
-                content: "<h1>Welcome</h1>".to_string(),
-                field_type: Some("wysiwyg".to_string()),
-            }
-        ])
+        let mut conn = read_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::ViewContent, &mut conn)?;
+
+        let page = match load_tenant_page(&page_uuid, tenant_id, &mut conn)? {
+            Some(page) => page,
+            None => return Ok(vec![]),
+        };
+
+        let modules = Module::belonging_to(&page)
+            .select(Module::as_select())
+            .load::<Module>(&mut conn)?;
+
+        Ok(modules.into_iter().map(GqlModule::from).collect())
     }
-    
-    /// Get media items
+
+    /// Get module categories (array fields) for a page, with their modules
+    async fn categories(
+        &self,
+        ctx: &Context<'_>,
+        page_uuid: String
+    ) -> Result<Vec<GqlCategory>> {
+        let mut conn = read_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::ViewContent, &mut conn)?;
+
+        let page = match load_tenant_page(&page_uuid, tenant_id, &mut conn)? {
+            Some(page) => page,
+            None => return Ok(vec![]),
+        };
+
+        let categories = ModuleCategory::belonging_to(&page)
+            .select(ModuleCategory::as_select())
+            .order(module_category::title.asc())
+            .load::<ModuleCategory>(&mut conn)?;
+
+        let grouped = Module::belonging_to(&categories)
+            .select(Module::as_select())
+            .load::<Module>(&mut conn)?
+            .grouped_by(&categories);
+
+        Ok(categories
+            .into_iter()
+            .zip(grouped)
+            .map(GqlCategory::from)
+            .collect())
+    }
+
+    /// Get media items of the current tenant, newest first
     async fn media_library(
         &self,
-        _ctx: &Context<'_>,
-        _pagination: Option<PaginationInput>
+        ctx: &Context<'_>,
+        pagination: Option<PaginationInput>
     ) -> Result<Vec<GqlMedia>> {
-        // Mock implementation
-        Ok(vec![
-            GqlMedia {
-                uuid: "media-1".to_string(),
-                filename: "image.jpg".to_string(),
-                original_filename: "my-image.jpg".to_string(),
-                mime_type: "image/jpeg".to_string(),
-                file_size: 1024000,
-                width: Some(1920),
-                height: Some(1080),
-                storage_path: "/uploads/image.jpg".to_string(),
-            }
-        ])
+        let mut conn = read_conn(ctx)?;
+        let tenant_id = request_context(ctx)?.authorize(Permission::ViewContent, &mut conn)?;
+
+        let page = pagination.as_ref().and_then(|p| p.page).unwrap_or(1).max(1);
+        let per_page = pagination.as_ref()
+            .and_then(|p| p.per_page.or(p.first))
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+
+        let items = media::table
+            .filter(media::tenant_id.eq(tenant_id))
+            .order(media::created_at.desc())
+            .offset((page - 1) * per_page)
+            .limit(per_page)
+            .select(Media::as_select())
+            .load::<Media>(&mut conn)?;
+
+        Ok(items.into_iter().map(GqlMedia::from).collect())
     }
 }
@@ -3,6 +3,10 @@
 use async_graphql::*;
 use serde::{Serialize, Deserialize};
 
+use crate::models::media_models::Media;
+use crate::models::module_models::{Module, ModuleCategory};
+use crate::models::page_models::Page;
+
 // Page Type
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 #[graphql(name = "Page")]
@@ -19,15 +23,70 @@ pub struct GqlPage {
     pub updated_at: Option<String>,
 }
 
+impl From<Page> for GqlPage {
+    fn from(page: Page) -> Self {
+        GqlPage {
+            uuid: page.uuid,
+            page_title: page.page_title,
+            page_url: page.page_url,
+            page_name: Some(page.page_name),
+            content: page.content,
+            meta_title: page.meta_title,
+            meta_description: page.meta_description,
+            status: page.status.map(|s| s.as_str()).unwrap_or("draft").to_string(),
+            created_at: Some(page.time_created.and_utc().to_rfc3339()),
+            // pages has no modification timestamp column
+            updated_at: None,
+        }
+    }
+}
+
 // Module Type
 #[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 #[graphql(name = "Module")]
 pub struct GqlModule {
     pub uuid: String,
     pub page_uuid: String,
+    pub category_uuid: Option<String>,
     pub title: String,
     pub content: String,
     pub field_type: Option<String>,
+    pub version: i32,
+}
+
+impl From<Module> for GqlModule {
+    fn from(module: Module) -> Self {
+        GqlModule {
+            uuid: module.uuid,
+            page_uuid: module.page_uuid,
+            category_uuid: module.category_uuid,
+            title: module.title,
+            content: module.content,
+            field_type: module.field_type,
+            version: module.version,
+        }
+    }
+}
+
+// Category Type (module_category)
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(name = "Category")]
+pub struct GqlCategory {
+    pub uuid: String,
+    pub page_uuid: String,
+    pub title: String,
+    pub modules: Vec<GqlModule>,
+}
+
+impl From<(ModuleCategory, Vec<Module>)> for GqlCategory {
+    fn from((category, modules): (ModuleCategory, Vec<Module>)) -> Self {
+        GqlCategory {
+            uuid: category.uuid,
+            page_uuid: category.page_uuid,
+            title: category.title,
+            modules: modules.into_iter().map(GqlModule::from).collect(),
+        }
+    }
 }
 
 // Media Type
@@ -42,6 +101,29 @@ pub struct GqlMedia {
     pub width: Option<i32>,
     pub height: Option<i32>,
     pub storage_path: String,
+    pub alt_text: Option<String>,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub created_at: String,
+}
+
+impl From<Media> for GqlMedia {
+    fn from(media: Media) -> Self {
+        GqlMedia {
+            uuid: media.uuid,
+            filename: media.filename,
+            original_filename: media.original_filename,
+            mime_type: media.mime_type,
+            file_size: media.file_size,
+            width: media.width,
+            height: media.height,
+            storage_path: media.file_path,
+            alt_text: media.alt_text,
+            title: media.title,
+            tags: media.tags.unwrap_or_default().into_iter().flatten().collect(),
+            created_at: media.created_at.and_utc().to_rfc3339(),
+        }
+    }
 }
 
 // Search Result Type
@@ -74,15 +156,57 @@ pub struct UpdatePageInput {
     pub status: Option<String>,
 }
 
+#[derive(Debug, InputObject)]
+pub struct CreateModuleInput {
+    pub page_uuid: String,
+    pub category_uuid: Option<String>,
+    pub title: String,
+    pub content: String,
+    pub field_type: Option<String>,
+}
+
+#[derive(Debug, InputObject)]
+pub struct UpdateModuleInput {
+    pub category_uuid: Option<String>,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub field_type: Option<String>,
+}
+
+#[derive(Debug, InputObject)]
+pub struct CreateCategoryInput {
+    pub page_uuid: String,
+    pub title: String,
+}
+
+#[derive(Debug, InputObject)]
+pub struct UpdateMediaInput {
+    pub alt_text: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// Either offset (`page`/`per_page`) or cursor (`first`/`after`) pagination.
+/// When `after` is set it takes precedence over `page`.
 #[derive(Debug, InputObject)]
 pub struct PaginationInput {
     pub page: Option<i64>,
     pub per_page: Option<i64>,
+    pub first: Option<i64>,
+    pub after: Option<String>,
 }
 
 // Connection types for pagination
+#[derive(Debug, SimpleObject)]
+pub struct PageEdge {
+    pub cursor: String,
+    pub node: GqlPage,
+}
+
 #[derive(Debug, SimpleObject)]
 pub struct PageConnection {
+    pub edges: Vec<PageEdge>,
     pub nodes: Vec<GqlPage>,
     pub total_count: i64,
     pub page_info: PageInfo,
@@ -92,4 +216,6 @@ pub struct PageConnection {
 pub struct PageInfo {
     pub has_next_page: bool,
     pub has_previous_page: bool,
+    pub start_cursor: Option<String>,
+    pub end_cursor: Option<String>,
 }
@@ -86,18 +86,14 @@ impl HelperDef for ArrayHelper {
 pub static ARRAY_HELPER: ArrayHelper = ArrayHelper;
 
 pub fn register_helpers(handlebars: Data<Mutex<Handlebars<'_>>>) {
-    handlebars
-        .lock()
-        .unwrap()
-        .register_helper("get", Box::new(get));
-    handlebars
-        .lock()
-        .unwrap()
-        .register_helper("getarray", Box::new(ARRAY_HELPER));
-    handlebars
-        .lock()
-        .unwrap()
-        .register_helper("formatPrice", Box::new(format_price));
+    register_helpers_on(&mut handlebars.lock().unwrap());
+}
+
+/// Registers the default helpers on a registry that isn't shared yet, e.g. a theme's
+pub fn register_helpers_on(handlebars: &mut Handlebars<'_>) {
+    handlebars.register_helper("get", Box::new(get));
+    handlebars.register_helper("getarray", Box::new(ARRAY_HELPER));
+    handlebars.register_helper("formatPrice", Box::new(format_price));
 }
 
 fn format_price(
@@ -0,0 +1,66 @@
+// ETag / If-Match helpers for optimistic concurrency control.
+// Pages are versioned by `pages.current_revision`, modules by `modules.version`.
+
+use actix_web::http::header;
+use actix_web::HttpRequest;
+
+pub fn page_etag(current_revision: Option<i32>) -> String {
+    format!("\"page-{}\"", current_revision.unwrap_or(0))
+}
+
+pub fn module_etag(version: i32) -> String {
+    format!("\"module-{}\"", version)
+}
+
+/// Entity tags listed in the `If-Match` header, or `None` when the header is absent.
+/// Weak validators (`W/"..."`) are kept as sent, so they never match: `If-Match`
+/// requires strong comparison (RFC 9110 §13.1.1).
+pub fn if_match_tags(req: &HttpRequest) -> Option<Vec<String>> {
+    let raw = req.headers().get(header::IF_MATCH)?.to_str().ok()?;
+
+    Some(
+        raw.split(',')
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| tag.to_string())
+            .collect()
+    )
+}
+
+/// Whether a write conditioned on `If-Match` may proceed against `current_etag`.
+/// Requests without the header are unconditional.
+pub fn if_match_satisfied(req: &HttpRequest, current_etag: &str) -> bool {
+    match if_match_tags(req) {
+        None => true,
+        Some(tags) => tags.iter().any(|tag| tag == "*" || tag == current_etag),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_missing_header_is_unconditional() {
+        let req = TestRequest::default().to_http_request();
+        assert!(if_match_tags(&req).is_none());
+        assert!(if_match_satisfied(&req, &page_etag(Some(3))));
+    }
+
+    #[test]
+    fn test_if_match_comparison() {
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, "\"page-2\", W/\"page-3\""))
+            .to_http_request();
+        assert!(if_match_satisfied(&req, &page_etag(Some(2))));
+        // Weak tags never satisfy If-Match
+        assert!(!if_match_satisfied(&req, &page_etag(Some(3))));
+        assert!(!if_match_satisfied(&req, &page_etag(Some(4))));
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_MATCH, "*"))
+            .to_http_request();
+        assert!(if_match_satisfied(&req, &module_etag(7)));
+    }
+}
@@ -0,0 +1,121 @@
+// Liquid equivalents of the default Handlebars helpers:
+//   {{ "Title" | get }}
+//   {% assign items = "Gallery" | getarray %}{% for item in items %}...{% endfor %}
+//   {{ amount_cents | formatPrice }}
+
+use liquid_core::model::ScalarCow;
+use liquid_core::Result;
+use liquid_core::Runtime;
+use liquid_core::{Display_filter, Filter, FilterReflection, ParseFilter};
+use liquid_core::{Value, ValueView};
+
+/// Registers the filters on a parser builder
+pub fn register_filters(builder: liquid::ParserBuilder) -> liquid::ParserBuilder {
+    builder.filter(Get).filter(GetArray).filter(FormatPrice)
+}
+
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "get",
+    description = "Renders the content of the page field with the given title.",
+    parsed(GetFilter)
+)]
+pub struct Get;
+
+#[derive(Debug, Default, Display_filter)]
+#[name = "get"]
+struct GetFilter;
+
+impl Filter for GetFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let title = input.to_kstr().into_owned();
+
+        // Like the Handlebars helper, a missing field renders a message instead of failing the page
+        if runtime.try_get(&[ScalarCow::new("fields")]).is_none() {
+            return Ok(Value::scalar("No fields exist on this page."));
+        }
+        let path = [ScalarCow::new("fields"), ScalarCow::new(title.clone()), ScalarCow::new("content")];
+        match runtime.try_get(&path) {
+            Some(content) => Ok(Value::scalar(content.render().to_string())),
+            None => Ok(Value::scalar(format!("Field `{}` does not exist on the page.", title))),
+        }
+    }
+}
+
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "getarray",
+    description = "Returns the array field with the given title, or an empty array.",
+    parsed(GetArrayFilter)
+)]
+pub struct GetArray;
+
+#[derive(Debug, Default, Display_filter)]
+#[name = "getarray"]
+struct GetArrayFilter;
+
+impl Filter for GetArrayFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let title = input.to_kstr().into_owned();
+        let path = [ScalarCow::new("array_fields"), ScalarCow::new(title)];
+        Ok(runtime
+            .try_get(&path)
+            .map(|values| values.to_value())
+            .unwrap_or_else(|| Value::Array(Vec::new())))
+    }
+}
+
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "formatPrice",
+    description = "Formats an amount in cents as dollars, e.g. 1999 as $19.99.",
+    parsed(FormatPriceFilter)
+)]
+pub struct FormatPrice;
+
+#[derive(Debug, Default, Display_filter)]
+#[name = "formatPrice"]
+struct FormatPriceFilter;
+
+impl Filter for FormatPriceFilter {
+    fn evaluate(&self, input: &dyn ValueView, _runtime: &dyn Runtime) -> Result<Value> {
+        let cents = input
+            .as_scalar()
+            .and_then(|s| s.to_float().or_else(|| s.to_integer().map(|i| i as f64)))
+            .unwrap_or(0.0);
+        Ok(Value::scalar(format!("${:.2}", cents / 100.0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(source: &str, data: serde_json::Value) -> String {
+        let parser = register_filters(liquid::ParserBuilder::with_stdlib()).build().unwrap();
+        let globals = liquid::to_object(&data).unwrap();
+        parser.parse(source).unwrap().render(&globals).unwrap()
+    }
+
+    #[test]
+    fn test_get_and_getarray() {
+        let data = json!({
+            "fields": {"Title": {"content": "Hello"}},
+            "array_fields": {"Gallery": [{"content": "a"}, {"content": "b"}]}
+        });
+        assert_eq!(render(r#"{{ "Title" | get }}"#, data.clone()), "Hello");
+        assert_eq!(render(r#"{{ "Body" | get }}"#, data.clone()), "Field `Body` does not exist on the page.");
+        assert_eq!(
+            render(r#"{% assign items = "Gallery" | getarray %}{% for i in items %}{{ i.content }}{% endfor %}"#, data.clone()),
+            "ab"
+        );
+        assert_eq!(render(r#"{% assign items = "None" | getarray %}{{ items.size }}"#, data), "0");
+    }
+
+    #[test]
+    fn test_format_price() {
+        assert_eq!(render("{{ amount | formatPrice }}", json!({"amount": 1999})), "$19.99");
+        assert_eq!(render("{{ amount | formatPrice }}", json!({"amount": null})), "$0.00");
+    }
+}
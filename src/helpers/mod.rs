@@ -1,3 +1,5 @@
-// These are the helpers for Handlebars.
+// These are the helpers for Handlebars and Liquid templates.
 pub mod default;
-pub mod tenant_helper;
\ No newline at end of file
+pub mod liquid_filters;
+pub mod tenant_helper;
+pub mod etag_helper;
@@ -1,41 +1,104 @@
 use actix_web::HttpRequest;
 use crate::models::db_connection::DatabasePool;
 use diesel::prelude::*;
-use crate::models::tenant_models::Tenant;
+use crate::middleware::auth_middleware::get_user_context;
+use crate::services::memory_cache::MemoryCache;
+use std::sync::OnceLock;
+use std::time::Duration;
 
-pub fn resolve_tenant_id(req: &HttpRequest, pool: &DatabasePool) -> Result<i32, String> {
-    // 1. Check for X-Tenant-ID header (Dev/API overrides)
-    if let Some(header_val) = req.headers().get("X-Tenant-ID") {
-        if let Ok(val_str) = header_val.to_str() {
-            if let Ok(id) = val_str.parse::<i32>() {
-                return Ok(id);
-            }
+/// Host → tenant id lookups. Entries expire so domain changes made on other
+/// instances are picked up; unknown hosts are remembered briefly too.
+static HOST_CACHE: OnceLock<MemoryCache> = OnceLock::new();
+const HOST_CACHE_CAPACITY: usize = 10_000;
+const HOST_CACHE_TTL: Duration = Duration::from_secs(60);
+const UNKNOWN_HOST_TTL: Duration = Duration::from_secs(10);
+
+define_sql_function!(fn lower(x: diesel::sql_types::Nullable<diesel::sql_types::Text>) -> diesel::sql_types::Nullable<diesel::sql_types::Text>);
+
+fn host_cache() -> &'static MemoryCache {
+    HOST_CACHE.get_or_init(|| MemoryCache::new(HOST_CACHE_CAPACITY))
+}
+
+/// Forget cached host lookups, e.g. after a tenant's domain changes
+pub fn invalidate_host_cache() {
+    host_cache().delete_pattern("*");
+}
+
+fn normalize_host(host: &str) -> String {
+    // Remove port if present
+    host.split(':').next().unwrap_or(host).trim_end_matches('.').to_lowercase()
+}
+
+/// The tenant named by an `X-Tenant-ID` header, or `None` when the header is
+/// missing or the request is anonymous. Only members of that tenant (per
+/// `is_member`) and platform admins may switch to it.
+fn header_tenant_id(
+    req: &HttpRequest,
+    is_member: impl FnOnce(i32, i32) -> Result<bool, String>,
+) -> Option<Result<i32, String>> {
+    let id = req
+        .headers()
+        .get("X-Tenant-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<i32>().ok())?;
+    let user_ctx = get_user_context(req)?;
+    if user_ctx.is_platform_admin() {
+        return Some(Ok(id));
+    }
+    Some(match is_member(id, user_ctx.user_id) {
+        Ok(true) => Ok(id),
+        Ok(false) => {
+            log::warn!("User {} sent X-Tenant-ID {} without membership", user_ctx.user_id, id);
+            Err(format!("Not a member of tenant {}", id))
         }
+        Err(e) => Err(e),
+    })
+}
+
+pub fn resolve_tenant_id(req: &HttpRequest, pool: &DatabasePool) -> Result<i32, String> {
+    // 1. X-Tenant-ID header, only from members of that tenant or platform admins.
+    // Anonymous requests can't switch tenants, so the header is ignored for them.
+    let header_tenant = header_tenant_id(req, |tid, uid| {
+        let mut conn = pool.get().map_err(|_| "Failed to get db connection".to_string())?;
+        Ok(get_tenant_role(tid, uid, &mut conn).is_ok())
+    });
+    if let Some(tenant) = header_tenant {
+        return tenant;
     }
 
     // 2. Resolve from Host header
     if let Some(host_val) = req.headers().get("Host") {
         if let Ok(host_str) = host_val.to_str() {
-            // Remove port if present
-            let host_only = host_str.split(':').next().unwrap_or(host_str);
-            
+            let host_only = normalize_host(host_str);
+
+            // Cached as the tenant id, or "" for hosts without a tenant
+            if let Some(cached) = host_cache().get(&host_only) {
+                return cached.parse::<i32>().map_err(|_| "Tenant not found".to_string());
+            }
+
             // Database lookup
             let mut conn = pool.get().map_err(|_| "Failed to get db connection".to_string())?;
             
             // Check custom domain OR subdomain
             // Logic: if host ends with .oxidly.com (or app domain), it's a subdomain. Else custom domain.
             // For simplicity in this iteration, we search both columns.
+            // Hosts are case-insensitive and stored as entered, so both sides are lowercased.
             
             use crate::schema::tenants::dsl::*;
             
             let tenant_opt = tenants
-                .filter(custom_domain.eq(host_only).or(subdomain.eq(host_only)))
-                .first::<Tenant>(&mut conn)
+                .filter(lower(custom_domain).eq(&host_only).or(lower(subdomain.nullable()).eq(&host_only)))
+                .select(id)
+                .first::<i32>(&mut conn)
                 .optional()
                 .map_err(|e| format!("Database error: {}", e))?;
-                
-            if let Some(t) = tenant_opt {
-                return Ok(t.id);
+
+            match tenant_opt {
+                Some(tid) => {
+                    host_cache().set(&host_only, tid.to_string(), HOST_CACHE_TTL);
+                    return Ok(tid);
+                }
+                None => host_cache().set(&host_only, String::new(), UNKNOWN_HOST_TTL),
             }
         }
     }
@@ -57,3 +120,43 @@ pub fn get_tenant_role(
         .first::<String>(conn)
         .map_err(|_| "Not a member of this tenant".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::middleware::auth_middleware::UserContext;
+    use actix_web::test::TestRequest;
+    use actix_web::HttpMessage;
+
+    fn request(tenant_header: &str, user: Option<(i32, &str)>) -> HttpRequest {
+        let req = TestRequest::default().insert_header(("X-Tenant-ID", tenant_header)).to_http_request();
+        if let Some((user_id, role)) = user {
+            req.extensions_mut().insert(UserContext { user_id, email: "user@example.com".to_string(), role: role.to_string() });
+        }
+        req
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host("Shop.Example.com:8080"), "shop.example.com");
+        assert_eq!(normalize_host("example.com."), "example.com");
+    }
+
+    #[test]
+    fn test_header_tenant_requires_membership() {
+        let member_of_7 = |tid: i32, uid: i32| Ok(tid == 7 && uid == 3);
+
+        assert_eq!(header_tenant_id(&request("7", Some((3, "user"))), member_of_7), Some(Ok(7)));
+        assert!(matches!(header_tenant_id(&request("8", Some((3, "user"))), member_of_7), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_header_tenant_ignored_for_anonymous_and_trusted_for_admins() {
+        let never_asked = |_: i32, _: i32| -> Result<bool, String> { panic!("membership looked up") };
+
+        assert_eq!(header_tenant_id(&request("7", None), never_asked), None);
+        assert_eq!(header_tenant_id(&request("not-a-number", Some((3, "user"))), never_asked), None);
+        assert_eq!(header_tenant_id(&request("8", Some((1, "admin"))), never_asked), Some(Ok(8)));
+    }
+}
@@ -97,13 +97,17 @@ async fn main() -> std::io::Result<()> {
     let storage_backend = StorageBackend::from_config(&conf).await;
 
     // Initialize Cache Service
-    let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+    // Without REDIS_URL (or if Redis is down) pages are cached in-process
+    let redis_url = std::env::var("REDIS_URL").ok();
     // Default TTL: 300 seconds (5 mins)
     let cache_service = services::cache_service_v2::CacheServiceV2::new(
-        &redis_url, 
+        redis_url.as_deref(), 
         conf.redis_cluster_nodes.clone(),
         300
-    ).await.expect("Failed to initialize Cache Service");
+    ).await;
+    // Optional local L1 in front of Redis, e.g. CACHE_L1_CAPACITY=1000 CACHE_L1_TTL=10
+    let env_usize = |name: &str, default: usize| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+    let cache_service = cache_service.with_l1(env_usize("CACHE_L1_CAPACITY", 0), env_usize("CACHE_L1_TTL", 10));
     let cache_service = web::Data::new(cache_service);
 
     // Initialize Template Service (Supports Handlebars + Liquid)
@@ -112,6 +116,15 @@ async fn main() -> std::io::Result<()> {
     
     // Register templates
     template_service.load_templates("./templates").unwrap();
+
+    // Each tenant's active theme package gets its own registry
+    match pool.get() {
+        Ok(mut conn) => match template_service.load_active_themes(&mut conn) {
+            Ok(n) => log::info!("🎨 Loaded {} active tenant themes", n),
+            Err(e) => log::error!("Failed to load tenant themes: {}", e),
+        },
+        Err(e) => log::error!("Failed to load tenant themes: {}", e),
+    }
     
     // Legacy support: Create web::Data from the inner Arc that TemplateService holds.
     // This allows existing controllers to continue accessing "Data<Mutex<Handlebars>>"
@@ -120,14 +133,25 @@ async fn main() -> std::io::Result<()> {
     // Registers all default handlebars functions.
     helpers::default::register_helpers(handlebars_ref.clone());
 
-    // Registers the fs watcher
-    // We pass the legacy ref to the watcher for now.
-    // Ideally, watcher should trigger template_service.reload()
-    let hb_for_watch = handlebars_ref.clone();
-    std::thread::spawn(|| watch::watch(hb_for_watch));
+    // Registers the fs watcher, which reloads both engines' templates
+    let template_service = web::Data::new(template_service);
+    let templates_for_watch = template_service.clone();
+    let cache_for_watch = cache_service.get_ref().clone();
+    let runtime_for_watch = tokio::runtime::Handle::current();
+    std::thread::spawn(move || watch::watch(templates_for_watch, cache_for_watch, runtime_for_watch));
+
+    // Initialize Plugin Registry
+    let plugin_registry = std::sync::Arc::new(services::plugin_service::PluginRegistry::new());
+    plugin_registry.register(std::sync::Arc::new(services::embedding_refresh_service::EmbeddingRefreshPlugin));
+    plugin_registry.load_all().await;
 
     // Initialize GraphQL Schema
-    let graphql_schema = web::Data::new(graphql::create_schema());
+    let graphql_schema = web::Data::new(graphql::create_schema(
+        pool.clone(),
+        read_pool.clone(),
+        cache_service.get_ref().clone(),
+        plugin_registry.clone(),
+    ));
 
     // Configure rate limiting with actix-governor
     let governor_conf = GovernorConfigBuilder::default()
@@ -146,10 +170,6 @@ async fn main() -> std::io::Result<()> {
         Err(e) => log::error!("Failed to run database migrations: {}", e),
     };
 
-    // Initialize Plugin Registry
-    let plugin_registry = std::sync::Arc::new(services::plugin_service::PluginRegistry::new());
-    plugin_registry.load_all().await;
-
     // Initialize Payment Handler Registry
     let mut payment_registry = services::payment_service::PaymentHandlerRegistry::new();
     
@@ -195,8 +215,16 @@ async fn main() -> std::io::Result<()> {
     // Clone pool for MCP Server before HttpServer closure captures it
     let pool_for_mcp = pool.clone();
     let pool_for_cleanup = pool.clone();
+    let pool_for_embeddings = pool.clone();
+    let pool_for_backups = pool.clone();
+    let storage_for_backups = storage_backend.clone();
+    let pool_for_carts = pool.clone();
+    let pool_for_reservations = pool.clone();
+    let pool_for_refunds = pool.clone();
+    let registry_for_refunds = payment_registry.clone();
 
     let email_service_for_server = email_service.clone();
+    let app_config = web::Data::new(conf.clone());
     let http_server = HttpServer::new(move || {
         let cors = Cors::permissive();
 
@@ -224,13 +252,39 @@ async fn main() -> std::io::Result<()> {
             .route("/orders/{id}", web::get().to(controllers::order_controller::get_order)) // Commerce disabled
             .route("/orders", web::post().to(controllers::order_controller::create_order)) // Commerce disabled
             .route("/orders/{id}/status", web::put().to(controllers::order_controller::update_order_status)) // Commerce disabled
+            .route("/orders/{id}/history", web::get().to(controllers::order_controller::get_order_history))
+            .route("/orders/{id}/refunds", web::post().to(controllers::order_controller::refund_order))
+            .route("/orders/{id}/refunds", web::get().to(controllers::order_controller::list_order_refunds))
             .route("/orders/{id}/payment", web::post().to(controllers::order_controller::link_payment_to_order)) // Commerce disabled
+            // Cart and checkout routes (guests identify with X-Cart-Token)
+            .route("/cart", web::get().to(controllers::cart_controller::get_cart))
+            .route("/cart/items", web::post().to(controllers::cart_controller::add_cart_item))
+            .route("/cart/items/{id}", web::put().to(controllers::cart_controller::update_cart_item))
+            .route("/cart/items/{id}", web::delete().to(controllers::cart_controller::remove_cart_item))
+            .route("/cart/merge", web::post().to(controllers::cart_controller::merge_cart))
+            .route("/cart/checkout", web::post().to(controllers::cart_controller::start_checkout))
+            .route("/checkout/{uuid}", web::get().to(controllers::cart_controller::get_checkout))
+            .route("/checkout/{uuid}", web::put().to(controllers::cart_controller::update_checkout))
+            .route("/checkout/{uuid}/complete", web::post().to(controllers::cart_controller::complete_checkout))
+            .route("/checkout/{uuid}/confirm", web::post().to(controllers::cart_controller::confirm_checkout))
             // Inventory management routes
             .route("/products/{id}/variants", web::get().to(services::inventory_service::get_product_variants))
             .route("/variants", web::post().to(services::inventory_service::create_variant))
             .route("/variants/{id}/stock", web::put().to(services::inventory_service::update_variant_stock))
             // .route("/products/{id}/inventory/audit", web::get().to(services::inventory_service::get_inventory_audit_log))
             .route("/variants/{id}", web::delete().to(services::inventory_service::delete_variant))
+            // Page revisions: diff & merge before {rev_number} so they aren't captured by it
+            .route("/api/pages/{page_uuid}/revisions/diff", web::get().to(controllers::revision_controller::diff_revisions))
+            .route("/api/pages/{page_uuid}/revisions/merge", web::post().to(controllers::revision_controller::merge_revision))
+            .route("/api/pages/{page_uuid}/revisions", web::get().to(controllers::revision_controller::list_revisions))
+            .route("/api/pages/{page_uuid}/revisions/{rev_number}", web::get().to(controllers::revision_controller::get_revision))
+            .route("/api/pages/{page_uuid}/rollback/{rev_number}", web::post().to(controllers::revision_controller::rollback_revision))
+            // Redirects: import before {id}
+            .route("/api/redirects", web::get().to(controllers::redirect_controller::list_redirects))
+            .route("/api/redirects", web::post().to(controllers::redirect_controller::create_redirect))
+            .route("/api/redirects/import", web::post().to(controllers::redirect_controller::import_redirects))
+            .route("/api/redirects/{id}", web::put().to(controllers::redirect_controller::update_redirect))
+            .route("/api/redirects/{id}", web::delete().to(controllers::redirect_controller::delete_redirect))
             // Tenant routes
             .route("/api/tenants", web::post().to(controllers::tenant_controller::create_tenant))
             .route("/api/tenants", web::get().to(controllers::tenant_controller::list_my_tenants))
@@ -300,6 +354,11 @@ async fn main() -> std::io::Result<()> {
             .route("/api/verification/settings", web::get().to(controllers::verification_settings_controller::get_verification_settings))
             .route("/api/verification/settings/{verification_type}", web::put().to(controllers::verification_settings_controller::update_verification_settings))
             .route("/api/verification/settings/{verification_type}", web::delete().to(controllers::verification_settings_controller::delete_verification_settings))
+            // Storage migration (platform admin only)
+            .route("/admin/storage/migrations", web::get().to(controllers::storage_migration_controller::list_storage_migrations))
+            .route("/admin/storage/migrations", web::post().to(controllers::storage_migration_controller::start_storage_migration))
+            .route("/admin/storage/migrations/{id}", web::get().to(controllers::storage_migration_controller::get_storage_migration))
+            .route("/admin/storage/migrations/{id}/pause", web::post().to(controllers::storage_migration_controller::pause_storage_migration))
             // AI Provider management routes (admin only)
             .route("/admin/ai/providers", web::get().to(services::ai_provider_service::list_providers))
             .route("/admin/ai/providers/{id}", web::get().to(services::ai_provider_service::get_provider))
@@ -316,6 +375,8 @@ async fn main() -> std::io::Result<()> {
             .route("/ai/metadata/categories", web::post().to(services::metadata_automation_service::suggest_categories))
             .route("/ai/metadata/alt-text", web::post().to(services::metadata_automation_service::generate_alt_text))
             .route("/ai/metadata/all", web::post().to(services::metadata_automation_service::generate_all_metadata))
+            .route("/search", web::get().to(controllers::search_controller::search_content))
+            .route("/search/{search_id}/click", web::post().to(controllers::search_controller::record_search_click))
             .route("/search/embedding", web::post().to(services::semantic_search_service::create_embedding))
             .route("/search/semantic", web::post().to(services::semantic_search_service::semantic_search))
             .route("/v1/ai/analyze/sentiment", web::post().to(services::ai_content_service::analyze_sentiment))
@@ -342,10 +403,30 @@ async fn main() -> std::io::Result<()> {
             // Backups
             .service(controllers::backup_controller::list_backups)
             .service(controllers::backup_controller::create_backup)
+            .service(controllers::backup_controller::get_tenant_backup_policy)
+            .service(controllers::backup_controller::update_tenant_backup_policy)
+            .service(controllers::backup_controller::list_tenant_backups)
+            .service(controllers::backup_controller::create_tenant_backup)
+            .service(controllers::backup_controller::download_tenant_backup)
+            .service(controllers::backup_controller::restore_tenant_backup)
+            .service(controllers::backup_controller::delete_tenant_backup)
             // Media routes
             .route("/api/media", web::get().to(controllers::media_controller::list_media))
+            .route("/api/media/policy", web::get().to(controllers::media_controller::get_media_policy))
+            .route("/api/media/folders", web::get().to(controllers::media_folder_controller::list_folders))
+            .route("/api/media/folders", web::post().to(controllers::media_folder_controller::create_folder))
+            .route("/api/media/folders/{id}", web::put().to(controllers::media_folder_controller::rename_folder))
+            .route("/api/media/folders/{id}", web::delete().to(controllers::media_folder_controller::delete_folder))
+            .route("/api/media/folders/{id}/move", web::post().to(controllers::media_folder_controller::move_folder))
+            .route("/api/media/move", web::post().to(controllers::media_folder_controller::move_media))
+            .route("/api/media/upload-url", web::post().to(controllers::media_controller::create_upload_url))
+            .route("/api/media/upload-complete", web::post().to(controllers::media_controller::complete_upload))
             .route("/api/media/{uuid}", web::get().to(controllers::media_controller::get_media))
             .route("/api/media/{uuid}", web::delete().to(controllers::media_controller::delete_media))
+            .route("/api/media/{uuid}/usage", web::get().to(controllers::media_controller::get_media_usage))
+            .route("/api/media/{uuid}/focal-point", web::put().to(controllers::media_controller::set_focal_point))
+            .route("/api/media/{uuid}/transform-url", web::post().to(controllers::media_controller::create_transform_url))
+            .route("/api/media/{uuid}/download-url", web::get().to(controllers::media_controller::get_download_url))
             .route("/api/media/upload", web::post().to(controllers::media_controller::upload_media))
             // Billing & Invoicing
             .configure(controllers::billing_controller::init_routes)
@@ -421,14 +502,18 @@ async fn main() -> std::io::Result<()> {
             }))
             .service(fs::Files::new("/assets", "./templates/assets").show_files_listing())
             .service(fs::Files::new("/static", "./static"))
+            .route("/media/{uuid}", web::get().to(controllers::media_controller::transform_media))
+            .route("/theme-assets/{path:.*}", web::get().to(controllers::theme_controller::theme_asset))
             .default_service(web::get().to(controllers::page_controllers::display_page))
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(read_pool.clone())) // Read Replica
             .app_data(web::Data::new(storage_backend.clone())) // Storage Backend
             .app_data(cache_service.clone()) // Cache Service
             .app_data(handlebars_ref.clone())
+            .app_data(template_service.clone())
             .app_data(payment_registry.clone())
             .app_data(email_service_for_server.clone())
+            .app_data(app_config.clone()) // App config, for building storage backends on demand
     })
     .bind(server_url)?
     .workers(2)
@@ -449,6 +534,21 @@ async fn main() -> std::io::Result<()> {
         let _ = services::scheduler_service::start_scheduler(email_service_for_sched).await;
     });
     
+    // Start Embedding Refresh Worker (re-embeds pages queued by content edits)
+    actix_web::rt::spawn(services::embedding_refresh_service::run_worker(pool_for_embeddings));
+
+    // Start Tenant Backup Scheduler (scheduled backups and retention per tenant policy)
+    actix_web::rt::spawn(services::tenant_backup_service::run_scheduler(pool_for_backups, storage_for_backups));
+
+    // Start Cart Abandonment Worker (marks idle carts abandoned and sends recovery emails)
+    actix_web::rt::spawn(services::cart_abandonment::run_worker(pool_for_carts, email_service.clone()));
+
+    // Start Stock Reservation Expiry (stock held for unpaid orders)
+    actix_web::rt::spawn(services::stock_reservation_service::run_worker(pool_for_reservations));
+
+    // Start Refund Worker (follows refunds the payment provider is still processing)
+    actix_web::rt::spawn(services::refund_service::run_worker(pool_for_refunds, registry_for_refunds, email_service.clone()));
+
     // Start Email Verification Cleanup Job (runs every hour)
     actix_web::rt::spawn(async move {
         use services::email_verification_service::EmailVerificationService;
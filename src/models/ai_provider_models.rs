@@ -29,6 +29,8 @@ pub struct AIProviderConfig {
     pub created_by: Option<i32>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub config: serde_json::Value,  // Provider-specific settings, e.g. {"endpoint": ...}
+    pub purpose: String,            // "completion" or "embedding"
 }
 
 /// New AI Provider for insertion
@@ -42,6 +44,8 @@ pub struct NewAIProviderConfig {
     pub daily_token_limit: Option<i32>,
     pub monthly_budget_cents: Option<i32>,
     pub created_by: Option<i32>,
+    pub config: Option<serde_json::Value>,
+    pub purpose: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable)]
@@ -82,6 +86,8 @@ pub struct AIProviderConfigPublic {
     pub is_active: Option<bool>,
     pub daily_token_limit: Option<i32>,
     pub monthly_budget_cents: Option<i32>,
+    pub config: serde_json::Value,
+    pub purpose: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
@@ -96,6 +102,8 @@ impl From<AIProviderConfig> for AIProviderConfigPublic {
             is_active: config.is_active,
             daily_token_limit: config.daily_token_limit,
             monthly_budget_cents: config.monthly_budget_cents,
+            config: config.config,
+            purpose: config.purpose,
             created_at: config.created_at,
             updated_at: config.updated_at,
         }
@@ -0,0 +1,67 @@
+// Backup Models
+// Stored backups and per-tenant backup schedules
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::backups)]
+pub struct Backup {
+    pub id: i32,
+    pub uuid: String,
+    /// "tenant" for logical tenant backups
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// "running", "completed" or "failed"
+    pub status: Option<String>,
+    pub file_path: Option<String>,
+    pub file_size: Option<i64>,
+    /// "local" or "s3"
+    pub storage_location: Option<String>,
+    /// Archive format and version, row counts per table, or the error of a failed backup
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    pub completed_at: Option<chrono::NaiveDateTime>,
+    pub tenant_id: Option<i32>,
+    /// SHA-256 of the archive, checked before restoring
+    pub checksum: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::backups)]
+pub struct NewBackup {
+    pub uuid: String,
+    pub type_: String,
+    pub status: Option<String>,
+    pub storage_location: Option<String>,
+    pub tenant_id: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::backup_policies)]
+pub struct BackupPolicy {
+    pub id: i32,
+    pub tenant_id: i32,
+    /// Hours between scheduled backups
+    pub interval_hours: i32,
+    /// Number of completed backups always kept
+    pub keep_last: i32,
+    /// Backups older than this are deleted, beyond the `keep_last` newest
+    pub keep_days: Option<i32>,
+    pub is_active: bool,
+    pub last_run_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::backup_policies)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewBackupPolicy {
+    pub tenant_id: i32,
+    pub interval_hours: i32,
+    pub keep_last: i32,
+    pub keep_days: Option<i32>,
+    pub is_active: bool,
+}
@@ -0,0 +1,118 @@
+// Cart Models
+// Persistent shopping carts and the checkout sessions that turn them into orders
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::carts)]
+pub struct Cart {
+    pub id: i32,
+    pub uuid: String,
+    pub tenant_id: Option<i32>,
+    /// Owner once signed in; guest carts have none
+    pub user_id: Option<i32>,
+    /// Secret a guest sends back in the `X-Cart-Token` header
+    #[serde(skip_serializing)]
+    pub guest_token: Option<String>,
+    /// "active", "converted" (checked out) or "merged" (into a user's cart)
+    pub status: String,
+    pub currency: String,
+    pub email: Option<String>,
+    pub last_activity_at: chrono::NaiveDateTime,
+    pub abandoned_at: Option<chrono::NaiveDateTime>,
+    pub recovery_sent_at: Option<chrono::NaiveDateTime>,
+    pub recovered_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::carts)]
+pub struct NewCart {
+    pub uuid: String,
+    pub tenant_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub guest_token: Option<String>,
+    pub currency: String,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[diesel(belongs_to(Cart))]
+#[diesel(table_name = crate::schema::cart_items)]
+pub struct CartItem {
+    pub id: i32,
+    pub cart_id: i32,
+    pub product_id: i64,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::cart_items)]
+pub struct NewCartItem {
+    pub cart_id: i32,
+    pub product_id: i64,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::checkout_sessions)]
+pub struct CheckoutSession {
+    pub id: i32,
+    pub uuid: String,
+    pub cart_id: i32,
+    /// "open", "completed" or "expired"
+    pub status: String,
+    pub currency: String,
+    /// The cart's lines with the prices locked when checkout started
+    #[schema(value_type = Vec<CheckoutLine>)]
+    pub line_items: serde_json::Value,
+    pub subtotal_cents: i64,
+    pub total_cents: i64,
+    pub email: Option<String>,
+    pub shipping_address: Option<serde_json::Value>,
+    pub billing_address: Option<serde_json::Value>,
+    pub order_id: Option<i64>,
+    pub expires_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub completed_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::checkout_sessions)]
+pub struct NewCheckoutSession {
+    pub uuid: String,
+    pub cart_id: i32,
+    pub currency: String,
+    pub line_items: serde_json::Value,
+    pub subtotal_cents: i64,
+    pub total_cents: i64,
+    pub email: Option<String>,
+    pub shipping_address: Option<serde_json::Value>,
+    pub billing_address: Option<serde_json::Value>,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+/// A cart line priced from the catalogue
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct CheckoutLine {
+    pub product_id: i64,
+    pub variant_id: Option<i32>,
+    pub name: String,
+    pub sku: Option<String>,
+    pub quantity: i32,
+    pub unit_price_cents: i64,
+    /// Tax on the whole line
+    #[serde(default)]
+    pub tax_cents: i64,
+    /// Discount on the whole line
+    #[serde(default)]
+    pub discount_cents: i64,
+    pub line_total_cents: i64,
+}
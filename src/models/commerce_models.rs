@@ -1,6 +1,6 @@
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
-use crate::schema::{products, orders, order_items};
+use crate::schema::{products, orders, order_items, order_refunds, order_status_history};
 use utoipa::ToSchema;
 
 #[derive(Queryable, Selectable, Identifiable, Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -80,7 +80,26 @@ pub struct OrderItem {
     pub order_id: i64,
     pub product_id: i64,
     pub quantity: i32,
+    /// Unit price paid
     pub price_cents: i64,
+    pub variant_id: Option<i32>,
+    /// Product name at purchase time
+    pub product_name: String,
+    pub sku: Option<String>,
+    pub variant_name: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub variant_attributes: Option<serde_json::Value>,
+    /// Tax charged on the whole line
+    pub tax_cents: i64,
+    /// Discount taken off the whole line
+    pub discount_cents: i64,
+}
+
+impl OrderItem {
+    /// What the customer paid for the line
+    pub fn line_total_cents(&self) -> i64 {
+        self.price_cents * self.quantity as i64 + self.tax_cents - self.discount_cents
+    }
 }
 
 #[derive(Insertable, Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -90,4 +109,75 @@ pub struct NewOrderItem {
     pub product_id: i64,
     pub quantity: i32,
     pub price_cents: i64,
+    pub variant_id: Option<i32>,
+    pub product_name: String,
+    pub sku: Option<String>,
+    pub variant_name: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub variant_attributes: Option<serde_json::Value>,
+    pub tax_cents: i64,
+    pub discount_cents: i64,
+}
+
+/// One step in an order's lifecycle
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[diesel(belongs_to(Order))]
+#[diesel(table_name = order_status_history)]
+pub struct OrderStatusChange {
+    pub id: i32,
+    pub order_id: i64,
+    /// None when the order was created
+    pub from_status: Option<String>,
+    pub to_status: String,
+    /// "customer", "staff", or the system that made the change, e.g. "stripe"
+    pub actor: String,
+    pub actor_user_id: Option<i32>,
+    pub reason: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = order_status_history)]
+pub struct NewOrderStatusChange {
+    pub order_id: i64,
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub actor: String,
+    pub actor_user_id: Option<i32>,
+    pub reason: Option<String>,
+}
+
+/// Money given back on an order's payment
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[diesel(belongs_to(Order))]
+#[diesel(table_name = order_refunds)]
+pub struct OrderRefund {
+    pub id: i32,
+    pub uuid: String,
+    pub order_id: i64,
+    pub idempotency_key: String,
+    pub provider: String,
+    pub provider_refund_id: Option<String>,
+    pub amount_cents: i64,
+    pub currency: String,
+    /// "pending", "succeeded", "failed" or "canceled"
+    pub status: String,
+    pub reason: Option<String>,
+    pub failure_reason: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = order_refunds)]
+pub struct NewOrderRefund {
+    pub uuid: String,
+    pub order_id: i64,
+    pub idempotency_key: String,
+    pub provider: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub reason: Option<String>,
+    pub created_by: Option<i32>,
 }
@@ -19,6 +19,11 @@ pub struct LocalConfig {
     pub aws_secret_access_key: Option<String>,
     pub aws_region: Option<String>,
     pub aws_s3_bucket: Option<String>,
+    // S3-compatible services (MinIO etc.): custom endpoint and path-style addressing
+    pub aws_s3_endpoint: Option<String>,
+    pub aws_s3_force_path_style: Option<bool>,
+    // Storage for new files, "local" or "s3" (defaults to S3 when configured)
+    pub storage_backend: Option<String>,
     // Read Replica Database URL (Optional)
     pub database_url_read: Option<String>,
     // Redis Configuration
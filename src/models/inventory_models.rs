@@ -5,7 +5,7 @@ use utoipa::ToSchema;
 use uuid::Uuid;
 use bigdecimal::BigDecimal;
 
-use crate::schema::{product_variants, inventory_audit_log};
+use crate::schema::{product_variants, inventory_audit_log, inventory_reservations};
 
 /// Product variant for SKU-level inventory management
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Identifiable, ToSchema)]
@@ -116,3 +116,31 @@ impl NewInventoryAuditLog {
         }
     }
 }
+
+/// Stock held for an unpaid order
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Identifiable, ToSchema)]
+#[diesel(table_name = inventory_reservations)]
+pub struct InventoryReservation {
+    pub id: i32,
+    pub order_id: i64,
+    pub product_id: i64,
+    /// Stock is held on the variant when set, otherwise on the product
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+    /// "active", "committed", "released" or "expired"
+    pub status: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// New stock reservation
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = inventory_reservations)]
+pub struct NewInventoryReservation {
+    pub order_id: i64,
+    pub product_id: i64,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+    pub expires_at: NaiveDateTime,
+}
@@ -13,6 +13,7 @@ pub struct Language {
     pub enabled: Option<bool>,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    pub search_config: String,  // Postgres text search configuration, e.g. "english"
 }
 
 #[derive(Debug, Insertable, Deserialize)]
@@ -24,6 +25,7 @@ pub struct NewLanguage {
     pub is_default: Option<bool>,
     pub is_rtl: Option<bool>,
     pub enabled: Option<bool>,
+    pub search_config: Option<String>,
 }
 
 #[derive(Debug, AsChangeset, Deserialize)]
@@ -34,4 +36,5 @@ pub struct UpdateLanguage {
     pub is_default: Option<bool>,
     pub is_rtl: Option<bool>,
     pub enabled: Option<bool>,
+    pub search_config: Option<String>,
 }
@@ -3,6 +3,7 @@
 
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Queryable, Selectable, Identifiable, Debug, Serialize, Deserialize, Clone)]
 #[diesel(table_name = crate::schema::media)]
@@ -24,6 +25,12 @@ pub struct Media {
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
     pub tenant_id: Option<i32>,
+    /// Per-type details: EXIF, PDF page count, duration
+    pub metadata: Option<serde_json::Value>,
+    /// Point kept in view when cropping, 0-1 across and down; centre if unset
+    pub focal_x: Option<f32>,
+    pub focal_y: Option<f32>,
+    pub folder_id: Option<i32>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -42,30 +49,80 @@ pub struct NewMedia {
     pub description: Option<String>,
     pub tags: Option<Vec<Option<String>>>,
     pub uploaded_by: Option<i32>,
+    pub tenant_id: Option<i32>,
+    pub metadata: Option<serde_json::Value>,
+    pub folder_id: Option<i32>,
+}
+
+/// A resized copy of an image, e.g. `320w-webp`
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Serialize, Deserialize, Clone)]
+#[diesel(belongs_to(Media, foreign_key = media_id))]
+#[diesel(table_name = crate::schema::media_variants)]
+pub struct MediaVariant {
+    pub id: i32,
+    pub media_id: i32,
+    pub variant_name: String,
+    pub file_path: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub file_size: Option<i64>,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    /// "jpeg" or "webp"
+    pub format: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::media_variants)]
+pub struct NewMediaVariant {
+    pub media_id: i32,
+    pub variant_name: String,
+    pub file_path: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub file_size: Option<i64>,
+    pub format: String,
 }
 
-// Media variants table doesn't exist in schema - commenting out
-// #[derive(Queryable, Selectable, Identifiable, Associations, Debug, Serialize, Deserialize, Clone)]
-// #[diesel(belongs_to(Media, foreign_key = media_id))]
-// #[diesel(table_name = crate::schema::media_variants)]
-// pub struct MediaVariant {
-//     pub id: i32,
-//     pub media_id: i32,
-//     pub variant_name: String,
-//     pub file_path: String,
-//     pub width: Option<i32>,
-//     pub height: Option<i32>,
-//     pub file_size: Option<i64>,
-//     pub created_at: Option<chrono::NaiveDateTime>,
-// }
- 
-// #[derive(Insertable)]
-// #[diesel(table_name = crate::schema::media_variants)]
-// pub struct NewMediaVariant {
-//     pub media_id: i32,
-//     pub variant_name: String,
-//     pub file_path: String,
-//     pub width: Option<i32>,
-//     pub height: Option<i32>,
-//     pub file_size: Option<i64>,
-// }
+/// A folder in a tenant's media library
+#[derive(Queryable, Selectable, Identifiable, Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::media_folders)]
+pub struct MediaFolder {
+    pub id: i32,
+    pub tenant_id: i32,
+    pub parent_id: Option<i32>,
+    pub name: String,
+    /// Full path, e.g. `/products/shoes`
+    pub path: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::media_folders)]
+pub struct NewMediaFolder {
+    pub tenant_id: i32,
+    pub parent_id: Option<i32>,
+    pub name: String,
+    pub path: String,
+}
+
+/// A page or module that references a media item
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[diesel(belongs_to(Media, foreign_key = media_id))]
+#[diesel(table_name = crate::schema::media_usages)]
+pub struct MediaUsage {
+    pub id: i32,
+    pub media_id: i32,
+    /// "page" or "module"
+    pub source_type: String,
+    pub source_uuid: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::media_usages)]
+pub struct NewMediaUsage {
+    pub media_id: i32,
+    pub source_type: String,
+    pub source_uuid: String,
+}
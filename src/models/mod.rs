@@ -13,6 +13,7 @@ pub mod revision_models;
 pub mod field_type_enum;
 pub mod category_models;
 pub mod inventory_models;  // Inventory management models
+pub mod cart_models; // Shopping carts and checkout sessions
 pub mod crm_models;
 pub mod crm_dtos; // CRM module
 pub mod language_models; // Language support
@@ -23,9 +24,12 @@ pub mod analytics_models; // Analytics event tracking
 pub mod db_connection;  // Database abstraction layer
 pub mod db_macros;      // Helper macros for database operations
 pub mod theme_models;
+pub mod redirect_models; // Per-tenant URL redirects
+pub mod storage_migration_models; // Local <-> S3 media migration runs
 pub mod marketplace_plugin_models;
 pub mod webhook_models;
 pub mod audit_models;
+pub mod backup_models; // Tenant backups and retention policies
 pub mod billing_models;
 pub mod verification_models; // Email verification models
 pub mod mcp_tool_models; // Custom MCP tools (Phase 2)
@@ -7,6 +7,7 @@ use super::page_models::Page;
 use super::{Model, PooledDatabaseConnection};
 use crate::schema::module_category;
 use crate::schema::modules;
+use crate::services::media_usage_service::{self, UsageSource};
 
 #[derive(Debug, Identifiable, Associations, Serialize, Deserialize, Queryable, Selectable, PartialEq, Clone, Eq, Hash, ToSchema)]
 #[diesel(belongs_to(Page, foreign_key = page_uuid))]
@@ -23,6 +24,13 @@ pub struct Module {
     pub field_config: Option<String>,
     pub validation_rules: Option<String>,
     pub tenant_id: Option<i32>,
+    // Optimistic concurrency version; absent from pre-versioning revision snapshots
+    #[serde(default = "default_module_version")]
+    pub version: i32,
+}
+
+fn default_module_version() -> i32 {
+    1
 }
 
 #[derive(Insertable, AsChangeset, Deserialize, Serialize, Clone, ToSchema)]
@@ -73,6 +81,31 @@ pub struct MutCategory {
     pub uuid: Option<String>
 }
 
+impl From<Module> for MutModule {
+    fn from(module: Module) -> MutModule {
+        MutModule {
+            uuid: Some(module.uuid),
+            title: module.title,
+            page_uuid: module.page_uuid,
+            category_uuid: module.category_uuid,
+            content: module.content,
+            field_type: module.field_type,
+            field_config: module.field_config,
+            validation_rules: module.validation_rules,
+        }
+    }
+}
+
+impl From<ModuleCategory> for MutCategory {
+    fn from(category: ModuleCategory) -> MutCategory {
+        MutCategory {
+            title: category.title,
+            page_uuid: category.page_uuid,
+            uuid: Some(category.uuid),
+        }
+    }
+}
+
 impl ModuleCategory {
     pub fn join(_id: String, db: &mut PooledDatabaseConnection) -> Result<Vec<Module>, diesel::result::Error> {
         use module_category::dsl::uuid;
@@ -120,9 +153,15 @@ impl Model<Self, MutModule, String, Module> for Module {
         new_module: &MutModule,
         db: &mut PooledDatabaseConnection,
     ) -> Result<usize, diesel::result::Error> {
-        diesel::insert_into(modules::table)
-            .values(new_module)
-            .execute(db)
+        db.transaction(|db| {
+            let inserted = diesel::insert_into(modules::table)
+                .values(new_module)
+                .execute(db)?;
+            if let Some(module_uuid) = &new_module.uuid {
+                media_usage_service::index_module(module_uuid, db)?;
+            }
+            Ok(inserted)
+        })
     }
 
     fn read_one(mod_id: String, db: &mut PooledDatabaseConnection) -> Result<Module, diesel::result::Error> {
@@ -140,7 +179,11 @@ impl Model<Self, MutModule, String, Module> for Module {
 
     fn delete(mod_id: String, db: &mut PooledDatabaseConnection) -> Result<usize, diesel::result::Error> {
         use modules::dsl::uuid;
-        diesel::delete(modules::table.filter(uuid.eq(mod_id))).execute(db)
+        db.transaction(|db| {
+            let deleted = diesel::delete(modules::table.filter(uuid.eq(&mod_id))).execute(db)?;
+            media_usage_service::remove(UsageSource::Module, &mod_id, db)?;
+            Ok(deleted)
+        })
     }
 
     fn update(
@@ -148,9 +191,25 @@ impl Model<Self, MutModule, String, Module> for Module {
         new_module: &MutModule,
         db: &mut PooledDatabaseConnection,
     ) -> Result<usize, diesel::result::Error> {
+        use modules::dsl::{uuid, version};
+        db.transaction(|db| {
+            let updated = diesel::update(modules::table.filter(uuid.eq(&mod_id)))
+                .set((new_module, version.eq(version + 1)))
+                .execute(db)?;
+            media_usage_service::index_module(&mod_id, db)?;
+            Ok(updated)
+        })
+    }
+}
+
+impl Module {
+    /// Locks the module row for the rest of the current transaction
+    pub fn read_one_for_update(mod_id: String, db: &mut PooledDatabaseConnection) -> Result<Module, diesel::result::Error> {
         use modules::dsl::uuid;
-        diesel::update(modules::table.filter(uuid.eq(mod_id)))
-            .set(new_module)
-            .execute(db)
+        modules::table
+            .filter(uuid.eq(mod_id))
+            .select(Module::as_select())
+            .for_update()
+            .first::<Self>(db)
     }
 }
\ No newline at end of file
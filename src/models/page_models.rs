@@ -14,6 +14,7 @@ use crate::models::module_models::ModuleCategory;
 use crate::schema::module_category;
 use crate::schema::modules;
 use crate::schema::pages;
+use crate::services::media_usage_service;
 
 #[derive(Identifiable, Debug, Serialize, Deserialize, Queryable, Selectable, PartialEq, Clone, ToSchema)]
 #[diesel(table_name = pages)]
@@ -174,6 +175,39 @@ impl From<Page> for PageDTO {
     }
 }
 
+impl From<Page> for MutPage {
+    fn from(page: Page) -> MutPage {
+        MutPage {
+            uuid: Some(page.uuid),
+            page_name: page.page_name,
+            page_url: page.page_url,
+            page_title: page.page_title,
+            meta_title: page.meta_title,
+            meta_description: page.meta_description,
+            meta_keywords: page.meta_keywords,
+            canonical_url: page.canonical_url,
+            og_title: page.og_title,
+            og_description: page.og_description,
+            og_image: page.og_image,
+            twitter_card: page.twitter_card,
+            twitter_title: page.twitter_title,
+            twitter_description: page.twitter_description,
+            author: page.author,
+            article_type: page.article_type,
+            featured_image: page.featured_image,
+            word_count: page.word_count,
+            reading_time: page.reading_time,
+            current_revision: page.current_revision,
+            last_modified_by: page.last_modified_by,
+            status: page.status,
+            publish_at: page.publish_at,
+            unpublish_at: page.unpublish_at,
+            tenant_id: page.tenant_id,
+            content: page.content,
+        }
+    }
+}
+
 impl From<Page> for PageModuleDTO {
     fn from(page: Page) -> PageModuleDTO {
         PageModuleDTO {
@@ -203,10 +237,16 @@ impl From<Page> for PageModuleDTO {
 
 impl Model<Page, MutPage, String, PageDTO> for Page {
     fn create(new_page: &MutPage, db: &mut PooledDatabaseConnection) -> Result<usize, diesel::result::Error> {
-        diesel::insert_into(pages::table)
-            .values(new_page)
-            .on_conflict_do_nothing()
-            .execute(db)
+        db.transaction(|db| {
+            let inserted = diesel::insert_into(pages::table)
+                .values(new_page)
+                .on_conflict_do_nothing()
+                .execute(db)?;
+            if let Some(page_uuid) = &new_page.uuid {
+                media_usage_service::index_page(page_uuid, db)?;
+            }
+            Ok(inserted)
+        })
     }
 
     fn read_one(_id: String, db: &mut PooledDatabaseConnection) -> Result<PageDTO, diesel::result::Error> {
@@ -228,14 +268,21 @@ impl Model<Page, MutPage, String, PageDTO> for Page {
         db: &mut PooledDatabaseConnection,
     ) -> Result<usize, diesel::result::Error> {
         use pages::dsl::uuid;
-        diesel::update(pages::table.filter(uuid.eq(_id)))
-            .set(new_page)
-            .execute(db)
+        db.transaction(|db| {
+            let updated = diesel::update(pages::table.filter(uuid.eq(&_id)))
+                .set(new_page)
+                .execute(db)?;
+            media_usage_service::index_page(&_id, db)?;
+            Ok(updated)
+        })
     }
 
     fn delete(_id: String, db: &mut PooledDatabaseConnection) -> Result<usize, diesel::result::Error> {
         use pages::dsl::uuid;
-        diesel::delete(pages::table.filter(uuid.eq(_id))).execute(db)
+        db.transaction(|db| {
+            media_usage_service::remove_page(&_id, db)?;
+            diesel::delete(pages::table.filter(uuid.eq(&_id))).execute(db)
+        })
     }
 }
 
@@ -373,6 +420,16 @@ impl Page {
         Ok((filtered_page, module_dto))
     }
 
+    /// Locks the page row for the rest of the current transaction
+    pub fn read_one_for_update(_id: String, db: &mut PooledDatabaseConnection) -> Result<Self, diesel::result::Error> {
+        use pages::dsl::uuid;
+        pages::table
+            .filter(uuid.eq(_id))
+            .select(Page::as_select())
+            .for_update()
+            .first::<Page>(db)
+    }
+
     pub fn read_all_by_tenant(tenant_id: i32, db: &mut PooledDatabaseConnection) -> Result<Vec<PageDTO>, diesel::result::Error> {
         pages::table
             .filter(pages::tenant_id.eq(tenant_id))
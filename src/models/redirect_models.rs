@@ -0,0 +1,32 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::schema::redirects;
+use utoipa::ToSchema;
+
+#[derive(Queryable, Identifiable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = redirects)]
+pub struct Redirect {
+    pub id: i32,
+    pub tenant_id: i32,
+    pub source: String,
+    pub target: String,
+    /// "exact", "prefix" or "regex"
+    pub match_type: String,
+    /// 301 or 302
+    pub status_code: i32,
+    pub hit_count: i64,
+    pub last_hit_at: Option<chrono::NaiveDateTime>,
+    pub is_active: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = redirects)]
+pub struct NewRedirect {
+    pub tenant_id: i32,
+    pub source: String,
+    pub target: String,
+    pub match_type: String,
+    pub status_code: i32,
+}
@@ -21,6 +21,8 @@ pub struct PageRevision {
     pub change_summary: Option<String>,
     pub changed_by_user_id: Option<i32>,
     pub created_at: Option<chrono::NaiveDateTime>,
+    pub modules_snapshot: Option<String>,  // JSON array of modules, NULL for legacy revisions
+    pub categories_snapshot: Option<String>,  // JSON array of module categories, NULL for legacy revisions
 }
 
 #[derive(Insertable)]
@@ -38,6 +40,8 @@ pub struct NewPageRevision {
     pub full_snapshot: String,  // Store JSON as String, serialize before insert
     pub change_summary: Option<String>,
     pub changed_by_user_id: Option<i32>,
+    pub modules_snapshot: Option<String>,
+    pub categories_snapshot: Option<String>,
 }
 
 #[derive(Serialize)]
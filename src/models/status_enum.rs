@@ -26,6 +26,16 @@ impl PageStatus {
             PageStatus::Archived => "archived",
         }
     }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "draft" => Some(PageStatus::Draft),
+            "scheduled" => Some(PageStatus::Scheduled),
+            "published" => Some(PageStatus::Published),
+            "archived" => Some(PageStatus::Archived),
+            _ => None,
+        }
+    }
 }
 
 
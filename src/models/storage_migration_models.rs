@@ -0,0 +1,38 @@
+// Storage Migration Models
+// Runs of the admin tool that moves media between local storage and S3
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Queryable, Selectable, Identifiable, Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[diesel(table_name = crate::schema::storage_migrations)]
+pub struct StorageMigration {
+    pub id: i32,
+    /// "local_to_s3" or "s3_to_local"
+    pub direction: String,
+    /// "running", "paused", "completed" or "failed"
+    pub status: String,
+    /// Whether files are removed from the old storage once copied
+    pub delete_source: bool,
+    /// Media are migrated in id order; the run resumes after this one
+    pub last_media_id: i32,
+    pub total: i32,
+    pub migrated: i32,
+    pub skipped: i32,
+    pub failed: i32,
+    pub last_error: Option<String>,
+    pub started_by: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub finished_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::storage_migrations)]
+pub struct NewStorageMigration {
+    pub direction: String,
+    pub delete_source: bool,
+    pub total: i32,
+    pub started_by: Option<i32>,
+}
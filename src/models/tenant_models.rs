@@ -4,6 +4,7 @@ use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::helpers::tenant_helper::invalidate_host_cache;
 use crate::schema::{tenants, tenant_members};
 
 #[derive(Queryable, Selectable, Identifiable, Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -84,10 +85,13 @@ impl Model<Tenant, NewTenant, i32> for Tenant {
                 tenants::updated_at.eq(chrono::Utc::now().naive_utc()),
             ))
             .execute(db)
+            .inspect(|_| invalidate_host_cache())
     }
 
     fn delete(id: i32, db: &mut PooledDatabaseConnection) -> Result<usize, diesel::result::Error> {
-        diesel::delete(tenants::table.find(id)).execute(db)
+        diesel::delete(tenants::table.find(id))
+            .execute(db)
+            .inspect(|_| invalidate_host_cache())
     }
 }
 
@@ -17,6 +17,8 @@ pub struct Theme {
     pub tenant_id: Option<i32>,
     pub created_at: Option<chrono::NaiveDateTime>,
     pub updated_at: Option<chrono::NaiveDateTime>,
+    /// Parsed `theme.json` of the unpacked package
+    pub manifest: Option<serde_json::Value>,
 }
 
 #[derive(Insertable, Deserialize, ToSchema)]
@@ -30,4 +32,5 @@ pub struct NewTheme {
     pub is_active: Option<bool>,
     pub is_default: Option<bool>,
     pub tenant_id: Option<i32>,
+    pub manifest: Option<serde_json::Value>,
 }
@@ -17,6 +17,8 @@ pub struct User {
     pub token: Option<String>,
     pub two_factor_secret: Option<String>,
     pub two_factor_enabled: bool,
+    /// Contact address; the username need not be one
+    pub email: Option<String>,
 }
 
 #[derive(Debug, AsChangeset, Insertable, Clone, Serialize, Deserialize, ToSchema)]
@@ -28,6 +30,7 @@ pub struct MutUser {
     pub token: Option<String>,
     pub two_factor_secret: Option<String>,
     pub two_factor_enabled: Option<bool>,
+    pub email: Option<String>,
 }
 
 impl Model<User, MutUser, String> for User {
@@ -85,6 +85,9 @@ diesel::table! {
         created_by -> Nullable<Int4>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        config -> Jsonb,
+        #[max_length = 20]
+        purpose -> Varchar,
     }
 }
 
@@ -194,6 +197,23 @@ diesel::table! {
         metadata -> Nullable<Json>,
         created_at -> Nullable<Timestamp>,
         completed_at -> Nullable<Timestamp>,
+        tenant_id -> Nullable<Int4>,
+        #[max_length = 64]
+        checksum -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    backup_policies (id) {
+        id -> Int4,
+        tenant_id -> Int4,
+        interval_hours -> Int4,
+        keep_last -> Int4,
+        keep_days -> Nullable<Int4>,
+        is_active -> Bool,
+        last_run_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -268,6 +288,67 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    cart_items (id) {
+        id -> Int4,
+        cart_id -> Int4,
+        product_id -> Int8,
+        variant_id -> Nullable<Int4>,
+        quantity -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    carts (id) {
+        id -> Int4,
+        #[max_length = 36]
+        uuid -> Varchar,
+        tenant_id -> Nullable<Int4>,
+        user_id -> Nullable<Int4>,
+        #[max_length = 64]
+        guest_token -> Nullable<Varchar>,
+        #[max_length = 20]
+        status -> Varchar,
+        #[max_length = 3]
+        currency -> Varchar,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        last_activity_at -> Timestamp,
+        abandoned_at -> Nullable<Timestamp>,
+        recovery_sent_at -> Nullable<Timestamp>,
+        recovered_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    checkout_sessions (id) {
+        id -> Int4,
+        #[max_length = 36]
+        uuid -> Varchar,
+        cart_id -> Int4,
+        #[max_length = 20]
+        status -> Varchar,
+        #[max_length = 3]
+        currency -> Varchar,
+        line_items -> Jsonb,
+        subtotal_cents -> Int8,
+        total_cents -> Int8,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        shipping_address -> Nullable<Jsonb>,
+        billing_address -> Nullable<Jsonb>,
+        order_id -> Nullable<Int8>,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::Vector;
@@ -475,6 +556,21 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    inventory_reservations (id) {
+        id -> Int4,
+        order_id -> Int8,
+        product_id -> Int8,
+        variant_id -> Nullable<Int4>,
+        quantity -> Int4,
+        #[max_length = 20]
+        status -> Varchar,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     inventory_audit_log (id) {
         id -> Int4,
@@ -507,6 +603,8 @@ diesel::table! {
         enabled -> Nullable<Bool>,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        #[max_length = 64]
+        search_config -> Varchar,
     }
 }
 
@@ -640,6 +738,52 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         tenant_id -> Nullable<Int4>,
+        metadata -> Nullable<Jsonb>,
+        focal_x -> Nullable<Float4>,
+        focal_y -> Nullable<Float4>,
+        folder_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    media_folders (id) {
+        id -> Int4,
+        tenant_id -> Int4,
+        parent_id -> Nullable<Int4>,
+        #[max_length = 255]
+        name -> Varchar,
+        path -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    media_usages (id) {
+        id -> Int4,
+        media_id -> Int4,
+        #[max_length = 20]
+        source_type -> Varchar,
+        #[max_length = 255]
+        source_uuid -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    media_variants (id) {
+        id -> Int4,
+        media_id -> Int4,
+        #[max_length = 50]
+        variant_name -> Varchar,
+        #[max_length = 500]
+        file_path -> Varchar,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        file_size -> Nullable<Int8>,
+        created_at -> Nullable<Timestamp>,
+        #[max_length = 10]
+        format -> Varchar,
     }
 }
 
@@ -684,6 +828,7 @@ diesel::table! {
         field_config -> Nullable<Text>,
         validation_rules -> Nullable<Text>,
         tenant_id -> Nullable<Int4>,
+        version -> Int4,
     }
 }
 
@@ -709,6 +854,57 @@ diesel::table! {
         product_id -> Int8,
         quantity -> Int4,
         price_cents -> Int8,
+        variant_id -> Nullable<Int4>,
+        #[max_length = 255]
+        product_name -> Varchar,
+        #[max_length = 100]
+        sku -> Nullable<Varchar>,
+        #[max_length = 255]
+        variant_name -> Nullable<Varchar>,
+        variant_attributes -> Nullable<Jsonb>,
+        tax_cents -> Int8,
+        discount_cents -> Int8,
+    }
+}
+
+diesel::table! {
+    order_refunds (id) {
+        id -> Int4,
+        #[max_length = 36]
+        uuid -> Varchar,
+        order_id -> Int8,
+        #[max_length = 255]
+        idempotency_key -> Varchar,
+        #[max_length = 50]
+        provider -> Varchar,
+        #[max_length = 255]
+        provider_refund_id -> Nullable<Varchar>,
+        amount_cents -> Int8,
+        #[max_length = 3]
+        currency -> Varchar,
+        #[max_length = 20]
+        status -> Varchar,
+        reason -> Nullable<Text>,
+        failure_reason -> Nullable<Text>,
+        created_by -> Nullable<Int4>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    order_status_history (id) {
+        id -> Int4,
+        order_id -> Int8,
+        #[max_length = 50]
+        from_status -> Nullable<Varchar>,
+        #[max_length = 50]
+        to_status -> Varchar,
+        #[max_length = 100]
+        actor -> Varchar,
+        actor_user_id -> Nullable<Int4>,
+        reason -> Nullable<Text>,
+        created_at -> Timestamp,
     }
 }
 
@@ -759,6 +955,8 @@ diesel::table! {
         change_summary -> Nullable<Varchar>,
         changed_by_user_id -> Nullable<Int4>,
         created_at -> Nullable<Timestamp>,
+        modules_snapshot -> Nullable<Text>,
+        categories_snapshot -> Nullable<Text>,
     }
 }
 
@@ -926,6 +1124,25 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    redirects (id) {
+        id -> Int4,
+        tenant_id -> Int4,
+        #[max_length = 1000]
+        source -> Varchar,
+        #[max_length = 1000]
+        target -> Varchar,
+        #[max_length = 10]
+        match_type -> Varchar,
+        status_code -> Int4,
+        hit_count -> Int8,
+        last_hit_at -> Nullable<Timestamp>,
+        is_active -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     refresh_tokens (id) {
         id -> Int4,
@@ -978,6 +1195,69 @@ diesel::table! {
         results_count -> Nullable<Int4>,
         top_result_id -> Nullable<Int8>,
         created_at -> Nullable<Timestamp>,
+        tenant_id -> Nullable<Int4>,
+        results -> Nullable<Jsonb>,
+        weights -> Nullable<Jsonb>,
+    }
+}
+
+diesel::table! {
+    search_clicks (id) {
+        id -> Int8,
+        search_id -> Int8,
+        #[max_length = 20]
+        resource_type -> Varchar,
+        #[max_length = 255]
+        resource_id -> Varchar,
+        position -> Nullable<Int4>,
+        user_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Tsvector;
+
+    search_documents (id) {
+        id -> Int8,
+        #[max_length = 20]
+        resource_type -> Varchar,
+        #[max_length = 255]
+        resource_id -> Varchar,
+        language_id -> Nullable<Int4>,
+        language_key -> Int4,
+        tenant_id -> Nullable<Int4>,
+        #[max_length = 255]
+        page_uuid -> Nullable<Varchar>,
+        title -> Text,
+        body -> Text,
+        url -> Nullable<Text>,
+        #[max_length = 64]
+        search_config -> Varchar,
+        document -> Tsvector,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    storage_migrations (id) {
+        id -> Int4,
+        #[max_length = 20]
+        direction -> Varchar,
+        #[max_length = 20]
+        status -> Varchar,
+        delete_source -> Bool,
+        last_media_id -> Int4,
+        total -> Int4,
+        migrated -> Int4,
+        skipped -> Int4,
+        failed -> Int4,
+        last_error -> Nullable<Text>,
+        started_by -> Nullable<Int4>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
     }
 }
 
@@ -1070,6 +1350,14 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    tenant_themes (tenant_id) {
+        tenant_id -> Int4,
+        theme_id -> Int4,
+        activated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     tenant_webhooks (id) {
         id -> Uuid,
@@ -1122,6 +1410,7 @@ diesel::table! {
         tenant_id -> Nullable<Int4>,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        manifest -> Nullable<Jsonb>,
     }
 }
 
@@ -1166,6 +1455,8 @@ diesel::table! {
         #[max_length = 255]
         two_factor_secret -> Nullable<Varchar>,
         two_factor_enabled -> Bool,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
     }
 }
 
@@ -1216,10 +1507,19 @@ diesel::joinable!(ai_provider_configs -> users (created_by));
 diesel::joinable!(ai_usage_log -> users (user_id));
 diesel::joinable!(audit_logs -> tenants (tenant_id));
 diesel::joinable!(audit_logs -> users (user_id));
+diesel::joinable!(backup_policies -> tenants (tenant_id));
+diesel::joinable!(backups -> tenants (tenant_id));
 diesel::joinable!(billing_invoices -> billing_subscriptions (subscription_id));
 diesel::joinable!(billing_payments -> billing_invoices (invoice_id));
 diesel::joinable!(billing_subscriptions -> billing_plans (plan_id));
 diesel::joinable!(billing_subscriptions -> tenants (tenant_id));
+diesel::joinable!(cart_items -> carts (cart_id));
+diesel::joinable!(cart_items -> product_variants (variant_id));
+diesel::joinable!(cart_items -> products (product_id));
+diesel::joinable!(carts -> tenants (tenant_id));
+diesel::joinable!(carts -> users (user_id));
+diesel::joinable!(checkout_sessions -> carts (cart_id));
+diesel::joinable!(checkout_sessions -> orders (order_id));
 diesel::joinable!(crm_campaigns -> crm_segments (segment_id));
 diesel::joinable!(crm_campaigns -> tenants (tenant_id));
 diesel::joinable!(crm_campaigns -> users (created_by));
@@ -1243,6 +1543,9 @@ diesel::joinable!(inventory_audit_log -> orders (order_id));
 diesel::joinable!(inventory_audit_log -> product_variants (variant_id));
 diesel::joinable!(inventory_audit_log -> products (product_id));
 diesel::joinable!(inventory_audit_log -> users (user_id));
+diesel::joinable!(inventory_reservations -> orders (order_id));
+diesel::joinable!(inventory_reservations -> product_variants (variant_id));
+diesel::joinable!(inventory_reservations -> products (product_id));
 diesel::joinable!(marketplace_plugins -> users (developer_id));
 diesel::joinable!(mcp_custom_tools -> tenants (tenant_id));
 diesel::joinable!(mcp_custom_tools -> users (created_by));
@@ -1251,8 +1554,12 @@ diesel::joinable!(mcp_tool_executions -> tenants (tenant_id));
 diesel::joinable!(mcp_tool_executions -> users (user_id));
 diesel::joinable!(mcp_tool_rate_limits -> mcp_custom_tools (tool_id));
 diesel::joinable!(mcp_tool_rate_limits -> tenants (tenant_id));
+diesel::joinable!(media -> media_folders (folder_id));
 diesel::joinable!(media -> tenants (tenant_id));
 diesel::joinable!(media -> users (uploaded_by));
+diesel::joinable!(media_folders -> tenants (tenant_id));
+diesel::joinable!(media_usages -> media (media_id));
+diesel::joinable!(media_variants -> media (media_id));
 diesel::joinable!(module_category -> pages (page_uuid));
 diesel::joinable!(module_translations -> languages (language_id));
 diesel::joinable!(module_translations -> modules (module_id));
@@ -1260,6 +1567,11 @@ diesel::joinable!(modules -> module_category (category_uuid));
 diesel::joinable!(modules -> pages (page_uuid));
 diesel::joinable!(modules -> tenants (tenant_id));
 diesel::joinable!(order_items -> orders (order_id));
+diesel::joinable!(order_items -> product_variants (variant_id));
+diesel::joinable!(order_refunds -> orders (order_id));
+diesel::joinable!(order_refunds -> users (created_by));
+diesel::joinable!(order_status_history -> orders (order_id));
+diesel::joinable!(order_status_history -> users (actor_user_id));
 diesel::joinable!(order_items -> products (product_id));
 diesel::joinable!(orders -> tenants (tenant_id));
 diesel::joinable!(page_translations -> languages (language_id));
@@ -1268,8 +1580,14 @@ diesel::joinable!(pages -> tenants (tenant_id));
 diesel::joinable!(pending_verifications -> tenants (tenant_id));
 diesel::joinable!(product_variants -> products (product_id));
 diesel::joinable!(products -> tenants (tenant_id));
+diesel::joinable!(redirects -> tenants (tenant_id));
 diesel::joinable!(refresh_tokens -> users (user_id));
+diesel::joinable!(search_clicks -> search_history (search_id));
+diesel::joinable!(search_clicks -> users (user_id));
+diesel::joinable!(search_documents -> languages (language_id));
+diesel::joinable!(search_history -> tenants (tenant_id));
 diesel::joinable!(search_history -> users (user_id));
+diesel::joinable!(storage_migrations -> users (started_by));
 diesel::joinable!(survey_questions -> surveys (survey_id));
 diesel::joinable!(survey_responses -> surveys (survey_id));
 diesel::joinable!(survey_responses -> users (respondent_id));
@@ -1280,6 +1598,8 @@ diesel::joinable!(tenant_members -> users (user_id));
 diesel::joinable!(tenant_plugins -> marketplace_plugins (plugin_id));
 diesel::joinable!(tenant_plugins -> tenants (tenant_id));
 diesel::joinable!(tenant_sso_configs -> tenants (tenant_id));
+diesel::joinable!(tenant_themes -> tenants (tenant_id));
+diesel::joinable!(tenant_themes -> themes (theme_id));
 diesel::joinable!(tenant_webhooks -> tenants (tenant_id));
 diesel::joinable!(themes -> tenants (tenant_id));
 diesel::joinable!(user_oauth_connections -> oauth_providers (provider_id));
@@ -1299,11 +1619,15 @@ diesel::allow_tables_to_appear_in_same_query!(
     analytics_events,
     analytics_summary,
     audit_logs,
+    backup_policies,
     backups,
     billing_invoices,
     billing_payments,
     billing_plans,
     billing_subscriptions,
+    cart_items,
+    carts,
+    checkout_sessions,
     content_embeddings,
     content_relationships,
     crm_campaigns,
@@ -1315,6 +1639,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     crm_tasks,
     email_templates,
     inventory_audit_log,
+    inventory_reservations,
     languages,
     marketplace_plugins,
     marketplace_submissions,
@@ -1322,11 +1647,16 @@ diesel::allow_tables_to_appear_in_same_query!(
     mcp_tool_executions,
     mcp_tool_rate_limits,
     media,
+    media_folders,
+    media_usages,
+    media_variants,
     module_category,
     module_translations,
     modules,
     oauth_providers,
     order_items,
+    order_refunds,
+    order_status_history,
     orders,
     page_revisions,
     page_translations,
@@ -1335,16 +1665,21 @@ diesel::allow_tables_to_appear_in_same_query!(
     pending_verifications,
     product_variants,
     products,
+    redirects,
     refresh_tokens,
     robots_rules,
     roles,
+    search_clicks,
+    search_documents,
     search_history,
+    storage_migrations,
     survey_questions,
     survey_responses,
     surveys,
     tenant_members,
     tenant_plugins,
     tenant_sso_configs,
+    tenant_themes,
     tenant_webhooks,
     tenants,
     themes,
@@ -17,6 +17,10 @@ pub struct CreateProviderRequest {
     pub model_name: Option<String>,
     pub daily_token_limit: Option<i32>,
     pub monthly_budget_cents: Option<i32>,
+    /// Provider-specific settings, e.g. {"endpoint": "http://localhost:11434/v1"}
+    pub config: Option<serde_json::Value>,
+    /// "completion" (default) or "embedding"
+    pub purpose: Option<String>,
 }
 
 /// Request to update AI provider
@@ -27,6 +31,8 @@ pub struct UpdateProviderRequest {
     pub is_active: Option<bool>,
     pub daily_token_limit: Option<i32>,
     pub monthly_budget_cents: Option<i32>,
+    pub config: Option<serde_json::Value>,
+    pub purpose: Option<String>,
 }
 
 /// Test provider connection request
@@ -106,6 +112,8 @@ pub async fn create_provider(
         daily_token_limit: payload.daily_token_limit,
         monthly_budget_cents: payload.monthly_budget_cents,
         created_by: user_id,
+        config: payload.config.clone(),
+        purpose: payload.purpose.clone(),
     };
     
     let provider = web::block(move || -> Result<AIProviderConfigPublic, diesel::result::Error> {
@@ -134,13 +142,34 @@ pub async fn create_provider(
 pub async fn update_provider(
     pool: web::Data<DatabasePool>,
     provider_id: web::Path<i64>,
-    _payload: web::Json<UpdateProviderRequest>,
+    payload: web::Json<UpdateProviderRequest>,
 ) -> Result<HttpResponse, CustomHttpError> {
     let id = provider_id.into_inner();
     
     let provider = web::block(move || -> Result<AIProviderConfigPublic, diesel::result::Error> {
         let mut conn = pool.get().map_err(|_| diesel::result::Error::NotFound)?;
         
+        if let Some(ref model) = payload.model_name {
+            diesel::update(ai_provider_configs::table.find(id as i32))
+                .set(ai_provider_configs::model_name.eq(model))
+                .execute(&mut conn)?;
+        }
+        if let Some(active) = payload.is_active {
+            diesel::update(ai_provider_configs::table.find(id as i32))
+                .set(ai_provider_configs::is_active.eq(Some(active)))
+                .execute(&mut conn)?;
+        }
+        if let Some(ref config) = payload.config {
+            diesel::update(ai_provider_configs::table.find(id as i32))
+                .set(ai_provider_configs::config.eq(config))
+                .execute(&mut conn)?;
+        }
+        if let Some(ref purpose) = payload.purpose {
+            diesel::update(ai_provider_configs::table.find(id as i32))
+                .set(ai_provider_configs::purpose.eq(purpose))
+                .execute(&mut conn)?;
+        }
+        
         // Note: name field doesn't exist in current schema
         // API key update also disabled since encrypted field update needs full implementation
         
@@ -0,0 +1,300 @@
+use async_trait::async_trait;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::AIProviderError;
+use crate::models::ai_provider_models::AIProviderConfig;
+
+/// Embedding provider trait
+/// Backends turn text into vectors for semantic search (`content_embeddings`)
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Embed a batch of texts, one vector per input in the same order
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AIProviderError>;
+
+    /// Get provider name
+    fn name(&self) -> &str;
+
+    /// Get model name; stored with each embedding so vectors from
+    /// different models are never compared
+    fn model(&self) -> &str;
+}
+
+/// Embeds a single text
+pub async fn embed_one(provider: &dyn EmbeddingProvider, text: &str) -> Result<Vec<f32>, AIProviderError> {
+    provider
+        .embed(&[text.to_string()])
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| AIProviderError::ApiError("No embedding in response".to_string()))
+}
+
+/// Provider-specific settings from `ai_provider_configs.config`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingConfig {
+    /// Base URL of an OpenAI-compatible API, e.g. http://localhost:11434/v1
+    pub endpoint: Option<String>,
+    /// Vector size for the hashing backend
+    pub dimensions: Option<usize>,
+}
+
+/// Builds the backend selected by an `ai_provider_configs` row.
+///
+/// * `openai` - OpenAI's hosted API
+/// * `ollama`, `llamacpp`, `openai_compatible`, `custom` - any server speaking
+///   the OpenAI `/embeddings` API, at `config.endpoint`
+/// * `hashing` / `local` - deterministic feature hashing, no network access
+pub fn embedding_provider_from_config(
+    config: &AIProviderConfig,
+    api_key: Option<String>,
+) -> Result<Box<dyn EmbeddingProvider>, AIProviderError> {
+    let settings: EmbeddingConfig = serde_json::from_value(config.config.clone())
+        .map_err(|e| AIProviderError::ParsingError(format!("Invalid embedding config: {}", e)))?;
+    let api_key = api_key.filter(|key| !key.is_empty());
+
+    let (default_endpoint, default_model) = match config.provider_type.as_str() {
+        "hashing" | "local" => {
+            let dimensions = settings.dimensions.unwrap_or(HashingEmbeddings::DEFAULT_DIMENSIONS);
+            return Ok(Box::new(HashingEmbeddings::new(dimensions)));
+        }
+        "openai" => ("https://api.openai.com/v1", "text-embedding-ada-002"),
+        "ollama" => ("http://localhost:11434/v1", "nomic-embed-text"),
+        "llamacpp" | "openai_compatible" | "custom" => ("http://localhost:8080/v1", "default"),
+        other => {
+            return Err(AIProviderError::UnsupportedOperation(format!(
+                "Provider type '{}' does not support embeddings",
+                other
+            )))
+        }
+    };
+
+    let endpoint = settings.endpoint.unwrap_or_else(|| default_endpoint.to_string());
+    let model = config.model_name.clone().unwrap_or_else(|| default_model.to_string());
+    Ok(Box::new(OpenAICompatibleEmbeddings::new(endpoint, api_key, model)))
+}
+
+/// Any server implementing the OpenAI embeddings API (OpenAI, Ollama, llama.cpp, vLLM, ...)
+pub struct OpenAICompatibleEmbeddings {
+    client: reqwest::Client,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    input: &'a [String],
+    model: &'a str,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: Option<usize>,
+}
+
+impl OpenAICompatibleEmbeddings {
+    pub fn new(endpoint: String, api_key: Option<String>, model: String) -> Self {
+        let endpoint = endpoint.trim_end_matches('/').to_string();
+        info!("✅ Embedding provider initialized: {} ({})", endpoint, model);
+
+        Self {
+            client: reqwest::Client::new(),
+            endpoint,
+            api_key,
+            model,
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAICompatibleEmbeddings {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AIProviderError> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut request = self
+            .client
+            .post(format!("{}/embeddings", self.endpoint))
+            .json(&EmbeddingRequest { input: texts, model: &self.model });
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| AIProviderError::NetworkError(e.to_string()))?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(AIProviderError::InvalidApiKey);
+        }
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(AIProviderError::RateLimitExceeded(self.endpoint.clone()));
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AIProviderError::ApiError(format!("Embedding request failed ({}): {}", status, body)));
+        }
+
+        let mut parsed: EmbeddingResponse = response
+            .json()
+            .await
+            .map_err(|e| AIProviderError::ParsingError(e.to_string()))?;
+        if parsed.data.len() != texts.len() {
+            return Err(AIProviderError::ApiError(format!(
+                "Expected {} embeddings, got {}",
+                texts.len(),
+                parsed.data.len()
+            )));
+        }
+
+        // Servers may return the batch out of order
+        parsed.data.sort_by_key(|d| d.index.unwrap_or(0));
+        Ok(parsed.data.into_iter().map(|d| d.embedding).collect())
+    }
+
+    fn name(&self) -> &str {
+        "openai_compatible"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// Deterministic local embeddings: hashed unigrams and bigrams weighted by
+/// sublinear TF, L2-normalised.
+///
+/// No semantics beyond shared vocabulary, but stable across runs and machines,
+/// so it works offline and in tests.
+pub struct HashingEmbeddings {
+    dimensions: usize,
+    model: String,
+}
+
+impl HashingEmbeddings {
+    /// Matches the `content_embeddings.embedding` column
+    pub const DEFAULT_DIMENSIONS: usize = 1536;
+
+    pub fn new(dimensions: usize) -> Self {
+        let dimensions = dimensions.max(1);
+        Self {
+            dimensions,
+            // Name kept so vectors already stored under it stay comparable
+            model: format!("hashing-tfidf-{}", dimensions),
+        }
+    }
+
+    fn tokens(text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|t| !t.is_empty())
+            .map(|t| t.to_lowercase())
+            .collect()
+    }
+
+    /// FNV-1a: stable across platforms and Rust versions, unlike `DefaultHasher`
+    fn hash(feature: &str) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in feature.as_bytes() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    /// (bucket, signed count) per feature occurrence
+    fn features(&self, text: &str) -> Vec<(usize, f32)> {
+        let tokens = Self::tokens(text);
+        let bigrams = tokens.windows(2).map(|pair| format!("{} {}", pair[0], pair[1]));
+
+        tokens
+            .iter()
+            .cloned()
+            .chain(bigrams)
+            .map(|feature| {
+                let hash = Self::hash(&feature);
+                // The top bit picks the sign so collisions tend to cancel out
+                let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+                ((hash % self.dimensions as u64) as usize, sign)
+            })
+            .collect()
+    }
+
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut counts: HashMap<usize, f32> = HashMap::new();
+        for (bucket, sign) in self.features(text) {
+            *counts.entry(bucket).or_insert(0.0) += sign;
+        }
+
+        let mut vector = vec![0.0f32; self.dimensions];
+        for (bucket, count) in counts {
+            if count == 0.0 {
+                continue;
+            }
+            let tf = 1.0 + count.abs().ln();
+            vector[bucket] = count.signum() * tf;
+        }
+
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashingEmbeddings {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AIProviderError> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+
+    fn name(&self) -> &str {
+        "hashing"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_hashing_is_deterministic_and_normalised() {
+        let provider = HashingEmbeddings::new(256);
+        let a = provider.embed_text("Running shoes for trail runners");
+        let b = provider.embed_text("Running shoes for trail runners");
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 256);
+        assert!((cosine(&a, &a) - 1.0).abs() < 1e-5);
+        assert!(provider.embed_text("").iter().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn test_hashing_similarity_follows_shared_vocabulary() {
+        let provider = HashingEmbeddings::new(HashingEmbeddings::DEFAULT_DIMENSIONS);
+        let query = provider.embed_text("trail running shoes");
+        let related = provider.embed_text("The best shoes for trail running");
+        let unrelated = provider.embed_text("Chocolate cake recipe with cherries");
+        assert!(cosine(&query, &related) > cosine(&query, &unrelated));
+    }
+}
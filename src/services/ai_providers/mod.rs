@@ -133,3 +133,4 @@ impl ProviderRegistry {
 pub mod openai;
 pub mod anthropic;
 pub mod google;
+pub mod embeddings;
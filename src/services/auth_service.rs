@@ -57,6 +57,13 @@ pub struct UserContext {
     pub role: String,
 }
 
+impl UserContext {
+    /// Platform-wide admin (global JWT role), as opposed to a tenant role
+    pub fn is_platform_admin(&self) -> bool {
+        self.role == "admin" || self.role == "administrator"
+    }
+}
+
 impl Claims {
     pub fn to_user_context(&self) -> Result<UserContext, String> {
         let user_id = self.sub.parse::<i32>()
@@ -1,72 +1,180 @@
-// Cache Service V2 - Redis-based caching with connection pooling (Single + Cluster)
+// Cache Service V2 - Redis-based caching with connection pooling (Single + Cluster),
+// falling back to an in-process LRU when Redis is unavailable
 
 use deadpool_redis::{Config, Runtime, Pool};
 use redis::{cluster::ClusterClient, AsyncCommands};
 use serde::{Serialize, de::DeserializeOwned};
 use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::services::memory_cache::MemoryCache;
+use crate::services::template_service::TemplateEngine;
+
+/// Entries kept by the in-process backend
+pub const DEFAULT_MEMORY_CAPACITY: usize = 10_000;
+
+/// How long startup waits for Redis before falling back to memory
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Redis sets indexing cache keys by tag live under this prefix
+const TAG_PREFIX: &str = "tag:";
+
+/// The tags a cached key was stored with live under this prefix, so an L1
+/// refill from Redis can be purged by tag like the original entry
+const KEY_TAGS_PREFIX: &str = "tags-of:";
+
+/// Tag carried by every rendered page, purged when templates change globally
+pub const ALL_PAGES_TAG: &str = "pages";
+
+pub fn page_tag(page_uuid: &str) -> String {
+    format!("page:{}", page_uuid)
+}
+
+pub fn module_tag(module_uuid: &str) -> String {
+    format!("module:{}", module_uuid)
+}
+
+pub fn tenant_tag(tenant_id: i32) -> String {
+    format!("tenant:{}", tenant_id)
+}
+
+pub fn template_tag(template_name: &str) -> String {
+    // A page_name with an engine suffix ("about.liquid") uses the same tag as "about"
+    let (name, _) = TemplateEngine::split_name(template_name);
+    format!("template:{}", name)
+}
 
 #[derive(Clone)]
 pub enum RedisBackend {
     Single(Pool),
     Cluster(ClusterClient),
+    /// In-process LRU, for dev/CI or when Redis is down. Not shared between instances.
+    Memory(Arc<MemoryCache>),
+}
+
+/// Small local tier in front of Redis for hot keys
+#[derive(Clone)]
+struct LocalTier {
+    cache: Arc<MemoryCache>,
+    /// Caps how long another instance's invalidation can go unseen here
+    ttl: usize,
 }
 
 #[derive(Clone)]
 pub struct CacheServiceV2 {
     backend: RedisBackend,
     default_ttl: usize,
+    l1: Option<LocalTier>,
 }
 
 impl CacheServiceV2 {
     /// Create new cache service.
-    /// Prioritizes Cluster configuration if `cluster_nodes` is provided.
-    pub async fn new(redis_url: &str, cluster_nodes: Option<String>, default_ttl: usize) -> Result<Self, Box<dyn Error>> {
-        if let Some(nodes_str) = cluster_nodes {
-            if !nodes_str.is_empty() {
-                log::info!("Initializing Redis Cluster...");
-                let nodes: Vec<&str> = nodes_str.split(',').collect();
-                let client = ClusterClient::new(nodes)?;
-                
-                // Test connection
-                // ClusterClient manages connection internally
-                
-                return Ok(Self {
-                    backend: RedisBackend::Cluster(client),
-                    default_ttl
-                });
+    /// Prioritizes Cluster configuration if `cluster_nodes` is provided. Without a
+    /// `redis_url`, or when Redis does not answer, falls back to the in-process backend.
+    pub async fn new(redis_url: Option<&str>, cluster_nodes: Option<String>, default_ttl: usize) -> Self {
+        if let Some(nodes_str) = cluster_nodes.filter(|nodes| !nodes.is_empty()) {
+            log::info!("Initializing Redis Cluster...");
+            match tokio::time::timeout(CONNECT_TIMEOUT, Self::connect_cluster(&nodes_str)).await {
+                Ok(Ok(client)) => return Self::with_backend(RedisBackend::Cluster(client), default_ttl),
+                Ok(Err(e)) => log::warn!("⚠️ Redis Cluster unavailable: {}", e),
+                Err(_) => log::warn!("⚠️ Redis Cluster did not answer within {:?}", CONNECT_TIMEOUT),
+            }
+        } else if let Some(redis_url) = redis_url.filter(|url| !url.is_empty()) {
+            log::info!("Initializing Single Node Redis...");
+            match tokio::time::timeout(CONNECT_TIMEOUT, Self::connect_single(redis_url)).await {
+                Ok(Ok(pool)) => return Self::with_backend(RedisBackend::Single(pool), default_ttl),
+                Ok(Err(e)) => log::warn!("⚠️ Redis unavailable: {}", e),
+                Err(_) => log::warn!("⚠️ Redis did not answer within {:?}", CONNECT_TIMEOUT),
             }
+        } else {
+            log::info!("REDIS_URL not set");
         }
-    
-        // Fallback to Single Node
-        log::info!("Initializing Single Node Redis...");
+
+        log::warn!("Using in-process cache ({} entries); cached pages are not shared between instances", DEFAULT_MEMORY_CAPACITY);
+        Self::in_memory(DEFAULT_MEMORY_CAPACITY, default_ttl)
+    }
+
+    /// Cache service backed only by an in-process LRU of `capacity` entries
+    pub fn in_memory(capacity: usize, default_ttl: usize) -> Self {
+        Self::with_backend(RedisBackend::Memory(Arc::new(MemoryCache::new(capacity))), default_ttl)
+    }
+
+    fn with_backend(backend: RedisBackend, default_ttl: usize) -> Self {
+        Self { backend, default_ttl, l1: None }
+    }
+
+    /// Adds a local L1 tier of `capacity` entries in front of Redis.
+    /// Entries live there at most `ttl` seconds. No-op on the in-process backend.
+    pub fn with_l1(mut self, capacity: usize, ttl: usize) -> Self {
+        if capacity > 0 && ttl > 0 && !self.is_in_memory() {
+            log::info!("Cache L1 enabled: {} entries, {}s TTL", capacity, ttl);
+            self.l1 = Some(LocalTier { cache: Arc::new(MemoryCache::new(capacity)), ttl });
+        }
+        self
+    }
+
+    pub fn is_in_memory(&self) -> bool {
+        matches!(self.backend, RedisBackend::Memory(_))
+    }
+
+    async fn connect_cluster(nodes_str: &str) -> Result<ClusterClient, Box<dyn Error>> {
+        let nodes: Vec<&str> = nodes_str.split(',').collect();
+        let client = ClusterClient::new(nodes)?;
+
+        // Test connection
+        let mut conn = client.get_async_connection().await?;
+        let _: String = redis::cmd("PING").query_async(&mut conn).await?;
+        Ok(client)
+    }
+
+    async fn connect_single(redis_url: &str) -> Result<Pool, Box<dyn Error>> {
         let cfg = Config::from_url(redis_url);
         let pool = cfg.create_pool(Some(Runtime::Tokio1))?;
-        
+
         // Test connection
         let mut conn = pool.get().await?;
         let _: String = redis::cmd("PING").query_async(&mut conn).await?;
-        
-        Ok(Self {
-            backend: RedisBackend::Single(pool),
-            default_ttl,
-        })
+        Ok(pool)
     }
-    
+
     /// Get cached value
     pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
-        let value: String = match &self.backend {
+        if let Some(value) = self.l1.as_ref().and_then(|l1| l1.cache.get(key)) {
+            return serde_json::from_str(&value).ok();
+        }
+
+        let (value, tags): (String, Option<Vec<String>>) = match &self.backend {
             RedisBackend::Single(pool) => {
                 let mut conn = pool.get().await.ok()?;
-                conn.get(key).await.ok()?
+                let value = conn.get(key).await.ok()?;
+                (value, self.l1_refill_tags(&mut conn, key).await)
             },
             RedisBackend::Cluster(client) => {
                 let mut conn = client.get_async_connection().await.ok()?;
-                conn.get(key).await.ok()?
-            }
+                let value = conn.get(key).await.ok()?;
+                (value, self.l1_refill_tags(&mut conn, key).await)
+            },
+            RedisBackend::Memory(cache) => (cache.get(key)?, None),
         };
 
+        // Without its tags the L1 copy would survive a purge_tags, so it is only refilled when they are known
+        if let (Some(l1), Some(tags)) = (&self.l1, tags) {
+            l1.cache.set_with_tags(key, value.clone(), Duration::from_secs(l1.ttl as u64), &tags);
+        }
         serde_json::from_str(&value).ok()
     }
+
+    /// Tags `key` was stored with, when there is an L1 tier to refill.
+    /// `None` if they could not be read.
+    async fn l1_refill_tags<C: AsyncCommands>(&self, conn: &mut C, key: &str) -> Option<Vec<String>> {
+        self.l1.as_ref()?;
+        let tags: Option<String> = conn.get(format!("{}{}", KEY_TAGS_PREFIX, key)).await.ok()?;
+        match tags {
+            Some(tags) => serde_json::from_str(&tags).ok(),
+            None => Some(Vec::new()),
+        }
+    }
     
     /// Set cached value with optional TTL
     pub async fn set<T: Serialize>(
@@ -74,37 +182,129 @@ impl CacheServiceV2 {
         key: &str,
         value: &T,
         ttl: Option<usize>
+    ) -> Result<(), Box<dyn Error>> {
+        self.set_with_tags(key, value, ttl, &[]).await
+    }
+    
+    /// Delete single cache entry
+    pub async fn delete(&self, key: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(l1) = &self.l1 {
+            l1.cache.delete(key);
+        }
+        match &self.backend {
+             RedisBackend::Single(pool) => {
+                let mut conn = pool.get().await?;
+                conn.del::<_, ()>(key).await?;
+             },
+             RedisBackend::Cluster(client) => {
+                let mut conn = client.get_async_connection().await?;
+                conn.del::<_, ()>(key).await?;
+             },
+             RedisBackend::Memory(cache) => {
+                cache.delete(key);
+             }
+        }
+        Ok(())
+    }
+
+    /// Set cached value and index it under `tags` for `purge_tags`
+    pub async fn set_with_tags<T: Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Option<usize>,
+        tags: &[String]
     ) -> Result<(), Box<dyn Error>> {
         let serialized = serde_json::to_string(value)?;
         let ttl = ttl.unwrap_or(self.default_ttl);
-        
+
+        if let Some(l1) = &self.l1 {
+            let l1_ttl = Duration::from_secs(ttl.min(l1.ttl) as u64);
+            l1.cache.set_with_tags(key, serialized.clone(), l1_ttl, tags);
+        }
+
         match &self.backend {
             RedisBackend::Single(pool) => {
                 let mut conn = pool.get().await?;
+                Self::store_key_tags(&mut conn, key, ttl as u64, tags).await?;
                 conn.set_ex::<_, _, ()>(key, serialized, ttl as u64).await?;
+                Self::index_tags(&mut conn, key, ttl as i64, tags).await?;
             },
             RedisBackend::Cluster(client) => {
                 let mut conn = client.get_async_connection().await?;
+                Self::store_key_tags(&mut conn, key, ttl as u64, tags).await?;
                 conn.set_ex::<_, _, ()>(key, serialized, ttl as u64).await?;
+                Self::index_tags(&mut conn, key, ttl as i64, tags).await?;
+            },
+            RedisBackend::Memory(cache) => {
+                cache.set_with_tags(key, serialized, Duration::from_secs(ttl as u64), tags);
             }
         }
+        Ok(())
+    }
 
+    /// Records `key`'s tags for L1 refills. Written before the value so a
+    /// reader never sees the value without its tags.
+    async fn store_key_tags<C: AsyncCommands>(conn: &mut C, key: &str, ttl: u64, tags: &[String]) -> Result<(), Box<dyn Error>> {
+        let tags_key = format!("{}{}", KEY_TAGS_PREFIX, key);
+        if tags.is_empty() {
+            conn.del::<_, ()>(&tags_key).await?;
+        } else {
+            conn.set_ex::<_, _, ()>(&tags_key, serde_json::to_string(tags)?, ttl).await?;
+        }
         Ok(())
     }
-    
-    /// Delete single cache entry
-    pub async fn delete(&self, key: &str) -> Result<(), Box<dyn Error>> {
-        match &self.backend {
-             RedisBackend::Single(pool) => {
+
+    async fn index_tags<C: AsyncCommands>(conn: &mut C, key: &str, ttl: i64, tags: &[String]) -> redis::RedisResult<()> {
+        for tag in tags {
+            let tag_key = format!("{}{}", TAG_PREFIX, tag);
+            conn.sadd::<_, _, ()>(&tag_key, key).await?;
+            // The index must live as long as the longest-lived key in it
+            let remaining: i64 = conn.ttl(&tag_key).await?;
+            if remaining < ttl {
+                conn.expire::<_, ()>(&tag_key, ttl).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete every entry stored with any of `tags`. Returns the number of keys purged.
+    /// Keys are deleted one at a time so this also works across Cluster slots.
+    /// The L1 tier is purged on this instance only; other instances' L1 entries expire on their own.
+    pub async fn purge_tags(&self, tags: &[String]) -> Result<usize, Box<dyn Error>> {
+        if let Some(l1) = &self.l1 {
+            l1.cache.purge_tags(tags);
+        }
+        let count = match &self.backend {
+            RedisBackend::Single(pool) => {
                 let mut conn = pool.get().await?;
-                conn.del::<_, ()>(key).await?;
-             },
-             RedisBackend::Cluster(client) => {
+                Self::purge_on(&mut conn, tags).await?
+            },
+            RedisBackend::Cluster(client) => {
                 let mut conn = client.get_async_connection().await?;
+                Self::purge_on(&mut conn, tags).await?
+            },
+            RedisBackend::Memory(cache) => cache.purge_tags(tags),
+        };
+        log::debug!("🗑️ Purged {} cache keys for tags {:?}", count, tags);
+        Ok(count)
+    }
+
+    async fn purge_on<C: AsyncCommands>(conn: &mut C, tags: &[String]) -> redis::RedisResult<usize> {
+        let mut count = 0;
+        for tag in tags {
+            let tag_key = format!("{}{}", TAG_PREFIX, tag);
+            let keys: Vec<String> = conn.smembers(&tag_key).await?;
+            for key in &keys {
                 conn.del::<_, ()>(key).await?;
-             }
+            }
+            // SREM rather than DEL: keys tagged while we were purging stay indexed
+            if !keys.is_empty() {
+                conn.srem::<_, _, ()>(&tag_key, &keys).await?;
+            }
+            count += keys.len();
         }
-        Ok(())
+        Ok(count)
     }
 
     // Note: delete_pattern is complex in Cluster (scan across nodes). 
@@ -113,6 +313,9 @@ impl CacheServiceV2 {
     /// Delete all keys matching pattern (e.g., "pages:*"). 
     /// WARNING: Pattern scan is expensive in Cluster.
     pub async fn delete_pattern(&self, pattern: &str) -> Result<usize, Box<dyn Error>> {
+         if let Some(l1) = &self.l1 {
+             l1.cache.delete_pattern(pattern);
+         }
          match &self.backend {
              RedisBackend::Single(pool) => {
                 let mut conn = pool.get().await?;
@@ -132,12 +335,16 @@ impl CacheServiceV2 {
                  // For now, return 0 and log warning.
                  log::warn!("delete_pattern not fully supported in Cluster mode yet without cross-slot scanning.");
                  Ok(0)
-             }
+             },
+             RedisBackend::Memory(cache) => Ok(cache.delete_pattern(pattern)),
          }
     }
 
     /// Check if key exists
     pub async fn exists(&self, key: &str) -> bool {
+        if self.l1.as_ref().is_some_and(|l1| l1.cache.get(key).is_some()) {
+            return true;
+        }
         match &self.backend {
             RedisBackend::Single(pool) => {
                 let mut conn = match pool.get().await {
@@ -152,7 +359,8 @@ impl CacheServiceV2 {
                     Err(_) => return false,
                 };
                 conn.exists(key).await.unwrap_or(false)
-            }
+            },
+            RedisBackend::Memory(cache) => cache.get(key).is_some(),
         }
     }
 }
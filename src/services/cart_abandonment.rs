@@ -1,7 +1,13 @@
+use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use log::info;
 use chrono::{DateTime, Utc, Duration};
 
+use crate::models::cart_models::Cart;
+use crate::models::DatabasePool;
+use crate::schema::{cart_items, carts, users};
+use crate::services::cart_service;
+
 /// Cart Abandonment Tracker
 /// Track and recover abandoned carts
 pub struct CartAbandonment;
@@ -38,14 +44,22 @@ pub struct RecoveryCampaign {
     pub emails_sent: usize,
 }
 
+/// Carts idle this long with items in them count as abandoned
+const IDLE_BEFORE_ABANDONED_HOURS: i64 = 1;
+/// Recovery emails are not sent for carts abandoned longer ago than this
+const RECOVERY_WINDOW_DAYS: i64 = 3;
+/// How often the worker looks for abandoned carts
+const WORKER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
 impl CartAbandonment {
-    /// Track abandoned cart
-    pub async fn track_abandonment(
+    /// Summarizes an abandoned cart
+    pub fn summarize(
         &self,
         cart_id: String,
         user_id: Option<String>,
         email: Option<String>,
         items: Vec<CartItem>,
+        abandoned_at: DateTime<Utc>,
     ) -> Result<AbandonedCart, String> {
         if items.is_empty() {
             return Err("Cart cannot be empty".to_string());
@@ -53,29 +67,85 @@ impl CartAbandonment {
 
         let total_value = items.iter().map(|item| item.price * item.quantity as f64).sum();
 
-        info!("Tracking cart abandonment: {} (value: ${:.2})", cart_id, total_value);
-
         Ok(AbandonedCart {
             cart_id,
             user_id,
             email,
             items,
             total_value,
-            abandoned_at: Utc::now(),
+            abandoned_at,
             recovery_sent: false,
             recovered: false,
         })
     }
 
+    /// Loads abandoned carts with their current contents and contact email
+    fn load(&self, carts_list: Vec<Cart>, conn: &mut PgConnection) -> Result<Vec<AbandonedCart>, String> {
+        let user_ids: Vec<i32> = carts_list.iter().filter_map(|cart| cart.user_id).collect();
+        let emails: std::collections::HashMap<i32, String> = users::table
+            .filter(users::id.eq_any(&user_ids))
+            .filter(users::email.is_not_null())
+            .select((users::id, users::email.assume_not_null()))
+            .load::<(i32, String)>(conn)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect();
+
+        let mut abandoned = Vec::new();
+        for cart in carts_list {
+            let lines = cart_service::cart_items_of(cart.id, conn)
+                .and_then(|items| cart_service::price_items(&items, conn))
+                .map_err(|e| e.to_string())?;
+            let items = lines
+                .into_iter()
+                .map(|line| CartItem {
+                    product_id: line.line.product_id.to_string(),
+                    product_name: line.line.name,
+                    quantity: line.line.quantity as usize,
+                    price: line.line.unit_price_cents as f64 / 100.0,
+                })
+                .collect();
+            let email = cart.email.clone().or_else(|| cart.user_id.and_then(|id| emails.get(&id)).cloned());
+            let abandoned_at = cart.abandoned_at.unwrap_or(cart.last_activity_at).and_utc();
+
+            if let Ok(mut summary) = self.summarize(cart.uuid, cart.user_id.map(|id| id.to_string()), email, items, abandoned_at) {
+                summary.recovery_sent = cart.recovery_sent_at.is_some();
+                summary.recovered = cart.recovered_at.is_some();
+                abandoned.push(summary);
+            }
+        }
+        Ok(abandoned)
+    }
+
+    /// Marks open carts idle for `idle` as abandoned and returns them
+    pub async fn track_abandonment(&self, idle: Duration, conn: &mut PgConnection) -> Result<Vec<AbandonedCart>, String> {
+        let now = Utc::now().naive_utc();
+        let newly_abandoned = diesel::update(
+            carts::table
+                .filter(carts::status.eq("active"))
+                .filter(carts::abandoned_at.is_null())
+                .filter(carts::last_activity_at.lt(now - idle))
+                .filter(diesel::dsl::exists(cart_items::table.filter(cart_items::cart_id.eq(carts::id)))),
+        )
+        .set(carts::abandoned_at.eq(now))
+        .get_results::<Cart>(conn)
+        .map_err(|e| e.to_string())?;
+
+        for cart in &newly_abandoned {
+            info!("Tracking cart abandonment: {}", cart.uuid);
+        }
+        self.load(newly_abandoned, conn)
+    }
+
     /// Send recovery email
     pub async fn send_recovery_email(
-        &self, 
+        &self,
         cart: &AbandonedCart,
         email_service: &crate::services::email_service::EmailService
     ) -> Result<(), String> {
         if let Some(email) = &cart.email {
             info!("Sending recovery email for cart: {}", cart.cart_id);
-            
+
             // Create template data
             let data = serde_json::json!({
                 "customer_name": cart.user_id.as_deref().unwrap_or("Valued Customer"),
@@ -83,11 +153,11 @@ impl CartAbandonment {
                 "items": cart.items,
                 "total_value": cart.total_value
             });
-            
+
             email_service.send_template_email(
                 email,
                 "Complete your purchase!",
-                "commerce/cart_abandonment", 
+                "commerce/cart_abandonment",
                 &data,
                 None
             ).await
@@ -97,60 +167,79 @@ impl CartAbandonment {
     }
 
     /// Get recovery campaign stats
-    pub async fn get_campaign_stats(&self, days: u32) -> Result<RecoveryCampaign, String> {
+    pub async fn get_campaign_stats(&self, days: u32, conn: &mut PgConnection) -> Result<RecoveryCampaign, String> {
         info!("Getting recovery campaign stats for {} days", days);
 
-        // TODO: Query actual database
-        let mock_data = self.get_mock_stats();
+        let since = Utc::now().naive_utc() - Duration::days(days as i64);
+        let abandoned_carts = carts::table
+            .filter(carts::abandoned_at.ge(since))
+            .load::<Cart>(conn)
+            .map_err(|e| e.to_string())?;
+        let carts_list = self.load(abandoned_carts, conn)?;
 
-        Ok(mock_data)
-    }
+        let total_value: f64 = carts_list.iter().map(|cart| cart.total_value).sum();
+        let recovered: Vec<&AbandonedCart> = carts_list.iter().filter(|cart| cart.recovered).collect();
+        let recovery_rate = if carts_list.is_empty() {
+            0.0
+        } else {
+            recovered.len() as f64 / carts_list.len() as f64 * 100.0
+        };
 
-    /// Mock stats for testing
-    fn get_mock_stats(&self) -> RecoveryCampaign {
-        RecoveryCampaign {
-            total_abandoned: 150,
-            total_value: 12500.0,
-            recovery_rate: 15.5,
-            recovered_value: 1937.5,
-            emails_sent: 120,
-        }
+        Ok(RecoveryCampaign {
+            total_abandoned: carts_list.len(),
+            total_value,
+            recovery_rate,
+            recovered_value: recovered.iter().map(|cart| cart.total_value).sum(),
+            emails_sent: carts_list.iter().filter(|cart| cart.recovery_sent).count(),
+        })
     }
 
     /// Identify high-value abandonments
-    pub async fn find_high_value_carts(&self, min_value: f64) -> Result<Vec<AbandonedCart>, String> {
+    pub async fn find_high_value_carts(&self, min_value: f64, conn: &mut PgConnection) -> Result<Vec<AbandonedCart>, String> {
         info!("Finding high-value abandoned carts (min: ${:.2})", min_value);
 
-        // TODO: Query database with filter
-        let all_carts = self.get_mock_carts();
-        let high_value: Vec<AbandonedCart> = all_carts.into_iter()
+        let open_carts = carts::table
+            .filter(carts::status.eq("active"))
+            .filter(carts::abandoned_at.is_not_null())
+            .order(carts::abandoned_at.desc())
+            .load::<Cart>(conn)
+            .map_err(|e| e.to_string())?;
+        let high_value: Vec<AbandonedCart> = self.load(open_carts, conn)?
+            .into_iter()
             .filter(|cart| cart.total_value >= min_value)
             .collect();
 
         Ok(high_value)
     }
 
-    /// Mock carts for testing
-    fn get_mock_carts(&self) -> Vec<AbandonedCart> {
-        vec![
-            AbandonedCart {
-                cart_id: "cart-1".to_string(),
-                user_id: Some("user-1".to_string()),
-                email: Some("user1@example.com".to_string()),
-                items: vec![
-                    CartItem {
-                        product_id: "prod-1".to_string(),
-                        product_name: "Premium Widget".to_string(),
-                        quantity: 2,
-                        price: 50.0,
-                    },
-                ],
-                total_value: 100.0,
-                abandoned_at: Utc::now() - Duration::hours(2),
-                recovery_sent: false,
-                recovered: false,
-            },
-        ]
+    /// Emails abandoned carts that have not had a recovery email yet
+    pub async fn send_pending_recovery_emails(
+        &self,
+        email_service: &crate::services::email_service::EmailService,
+        conn: &mut PgConnection,
+    ) -> Result<usize, String> {
+        let since = Utc::now().naive_utc() - Duration::days(RECOVERY_WINDOW_DAYS);
+        let pending = carts::table
+            .filter(carts::status.eq("active"))
+            .filter(carts::abandoned_at.ge(since))
+            .filter(carts::recovery_sent_at.is_null())
+            .load::<Cart>(conn)
+            .map_err(|e| e.to_string())?;
+
+        let mut sent = 0;
+        for cart in self.load(pending, conn)?.into_iter().filter(|cart| cart.email.is_some()) {
+            match self.send_recovery_email(&cart, email_service).await {
+                Ok(()) => {
+                    diesel::update(carts::table.filter(carts::uuid.eq(&cart.cart_id)))
+                        .set(carts::recovery_sent_at.eq(Utc::now().naive_utc()))
+                        .execute(conn)
+                        .map_err(|e| e.to_string())?;
+                    sent += 1;
+                }
+                Err(e) => log::warn!("Recovery email for cart {} failed: {}", cart.cart_id, e),
+            }
+        }
+        Ok(sent)
     }
 
     /// Calculate recovery potential
@@ -181,12 +270,35 @@ impl Default for CartAbandonment {
     }
 }
 
+/// Marks idle carts abandoned and sends their recovery emails, every few minutes
+pub async fn run_worker(pool: DatabasePool, email_service: actix_web::web::Data<crate::services::email_service::EmailService>) {
+    let tracker = CartAbandonment;
+    loop {
+        tokio::time::sleep(WORKER_INTERVAL).await;
+        let mut conn = match pool.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("Cart abandonment worker: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = tracker.track_abandonment(Duration::hours(IDLE_BEFORE_ABANDONED_HOURS), &mut conn).await {
+            log::error!("Cart abandonment tracking failed: {}", e);
+        }
+        match tracker.send_pending_recovery_emails(&email_service, &mut conn).await {
+            Ok(0) => {}
+            Ok(sent) => info!("Sent {} cart recovery emails", sent),
+            Err(e) => log::error!("Cart recovery emails failed: {}", e),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[tokio::test]
-    async fn test_track_abandonment() {
+    #[test]
+    fn test_track_abandonment() {
         let tracker = CartAbandonment;
         let items = vec![
             CartItem {
@@ -197,27 +309,29 @@ mod tests {
             },
         ];
 
-        let result = tracker.track_abandonment(
+        let result = tracker.summarize(
             "cart-123".to_string(),
             Some("user-1".to_string()),
             Some("test@example.com".to_string()),
             items,
-        ).await;
+            Utc::now(),
+        );
 
         assert!(result.is_ok());
         let cart = result.unwrap();
         assert_eq!(cart.total_value, 25.0);
     }
 
-    #[tokio::test]
-    async fn test_empty_cart() {
+    #[test]
+    fn test_empty_cart() {
         let tracker = CartAbandonment;
-        let result = tracker.track_abandonment(
+        let result = tracker.summarize(
             "cart-123".to_string(),
             None,
             None,
             vec![],
-        ).await;
+            Utc::now(),
+        );
 
         assert!(result.is_err());
     }
@@ -0,0 +1,653 @@
+// Cart Service
+// Persistent carts for signed-in users and guests, and the checkout sessions
+// that lock a cart's prices and turn it into an order with a payment intent.
+// Guests are identified by the cart's secret token; at login their cart is
+// merged into the user's.
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::cart_models::{
+    Cart, CartItem, CheckoutLine, CheckoutSession, NewCart, NewCartItem, NewCheckoutSession,
+};
+use crate::models::commerce_models::{NewOrder, Order, Product};
+use crate::schema::{cart_items, carts, checkout_sessions, orders, product_variants, products};
+use crate::services::errors_service::CustomHttpError;
+use crate::services::order_service::{self, Actor, OrderLine};
+use crate::services::payment_service::{CreatePaymentIntentRequest, PaymentHandler, PaymentIntent, PaymentStatus};
+use crate::services::stock_reservation_service;
+
+/// Header carrying a guest's cart token
+pub const CART_TOKEN_HEADER: &str = "X-Cart-Token";
+pub const DEFAULT_CURRENCY: &str = "usd";
+/// How long a checkout session holds its prices
+const CHECKOUT_TTL_MINUTES: i64 = 30;
+/// Upper bound for the quantity of one line
+pub const MAX_QUANTITY: i32 = 999;
+/// `orders.user_uuid` of orders placed without an account
+const GUEST_USER: &str = "guest";
+/// First key of the advisory lock held while a checkout session completes;
+/// the session id is the second
+const CHECKOUT_LOCK_SPACE: i32 = 0x4348_4b4f;
+
+define_sql_function!(fn pg_try_advisory_lock(space: diesel::sql_types::Integer, key: diesel::sql_types::Integer) -> diesel::sql_types::Bool);
+define_sql_function!(fn pg_advisory_unlock(space: diesel::sql_types::Integer, key: diesel::sql_types::Integer) -> diesel::sql_types::Bool);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CartOwner {
+    User(i32),
+    /// A guest, by cart token
+    Guest(String),
+}
+
+/// A cart line with its current catalogue price
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CartLine {
+    pub item_id: i32,
+    #[serde(flatten)]
+    pub line: CheckoutLine,
+    /// False once the product or variant is no longer for sale; such lines
+    /// block checkout until removed
+    pub available: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CartView {
+    pub cart: Cart,
+    /// Guests send this back in the `X-Cart-Token` header
+    pub guest_token: Option<String>,
+    pub items: Vec<CartLine>,
+    pub subtotal_cents: i64,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CheckoutDetails {
+    /// Required for guests before the checkout can complete
+    pub email: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub shipping_address: Option<serde_json::Value>,
+    #[schema(value_type = Option<Object>)]
+    pub billing_address: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CheckoutResult {
+    pub order: Order,
+    /// Provider payment intent; its client secret completes the payment
+    #[schema(value_type = Object)]
+    pub payment_intent: PaymentIntent,
+}
+
+/// Cents of a variant price, which is stored in currency units
+pub fn price_to_cents(price: &BigDecimal) -> Option<i64> {
+    (price * BigDecimal::from(100)).round(0).to_i64()
+}
+
+fn is_valid_quantity(quantity: i32) -> bool {
+    (1..=MAX_QUANTITY).contains(&quantity)
+}
+
+fn touch(cart_id: i32, conn: &mut PgConnection) -> QueryResult<()> {
+    let now = chrono::Utc::now().naive_utc();
+    diesel::update(carts::table.find(cart_id))
+        .set((carts::last_activity_at.eq(now), carts::updated_at.eq(now)))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The owner's open cart on a site
+pub fn find_active_cart(owner: &CartOwner, tenant_id: i32, conn: &mut PgConnection) -> QueryResult<Option<Cart>> {
+    let query = carts::table
+        .filter(carts::status.eq("active"))
+        .filter(carts::tenant_id.eq(tenant_id))
+        .into_boxed();
+    let query = match owner {
+        CartOwner::User(user_id) => query.filter(carts::user_id.eq(*user_id)),
+        CartOwner::Guest(token) => query.filter(carts::guest_token.eq(token)).filter(carts::user_id.is_null()),
+    };
+    query.first::<Cart>(conn).optional()
+}
+
+/// The owner's open cart, or a new one. A guest whose token no longer
+/// matches an open cart gets a new cart with a new token.
+pub fn get_or_create_cart(owner: Option<&CartOwner>, tenant_id: i32, conn: &mut PgConnection) -> QueryResult<Cart> {
+    if let Some(owner) = owner {
+        if let Some(cart) = find_active_cart(owner, tenant_id, conn)? {
+            return Ok(cart);
+        }
+    }
+    let (user_id, guest_token) = match owner {
+        Some(CartOwner::User(user_id)) => (Some(*user_id), None),
+        _ => (None, Some(Uuid::new_v4().simple().to_string())),
+    };
+    diesel::insert_into(carts::table)
+        .values(&NewCart {
+            uuid: Uuid::new_v4().to_string(),
+            tenant_id: Some(tenant_id),
+            user_id,
+            guest_token,
+            currency: DEFAULT_CURRENCY.to_string(),
+        })
+        .get_result::<Cart>(conn)
+}
+
+pub fn cart_items_of(cart_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<CartItem>> {
+    cart_items::table
+        .filter(cart_items::cart_id.eq(cart_id))
+        .order(cart_items::id.asc())
+        .load::<CartItem>(conn)
+}
+
+/// Product id, SKU, name, price and active flag of a variant
+type VariantPricing = (i64, Option<String>, String, Option<BigDecimal>, Option<bool>);
+
+/// Prices cart lines from the current catalogue
+pub fn price_items(items: &[CartItem], conn: &mut PgConnection) -> QueryResult<Vec<CartLine>> {
+    let product_ids: Vec<i64> = items.iter().map(|item| item.product_id).collect();
+    let variant_ids: Vec<i32> = items.iter().filter_map(|item| item.variant_id).collect();
+    let products: HashMap<i64, Product> = products::table
+        .filter(products::id.eq_any(&product_ids))
+        .load::<Product>(conn)?
+        .into_iter()
+        .map(|product| (product.id, product))
+        .collect();
+    let variants: HashMap<i32, VariantPricing> = product_variants::table
+        .filter(product_variants::id.eq_any(&variant_ids))
+        .select((
+            product_variants::id,
+            product_variants::product_id,
+            product_variants::sku,
+            product_variants::variant_name,
+            product_variants::price,
+            product_variants::is_active,
+        ))
+        .load::<(i32, i64, Option<String>, String, Option<BigDecimal>, Option<bool>)>(conn)?
+        .into_iter()
+        .map(|(id, product_id, sku, name, price, is_active)| (id, (product_id, sku, name, price, is_active)))
+        .collect();
+
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let product = products.get(&item.product_id)?;
+            let (name, sku, unit_price_cents, available) = match item.variant_id {
+                Some(variant_id) => {
+                    let (product_id, sku, variant_name, price, is_active) = variants.get(&variant_id)?;
+                    let unit_price = price.as_ref().and_then(price_to_cents).unwrap_or(product.price_cents);
+                    (
+                        format!("{} - {}", product.name, variant_name),
+                        sku.clone().or_else(|| product.sku.clone()),
+                        unit_price,
+                        product.is_active && *product_id == product.id && is_active.unwrap_or(true),
+                    )
+                }
+                None => (product.name.clone(), product.sku.clone(), product.price_cents, product.is_active),
+            };
+            Some(CartLine {
+                item_id: item.id,
+                line: CheckoutLine {
+                    product_id: item.product_id,
+                    variant_id: item.variant_id,
+                    name,
+                    sku,
+                    quantity: item.quantity,
+                    unit_price_cents,
+                    // Nothing computes tax or discounts yet
+                    tax_cents: 0,
+                    discount_cents: 0,
+                    line_total_cents: unit_price_cents * item.quantity as i64,
+                },
+                available,
+            })
+        })
+        .collect())
+}
+
+pub fn view(cart: Cart, conn: &mut PgConnection) -> QueryResult<CartView> {
+    let items = price_items(&cart_items_of(cart.id, conn)?, conn)?;
+    let subtotal_cents = items.iter().map(|line| line.line.line_total_cents).sum();
+    Ok(CartView {
+        guest_token: cart.guest_token.clone(),
+        cart,
+        items,
+        subtotal_cents,
+    })
+}
+
+/// Adds a product of the cart's site to it, raising the quantity if the
+/// product/variant is already in it
+pub fn add_item(
+    cart: &Cart,
+    product_id: i64,
+    variant_id: Option<i32>,
+    quantity: i32,
+    conn: &mut PgConnection,
+) -> Result<(), CustomHttpError> {
+    if !is_valid_quantity(quantity) {
+        return Err(CustomHttpError::BadRequest(format!("Quantity must be between 1 and {}", MAX_QUANTITY)));
+    }
+    let product = products::table
+        .find(product_id)
+        .filter(products::is_active.eq(true))
+        .first::<Product>(conn)
+        .optional()?
+        .filter(|product| product.tenant_id.is_some() && product.tenant_id == cart.tenant_id)
+        .ok_or(CustomHttpError::NotFound("Product not found".to_string()))?;
+    if let Some(variant_id) = variant_id {
+        let exists = diesel::select(diesel::dsl::exists(
+            product_variants::table
+                .filter(product_variants::id.eq(variant_id))
+                .filter(product_variants::product_id.eq(product.id))
+                .filter(product_variants::is_active.eq(true)),
+        ))
+        .get_result::<bool>(conn)?;
+        if !exists {
+            return Err(CustomHttpError::NotFound("Variant not found".to_string()));
+        }
+    }
+
+    conn.transaction::<_, CustomHttpError, _>(|conn| {
+        add_quantity(cart.id, product.id, variant_id, quantity, conn)?;
+        touch(cart.id, conn)?;
+        Ok(())
+    })
+}
+
+fn add_quantity(cart_id: i32, product_id: i64, variant_id: Option<i32>, quantity: i32, conn: &mut PgConnection) -> QueryResult<()> {
+    let existing = cart_items::table
+        .filter(cart_items::cart_id.eq(cart_id))
+        .filter(cart_items::product_id.eq(product_id))
+        .filter(cart_items::variant_id.is_not_distinct_from(variant_id))
+        .for_update()
+        .first::<CartItem>(conn)
+        .optional()?;
+    match existing {
+        Some(item) => {
+            diesel::update(cart_items::table.find(item.id))
+                .set((
+                    cart_items::quantity.eq((item.quantity + quantity).min(MAX_QUANTITY)),
+                    cart_items::updated_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .execute(conn)?;
+        }
+        None => {
+            diesel::insert_into(cart_items::table)
+                .values(&NewCartItem { cart_id, product_id, variant_id, quantity })
+                .execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// Sets a line's quantity; 0 removes it
+pub fn update_item(cart: &Cart, item_id: i32, quantity: i32, conn: &mut PgConnection) -> Result<(), CustomHttpError> {
+    if quantity == 0 {
+        return remove_item(cart, item_id, conn);
+    }
+    if !is_valid_quantity(quantity) {
+        return Err(CustomHttpError::BadRequest(format!("Quantity must be between 0 and {}", MAX_QUANTITY)));
+    }
+    let updated = diesel::update(cart_items::table.find(item_id).filter(cart_items::cart_id.eq(cart.id)))
+        .set((cart_items::quantity.eq(quantity), cart_items::updated_at.eq(chrono::Utc::now().naive_utc())))
+        .execute(conn)?;
+    if updated == 0 {
+        return Err(CustomHttpError::NotFound("Cart item not found".to_string()));
+    }
+    Ok(touch(cart.id, conn)?)
+}
+
+pub fn remove_item(cart: &Cart, item_id: i32, conn: &mut PgConnection) -> Result<(), CustomHttpError> {
+    let deleted = diesel::delete(cart_items::table.find(item_id).filter(cart_items::cart_id.eq(cart.id))).execute(conn)?;
+    if deleted == 0 {
+        return Err(CustomHttpError::NotFound("Cart item not found".to_string()));
+    }
+    Ok(touch(cart.id, conn)?)
+}
+
+/// Moves a guest cart to a user who just signed in. The guest cart becomes
+/// the user's cart if they have none on that site; otherwise its lines are
+/// added to the user's cart. `None` if the token has no open cart.
+pub fn merge_guest_cart(token: &str, user_id: i32, conn: &mut PgConnection) -> QueryResult<Option<Cart>> {
+    conn.transaction(|conn| {
+        let Some(guest) = carts::table
+            .filter(carts::guest_token.eq(token))
+            .filter(carts::user_id.is_null())
+            .filter(carts::status.eq("active"))
+            .for_update()
+            .first::<Cart>(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let Some(tenant_id) = guest.tenant_id else {
+            return Ok(None);
+        };
+        let now = chrono::Utc::now().naive_utc();
+
+        let Some(cart) = find_active_cart(&CartOwner::User(user_id), tenant_id, conn)? else {
+            let claimed = diesel::update(carts::table.find(guest.id))
+                .set((
+                    carts::user_id.eq(user_id),
+                    carts::guest_token.eq(None::<String>),
+                    carts::last_activity_at.eq(now),
+                    carts::updated_at.eq(now),
+                ))
+                .get_result::<Cart>(conn)?;
+            return Ok(Some(claimed));
+        };
+
+        for item in cart_items_of(guest.id, conn)? {
+            add_quantity(cart.id, item.product_id, item.variant_id, item.quantity, conn)?;
+        }
+        diesel::update(carts::table.find(guest.id))
+            .set((carts::status.eq("merged"), carts::updated_at.eq(now)))
+            .execute(conn)?;
+        if cart.email.is_none() && guest.email.is_some() {
+            diesel::update(carts::table.find(cart.id)).set(carts::email.eq(&guest.email)).execute(conn)?;
+        }
+        touch(cart.id, conn)?;
+        carts::table.find(cart.id).first::<Cart>(conn).map(Some)
+    })
+}
+
+fn validate_email(email: &Option<String>) -> Result<(), CustomHttpError> {
+    match email {
+        Some(email) if !email.contains('@') || email.len() > 255 => {
+            Err(CustomHttpError::BadRequest("Invalid email address".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Locks the cart's current prices into a new checkout session. Earlier open
+/// sessions of the cart expire.
+pub fn start_checkout(cart: &Cart, details: CheckoutDetails, conn: &mut PgConnection) -> Result<CheckoutSession, CustomHttpError> {
+    validate_email(&details.email)?;
+    let lines = price_items(&cart_items_of(cart.id, conn)?, conn)?;
+    if lines.is_empty() {
+        return Err(CustomHttpError::BadRequest("Cart is empty".to_string()));
+    }
+    if let Some(line) = lines.iter().find(|line| !line.available) {
+        return Err(CustomHttpError::BadRequest(format!("{} is no longer available", line.line.name)));
+    }
+    let lines: Vec<CheckoutLine> = lines.into_iter().map(|line| line.line).collect();
+    let subtotal_cents: i64 = lines.iter().map(|line| line.line_total_cents).sum();
+    let now = chrono::Utc::now().naive_utc();
+
+    conn.transaction::<_, CustomHttpError, _>(|conn| {
+        diesel::update(
+            checkout_sessions::table
+                .filter(checkout_sessions::cart_id.eq(cart.id))
+                .filter(checkout_sessions::status.eq("open")),
+        )
+        .set((checkout_sessions::status.eq("expired"), checkout_sessions::updated_at.eq(now)))
+        .execute(conn)?;
+        touch(cart.id, conn)?;
+
+        Ok(diesel::insert_into(checkout_sessions::table)
+            .values(&NewCheckoutSession {
+                uuid: Uuid::new_v4().to_string(),
+                cart_id: cart.id,
+                currency: cart.currency.clone(),
+                line_items: serde_json::to_value(&lines).map_err(|e| CustomHttpError::InternalServerError(e.to_string()))?,
+                subtotal_cents,
+                total_cents: subtotal_cents,
+                email: details.email.or_else(|| cart.email.clone()),
+                shipping_address: details.shipping_address,
+                billing_address: details.billing_address,
+                expires_at: now + chrono::Duration::minutes(CHECKOUT_TTL_MINUTES),
+            })
+            .get_result::<CheckoutSession>(conn)?)
+    })
+}
+
+/// A checkout session with its cart, if the owner holds the cart
+pub fn find_checkout(uuid: &str, owner: &CartOwner, conn: &mut PgConnection) -> Result<(CheckoutSession, Cart), CustomHttpError> {
+    let found = checkout_sessions::table
+        .inner_join(carts::table)
+        .filter(checkout_sessions::uuid.eq(uuid))
+        .select((CheckoutSession::as_select(), Cart::as_select()))
+        .first::<(CheckoutSession, Cart)>(conn)
+        .optional()?;
+    match found {
+        Some((session, cart)) if owns(&cart, owner) => Ok((session, cart)),
+        _ => Err(CustomHttpError::NotFound("Checkout session not found".to_string())),
+    }
+}
+
+fn owns(cart: &Cart, owner: &CartOwner) -> bool {
+    match owner {
+        CartOwner::User(user_id) => cart.user_id == Some(*user_id),
+        CartOwner::Guest(token) => cart.user_id.is_none() && cart.guest_token.as_deref() == Some(token.as_str()),
+    }
+}
+
+/// An open session, or why it can't change any more
+fn ensure_open(session: &CheckoutSession, now: NaiveDateTime) -> Result<(), CustomHttpError> {
+    match session.status.as_str() {
+        "open" if session.expires_at > now => Ok(()),
+        "completed" => Err(CustomHttpError::BadRequest("Checkout session is already completed".to_string())),
+        _ => Err(CustomHttpError::BadRequest("Checkout session expired; start checkout again".to_string())),
+    }
+}
+
+/// Updates the contact and addresses of an open session
+pub fn update_checkout(session: &CheckoutSession, details: CheckoutDetails, conn: &mut PgConnection) -> Result<CheckoutSession, CustomHttpError> {
+    let now = chrono::Utc::now().naive_utc();
+    ensure_open(session, now)?;
+    validate_email(&details.email)?;
+    Ok(diesel::update(checkout_sessions::table.find(session.id))
+        .set((
+            checkout_sessions::email.eq(details.email.or_else(|| session.email.clone())),
+            checkout_sessions::shipping_address.eq(details.shipping_address.or_else(|| session.shipping_address.clone())),
+            checkout_sessions::billing_address.eq(details.billing_address.or_else(|| session.billing_address.clone())),
+            checkout_sessions::updated_at.eq(now),
+        ))
+        .get_result::<CheckoutSession>(conn)?)
+}
+
+/// Places the order for a checkout session at its locked prices, reserves
+/// its stock and opens the payment with `handler`. The cart is converted;
+/// the order stays pending until the payment succeeds. The session is
+/// locked before the payment is created, so completing it twice at once
+/// opens one payment.
+pub async fn complete_checkout(
+    session: &CheckoutSession,
+    cart: &Cart,
+    handler: &dyn PaymentHandler,
+    conn: &mut PgConnection,
+) -> Result<CheckoutResult, CustomHttpError> {
+    let locked = diesel::select(pg_try_advisory_lock(CHECKOUT_LOCK_SPACE, session.id)).get_result::<bool>(conn)?;
+    if !locked {
+        return Err(CustomHttpError::BadRequest("Checkout session is already being completed".to_string()));
+    }
+    let result = complete_locked_checkout(session.id, cart, handler, conn).await;
+    if let Err(e) = diesel::select(pg_advisory_unlock(CHECKOUT_LOCK_SPACE, session.id)).get_result::<bool>(conn) {
+        log::warn!("Failed to unlock checkout session {}: {}", session.uuid, e);
+    }
+    result
+}
+
+async fn complete_locked_checkout(
+    session_id: i32,
+    cart: &Cart,
+    handler: &dyn PaymentHandler,
+    conn: &mut PgConnection,
+) -> Result<CheckoutResult, CustomHttpError> {
+    // Read again under the lock, as a concurrent completion may have finished
+    let session = &checkout_sessions::table.find(session_id).first::<CheckoutSession>(conn)?;
+    ensure_open(session, chrono::Utc::now().naive_utc())?;
+    if cart.user_id.is_none() && session.email.is_none() {
+        return Err(CustomHttpError::BadRequest("An email address is required for guest checkout".to_string()));
+    }
+    let lines: Vec<CheckoutLine> = serde_json::from_value(session.line_items.clone())
+        .map_err(|e| CustomHttpError::InternalServerError(format!("Invalid checkout lines: {}", e)))?;
+
+    let order_uuid = Uuid::new_v4().to_string();
+    let metadata = HashMap::from([
+        ("order_uuid".to_string(), order_uuid.clone()),
+        ("checkout_session".to_string(), session.uuid.clone()),
+    ]);
+    let intent = handler
+        .create_payment_intent(CreatePaymentIntentRequest {
+            amount_cents: session.total_cents,
+            currency: session.currency.clone(),
+            metadata,
+        })
+        .await
+        .map_err(|e| {
+            log::error!("Payment creation for checkout {} failed: {}", session.uuid, e);
+            CustomHttpError::InternalServerError("Payment creation failed".to_string())
+        })?;
+
+    let placed = place_order(session, cart, &lines, &order_uuid, handler.provider_name(), &intent, conn);
+    if placed.is_err() {
+        if let Err(e) = handler.cancel_payment_intent(&intent.id).await {
+            log::warn!("Failed to cancel payment intent {}: {}", intent.id, e);
+        }
+    }
+    Ok(CheckoutResult { order: placed?, payment_intent: intent })
+}
+
+/// `orders.payment_status` for a provider's payment status
+pub fn payment_status_name(status: &PaymentStatus) -> &'static str {
+    match status {
+        PaymentStatus::Pending => "pending",
+        PaymentStatus::Processing => "processing",
+        PaymentStatus::Succeeded => "succeeded",
+        PaymentStatus::Failed => "failed",
+        PaymentStatus::Canceled => "canceled",
+    }
+}
+
+/// The order a completed checkout session placed
+pub fn checkout_order(session: &CheckoutSession, conn: &mut PgConnection) -> Result<Order, CustomHttpError> {
+    let order_id = session
+        .order_id
+        .ok_or(CustomHttpError::BadRequest("Checkout session is not completed".to_string()))?;
+    Ok(orders::table.find(order_id).select(Order::as_select()).first::<Order>(conn)?)
+}
+
+fn place_order(
+    session: &CheckoutSession,
+    cart: &Cart,
+    lines: &[CheckoutLine],
+    order_uuid: &str,
+    provider: &str,
+    intent: &PaymentIntent,
+    conn: &mut PgConnection,
+) -> Result<Order, CustomHttpError> {
+    conn.transaction::<_, CustomHttpError, _>(|conn| {
+        // Locked, so a session completed twice at once places one order
+        let session = checkout_sessions::table.find(session.id).for_update().first::<CheckoutSession>(conn)?;
+        let now = chrono::Utc::now().naive_utc();
+        ensure_open(&session, now)?;
+
+        let order_id = diesel::insert_into(orders::table)
+            .values(&NewOrder {
+                uuid: order_uuid.to_string(),
+                user_uuid: cart.user_id.map(|id| id.to_string()).unwrap_or_else(|| GUEST_USER.to_string()),
+                status: "pending".to_string(),
+                total_amount_cents: session.total_cents,
+                payment_status: Some(payment_status_name(&intent.status).to_string()),
+                payment_provider: Some(provider.to_string()),
+                payment_intent_id: Some(intent.id.clone()),
+            })
+            .returning(orders::id)
+            .get_result::<i64>(conn)?;
+        let order = diesel::update(orders::table.find(order_id))
+            .set((
+                orders::tenant_id.eq(cart.tenant_id),
+                orders::metadata.eq(serde_json::json!({
+                    "checkout_session": session.uuid,
+                    "cart": cart.uuid,
+                    "currency": session.currency,
+                    "email": session.email,
+                    "shipping_address": session.shipping_address,
+                    "billing_address": session.billing_address,
+                })),
+            ))
+            .returning(Order::as_returning())
+            .get_result::<Order>(conn)?;
+
+        let items: Vec<OrderLine> = lines
+            .iter()
+            .map(|line| OrderLine {
+                product_id: line.product_id,
+                variant_id: line.variant_id,
+                quantity: line.quantity,
+                unit_price_cents: line.unit_price_cents,
+                tax_cents: line.tax_cents,
+                discount_cents: line.discount_cents,
+            })
+            .collect();
+        let customer = Actor { name: "customer".to_string(), user_id: cart.user_id };
+        order_service::record_created(order_id, &customer, conn)?;
+        order_service::add_items(order_id, &items, conn)?;
+        let held: Vec<(i64, Option<i32>, i32)> = lines.iter().map(|line| (line.product_id, line.variant_id, line.quantity)).collect();
+        stock_reservation_service::reserve(order_id, &held, conn)?;
+
+        diesel::update(checkout_sessions::table.find(session.id))
+            .set((
+                checkout_sessions::status.eq("completed"),
+                checkout_sessions::order_id.eq(order_id),
+                checkout_sessions::completed_at.eq(now),
+                checkout_sessions::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+        diesel::update(carts::table.find(cart.id))
+            .set((
+                carts::status.eq("converted"),
+                carts::email.eq(session.email.clone().or_else(|| cart.email.clone())),
+                carts::recovered_at.eq(cart.abandoned_at.map(|_| now)),
+                carts::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+        Ok(order)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_price_to_cents() {
+        assert_eq!(price_to_cents(&BigDecimal::from_str("19.99").unwrap()), Some(1999));
+        assert_eq!(price_to_cents(&BigDecimal::from_str("5").unwrap()), Some(500));
+        assert_eq!(price_to_cents(&BigDecimal::from_str("0.994").unwrap()), Some(99));
+    }
+
+    #[test]
+    fn test_checkout_session_expiry() {
+        let now = chrono::Utc::now().naive_utc();
+        let mut session = CheckoutSession {
+            id: 1,
+            uuid: "s".to_string(),
+            cart_id: 1,
+            status: "open".to_string(),
+            currency: DEFAULT_CURRENCY.to_string(),
+            line_items: serde_json::json!([]),
+            subtotal_cents: 0,
+            total_cents: 0,
+            email: None,
+            shipping_address: None,
+            billing_address: None,
+            order_id: None,
+            expires_at: now + chrono::Duration::minutes(1),
+            created_at: now,
+            updated_at: now,
+            completed_at: None,
+        };
+        assert!(ensure_open(&session, now).is_ok());
+        assert!(ensure_open(&session, now + chrono::Duration::minutes(2)).is_err());
+        session.status = "completed".to_string();
+        assert!(ensure_open(&session, now).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+// Embedding Refresh Service
+// Keeps content_embeddings in step with page and module edits
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use sha2::{Digest, Sha256};
+
+use crate::models::module_models::Module;
+use crate::models::page_models::Page;
+use crate::models::{DatabasePool, PooledDatabaseConnection};
+use crate::schema::pages;
+use crate::services::ai_rate_limiter::AIRateLimiter;
+use crate::services::plugin_service::Plugin;
+use crate::services::semantic_search_service::{self, EMBEDDING_DIMENSIONS};
+
+/// Pages embedded per provider request
+pub const BATCH_SIZE: usize = 16;
+
+/// How often the worker drains the queue
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Text beyond this is not embedded (roughly the 8k token window of ada-002)
+const MAX_TEXT_CHARS: usize = 24_000;
+
+/// Rate limits for embedding backends the limiter has no defaults for
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 60;
+const DEFAULT_TOKENS_PER_MINUTE: u64 = 150_000;
+
+/// What to do with a page's embeddings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshAction {
+    Refresh,
+    Delete,
+}
+
+/// De-duplicating FIFO of pages waiting for re-embedding.
+/// A page queued several times is processed once; a deletion overrides a refresh.
+pub struct RefreshQueue {
+    state: Mutex<QueueState>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    order: VecDeque<String>,
+    pending: HashMap<String, RefreshAction>,
+}
+
+impl RefreshQueue {
+    pub fn new() -> Self {
+        Self { state: Mutex::new(QueueState::default()) }
+    }
+
+    pub fn push(&self, page_uuid: &str, action: RefreshAction) {
+        let mut state = self.state.lock().unwrap();
+        match state.pending.get(page_uuid).copied() {
+            Some(RefreshAction::Delete) => {}
+            Some(RefreshAction::Refresh) => {
+                state.pending.insert(page_uuid.to_string(), action);
+            }
+            None => {
+                state.pending.insert(page_uuid.to_string(), action);
+                state.order.push_back(page_uuid.to_string());
+            }
+        }
+    }
+
+    /// Takes up to `max` pages, oldest first
+    pub fn drain(&self, max: usize) -> Vec<(String, RefreshAction)> {
+        let mut state = self.state.lock().unwrap();
+        let mut batch = Vec::new();
+        while batch.len() < max {
+            let Some(page_uuid) = state.order.pop_front() else { break };
+            if let Some(action) = state.pending.remove(&page_uuid) {
+                batch.push((page_uuid, action));
+            }
+        }
+        batch
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for RefreshQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn queue() -> &'static RefreshQueue {
+    static QUEUE: OnceLock<RefreshQueue> = OnceLock::new();
+    QUEUE.get_or_init(RefreshQueue::new)
+}
+
+/// Queues a page for re-embedding after it or one of its modules changed
+pub fn enqueue_page(page_uuid: &str) {
+    queue().push(page_uuid, RefreshAction::Refresh);
+}
+
+/// Removes a page's embeddings. Must run before the page row is deleted:
+/// `content_embeddings.page_uuid` references `pages` without cascading.
+pub fn delete_page_embeddings(page_uuid: &str, conn: &mut PooledDatabaseConnection) -> Result<usize, diesel::result::Error> {
+    // Overrides any refresh still queued for the page
+    queue().push(page_uuid, RefreshAction::Delete);
+    diesel::sql_query("DELETE FROM content_embeddings WHERE page_uuid = $1")
+        .bind::<Text, _>(page_uuid)
+        .execute(conn)
+}
+
+/// Enqueues pages reported through `Plugin::on_save_content`
+/// (`content_type` "page" with `uuid`, or "module" with `page_uuid`)
+pub struct EmbeddingRefreshPlugin;
+
+#[async_trait]
+impl Plugin for EmbeddingRefreshPlugin {
+    fn name(&self) -> &str {
+        "embedding-refresh"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    async fn on_save_content(&self, content_type: &str, data: &serde_json::Value) -> Result<(), String> {
+        let field = match content_type {
+            "page" => "uuid",
+            "module" => "page_uuid",
+            _ => return Ok(()),
+        };
+        if let Some(page_uuid) = data.get(field).and_then(|v| v.as_str()) {
+            enqueue_page(page_uuid);
+        }
+        Ok(())
+    }
+}
+
+/// Drains the queue every few seconds until the process exits
+pub async fn run_worker(pool: DatabasePool) {
+    let limiter = AIRateLimiter::new();
+    log::info!("🧭 Embedding refresh worker started");
+    loop {
+        tokio::time::sleep(REFRESH_INTERVAL).await;
+        while !queue().is_empty() {
+            match process_batch(&pool, &limiter).await {
+                Ok(BatchOutcome::Done(0)) => {}
+                Ok(BatchOutcome::Done(count)) => log::info!("✅ Refreshed embeddings for {} page(s)", count),
+                Ok(BatchOutcome::RateLimited(reason)) => {
+                    log::debug!("Embedding refresh deferred: {}", reason);
+                    break;
+                }
+                Err(e) => {
+                    log::error!("❌ Embedding refresh failed: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+enum BatchOutcome {
+    Done(usize),
+    RateLimited(String),
+}
+
+/// Text embedded for a page: title, description, body and module content, without markup
+pub fn page_embedding_text(page: &Page, modules: &[Module]) -> String {
+    let mut parts = vec![page.page_title.clone()];
+    parts.extend(page.meta_description.clone());
+    parts.extend(page.content.as_deref().map(strip_html));
+    for module in modules {
+        parts.push(module.title.clone());
+        parts.push(strip_html(&module.content));
+    }
+
+    let text = parts
+        .iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    text.chars().take(MAX_TEXT_CHARS).collect()
+}
+
+/// Drops tags and collapses whitespace
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                text.push(' ');
+            }
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn content_hash(text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(text.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+fn is_current(page_uuid: &str, hash: &str, model: &str, conn: &mut PooledDatabaseConnection) -> Result<bool, diesel::result::Error> {
+    let row: Count = diesel::sql_query(
+        "SELECT COUNT(*) AS count FROM content_embeddings
+          WHERE page_uuid = $1 AND content_hash = $2 AND model_name = $3",
+    )
+    .bind::<Text, _>(page_uuid)
+    .bind::<Text, _>(hash)
+    .bind::<Text, _>(model)
+    .get_result(conn)?;
+    Ok(row.count > 0)
+}
+
+/// Replaces every embedding of the page with the new one
+fn store_embedding(
+    page_uuid: &str,
+    hash: &str,
+    model: &str,
+    text: &str,
+    embedding: &[f32],
+    conn: &mut PooledDatabaseConnection,
+) -> Result<(), diesel::result::Error> {
+    let vector = format!("[{}]", embedding.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","));
+    let preview: String = text.chars().take(200).collect();
+
+    conn.transaction(|conn| {
+        diesel::sql_query("DELETE FROM content_embeddings WHERE page_uuid = $1")
+            .bind::<Text, _>(page_uuid)
+            .execute(conn)?;
+        diesel::sql_query(
+            "INSERT INTO content_embeddings (page_uuid, content_hash, embedding, model_name, content_preview, created_at, updated_at)
+             VALUES ($1, $2, $3::vector, $4, $5, NOW(), NOW())",
+        )
+        .bind::<Text, _>(page_uuid)
+        .bind::<Text, _>(hash)
+        .bind::<Text, _>(vector)
+        .bind::<Text, _>(model)
+        .bind::<Text, _>(preview)
+        .execute(conn)?;
+        Ok(())
+    })
+}
+
+async fn process_batch(pool: &DatabasePool, limiter: &AIRateLimiter) -> Result<BatchOutcome, String> {
+    let batch = queue().drain(BATCH_SIZE);
+    if batch.is_empty() {
+        return Ok(BatchOutcome::Done(0));
+    }
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let provider = semantic_search_service::load_embedding_provider(&mut conn).map_err(|e| e.to_string())?;
+    let model = provider.model().to_string();
+
+    // Pages whose text changed since their stored embedding: (uuid, hash, text)
+    let mut stale: Vec<(String, String, String)> = Vec::new();
+    for (page_uuid, action) in &batch {
+        let page = match action {
+            RefreshAction::Refresh => pages::table
+                .find(page_uuid)
+                .first::<Page>(&mut conn)
+                .optional()
+                .map_err(|e| e.to_string())?,
+            RefreshAction::Delete => None,
+        };
+        let Some(page) = page else {
+            diesel::sql_query("DELETE FROM content_embeddings WHERE page_uuid = $1")
+                .bind::<Text, _>(page_uuid)
+                .execute(&mut conn)
+                .map_err(|e| e.to_string())?;
+            continue;
+        };
+
+        let modules = Module::belonging_to(&page).load::<Module>(&mut conn).map_err(|e| e.to_string())?;
+        let text = page_embedding_text(&page, &modules);
+        let hash = content_hash(&text);
+        if text.is_empty() || is_current(page_uuid, &hash, &model, &mut conn).map_err(|e| e.to_string())? {
+            continue;
+        }
+        stale.push((page_uuid.clone(), hash, text));
+    }
+    if stale.is_empty() {
+        return Ok(BatchOutcome::Done(0));
+    }
+
+    // Roughly four characters per token
+    let estimated_tokens = stale.iter().map(|(_, _, text)| text.len() as u64 / 4 + 1).sum();
+    if limiter.get_usage(provider.name()).is_err() {
+        let _ = limiter.update_limits(provider.name(), DEFAULT_REQUESTS_PER_MINUTE, DEFAULT_TOKENS_PER_MINUTE);
+    }
+    if let Err(reason) = limiter.check_limit(provider.name(), estimated_tokens) {
+        for (page_uuid, _, _) in &stale {
+            enqueue_page(page_uuid);
+        }
+        return Ok(BatchOutcome::RateLimited(reason));
+    }
+
+    let texts: Vec<String> = stale.iter().map(|(_, _, text)| text.clone()).collect();
+    let embeddings = match provider.embed(&texts).await {
+        Ok(embeddings) => embeddings,
+        Err(e) => {
+            // Retry on the next tick rather than losing the refresh
+            for (page_uuid, _, _) in &stale {
+                enqueue_page(page_uuid);
+            }
+            return Err(e.to_string());
+        }
+    };
+    let _ = limiter.record_usage(provider.name(), estimated_tokens);
+
+    let mut refreshed = 0;
+    for ((page_uuid, hash, text), mut embedding) in stale.into_iter().zip(embeddings) {
+        if embedding.len() > EMBEDDING_DIMENSIONS {
+            log::warn!("Skipping embedding for {}: {} dimensions", page_uuid, embedding.len());
+            continue;
+        }
+        embedding.resize(EMBEDDING_DIMENSIONS, 0.0);
+        // The page may have been deleted while the provider was busy
+        if let Err(e) = store_embedding(&page_uuid, &hash, &model, &text, &embedding, &mut conn) {
+            log::warn!("Failed to store embedding for {}: {}", page_uuid, e);
+            continue;
+        }
+        refreshed += 1;
+    }
+
+    Ok(BatchOutcome::Done(refreshed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::plugin_service::PluginRegistry;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_queue_deduplicates_and_keeps_order() {
+        let queue = RefreshQueue::new();
+        queue.push("a", RefreshAction::Refresh);
+        queue.push("b", RefreshAction::Refresh);
+        queue.push("a", RefreshAction::Refresh);
+        queue.push("c", RefreshAction::Refresh);
+
+        assert_eq!(queue.len(), 3);
+        let first = queue.drain(2);
+        assert_eq!(first, vec![("a".to_string(), RefreshAction::Refresh), ("b".to_string(), RefreshAction::Refresh)]);
+        assert_eq!(queue.drain(10), vec![("c".to_string(), RefreshAction::Refresh)]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_delete_wins_over_refresh() {
+        let queue = RefreshQueue::new();
+        queue.push("a", RefreshAction::Refresh);
+        queue.push("a", RefreshAction::Delete);
+        queue.push("a", RefreshAction::Refresh);
+        assert_eq!(queue.drain(10), vec![("a".to_string(), RefreshAction::Delete)]);
+    }
+
+    #[tokio::test]
+    async fn test_saves_reported_to_plugins_enqueue_pages() {
+        let registry = PluginRegistry::new();
+        registry.register(Arc::new(EmbeddingRefreshPlugin));
+        let page_uuid = uuid::Uuid::new_v4().to_string();
+        let module_page_uuid = uuid::Uuid::new_v4().to_string();
+
+        registry.page_saved(&page_uuid).await;
+        registry.module_saved("module-1", &module_page_uuid).await;
+
+        let state = queue().state.lock().unwrap();
+        assert_eq!(state.pending.get(&page_uuid), Some(&RefreshAction::Refresh));
+        assert_eq!(state.pending.get(&module_page_uuid), Some(&RefreshAction::Refresh));
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(strip_html("<p>Trail <b>running</b></p>\n<ul><li>shoes</li></ul>"), "Trail running shoes");
+        assert_eq!(strip_html("no markup"), "no markup");
+    }
+}
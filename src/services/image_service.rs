@@ -4,26 +4,71 @@ use std::path::Path;
 use image::{imageops::FilterType, DynamicImage, ImageFormat};
 use std::io::Cursor;
 
-/// Generate responsive srcset attribute for an image
-/// Example: generate_srcset("/images/photo.jpg") returns:
-/// "/images/photo-320w.jpg 320w, /images/photo-768w.jpg 768w, /images/photo-1024w.jpg 1024w"
-pub fn generate_srcset(base_url: &str) -> String {
-    let widths = vec![320, 768, 1024, 1920];
-    let path = Path::new(base_url);
-    
-    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
-    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
-    let dir = path.parent().and_then(|p| p.to_str()).unwrap_or("");
-    
-    widths
+/// Widths generated for responsive images
+pub const RESPONSIVE_WIDTHS: [u32; 4] = [320, 768, 1024, 1920];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VariantFormat {
+    Jpeg,
+    WebP,
+}
+
+impl VariantFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VariantFormat::Jpeg => "jpeg",
+            VariantFormat::WebP => "webp",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "jpeg" => Some(VariantFormat::Jpeg),
+            "webp" => Some(VariantFormat::WebP),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VariantFormat::Jpeg => "jpg",
+            VariantFormat::WebP => "webp",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            VariantFormat::Jpeg => "image/jpeg",
+            VariantFormat::WebP => "image/webp",
+        }
+    }
+}
+
+/// A resized, encoded copy of an image
+pub struct ResponsiveVariant {
+    pub width: u32,
+    pub height: u32,
+    pub format: VariantFormat,
+    pub data: Vec<u8>,
+}
+
+/// A stored variant, as referenced from HTML
+#[derive(Debug, Clone)]
+pub struct ImageSource {
+    pub url: String,
+    pub width: u32,
+    pub format: VariantFormat,
+}
+
+/// Generate a srcset attribute from the stored variants of one format
+/// Example: "https://cdn/a-320w.jpg 320w, https://cdn/a-768w.jpg 768w"
+pub fn generate_srcset(sources: &[ImageSource], format: VariantFormat) -> String {
+    let mut matching: Vec<&ImageSource> = sources.iter().filter(|s| s.format == format).collect();
+    matching.sort_by_key(|s| s.width);
+
+    matching
         .iter()
-        .map(|w| {
-            if dir.is_empty() {
-                format!("{}-{}w.{} {}w", stem, w, extension, w)
-            } else {
-                format!("{}/{}-{}w.{} {}w", dir, stem, w, extension, w)
-            }
-        })
+        .map(|s| format!("{} {}w", s.url, s.width))
         .collect::<Vec<_>>()
         .join(", ")
 }
@@ -49,26 +94,48 @@ pub fn should_lazy_load(index: usize) -> bool {
     index >= 3
 }
 
+/// Escapes a value for use inside a double-quoted HTML attribute
+fn escape_attr(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 /// Generate picture element HTML with WebP support
-/// This provides modern browsers with WebP, fallback to original format
+/// Modern browsers pick from the WebP variants, others from the JPEG ones;
+/// without variants this is a plain <img> of the original. Alt text, title
+/// and URLs are escaped, as they come from user-edited media metadata.
 pub fn generate_picture_html(
     original_url: &str,
+    sources: &[ImageSource],
     alt_text: &str,
     title: Option<&str>,
     lazy: bool,
 ) -> String {
-    let webp_url = get_webp_url(original_url);
-    let srcset = generate_srcset(original_url);
     let loading = if lazy { " loading=\"lazy\"" } else { "" };
-    let title_attr = title.map(|t| format!(" title=\"{}\"", t)).unwrap_or_default();
-    
+    let title_attr = title.map(|t| format!(" title=\"{}\"", escape_attr(t))).unwrap_or_default();
+
+    let source_tags: String = [VariantFormat::WebP, VariantFormat::Jpeg]
+        .iter()
+        .map(|format| (format, generate_srcset(sources, *format)))
+        .filter(|(_, srcset)| !srcset.is_empty())
+        .map(|(format, srcset)| format!("\n  <source type=\"{}\" srcset=\"{}\">", format.mime_type(), escape_attr(&srcset)))
+        .collect();
+
     format!(
-        r#"<picture>
-  <source type="image/webp" srcset="{}">
-  <source type="image/jpeg" srcset="{}">
+        r#"<picture>{}
   <img src="{}" alt="{}"{}{}>
 </picture>"#,
-        webp_url, srcset, original_url, alt_text, title_attr, loading
+        source_tags, escape_attr(original_url), escape_attr(alt_text), title_attr, loading
     )
 }
 
@@ -132,24 +199,34 @@ pub fn optimize_image(
     Ok((jpeg_bytes, webp_bytes, width, height))
 }
 
-/// Generate responsive image variants at multiple widths
-/// Returns vector of (width, image_bytes) tuples
+/// Generate responsive image variants at multiple widths, as JPEG and WebP
+/// Widths at or above the image's own width are skipped rather than upscaled
 pub fn generate_responsive_variants(
     image_data: &[u8],
-) -> Result<Vec<(u32, Vec<u8>)>, String> {
+) -> Result<Vec<ResponsiveVariant>, String> {
     let img = image::load_from_memory(image_data)
         .map_err(|e| format!("Failed to load image: {}", e))?;
     
-    let widths = vec![320, 768, 1024, 1920];
     let mut variants = Vec::new();
     
-    for width in widths {
+    for width in RESPONSIVE_WIDTHS.iter().copied().filter(|w| *w < img.width()) {
         let resized = resize_image(&img, width);
-        let mut buffer = Cursor::new(Vec::new());
-        resized
-            .write_to(&mut buffer, ImageFormat::Jpeg)
-            .map_err(|e| format!("Failed to encode variant: {}", e))?;
-        variants.push((width, buffer.into_inner()));
+        for format in [VariantFormat::Jpeg, VariantFormat::WebP] {
+            let image_format = match format {
+                VariantFormat::Jpeg => ImageFormat::Jpeg,
+                VariantFormat::WebP => ImageFormat::WebP,
+            };
+            let mut buffer = Cursor::new(Vec::new());
+            resized
+                .write_to(&mut buffer, image_format)
+                .map_err(|e| format!("Failed to encode variant: {}", e))?;
+            variants.push(ResponsiveVariant {
+                width: resized.width(),
+                height: resized.height(),
+                format,
+                data: buffer.into_inner(),
+            });
+        }
     }
     
     Ok(variants)
@@ -159,13 +236,38 @@ pub fn generate_responsive_variants(
 mod tests {
     use super::*;
 
+    fn sources() -> Vec<ImageSource> {
+        vec![
+            ImageSource { url: "/uploads/a-768w.jpg".to_string(), width: 768, format: VariantFormat::Jpeg },
+            ImageSource { url: "/uploads/a-320w.jpg".to_string(), width: 320, format: VariantFormat::Jpeg },
+            ImageSource { url: "/uploads/a-320w.webp".to_string(), width: 320, format: VariantFormat::WebP },
+        ]
+    }
+
     #[test]
     fn test_generate_srcset() {
-        let result = generate_srcset("/images/photo.jpg");
-        assert!(result.contains("320w"));
-        assert!(result.contains("768w"));
-        assert!(result.contains("1024w"));
-        assert!(result.contains("1920w"));
+        assert_eq!(
+            generate_srcset(&sources(), VariantFormat::Jpeg),
+            "/uploads/a-320w.jpg 320w, /uploads/a-768w.jpg 768w"
+        );
+        assert_eq!(generate_srcset(&sources(), VariantFormat::WebP), "/uploads/a-320w.webp 320w");
+        assert_eq!(generate_srcset(&[], VariantFormat::WebP), "");
+    }
+
+    #[test]
+    fn test_generate_responsive_variants() {
+        let img = DynamicImage::new_rgb8(800, 400);
+        let mut png = Cursor::new(Vec::new());
+        img.write_to(&mut png, ImageFormat::Png).unwrap();
+
+        let variants = generate_responsive_variants(png.get_ref()).unwrap();
+        let sizes: Vec<_> = variants.iter().map(|v| (v.width, v.height, v.format)).collect();
+        assert_eq!(sizes, vec![
+            (320, 160, VariantFormat::Jpeg),
+            (320, 160, VariantFormat::WebP),
+            (768, 384, VariantFormat::Jpeg),
+            (768, 384, VariantFormat::WebP),
+        ]);
     }
 
     #[test]
@@ -184,10 +286,18 @@ mod tests {
 
     #[test]
     fn test_generate_picture_html() {
-        let html = generate_picture_html("/images/test.jpg", "Test Image", Some("My Title"), true);
+        let html = generate_picture_html("/images/test.jpg", &sources(), "Test Image", Some("My Title"), true);
         assert!(html.contains("loading=\"lazy\""));
         assert!(html.contains("title=\"My Title\""));
         assert!(html.contains("alt=\"Test Image\""));
-        assert!(html.contains("image/webp"));
+        assert!(html.contains(r#"<source type="image/webp" srcset="/uploads/a-320w.webp 320w">"#));
+
+        let plain = generate_picture_html("/images/test.jpg", &[], "Test Image", None, false);
+        assert!(!plain.contains("<source"));
+
+        let hostile = generate_picture_html("/images/test.jpg", &[], "\"><script>x</script>", Some("a & 'b'"), false);
+        assert!(hostile.contains(r#"alt="&quot;&gt;&lt;script&gt;x&lt;/script&gt;""#));
+        assert!(hostile.contains(r#"title="a &amp; &#39;b&#39;""#));
+        assert!(!hostile.contains("<script>"));
     }
 }
@@ -0,0 +1,335 @@
+// Image Transform Service
+// On-the-fly resizing, focal-point cropping and re-encoding for
+// /media/{uuid}?w=600&h=400&fit=cover&fmt=webp&q=80&s=<signature>
+//
+// Parameters are signed with HMAC-SHA256 so arbitrary sizes can't be
+// requested to exhaust CPU or storage. The key comes from MEDIA_SIGNING_KEY;
+// without it every transform request is refused.
+
+use hmac::{Hmac, Mac};
+use image::codecs::jpeg::JpegEncoder;
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use utoipa::IntoParams;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNING_KEY_ENV: &str = "MEDIA_SIGNING_KEY";
+
+/// Variant names of transform outputs start with this, responsive ones don't
+pub const TRANSFORM_VARIANT_PREFIX: &str = "t-";
+
+const MAX_DIMENSION: u32 = 4000;
+const DEFAULT_QUALITY: u8 = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fit {
+    /// Fill the box exactly, cropping around the focal point
+    Cover,
+    /// Fit inside the box, keeping the whole image
+    Contain,
+    /// Stretch to the box
+    Fill,
+}
+
+impl Fit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Fit::Cover => "cover",
+            Fit::Contain => "contain",
+            Fit::Fill => "fill",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "cover" => Some(Fit::Cover),
+            "contain" => Some(Fit::Contain),
+            "fill" => Some(Fit::Fill),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Jpeg,
+    WebP,
+    Png,
+}
+
+impl OutputFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpeg",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Png => "png",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "jpeg" | "jpg" => Some(OutputFormat::Jpeg),
+            "webp" => Some(OutputFormat::WebP),
+            "png" => Some(OutputFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Png => "png",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::WebP => "image/webp",
+            OutputFormat::Png => "image/png",
+        }
+    }
+}
+
+/// Query string of a transform request
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct TransformQuery {
+    /// Width in pixels
+    pub w: Option<u32>,
+    /// Height in pixels
+    pub h: Option<u32>,
+    /// "cover" (default), "contain" or "fill"
+    pub fit: Option<String>,
+    /// "jpeg" (default), "webp" or "png"
+    pub fmt: Option<String>,
+    /// JPEG quality, 1-100 (default 80); WebP output is lossless
+    pub q: Option<u8>,
+    /// Hex HMAC-SHA256 signature of the other parameters
+    pub s: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformParams {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fit: Fit,
+    pub format: OutputFormat,
+    pub quality: u8,
+}
+
+impl TransformParams {
+    pub fn from_query(query: &TransformQuery) -> Result<Self, String> {
+        for (name, value) in [("w", query.w), ("h", query.h)] {
+            if let Some(value) = value {
+                if value == 0 || value > MAX_DIMENSION {
+                    return Err(format!("{} must be between 1 and {}", name, MAX_DIMENSION));
+                }
+            }
+        }
+        let quality = query.q.unwrap_or(DEFAULT_QUALITY);
+        if !(1..=100).contains(&quality) {
+            return Err("q must be between 1 and 100".to_string());
+        }
+
+        Ok(Self {
+            width: query.w,
+            height: query.h,
+            fit: match query.fit.as_deref() {
+                None => Fit::Cover,
+                Some(fit) => Fit::parse(fit).ok_or_else(|| format!("unknown fit '{}'", fit))?,
+            },
+            format: match query.fmt.as_deref() {
+                None => OutputFormat::Jpeg,
+                Some(fmt) => OutputFormat::parse(fmt).ok_or_else(|| format!("unknown fmt '{}'", fmt))?,
+            },
+            quality,
+        })
+    }
+
+    /// Query string with every parameter spelled out, in a fixed order
+    pub fn query_string(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(w) = self.width {
+            parts.push(format!("w={}", w));
+        }
+        if let Some(h) = self.height {
+            parts.push(format!("h={}", h));
+        }
+        parts.push(format!("fit={}", self.fit.as_str()));
+        parts.push(format!("fmt={}", self.format.as_str()));
+        parts.push(format!("q={}", self.quality));
+        parts.join("&")
+    }
+
+    /// What gets signed: defaults are filled in, so `?w=600` and
+    /// `?w=600&fit=cover` share a signature
+    fn canonical(&self, media_uuid: &str) -> String {
+        format!("{}?{}", media_uuid, self.query_string())
+    }
+
+    /// Name of the stored output. The focal point is part of it, so moving
+    /// the focal point never serves a stale crop.
+    pub fn variant_name(&self, media_uuid: &str, focal: (f32, f32)) -> String {
+        let digest = Sha256::digest(format!("{}@{:.4},{:.4}", self.canonical(media_uuid), focal.0, focal.1));
+        format!("{}{}", TRANSFORM_VARIANT_PREFIX, &hex::encode(digest)[..24])
+    }
+}
+
+/// The configured signing key, if any
+pub fn signing_key() -> Option<Vec<u8>> {
+    std::env::var(SIGNING_KEY_ENV)
+        .ok()
+        .filter(|key| !key.is_empty())
+        .map(String::into_bytes)
+}
+
+pub fn sign(key: &[u8], media_uuid: &str, params: &TransformParams) -> String {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(params.canonical(media_uuid).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Constant-time check of a request's signature
+pub fn verify(key: &[u8], media_uuid: &str, params: &TransformParams, signature: &str) -> bool {
+    let signature = match hex::decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(params.canonical(media_uuid).as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Path and query of a signed transform URL
+pub fn signed_path(key: &[u8], media_uuid: &str, params: &TransformParams) -> String {
+    format!("/media/{}?{}&s={}", media_uuid, params.query_string(), sign(key, media_uuid, params))
+}
+
+/// Resizes, crops and encodes an image. `focal` is the point to keep in
+/// view when cropping, as fractions of the width and height (0.5, 0.5 is the centre).
+/// Returns the encoded bytes and the output dimensions.
+pub fn transform(data: &[u8], params: &TransformParams, focal: (f32, f32)) -> Result<(Vec<u8>, u32, u32), String> {
+    let img = image::load_from_memory(data).map_err(|e| format!("Failed to load image: {}", e))?;
+    let (src_w, src_h) = (img.width(), img.height());
+
+    let output = match (params.width, params.height) {
+        (None, None) => img,
+        (Some(w), None) => scale_down(&img, w, (w as u64 * src_h as u64 / src_w as u64).max(1) as u32),
+        (None, Some(h)) => scale_down(&img, (h as u64 * src_w as u64 / src_h as u64).max(1) as u32, h),
+        (Some(w), Some(h)) => match params.fit {
+            Fit::Fill => img.resize_exact(w, h, FilterType::Lanczos3),
+            Fit::Contain => scale_down(&img, w, h),
+            Fit::Cover => {
+                let (x, y, crop_w, crop_h) = focal_crop(src_w, src_h, w, h, focal);
+                img.crop_imm(x, y, crop_w, crop_h).resize_exact(w, h, FilterType::Lanczos3)
+            }
+        },
+    };
+
+    let mut buffer = Cursor::new(Vec::new());
+    match params.format {
+        OutputFormat::Jpeg => {
+            let rgb = output.to_rgb8();
+            JpegEncoder::new_with_quality(&mut buffer, params.quality)
+                .encode(&rgb, rgb.width(), rgb.height(), image::ColorType::Rgb8)
+                .map_err(|e| format!("JPEG encoding failed: {}", e))?;
+        }
+        OutputFormat::WebP => output
+            .write_to(&mut buffer, ImageFormat::WebP)
+            .map_err(|e| format!("WebP encoding failed: {}", e))?,
+        OutputFormat::Png => output
+            .write_to(&mut buffer, ImageFormat::Png)
+            .map_err(|e| format!("PNG encoding failed: {}", e))?,
+    }
+    Ok((buffer.into_inner(), output.width(), output.height()))
+}
+
+/// Fits the image inside `w`×`h` without upscaling
+fn scale_down(img: &DynamicImage, w: u32, h: u32) -> DynamicImage {
+    if img.width() <= w && img.height() <= h {
+        return img.clone();
+    }
+    img.resize(w, h, FilterType::Lanczos3)
+}
+
+/// The largest region with the target's aspect ratio, centred on the focal
+/// point as far as the image edges allow
+fn focal_crop(src_w: u32, src_h: u32, target_w: u32, target_h: u32, focal: (f32, f32)) -> (u32, u32, u32, u32) {
+    let target_ratio = target_w as f64 / target_h as f64;
+    let (crop_w, crop_h) = if src_w as f64 / src_h as f64 > target_ratio {
+        (((src_h as f64 * target_ratio).round() as u32).clamp(1, src_w), src_h)
+    } else {
+        (src_w, ((src_w as f64 / target_ratio).round() as u32).clamp(1, src_h))
+    };
+
+    let offset = |size: u32, crop: u32, focus: f32| {
+        let centre = focus.clamp(0.0, 1.0) as f64 * size as f64;
+        (centre - crop as f64 / 2.0).round().clamp(0.0, (size - crop) as f64) as u32
+    };
+    (offset(src_w, crop_w, focal.0), offset(src_h, crop_h, focal.1), crop_w, crop_h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(query: &str) -> TransformParams {
+        let query = actix_web::web::Query::<TransformQuery>::from_query(query).unwrap();
+        TransformParams::from_query(&query).unwrap()
+    }
+
+    #[test]
+    fn test_signatures() {
+        let key = b"secret";
+        let p = params("w=600&h=400&fmt=webp");
+        let signature = sign(key, "abc", &p);
+
+        assert!(verify(key, "abc", &p, &signature));
+        // Defaults don't change the signature, anything else does
+        assert!(verify(key, "abc", &params("w=600&h=400&fit=cover&fmt=webp&q=80"), &signature));
+        assert!(!verify(key, "abc", &params("w=601&h=400&fmt=webp"), &signature));
+        assert!(!verify(key, "other", &p, &signature));
+        assert!(!verify(b"other key", "abc", &p, &signature));
+        assert!(!verify(key, "abc", &p, "not hex"));
+
+        assert_eq!(
+            signed_path(key, "abc", &p),
+            format!("/media/abc?w=600&h=400&fit=cover&fmt=webp&q=80&s={}", signature)
+        );
+        assert_ne!(p.variant_name("abc", (0.5, 0.5)), p.variant_name("abc", (0.2, 0.5)));
+
+        let invalid = actix_web::web::Query::<TransformQuery>::from_query("w=99999").unwrap();
+        assert!(TransformParams::from_query(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_focal_crop() {
+        // Landscape to square: full height, width follows the focal point
+        assert_eq!(focal_crop(1000, 500, 100, 100, (0.5, 0.5)), (250, 0, 500, 500));
+        assert_eq!(focal_crop(1000, 500, 100, 100, (0.9, 0.5)), (500, 0, 500, 500));
+        assert_eq!(focal_crop(1000, 500, 100, 100, (0.0, 0.5)), (0, 0, 500, 500));
+        // Portrait to landscape: full width
+        assert_eq!(focal_crop(400, 800, 200, 100, (0.5, 0.25)), (0, 100, 400, 200));
+    }
+
+    #[test]
+    fn test_transform_dimensions() {
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(800, 400).write_to(&mut png, ImageFormat::Png).unwrap();
+        let data = png.into_inner();
+
+        let dims = |query: &str| {
+            let (_, w, h) = transform(&data, &params(query), (0.5, 0.5)).unwrap();
+            (w, h)
+        };
+        assert_eq!(dims("w=300&h=300"), (300, 300));
+        assert_eq!(dims("w=300&h=300&fit=contain"), (300, 150));
+        assert_eq!(dims("w=200"), (200, 100));
+        assert_eq!(dims("w=2000"), (800, 400));
+        assert_eq!(dims("w=100&h=300&fit=fill&fmt=png"), (100, 300));
+    }
+}
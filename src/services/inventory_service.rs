@@ -177,47 +177,49 @@ pub async fn update_variant_stock(
                 Box::new(e.to_string())
             ))?;
         
-        // Get current variant
-        let current: ProductVariant = product_variants::table
-            .select((product_variants::id, product_variants::uuid, product_variants::product_id, product_variants::sku, product_variants::variant_name, product_variants::price, product_variants::stock_quantity, product_variants::weight, product_variants::image_url, product_variants::is_active, product_variants::created_at, product_variants::updated_at))
-            .find(variant_id)
-            .first(&mut conn)?;
-        
-        let old_quantity = current.stock_quantity.unwrap_or(0);
-        let new_quantity = old_quantity + quantity_change;
-        
-        // Update stock
-        diesel::update(product_variants::table.find(variant_id))
-            .set(product_variants::stock_quantity.eq(new_quantity))
-            .execute(&mut conn)?;
-        
-        // TODO: Re-enable when inventory_audit_log table is added
-        // Create audit log
-        let audit = NewInventoryAuditLog {
-            product_id: Some(current.product_id),
-            variant_id: Some(variant_id),
-            user_id: None, // Auth integration ready - uncomment when audit log enabled
-            order_id: None,
-            change_type: if quantity_change > 0 {
-                "restock".to_string()
-            } else {
-                "adjustment".to_string()
-            },
-            quantity_before: old_quantity,
-            quantity_after: new_quantity,
-            quantity_change,
-            reason,
-        };
-        
-        diesel::insert_into(inventory_audit_log::table)
-            .values(&audit)
-            .execute(&mut conn)?;
-        
-        // Return updated variant
-        product_variants::table
-            .select((product_variants::id, product_variants::uuid, product_variants::product_id, product_variants::sku, product_variants::variant_name, product_variants::price, product_variants::stock_quantity, product_variants::weight, product_variants::image_url, product_variants::is_active, product_variants::created_at, product_variants::updated_at))
-            .find(variant_id)
-            .first::<ProductVariant>(&mut conn)
+        // Lock the row so concurrent changes and sales can't interleave
+        conn.transaction(|conn| {
+            let current: ProductVariant = product_variants::table
+                .select((product_variants::id, product_variants::uuid, product_variants::product_id, product_variants::sku, product_variants::variant_name, product_variants::price, product_variants::stock_quantity, product_variants::weight, product_variants::image_url, product_variants::is_active, product_variants::created_at, product_variants::updated_at))
+                .find(variant_id)
+                .for_update()
+                .first(conn)?;
+
+            let old_quantity = current.stock_quantity.unwrap_or(0);
+            let new_quantity = old_quantity + quantity_change;
+
+            // Update stock
+            diesel::update(product_variants::table.find(variant_id))
+                .set(product_variants::stock_quantity.eq(new_quantity))
+                .execute(conn)?;
+
+            // Create audit log
+            let audit = NewInventoryAuditLog {
+                product_id: Some(current.product_id),
+                variant_id: Some(variant_id),
+                user_id: None, // Auth integration ready - uncomment when audit log enabled
+                order_id: None,
+                change_type: if quantity_change > 0 {
+                    "restock".to_string()
+                } else {
+                    "adjustment".to_string()
+                },
+                quantity_before: old_quantity,
+                quantity_after: new_quantity,
+                quantity_change,
+                reason,
+            };
+
+            diesel::insert_into(inventory_audit_log::table)
+                .values(&audit)
+                .execute(conn)?;
+
+            // Return updated variant
+            product_variants::table
+                .select((product_variants::id, product_variants::uuid, product_variants::product_id, product_variants::sku, product_variants::variant_name, product_variants::price, product_variants::stock_quantity, product_variants::weight, product_variants::image_url, product_variants::is_active, product_variants::created_at, product_variants::updated_at))
+                .find(variant_id)
+                .first::<ProductVariant>(conn)
+        })
     })
     .await {
         Ok(Ok(value)) => value,
@@ -63,6 +63,32 @@ impl LanguageService {
             .optional()
     }
     
+    /// Postgres text search configuration for a language code, "simple" when Postgres has no stemmer for it
+    pub fn search_config_for_code(language_code: &str) -> &'static str {
+        let base = language_code.split('-').next().unwrap_or("").to_lowercase();
+        match base.as_str() {
+            "ar" => "arabic",
+            "da" => "danish",
+            "de" => "german",
+            "el" => "greek",
+            "en" => "english",
+            "es" => "spanish",
+            "fi" => "finnish",
+            "fr" => "french",
+            "hu" => "hungarian",
+            "id" => "indonesian",
+            "it" => "italian",
+            "nl" => "dutch",
+            "no" | "nb" => "norwegian",
+            "pt" => "portuguese",
+            "ro" => "romanian",
+            "ru" => "russian",
+            "sv" => "swedish",
+            "tr" => "turkish",
+            _ => "simple",
+        }
+    }
+    
     /// Save page translation
     pub fn save_page_translation(
         conn: &mut diesel::pg::PgConnection,
@@ -0,0 +1,241 @@
+// Media Folder Service
+// Hierarchical folders for a tenant's media library. Each folder stores its
+// full path ("/products/shoes"), so a subtree is one prefix match and a
+// rename or move rewrites the paths below it in a single update.
+
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+
+use crate::models::media_models::{MediaFolder, NewMediaFolder};
+use crate::schema::{media, media_folders};
+use crate::services::errors_service::CustomHttpError;
+
+/// Trims a folder name and checks it can be a path segment
+pub fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name == "." || name == ".." {
+        return Err("Folder name is required".to_string());
+    }
+    if name.contains('/') {
+        return Err("Folder names cannot contain '/'".to_string());
+    }
+    if name.chars().count() > 255 {
+        return Err("Folder names are limited to 255 characters".to_string());
+    }
+    Ok(name.to_string())
+}
+
+pub fn child_path(parent_path: Option<&str>, name: &str) -> String {
+    format!("{}/{}", parent_path.unwrap_or(""), name)
+}
+
+/// Whether `path` is `ancestor` or lies below it
+pub fn is_within(path: &str, ancestor: &str) -> bool {
+    path == ancestor || path.strip_prefix(ancestor).map(|rest| rest.starts_with('/')).unwrap_or(false)
+}
+
+/// LIKE pattern matching every path below `path`
+fn descendants_pattern(path: &str) -> String {
+    let escaped = path.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("{}/%", escaped)
+}
+
+pub fn list(tenant_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<MediaFolder>> {
+    media_folders::table
+        .filter(media_folders::tenant_id.eq(tenant_id))
+        .order(media_folders::path.asc())
+        .load::<MediaFolder>(conn)
+}
+
+pub fn find(tenant_id: i32, id: i32, conn: &mut PgConnection) -> Result<MediaFolder, CustomHttpError> {
+    media_folders::table
+        .find(id)
+        .filter(media_folders::tenant_id.eq(tenant_id))
+        .first::<MediaFolder>(conn)
+        .optional()?
+        .ok_or(CustomHttpError::NotFound("Folder not found".to_string()))
+}
+
+/// Looks a folder up by ID ("12") or path ("/products/shoes")
+pub fn resolve(tenant_id: i32, reference: &str, conn: &mut PgConnection) -> Result<MediaFolder, CustomHttpError> {
+    let reference = reference.trim();
+    if let Ok(id) = reference.parse::<i32>() {
+        return find(tenant_id, id, conn);
+    }
+    let path = format!("/{}", reference.trim_matches('/'));
+    media_folders::table
+        .filter(media_folders::tenant_id.eq(tenant_id))
+        .filter(media_folders::path.eq(&path))
+        .first::<MediaFolder>(conn)
+        .optional()?
+        .ok_or(CustomHttpError::NotFound(format!("Folder {} not found", path)))
+}
+
+/// IDs of a folder and every folder below it
+pub fn subtree_ids(folder: &MediaFolder, conn: &mut PgConnection) -> QueryResult<Vec<i32>> {
+    media_folders::table
+        .filter(media_folders::tenant_id.eq(folder.tenant_id))
+        .filter(
+            media_folders::id
+                .eq(folder.id)
+                .or(media_folders::path.like(descendants_pattern(&folder.path))),
+        )
+        .select(media_folders::id)
+        .load::<i32>(conn)
+}
+
+fn ensure_path_free(tenant_id: i32, path: &str, conn: &mut PgConnection) -> Result<(), CustomHttpError> {
+    let taken = diesel::select(diesel::dsl::exists(
+        media_folders::table
+            .filter(media_folders::tenant_id.eq(tenant_id))
+            .filter(media_folders::path.eq(path)),
+    ))
+    .get_result::<bool>(conn)?;
+    if taken {
+        return Err(CustomHttpError::BadRequest(format!("A folder already exists at {}", path)));
+    }
+    Ok(())
+}
+
+pub fn create(
+    tenant_id: i32,
+    parent_id: Option<i32>,
+    name: &str,
+    conn: &mut PgConnection,
+) -> Result<MediaFolder, CustomHttpError> {
+    let name = validate_name(name).map_err(CustomHttpError::BadRequest)?;
+    let parent = parent_id.map(|id| find(tenant_id, id, conn)).transpose()?;
+    let path = child_path(parent.as_ref().map(|p| p.path.as_str()), &name);
+    ensure_path_free(tenant_id, &path, conn)?;
+
+    Ok(diesel::insert_into(media_folders::table)
+        .values(&NewMediaFolder { tenant_id, parent_id, name, path })
+        .get_result::<MediaFolder>(conn)?)
+}
+
+/// Gives a folder a new name and/or parent and rewrites the paths of its
+/// subtree. `None` keeps the folder's current parent or name.
+fn relocate(
+    folder: &MediaFolder,
+    new_parent: Option<Option<i32>>,
+    new_name: Option<String>,
+    conn: &mut PgConnection,
+) -> Result<MediaFolder, CustomHttpError> {
+    conn.transaction(|conn| {
+        // Both rows are locked (in id order) and re-read, so a concurrent move
+        // can't change either path between the cycle check and the update
+        let ids: Vec<i32> = std::iter::once(folder.id).chain(new_parent.flatten()).collect();
+        let locked = media_folders::table
+            .filter(media_folders::tenant_id.eq(folder.tenant_id))
+            .filter(media_folders::id.eq_any(&ids))
+            .order(media_folders::id)
+            .for_update()
+            .load::<MediaFolder>(conn)?;
+        let reread = |id: i32| {
+            locked
+                .iter()
+                .find(|f| f.id == id)
+                .cloned()
+                .ok_or(CustomHttpError::NotFound("Folder not found".to_string()))
+        };
+        let folder = reread(folder.id)?;
+        let name = new_name.unwrap_or_else(|| folder.name.clone());
+        let parent = match new_parent {
+            Some(parent_id) => parent_id.map(reread).transpose()?,
+            // Moving the parent rewrites this folder's row too, so the lock above
+            // already waited for any such move and the parent read here is current
+            None => folder.parent_id.map(|id| find(folder.tenant_id, id, conn)).transpose()?,
+        };
+
+        let new_path = child_path(parent.as_ref().map(|p| p.path.as_str()), &name);
+        if new_path == folder.path {
+            return Ok(folder);
+        }
+        if let Some(parent) = &parent {
+            if is_within(&parent.path, &folder.path) {
+                return Err(CustomHttpError::BadRequest("A folder cannot be moved into itself".to_string()));
+            }
+        }
+
+        // Checked in the transaction doing the move; the (tenant_id, path)
+        // unique key stops a concurrent move to the same path
+        ensure_path_free(folder.tenant_id, &new_path, conn)?;
+        let now = chrono::Utc::now().naive_utc();
+        // "/old/child" -> "/new" || "/child"
+        diesel::update(
+            media_folders::table
+                .filter(media_folders::tenant_id.eq(folder.tenant_id))
+                .filter(media_folders::path.like(descendants_pattern(&folder.path))),
+        )
+        .set((
+            media_folders::path.eq(sql::<Text>("")
+                .bind::<Text, _>(&new_path)
+                .sql(" || substr(path, ")
+                .bind::<diesel::sql_types::Integer, _>(folder.path.chars().count() as i32 + 1)
+                .sql(")")),
+            media_folders::updated_at.eq(now),
+        ))
+        .execute(conn)?;
+
+        Ok(diesel::update(media_folders::table.find(folder.id))
+            .set((
+                media_folders::name.eq(&name),
+                media_folders::parent_id.eq(parent.as_ref().map(|p| p.id)),
+                media_folders::path.eq(&new_path),
+                media_folders::updated_at.eq(now),
+            ))
+            .get_result::<MediaFolder>(conn)?)
+    })
+}
+
+pub fn rename(folder: &MediaFolder, name: &str, conn: &mut PgConnection) -> Result<MediaFolder, CustomHttpError> {
+    let name = validate_name(name).map_err(CustomHttpError::BadRequest)?;
+    relocate(folder, None, Some(name), conn)
+}
+
+/// Moves a folder under another one, or to the top level with `None`
+pub fn move_to(
+    folder: &MediaFolder,
+    parent_id: Option<i32>,
+    conn: &mut PgConnection,
+) -> Result<MediaFolder, CustomHttpError> {
+    relocate(folder, Some(parent_id), None, conn)
+}
+
+/// Deletes an empty folder
+pub fn delete(folder: &MediaFolder, conn: &mut PgConnection) -> Result<(), CustomHttpError> {
+    let has_children = diesel::select(diesel::dsl::exists(
+        media_folders::table.filter(media_folders::parent_id.eq(folder.id)),
+    ))
+    .get_result::<bool>(conn)?;
+    let has_media = diesel::select(diesel::dsl::exists(media::table.filter(media::folder_id.eq(folder.id))))
+        .get_result::<bool>(conn)?;
+    if has_children || has_media {
+        return Err(CustomHttpError::BadRequest("Only empty folders can be deleted".to_string()));
+    }
+
+    diesel::delete(media_folders::table.find(folder.id)).execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(validate_name("  Shoes "), Ok("Shoes".to_string()));
+        assert!(validate_name("a/b").is_err());
+        assert!(validate_name("..").is_err());
+
+        assert_eq!(child_path(None, "products"), "/products");
+        assert_eq!(child_path(Some("/products"), "shoes"), "/products/shoes");
+
+        assert!(is_within("/products/shoes", "/products"));
+        assert!(is_within("/products", "/products"));
+        assert!(!is_within("/products-old", "/products"));
+
+        assert_eq!(descendants_pattern("/100%_off"), "/100\\%\\_off/%");
+    }
+}
@@ -0,0 +1,662 @@
+// Media Inspection Service
+// Type detection, SVG sanitizing and per-type metadata (EXIF, PDF pages,
+// audio/video duration) for uploaded media
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::convert::TryInto;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use utoipa::ToSchema;
+
+/// Bytes read from the start of an upload before its type is decided
+pub const SNIFF_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Image,
+    Svg,
+    Pdf,
+    Video,
+    Audio,
+    Document,
+}
+
+impl MediaKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaKind::Image => "image",
+            MediaKind::Svg => "svg",
+            MediaKind::Pdf => "pdf",
+            MediaKind::Video => "video",
+            MediaKind::Audio => "audio",
+            MediaKind::Document => "document",
+        }
+    }
+
+    /// The kind for a detected MIME type, or None if the library doesn't accept it
+    pub fn of_mime(mime: &str) -> Option<Self> {
+        match mime {
+            SVG_MIME => Some(MediaKind::Svg),
+            "application/pdf" => Some(MediaKind::Pdf),
+            "image/jpeg" | "image/png" | "image/gif" | "image/webp" | "image/bmp" | "image/tiff" | "image/avif" => {
+                Some(MediaKind::Image)
+            }
+            m if m.starts_with("video/") => Some(MediaKind::Video),
+            m if m.starts_with("audio/") => Some(MediaKind::Audio),
+            m if DOCUMENT_TYPES.iter().any(|(_, mime)| *mime == m) => Some(MediaKind::Document),
+            _ => None,
+        }
+    }
+}
+
+const SVG_MIME: &str = "image/svg+xml";
+
+/// Office and text documents, by extension. The extension is only trusted
+/// when the content sniffs as the matching container (zip, OLE or text).
+const DOCUMENT_TYPES: &[(&str, &str)] = &[
+    ("doc", "application/msword"),
+    ("xls", "application/vnd.ms-excel"),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+    ("pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation"),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("odp", "application/vnd.oasis.opendocument.presentation"),
+    ("rtf", "application/rtf"),
+    ("csv", "text/csv"),
+    ("txt", "text/plain"),
+];
+
+/// Detects the MIME type from the first bytes of a file.
+///
+/// Binary formats are identified by signature. Zip and OLE containers fall
+/// back to the extension to tell office formats apart, and text is checked
+/// for an `<svg` root before falling back to CSV or plain text.
+pub fn detect_mime(head: &[u8], filename: &str) -> String {
+    let ext = std::path::Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    let by_extension = |allowed: &[&str]| {
+        DOCUMENT_TYPES
+            .iter()
+            .find(|(e, _)| *e == ext && allowed.contains(e))
+            .map(|(_, mime)| mime.to_string())
+    };
+
+    if let Some(kind) = infer::get(head) {
+        return match kind.mime_type() {
+            "application/zip" => by_extension(&["docx", "xlsx", "pptx", "odt", "ods", "odp"]),
+            "application/x-ole-storage" => by_extension(&["doc", "xls", "ppt"]),
+            "text/xml" if text_prefix(head).map(looks_like_svg).unwrap_or(false) => Some(SVG_MIME.to_string()),
+            mime => Some(mime.to_string()),
+        }
+        .unwrap_or_else(|| kind.mime_type().to_string());
+    }
+
+    match text_prefix(head) {
+        Some(text) if looks_like_svg(text) => SVG_MIME.to_string(),
+        Some(_) => by_extension(&["csv", "txt"]).unwrap_or_else(|| "text/plain".to_string()),
+        None => "application/octet-stream".to_string(),
+    }
+}
+
+/// The head as UTF-8, tolerating a character cut off at the end
+fn text_prefix(head: &[u8]) -> Option<&str> {
+    match std::str::from_utf8(head) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    }
+}
+
+fn looks_like_svg(text: &str) -> bool {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    (text.starts_with('<') && text.contains("<svg")) && !text.to_lowercase().starts_with("<!doctype html")
+}
+
+// ---------------------------------------------------------------------------
+// SVG
+// ---------------------------------------------------------------------------
+
+/// Elements removed along with everything inside them
+const SVG_BLOCKED_ELEMENTS: &[&str] = &[
+    "script", "foreignobject", "iframe", "frame", "embed", "object", "applet", "meta", "link", "base", "handler",
+    "listener",
+];
+
+/// Rewrites an SVG keeping only inert markup.
+///
+/// Scripts, foreign content, event handlers, DTDs, processing instructions
+/// and comments are dropped, and links or CSS may only point inside the
+/// document or at embedded raster images. Fails if the input is not
+/// well-formed or its root is not `<svg>`.
+pub fn sanitize_svg(input: &str) -> Result<String, String> {
+    use xmlparser::{ElementEnd, Token};
+
+    let mut out = String::with_capacity(input.len());
+    let mut stack: Vec<(String, bool)> = Vec::new(); // (qualified name, kept)
+    let mut pending: Option<(String, bool, String)> = None; // (qualified name, kept, tag so far)
+    let mut seen_root = false;
+
+    let skipping = |stack: &[(String, bool)]| stack.iter().any(|(_, kept)| !kept);
+
+    for token in xmlparser::Tokenizer::from(input) {
+        let token = token.map_err(|e| format!("invalid SVG: {}", e))?;
+        match token {
+            Token::ElementStart { prefix, local, .. } => {
+                let name = qualified(prefix.as_str(), local.as_str());
+                let is_svg_namespace = prefix.is_empty() || prefix.as_str() == "svg";
+                if !seen_root {
+                    if local.as_str() != "svg" || !is_svg_namespace {
+                        return Err("root element must be <svg>".to_string());
+                    }
+                    seen_root = true;
+                }
+                let kept = is_svg_namespace
+                    && !SVG_BLOCKED_ELEMENTS.contains(&local.as_str().to_lowercase().as_str())
+                    && !skipping(&stack);
+                pending = Some((name.clone(), kept, format!("<{}", name)));
+            }
+            Token::Attribute { prefix, local, value, .. } => {
+                if let Some((_, true, tag)) = pending.as_mut() {
+                    if is_safe_attribute(prefix.as_str(), local.as_str(), value.as_str()) {
+                        tag.push_str(&format!(
+                            " {}=\"{}\"",
+                            qualified(prefix.as_str(), local.as_str()),
+                            value.as_str().replace('"', "&quot;")
+                        ));
+                    }
+                }
+            }
+            Token::ElementEnd { end, .. } => match end {
+                ElementEnd::Open => {
+                    let (name, kept, tag) = pending.take().ok_or("invalid SVG: unexpected '>'")?;
+                    if kept {
+                        out.push_str(&tag);
+                        out.push('>');
+                    }
+                    stack.push((name, kept));
+                }
+                ElementEnd::Empty => {
+                    let (_, kept, tag) = pending.take().ok_or("invalid SVG: unexpected '/>'")?;
+                    if kept {
+                        out.push_str(&tag);
+                        out.push_str("/>");
+                    }
+                }
+                ElementEnd::Close(prefix, local) => {
+                    let (name, kept) = stack.pop().ok_or("invalid SVG: unbalanced closing tag")?;
+                    if name != qualified(prefix.as_str(), local.as_str()) {
+                        return Err(format!("invalid SVG: </{}> closes <{}>", qualified(prefix.as_str(), local.as_str()), name));
+                    }
+                    if kept {
+                        out.push_str(&format!("</{}>", name));
+                    }
+                }
+            },
+            Token::Text { text } | Token::Cdata { text, .. } => {
+                if stack.is_empty() || skipping(&stack) {
+                    continue;
+                }
+                let in_style = stack.last().map(|(name, _)| name == "style").unwrap_or(false);
+                if in_style && !is_safe_css(text.as_str()) {
+                    continue;
+                }
+                if matches!(token, Token::Cdata { .. }) {
+                    out.push_str(&format!("<![CDATA[{}]]>", text.as_str()));
+                } else {
+                    out.push_str(text.as_str());
+                }
+            }
+            // Declarations, DTDs (entity expansion), processing instructions
+            // (external stylesheets) and comments are all dropped
+            _ => {}
+        }
+    }
+
+    if !seen_root || !stack.is_empty() {
+        return Err("invalid SVG: document is incomplete".to_string());
+    }
+    Ok(out)
+}
+
+fn qualified(prefix: &str, local: &str) -> String {
+    if prefix.is_empty() {
+        local.to_string()
+    } else {
+        format!("{}:{}", prefix, local)
+    }
+}
+
+fn is_safe_attribute(prefix: &str, local: &str, raw_value: &str) -> bool {
+    let name = local.to_lowercase();
+    if name.starts_with("on") {
+        return false;
+    }
+    if !matches!(prefix, "" | "xlink" | "xml" | "xmlns") {
+        return false;
+    }
+
+    let value = normalized(raw_value);
+    if value.contains("javascript:") || value.contains("vbscript:") {
+        return false;
+    }
+    match name.as_str() {
+        "href" | "src" => value.starts_with('#') || is_embedded_image(&value),
+        "style" => is_safe_css(raw_value),
+        _ => !value.contains("url(") || is_safe_css(raw_value),
+    }
+}
+
+/// CSS may only reference fragments in the document or embedded images
+fn is_safe_css(css: &str) -> bool {
+    let css = normalized(css);
+    if css.contains("@import") || css.contains("expression(") || css.contains("javascript:") {
+        return false;
+    }
+    css.match_indices("url(").all(|(i, _)| {
+        let target = css[i + 4..].trim_start_matches(['"', '\'']);
+        target.starts_with('#') || is_embedded_image(target)
+    })
+}
+
+fn is_embedded_image(value: &str) -> bool {
+    ["data:image/png", "data:image/jpeg", "data:image/gif", "data:image/webp"]
+        .iter()
+        .any(|prefix| value.starts_with(prefix))
+}
+
+/// Lowercases, decodes character references and drops whitespace and
+/// control characters, so `java&#x09;script:` is seen as `javascript:`
+fn normalized(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) if end <= 12 => end,
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let entity = &rest[1..end];
+        let ch = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match ch {
+            Some(ch) => {
+                decoded.push(ch);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Images
+// ---------------------------------------------------------------------------
+
+/// EXIF tags copied into the media metadata. GPS tags never are.
+const EXIF_TAGS: &[(exif::Tag, &str)] = &[
+    (exif::Tag::Make, "camera_make"),
+    (exif::Tag::Model, "camera_model"),
+    (exif::Tag::LensModel, "lens_model"),
+    (exif::Tag::DateTimeOriginal, "taken_at"),
+    (exif::Tag::ExposureTime, "exposure_time"),
+    (exif::Tag::FNumber, "f_number"),
+    (exif::Tag::PhotographicSensitivity, "iso"),
+    (exif::Tag::FocalLength, "focal_length"),
+    (exif::Tag::Orientation, "orientation"),
+];
+
+/// Camera details from an image's EXIF data, and whether it carried a location
+pub fn exif_metadata(data: &[u8]) -> (Map<String, Value>, bool) {
+    let mut metadata = Map::new();
+    let exif = match exif::Reader::new().read_from_container(&mut Cursor::new(data)) {
+        Ok(exif) => exif,
+        Err(_) => return (metadata, false),
+    };
+
+    for (tag, key) in EXIF_TAGS {
+        if let Some(field) = exif.get_field(*tag, exif::In::PRIMARY) {
+            let value = field.display_value().to_string();
+            metadata.insert(key.to_string(), Value::String(value.trim_matches('"').trim().to_string()));
+        }
+    }
+    let has_gps = exif
+        .fields()
+        .any(|f| f.tag == exif::Tag::GPSInfoIFDPointer || f.tag.context() == exif::Context::Gps);
+    (metadata, has_gps)
+}
+
+/// Removes the APP1 segments (EXIF and XMP, which carry GPS coordinates)
+/// from a JPEG, leaving the image data untouched. Returns None if `data`
+/// is not a well-formed JPEG.
+pub fn strip_jpeg_metadata(data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < 4 || data[0] != 0xFF || data[1] != 0xD8 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..2]);
+    let mut pos = 2;
+
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        // Start of scan: the rest is entropy-coded image data
+        if marker == 0xDA {
+            out.extend_from_slice(&data[pos..]);
+            return Some(out);
+        }
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let end = pos + 2 + length;
+        if length < 2 || end > data.len() {
+            return None;
+        }
+        if marker != 0xE1 {
+            out.extend_from_slice(&data[pos..end]);
+        }
+        pos = end;
+    }
+    None
+}
+
+// ---------------------------------------------------------------------------
+// Documents and time-based media
+// ---------------------------------------------------------------------------
+
+pub fn pdf_page_count(data: &[u8]) -> Option<u32> {
+    lopdf::Document::load_mem(data)
+        .ok()
+        .map(|doc| doc.get_pages().len() as u32)
+}
+
+/// Duration in seconds of an MP4/MOV/M4A, WAV or MP3 file
+pub fn media_duration<R: Read + Seek>(mime: &str, reader: &mut R) -> Option<f64> {
+    match mime {
+        "audio/x-wav" | "audio/wav" | "audio/wave" => wav_duration(reader),
+        "audio/mpeg" => mp3_duration(reader),
+        _ => mp4_duration(reader),
+    }
+}
+
+/// Reads the movie header (`moov/mvhd`) of an ISO base media file. The
+/// `moov` box may come after the media data, so boxes are skipped by seeking.
+fn mp4_duration<R: Read + Seek>(reader: &mut R) -> Option<f64> {
+    let end = reader.seek(SeekFrom::End(0)).ok()?;
+    let (moov_start, moov_end) = find_box(reader, 0, end, b"moov")?;
+    let (mvhd_start, _) = find_box(reader, moov_start, moov_end, b"mvhd")?;
+
+    reader.seek(SeekFrom::Start(mvhd_start)).ok()?;
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version).ok()?;
+    let (timescale, duration) = if version[0] == 1 {
+        let mut header = [0u8; 28];
+        reader.read_exact(&mut header).ok()?;
+        (
+            u32::from_be_bytes(header[16..20].try_into().ok()?),
+            u64::from_be_bytes(header[20..28].try_into().ok()?),
+        )
+    } else {
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header).ok()?;
+        (
+            u32::from_be_bytes(header[8..12].try_into().ok()?),
+            u32::from_be_bytes(header[12..16].try_into().ok()?) as u64,
+        )
+    };
+    (timescale > 0).then(|| duration as f64 / timescale as f64)
+}
+
+/// Finds a child box between `start` and `end`, returning its payload range
+fn find_box<R: Read + Seek>(reader: &mut R, start: u64, end: u64, name: &[u8; 4]) -> Option<(u64, u64)> {
+    let mut pos = start;
+    while pos.checked_add(8)? <= end {
+        reader.seek(SeekFrom::Start(pos)).ok()?;
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).ok()?;
+        let (size, header_len) = match u32::from_be_bytes(header[0..4].try_into().ok()?) {
+            0 => (end - pos, 8),
+            1 => {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large).ok()?;
+                (u64::from_be_bytes(large), 16)
+            }
+            size => (size as u64, 8),
+        };
+        // A crafted 64-bit size must not wrap around and send us back to an earlier box
+        let next = pos.checked_add(size)?;
+        if size < header_len || next <= pos || next > end {
+            return None;
+        }
+        if &header[4..8] == name {
+            return Some((pos + header_len, next));
+        }
+        pos = next;
+    }
+    None
+}
+
+fn wav_duration<R: Read + Seek>(reader: &mut R) -> Option<f64> {
+    let mut riff = [0u8; 12];
+    reader.seek(SeekFrom::Start(0)).ok()?;
+    reader.read_exact(&mut riff).ok()?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return None;
+    }
+
+    let mut byte_rate = None;
+    loop {
+        let mut chunk = [0u8; 8];
+        reader.read_exact(&mut chunk).ok()?;
+        let size = u32::from_le_bytes(chunk[4..8].try_into().ok()?) as u64;
+        match &chunk[0..4] {
+            b"fmt " => {
+                let mut fmt = [0u8; 12];
+                reader.read_exact(&mut fmt).ok()?;
+                byte_rate = Some(u32::from_le_bytes(fmt[8..12].try_into().ok()?));
+                reader.seek(SeekFrom::Current(size as i64 + (size & 1) as i64 - 12)).ok()?;
+            }
+            b"data" => {
+                return byte_rate.filter(|rate| *rate > 0).map(|rate| size as f64 / rate as f64);
+            }
+            _ => {
+                reader.seek(SeekFrom::Current(size as i64 + (size & 1) as i64)).ok()?;
+            }
+        }
+    }
+}
+
+/// MPEG-1 Layer III bitrates in kbit/s, by header index
+const MP3_BITRATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+const MP3_SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+/// Uses the Xing/Info frame count when present (VBR files), otherwise
+/// assumes a constant bitrate from the first frame
+fn mp3_duration<R: Read + Seek>(reader: &mut R) -> Option<f64> {
+    let file_len = reader.seek(SeekFrom::End(0)).ok()?;
+    reader.seek(SeekFrom::Start(0)).ok()?;
+
+    let mut start = 0u64;
+    let mut id3 = [0u8; 10];
+    reader.read_exact(&mut id3).ok()?;
+    if &id3[0..3] == b"ID3" {
+        let size = id3[6..10].iter().fold(0u64, |acc, b| (acc << 7) | (*b & 0x7F) as u64);
+        start = 10 + size;
+    }
+
+    reader.seek(SeekFrom::Start(start)).ok()?;
+    let mut frame = [0u8; 64];
+    reader.read_exact(&mut frame).ok()?;
+    // Frame sync, MPEG-1, Layer III
+    if frame[0] != 0xFF || frame[1] & 0xFE != 0xFA {
+        return None;
+    }
+    let bitrate = *MP3_BITRATES.get((frame[2] >> 4) as usize)?;
+    let sample_rate = *MP3_SAMPLE_RATES.get(((frame[2] >> 2) & 0x03) as usize)?;
+    if bitrate == 0 {
+        return None;
+    }
+
+    let mono = frame[3] >> 6 == 0x03;
+    let side_info = if mono { 17 } else { 32 };
+    let tag = &frame[4 + side_info..4 + side_info + 12];
+    if &tag[0..4] == b"Xing" || &tag[0..4] == b"Info" {
+        let has_frames = tag[7] & 0x01 != 0;
+        if has_frames {
+            let frames = u32::from_be_bytes(tag[8..12].try_into().ok()?);
+            return Some(frames as f64 * 1152.0 / sample_rate as f64);
+        }
+    }
+    Some(file_len.saturating_sub(start) as f64 * 8.0 / (bitrate as f64 * 1000.0))
+}
+
+/// Per-type metadata for a file that has been written to `path`
+pub fn extract_metadata(kind: MediaKind, mime: &str, path: &std::path::Path) -> Value {
+    let mut metadata = Map::new();
+    metadata.insert("kind".to_string(), json!(kind.as_str()));
+
+    match kind {
+        MediaKind::Pdf => {
+            if let Some(pages) = std::fs::read(path).ok().as_deref().and_then(pdf_page_count) {
+                metadata.insert("page_count".to_string(), json!(pages));
+            }
+        }
+        MediaKind::Video | MediaKind::Audio => {
+            let duration = std::fs::File::open(path)
+                .ok()
+                .and_then(|file| media_duration(mime, &mut std::io::BufReader::new(file)));
+            if let Some(seconds) = duration {
+                metadata.insert("duration_seconds".to_string(), json!((seconds * 1000.0).round() / 1000.0));
+            }
+        }
+        MediaKind::Image | MediaKind::Svg | MediaKind::Document => {}
+    }
+    Value::Object(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_mime() {
+        assert_eq!(detect_mime(b"%PDF-1.7\n", "a.pdf"), "application/pdf");
+        assert_eq!(detect_mime(b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>", "x"), SVG_MIME);
+        assert_eq!(detect_mime(b"name,price\nmug,12\n", "prices.csv"), "text/csv");
+        // An extension can't turn a zip into a spreadsheet unless it is a zip
+        assert_eq!(detect_mime(b"PK\x03\x04rest", "report.xlsx"), DOCUMENT_TYPES[4].1);
+        assert_eq!(detect_mime(b"plain", "report.xlsx"), "text/plain");
+        assert_eq!(MediaKind::of_mime("application/zip"), None);
+        assert_eq!(MediaKind::of_mime("video/mp4"), Some(MediaKind::Video));
+    }
+
+    #[test]
+    fn test_sanitize_svg() {
+        let svg = r##"<?xml version="1.0"?>
+<!DOCTYPE svg [<!ENTITY x "boom">]>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" onload="alert(1)">
+  <script>alert(1)</script>
+  <style>.a { fill: url(#g) }</style>
+  <style>@import url(https://evil.example/x.css);</style>
+  <foreignObject><div>hi</div></foreignObject>
+  <a href="java&#x09;script:alert(1)"><rect class="a" width="10" height="10" fill="url(#g)"/></a>
+  <use xlink:href="#shape"/>
+  <image href="https://tracker.example/p.png"/>
+</svg>"##;
+        let clean = sanitize_svg(svg).unwrap();
+
+        assert!(clean.starts_with("<svg"));
+        for bad in ["script", "onload", "ENTITY", "evil.example", "foreignObject", "javascript", "tracker.example"] {
+            assert!(!clean.contains(bad), "{} survived in {}", bad, clean);
+        }
+        assert!(clean.contains(r#"fill="url(#g)""#));
+        assert!(clean.contains(r##"<use xlink:href="#shape"/>"##));
+        assert!(clean.contains(".a { fill: url(#g) }"));
+
+        assert!(sanitize_svg("<html><svg/></html>").is_err());
+        assert!(sanitize_svg("<svg><g></svg>").is_err());
+    }
+
+    #[test]
+    fn test_strip_jpeg_metadata() {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46]); // APP0
+        jpeg.extend_from_slice(&[0xFF, 0xE1, 0x00, 0x08, b'E', b'x', b'i', b'f', 0, 0]); // APP1
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]);
+
+        let stripped = strip_jpeg_metadata(&jpeg).unwrap();
+        assert_eq!(stripped.len(), jpeg.len() - 10);
+        assert!(!stripped.windows(4).any(|w| w == b"Exif"));
+        assert!(stripped.ends_with(&[0x12, 0x34, 0xFF, 0xD9]));
+        assert!(strip_jpeg_metadata(b"not a jpeg").is_none());
+    }
+
+    #[test]
+    fn test_durations() {
+        // ftyp, then mdat, then moov/mvhd (timescale 1000, duration 90500)
+        let mut mp4 = Vec::new();
+        mp4.extend_from_slice(&[0, 0, 0, 16]);
+        mp4.extend_from_slice(b"ftypisom\0\0\0\0");
+        mp4.extend_from_slice(&[0, 0, 0, 12]);
+        mp4.extend_from_slice(b"mdat\0\0\0\0");
+        mp4.extend_from_slice(&[0, 0, 0, 36]);
+        mp4.extend_from_slice(b"moov");
+        mp4.extend_from_slice(&[0, 0, 0, 28]);
+        mp4.extend_from_slice(b"mvhd");
+        mp4.extend_from_slice(&[0; 12]);
+        mp4.extend_from_slice(&1000u32.to_be_bytes());
+        mp4.extend_from_slice(&90500u32.to_be_bytes());
+        assert_eq!(media_duration("video/mp4", &mut Cursor::new(mp4)), Some(90.5));
+
+        // A 64-bit box size that would wrap back to the start of the file
+        let mut looping = Vec::new();
+        looping.extend_from_slice(&[0, 0, 0, 16]);
+        looping.extend_from_slice(b"ftypisom\0\0\0\0");
+        looping.extend_from_slice(&[0, 0, 0, 1]);
+        looping.extend_from_slice(b"free");
+        looping.extend_from_slice(&(u64::MAX - 15).to_be_bytes());
+        assert_eq!(media_duration("video/mp4", &mut Cursor::new(looping)), None);
+
+        // 8kHz mono 16-bit: 16000 bytes/s, 32000 bytes of samples
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF\0\0\0\0WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&[1, 0, 1, 0]);
+        wav.extend_from_slice(&8000u32.to_le_bytes());
+        wav.extend_from_slice(&16000u32.to_le_bytes());
+        wav.extend_from_slice(&[2, 0, 16, 0]);
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&32000u32.to_le_bytes());
+        assert_eq!(media_duration("audio/x-wav", &mut Cursor::new(wav)), Some(2.0));
+    }
+}
@@ -0,0 +1,117 @@
+// Media Policy Service
+// Per-tenant rules for which kinds of media may be uploaded and how large.
+// Stored under `media_policy` in the tenant's settings, e.g.
+//   {"media_policy": {"allowed_kinds": ["image", "pdf"], "max_size_mb": {"pdf": 100}}}
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::schema::tenants;
+use crate::services::media_inspection_service::MediaKind;
+
+/// No tenant can raise a limit beyond this
+const PLATFORM_MAX_SIZE_MB: u64 = 5 * 1024;
+
+const ALL_KINDS: [MediaKind; 6] = [
+    MediaKind::Image,
+    MediaKind::Svg,
+    MediaKind::Pdf,
+    MediaKind::Video,
+    MediaKind::Audio,
+    MediaKind::Document,
+];
+
+fn default_max_size_mb(kind: MediaKind) -> u64 {
+    match kind {
+        MediaKind::Image => 10,
+        MediaKind::Svg => 1,
+        MediaKind::Pdf => 50,
+        MediaKind::Document => 25,
+        MediaKind::Audio => 100,
+        MediaKind::Video => 1024,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct MediaPolicy {
+    /// Kinds that may be uploaded
+    pub allowed_kinds: Vec<MediaKind>,
+    /// Size limit per kind in megabytes; kinds left out use the default
+    pub max_size_mb: HashMap<MediaKind, u64>,
+}
+
+impl Default for MediaPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_kinds: ALL_KINDS.to_vec(),
+            max_size_mb: ALL_KINDS.iter().map(|kind| (*kind, default_max_size_mb(*kind))).collect(),
+        }
+    }
+}
+
+impl MediaPolicy {
+    /// The policy in a tenant's settings; missing or invalid policies fall back to the defaults
+    pub fn from_settings(settings: Option<&serde_json::Value>) -> Self {
+        match settings.and_then(|s| s.get("media_policy")) {
+            Some(value) => serde_json::from_value(value.clone()).unwrap_or_else(|e| {
+                log::warn!("Invalid media_policy in tenant settings, using defaults: {}", e);
+                Self::default()
+            }),
+            None => Self::default(),
+        }
+    }
+
+    pub fn for_tenant(tenant_id: i32, conn: &mut PgConnection) -> QueryResult<Self> {
+        let settings = tenants::table
+            .find(tenant_id)
+            .select(tenants::settings)
+            .first::<Option<serde_json::Value>>(conn)?;
+        Ok(Self::from_settings(settings.as_ref()))
+    }
+
+    pub fn max_bytes(&self, kind: MediaKind) -> u64 {
+        let mb = self
+            .max_size_mb
+            .get(&kind)
+            .copied()
+            .unwrap_or_else(|| default_max_size_mb(kind))
+            .min(PLATFORM_MAX_SIZE_MB);
+        mb * 1024 * 1024
+    }
+
+    /// The kind and size limit for an upload of `mime`, or why it is refused
+    pub fn check(&self, mime: &str) -> Result<(MediaKind, u64), String> {
+        let kind = MediaKind::of_mime(mime).ok_or_else(|| format!("Unsupported file type: {}", mime))?;
+        if !self.allowed_kinds.contains(&kind) {
+            return Err(format!("{} uploads are not enabled for this site", kind.as_str()));
+        }
+        Ok((kind, self.max_bytes(kind)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_policy_from_settings() {
+        let settings = json!({
+            "media_policy": {"allowed_kinds": ["image", "pdf"], "max_size_mb": {"pdf": 100, "image": 99999}}
+        });
+        let policy = MediaPolicy::from_settings(Some(&settings));
+
+        assert_eq!(policy.check("application/pdf"), Ok((MediaKind::Pdf, 100 * 1024 * 1024)));
+        assert_eq!(policy.max_bytes(MediaKind::Image), PLATFORM_MAX_SIZE_MB * 1024 * 1024);
+        assert!(policy.check("video/mp4").unwrap_err().contains("not enabled"));
+        assert!(policy.check("application/x-msdownload").unwrap_err().contains("Unsupported"));
+
+        let defaults = MediaPolicy::from_settings(Some(&json!({"theme": "dark"})));
+        assert_eq!(defaults.check("video/mp4"), Ok((MediaKind::Video, 1024 * 1024 * 1024)));
+        // Kinds missing from a partial map keep their default limit
+        assert_eq!(policy.max_bytes(MediaKind::Svg), 1024 * 1024);
+    }
+}
@@ -0,0 +1,166 @@
+// Media Usage Service
+// Index of the pages and modules that reference each media item. Content
+// refers to media by URL, and every stored file and transform URL carries the
+// media UUID, so the index is rebuilt from the UUIDs found in a page or module
+// whenever it is saved.
+
+use diesel::prelude::*;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::BTreeSet;
+
+use crate::models::media_models::{MediaUsage, NewMediaUsage};
+use crate::schema::{media, media_usages, modules, pages};
+
+pub static UUID_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b").expect("valid UUID pattern")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsageSource {
+    Page,
+    Module,
+}
+
+impl UsageSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageSource::Page => "page",
+            UsageSource::Module => "module",
+        }
+    }
+}
+
+/// Every UUID-shaped string in `texts`, lowercased
+pub fn referenced_uuids<'a>(texts: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+    texts
+        .into_iter()
+        .flat_map(|text| UUID_PATTERN.find_iter(text))
+        .map(|m| m.as_str().to_lowercase())
+        .collect()
+}
+
+/// Replaces the index entries of one page or module with the media its
+/// `texts` reference. Only media of `tenant_id` count, when it is known.
+pub fn index(
+    tenant_id: Option<i32>,
+    source: UsageSource,
+    source_uuid: &str,
+    texts: &[&str],
+    conn: &mut PgConnection,
+) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        remove(source, source_uuid, conn)?;
+
+        let uuids = referenced_uuids(texts.iter().copied());
+        if uuids.is_empty() {
+            return Ok(0);
+        }
+        let mut query = media::table.filter(media::uuid.eq_any(&uuids)).select(media::id).into_boxed();
+        if let Some(tenant_id) = tenant_id {
+            query = query.filter(media::tenant_id.eq(tenant_id));
+        }
+        let usages: Vec<NewMediaUsage> = query
+            .load::<i32>(conn)?
+            .into_iter()
+            .map(|media_id| NewMediaUsage {
+                media_id,
+                source_type: source.as_str().to_string(),
+                source_uuid: source_uuid.to_string(),
+            })
+            .collect();
+
+        diesel::insert_into(media_usages::table)
+            .values(&usages)
+            .on_conflict_do_nothing()
+            .execute(conn)
+    })
+}
+
+/// Re-indexes a page from its stored content and images
+pub fn index_page(page_uuid: &str, conn: &mut PgConnection) -> QueryResult<usize> {
+    let page = pages::table
+        .find(page_uuid)
+        .select((pages::tenant_id, pages::content, pages::og_image, pages::featured_image))
+        .first::<(Option<i32>, Option<String>, Option<String>, Option<String>)>(conn)
+        .optional()?;
+
+    match page {
+        Some((tenant_id, content, og_image, featured_image)) => {
+            let texts: Vec<&str> = [&content, &og_image, &featured_image].iter().copied().flatten().map(String::as_str).collect();
+            index(tenant_id, UsageSource::Page, page_uuid, &texts, conn)
+        }
+        None => remove(UsageSource::Page, page_uuid, conn).map(|_| 0),
+    }
+}
+
+/// Re-indexes a module from its stored content and field config
+pub fn index_module(module_uuid: &str, conn: &mut PgConnection) -> QueryResult<usize> {
+    let module = modules::table
+        .left_join(pages::table)
+        .filter(modules::uuid.eq(module_uuid))
+        .select((modules::tenant_id, pages::tenant_id.nullable(), modules::content, modules::field_config))
+        .first::<(Option<i32>, Option<i32>, String, Option<String>)>(conn)
+        .optional()?;
+
+    match module {
+        Some((tenant_id, page_tenant_id, content, field_config)) => {
+            let mut texts = vec![content.as_str()];
+            texts.extend(field_config.as_deref());
+            index(tenant_id.or(page_tenant_id), UsageSource::Module, module_uuid, &texts, conn)
+        }
+        None => remove(UsageSource::Module, module_uuid, conn).map(|_| 0),
+    }
+}
+
+pub fn remove(source: UsageSource, source_uuid: &str, conn: &mut PgConnection) -> QueryResult<usize> {
+    diesel::delete(
+        media_usages::table
+            .filter(media_usages::source_type.eq(source.as_str()))
+            .filter(media_usages::source_uuid.eq(source_uuid)),
+    )
+    .execute(conn)
+}
+
+/// Drops the entries of a page and of the modules on it; call before deleting the page
+pub fn remove_page(page_uuid: &str, conn: &mut PgConnection) -> QueryResult<usize> {
+    let module_uuids = modules::table
+        .filter(modules::page_uuid.eq(page_uuid))
+        .select(modules::uuid)
+        .load::<String>(conn)?;
+    let modules_removed = diesel::delete(
+        media_usages::table
+            .filter(media_usages::source_type.eq(UsageSource::Module.as_str()))
+            .filter(media_usages::source_uuid.eq_any(&module_uuids)),
+    )
+    .execute(conn)?;
+    Ok(modules_removed + remove(UsageSource::Page, page_uuid, conn)?)
+}
+
+pub fn usages_of(media_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<MediaUsage>> {
+    media_usages::table
+        .filter(media_usages::media_id.eq(media_id))
+        .order((media_usages::source_type.asc(), media_usages::source_uuid.asc()))
+        .load::<MediaUsage>(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_referenced_uuids() {
+        let content = r#"<img src="https://cdn.example.com/3F2504E0-4F89-11D3-9A0C-0305E82C3301-768w.webp">
+            <a href="/media/6ba7b810-9dad-11d1-80b4-00c04fd430c8?w=600&s=ab">x</a>
+            <img src="/uploads/6ba7b810-9dad-11d1-80b4-00c04fd430c8.jpg">"#;
+        let uuids = referenced_uuids([content, "not-a-uuid-1234"]);
+
+        assert_eq!(
+            uuids.into_iter().collect::<Vec<_>>(),
+            vec![
+                "3f2504e0-4f89-11d3-9a0c-0305e82c3301".to_string(),
+                "6ba7b810-9dad-11d1-80b4-00c04fd430c8".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+// Media Variant Service
+// Generates, stores and looks up the responsive variants of uploaded images
+
+use actix_web::web;
+use diesel::prelude::*;
+
+use crate::models::db_connection::DatabasePool;
+use crate::models::media_models::{MediaVariant, NewMediaVariant};
+use crate::schema::media_variants;
+use crate::services::image_service::{self, ImageSource, VariantFormat};
+use crate::services::image_transform_service::TRANSFORM_VARIANT_PREFIX;
+use crate::services::storage_service::{StorageBackend, StorageService};
+
+/// e.g. `768w-webp`
+pub fn variant_name(width: u32, format: VariantFormat) -> String {
+    format!("{}w-{}", width, format.as_str())
+}
+
+/// e.g. `<uuid>-768w.webp`
+pub fn variant_filename(media_uuid: &str, width: u32, format: VariantFormat) -> String {
+    format!("{}-{}w.{}", media_uuid, width, format.extension())
+}
+
+/// Generates the variants of an image, stores them and records them against
+/// the media row. Meant to run in the background after the upload response;
+/// if the media was deleted in the meantime the stored files are removed again.
+pub async fn generate_for_media(
+    media_id: i32,
+    media_uuid: String,
+    source: Vec<u8>,
+    storage: web::Data<StorageBackend>,
+    pool: DatabasePool,
+) -> Result<usize, String> {
+    let variants = web::block(move || image_service::generate_responsive_variants(&source))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let mut saved = Vec::new();
+    for variant in variants {
+        let filename = variant_filename(&media_uuid, variant.width, variant.format);
+        match storage.save(&filename, &variant.data, variant.format.mime_type()).await {
+            Ok(file_path) => saved.push(NewMediaVariant {
+                media_id,
+                variant_name: variant_name(variant.width, variant.format),
+                file_path,
+                width: Some(variant.width as i32),
+                height: Some(variant.height as i32),
+                file_size: Some(variant.data.len() as i64),
+                format: variant.format.as_str().to_string(),
+            }),
+            Err(e) => log::warn!("Failed to store variant {} of media {}: {}", filename, media_uuid, e),
+        }
+    }
+
+    let inserted = pool.get().map_err(|e| e.to_string()).and_then(|mut conn| {
+        diesel::insert_into(media_variants::table)
+            .values(&saved)
+            .on_conflict_do_nothing()
+            .execute(&mut conn)
+            .map_err(|e| e.to_string())
+    });
+    if let Err(e) = inserted {
+        for variant in &saved {
+            let _ = storage.delete(&variant.file_path).await;
+        }
+        return Err(e);
+    }
+    Ok(saved.len())
+}
+
+pub fn variants_for(media_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<MediaVariant>> {
+    media_variants::table
+        .filter(media_variants::media_id.eq(media_id))
+        .order((media_variants::format.asc(), media_variants::width.asc()))
+        .load::<MediaVariant>(conn)
+}
+
+/// Variants made on upload, without on-the-fly transforms
+pub fn responsive_variants_for(media_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<MediaVariant>> {
+    media_variants::table
+        .filter(media_variants::media_id.eq(media_id))
+        .filter(media_variants::variant_name.not_like(format!("{}%", TRANSFORM_VARIANT_PREFIX)))
+        .order((media_variants::format.asc(), media_variants::width.asc()))
+        .load::<MediaVariant>(conn)
+}
+
+/// Public URLs of stored variants, for srcset and <picture>
+pub fn image_sources(variants: &[MediaVariant], storage: &StorageBackend) -> Vec<ImageSource> {
+    variants
+        .iter()
+        .filter_map(|v| {
+            Some(ImageSource {
+                url: storage.get_url(&v.file_path),
+                width: v.width? as u32,
+                format: VariantFormat::parse(&v.format)?,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,228 @@
+// Memory Cache - bounded, TTL-aware in-process LRU
+// Backs CacheServiceV2 when Redis is unavailable, and serves as its optional L1 tier
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Entry {
+    value: String,
+    expires_at: Instant,
+    /// Recency stamp, key into `Inner::recency`
+    last_used: u64,
+    tags: Vec<String>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    /// Least recently used first
+    recency: BTreeMap<u64, String>,
+    tags: HashMap<String, HashSet<String>>,
+    clock: u64,
+}
+
+impl Inner {
+    fn touch(&mut self, key: &str) {
+        self.clock += 1;
+        let stamp = self.clock;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = stamp;
+            self.recency.insert(stamp, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        let Some(entry) = self.entries.remove(key) else { return false };
+        self.recency.remove(&entry.last_used);
+        for tag in &entry.tags {
+            if let Some(keys) = self.tags.get_mut(tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.tags.remove(tag);
+                }
+            }
+        }
+        true
+    }
+
+    fn evict_lru(&mut self) {
+        if let Some(key) = self.recency.values().next().cloned() {
+            self.remove(&key);
+        }
+    }
+}
+
+/// Thread-safe LRU cache of strings with per-entry expiry and tag index
+pub struct MemoryCache {
+    inner: Mutex<Inner>,
+    capacity: usize,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let mut inner = self.inner.lock().unwrap();
+        let expired = inner.entries.get(key)?.expires_at <= Instant::now();
+        if expired {
+            inner.remove(key);
+            return None;
+        }
+        inner.touch(key);
+        inner.entries.get(key).map(|entry| entry.value.clone())
+    }
+
+    pub fn set(&self, key: &str, value: String, ttl: Duration) {
+        self.set_with_tags(key, value, ttl, &[]);
+    }
+
+    /// Stores `value`, replacing any previous entry and its tags, evicting the
+    /// least recently used entries beyond capacity
+    pub fn set_with_tags(&self, key: &str, value: String, ttl: Duration, tags: &[String]) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.remove(key);
+
+        while inner.entries.len() >= self.capacity {
+            inner.evict_lru();
+        }
+
+        for tag in tags {
+            inner.tags.entry(tag.clone()).or_default().insert(key.to_string());
+        }
+        inner.entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                expires_at: Instant::now() + ttl,
+                // Stamps start at 1, so 0 is never in `recency`
+                last_used: 0,
+                tags: tags.to_vec(),
+            },
+        );
+        inner.touch(key);
+    }
+
+    pub fn delete(&self, key: &str) -> bool {
+        self.inner.lock().unwrap().remove(key)
+    }
+
+    /// Deletes keys matching a Redis-style glob (`*` and `?`)
+    pub fn delete_pattern(&self, pattern: &str) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let keys: Vec<String> = inner.entries.keys().filter(|key| glob_match(pattern, key)).cloned().collect();
+        for key in &keys {
+            inner.remove(key);
+        }
+        keys.len()
+    }
+
+    /// Deletes every entry carrying any of `tags`
+    pub fn purge_tags(&self, tags: &[String]) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let keys: HashSet<String> = tags
+            .iter()
+            .filter_map(|tag| inner.tags.get(tag))
+            .flatten()
+            .cloned()
+            .collect();
+        for key in &keys {
+            inner.remove(key);
+        }
+        keys.len()
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Redis `KEYS` glob subset: `*` matches any run, `?` any single character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        cache.set("a", "1".to_string(), MINUTE);
+        cache.set("b", "2".to_string(), MINUTE);
+        assert_eq!(cache.get("a").as_deref(), Some("1"));
+
+        cache.set("c", "3".to_string(), MINUTE);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn test_expired_entries_are_misses() {
+        let cache = MemoryCache::new(10);
+        cache.set("gone", "x".to_string(), Duration::ZERO);
+        assert!(cache.get("gone").is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_purge_tags_and_patterns() {
+        let cache = MemoryCache::new(10);
+        cache.set_with_tags("page:1:/a:html", "a".to_string(), MINUTE, &["page:p1".to_string(), "tenant:1".to_string()]);
+        cache.set_with_tags("page:1:/b:html", "b".to_string(), MINUTE, &["page:p2".to_string(), "tenant:1".to_string()]);
+        cache.set("other", "c".to_string(), MINUTE);
+
+        assert_eq!(cache.purge_tags(&["page:p1".to_string()]), 1);
+        assert!(cache.get("page:1:/b:html").is_some());
+        assert_eq!(cache.purge_tags(&["tenant:1".to_string()]), 1);
+
+        cache.set("page:2:/x:html", "x".to_string(), MINUTE);
+        assert_eq!(cache.delete_pattern("page:2:*"), 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("page:*:html", "page:1:/about:html"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(!glob_match("page:*", "pages"));
+        assert!(glob_match("*", ""));
+    }
+}
@@ -4,7 +4,9 @@ pub mod analytics_service;
 pub mod auth_service;
 pub mod database_service;
 pub mod revision_service;
+pub mod revision_diff_service; // Revision diffs & three-way merge
 pub mod inventory_service;
+pub mod stock_reservation_service; // Stock held for unpaid orders
 
 // AI/MCP Services - Phase 0: Security layer only for v1.4.0
 pub mod ai_scope_service; // AI security and scoping
@@ -14,6 +16,7 @@ pub mod ai_provider_service; // Provider CRUD operations
 pub mod ai_content_service; // Content generation
 pub mod ai_authorization_service; // Budget and permission checks
 pub mod storage_service; // Multi-region storage (S3/Local)
+pub mod storage_migration_service; // Move media between local storage and S3
 pub mod semantic_search_service;
 pub mod recommendation_service; // Content recommendations
 
@@ -54,11 +57,14 @@ pub mod voice_search; // Voice search optimization
 pub mod inventory_analytics; // Inventory analytics & insights
 pub mod product_reviews; // Product reviews & ratings
 pub mod cart_abandonment; // Cart abandonment tracking
+pub mod cart_service; // Persistent carts and checkout sessions
 pub mod wishlist; // Wishlist system
 pub mod product_bundles; // Product bundling
 pub mod conversion_analytics; // Conversion funnel analytics
 pub mod product_import_export; // Bulk import/export
 pub mod order_management; // Order processing
+pub mod order_service; // Order placement and line snapshots
+pub mod refund_service; // Refunds of order payments
 pub mod payment_processor; // Payment handling
 pub mod shipping_manager; // Shipping & fulfillment
 pub mod customer_support; // Support tickets
@@ -78,16 +84,27 @@ pub mod field_validation_service;
 pub mod permission_service;
 pub mod search_service;
 pub mod cache_service_v2;
+pub mod memory_cache; // In-process fallback / L1 for cache_service_v2
 pub mod webhook_service;
 pub mod image_service;
+pub mod image_transform_service; // Signed on-the-fly resize/crop/format for /media/{uuid}
+pub mod media_inspection_service; // Upload type detection, SVG sanitizing and metadata
+pub mod media_folder_service; // Hierarchical media folders with move/rename
+pub mod media_policy_service; // Per-tenant upload types and size limits
+pub mod media_usage_service; // Which pages and modules reference each media item
+pub mod media_variant_service; // Responsive image variants stored per media item
 pub mod plugin_service;
 pub mod template_service;
+pub mod theme_package_service; // Unpacks and validates uploaded themes
+pub mod redirect_service; // Per-tenant redirects for public pages
 pub mod totp_service;
 pub mod backup_service;
+pub mod tenant_backup_service; // Per-tenant logical backup archives, restore and retention
 pub mod payment_service;
 pub mod scheduler_service;
 pub mod metadata_automation_service; // AI metadata generation
 pub mod encryption_service; // Phase 2: API key encryption
+pub mod embedding_refresh_service; // Re-embeds pages when content changes
 // Services below require migrations to be run first
 pub mod language_service;
 pub mod analytics_service_v2;
@@ -1,6 +1,12 @@
+use actix_web::web;
 use serde::{Deserialize, Serialize};
 use log::info;
 
+use crate::models::DbPool;
+use crate::services::email_service::EmailService;
+use crate::services::order_service::{self, Actor, Transition};
+use crate::services::payment_service::PaymentHandlerRegistry;
+
 /// Order Management System
 /// Manage e-commerce orders
 pub struct OrderManagement;
@@ -28,15 +34,7 @@ pub struct OrderItem {
 }
 
 /// Order status
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum OrderStatus {
-    Pending,
-    Processing,
-    Shipped,
-    Delivered,
-    Cancelled,
-    Refunded,
-}
+pub use crate::services::order_service::OrderStatus;
 
 impl OrderManagement {
     /// Create order
@@ -68,11 +66,22 @@ impl OrderManagement {
         })
     }
 
-    /// Update order status
-    pub async fn update_status(&self, order_id: &str, status: OrderStatus) -> Result<(), String> {
-        info!("Updating order {} to {:?}", order_id, status);
-        // TODO: Update database
-        Ok(())
+    /// Update order status, rejecting changes the order lifecycle doesn't allow
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_status(
+        &self,
+        order_id: i64,
+        status: OrderStatus,
+        actor: &Actor,
+        reason: Option<String>,
+        registry: &PaymentHandlerRegistry,
+        pool: &web::Data<DbPool>,
+        email_service: &EmailService,
+    ) -> Result<Transition, String> {
+        info!("Updating order {} to {}", order_id, status);
+        order_service::change_status(order_id, status, actor, reason, registry, pool, email_service)
+            .await
+            .map_err(|e| e.to_string())
     }
 
     /// Get order analytics
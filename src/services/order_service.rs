@@ -0,0 +1,698 @@
+// Order Service
+// Placing orders and moving them through their lifecycle. Order lines copy
+// the product as it was at purchase time, so order history and invoices stay
+// exact when the catalogue changes; every status change is recorded.
+
+use actix_web::web;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use utoipa::ToSchema;
+
+use crate::models::commerce_models::{
+    NewOrderItem, NewOrderStatusChange, Order, OrderItem, OrderStatusChange, Product,
+};
+use crate::models::DbPool;
+use crate::schema::{order_items, order_status_history, orders, product_variants, products, users};
+use crate::services::email_service::EmailService;
+use crate::services::errors_service::CustomHttpError;
+use crate::services::payment_service::{PaymentHandlerRegistry, PaymentStatus};
+use crate::services::stock_reservation_service;
+use crate::services::webhook_service::WebhookService;
+
+/// A line to add to an order, at the price being charged
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderLine {
+    pub product_id: i64,
+    pub variant_id: Option<i32>,
+    pub quantity: i32,
+    pub unit_price_cents: i64,
+    /// Tax on the whole line
+    pub tax_cents: i64,
+    /// Discount on the whole line
+    pub discount_cents: i64,
+}
+
+/// The parts of a variant an order line keeps
+struct VariantSnapshot {
+    product_id: i64,
+    sku: Option<String>,
+    name: String,
+    attributes: Option<serde_json::Value>,
+}
+
+fn snapshot(order_id: i64, line: &OrderLine, product: &Product, variant: Option<&VariantSnapshot>) -> NewOrderItem {
+    NewOrderItem {
+        order_id,
+        product_id: line.product_id,
+        quantity: line.quantity,
+        price_cents: line.unit_price_cents,
+        variant_id: line.variant_id,
+        product_name: product.name.clone(),
+        sku: variant.and_then(|v| v.sku.clone()).or_else(|| product.sku.clone()),
+        variant_name: variant.map(|v| v.name.clone()),
+        variant_attributes: variant.and_then(|v| v.attributes.clone()),
+        tax_cents: line.tax_cents,
+        discount_cents: line.discount_cents,
+    }
+}
+
+/// Adds `lines` to an order with a snapshot of each product and variant
+pub fn add_items(order_id: i64, lines: &[OrderLine], conn: &mut PgConnection) -> Result<Vec<OrderItem>, CustomHttpError> {
+    let product_ids: Vec<i64> = lines.iter().map(|line| line.product_id).collect();
+    let variant_ids: Vec<i32> = lines.iter().filter_map(|line| line.variant_id).collect();
+    let products: HashMap<i64, Product> = products::table
+        .filter(products::id.eq_any(&product_ids))
+        .load::<Product>(conn)?
+        .into_iter()
+        .map(|product| (product.id, product))
+        .collect();
+    let variants: HashMap<i32, VariantSnapshot> = product_variants::table
+        .filter(product_variants::id.eq_any(&variant_ids))
+        .select((
+            product_variants::id,
+            product_variants::product_id,
+            product_variants::sku,
+            product_variants::variant_name,
+            product_variants::attributes,
+        ))
+        .load::<(i32, i64, Option<String>, String, Option<serde_json::Value>)>(conn)?
+        .into_iter()
+        .map(|(id, product_id, sku, name, attributes)| (id, VariantSnapshot { product_id, sku, name, attributes }))
+        .collect();
+
+    let mut items = Vec::with_capacity(lines.len());
+    for line in lines {
+        let product = products
+            .get(&line.product_id)
+            .ok_or(CustomHttpError::BadRequest(format!("Product {} not found", line.product_id)))?;
+        let variant = match line.variant_id {
+            Some(variant_id) => Some(
+                variants
+                    .get(&variant_id)
+                    .filter(|variant| variant.product_id == product.id)
+                    .ok_or(CustomHttpError::BadRequest(format!("Variant {} not found", variant_id)))?,
+            ),
+            None => None,
+        };
+        items.push(snapshot(order_id, line, product, variant));
+    }
+
+    Ok(diesel::insert_into(order_items::table)
+        .values(&items)
+        .returning(OrderItem::as_returning())
+        .get_results::<OrderItem>(conn)?)
+}
+
+/// Order lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Pending,
+    Paid,
+    Fulfilling,
+    Shipped,
+    Delivered,
+    Cancelled,
+    Refunded,
+    PartiallyRefunded,
+    /// Paid for, but the order couldn't go ahead: its stock sold out after
+    /// the reservation expired, or it was cancelled first. Staff refund it,
+    /// or mark it paid once the stock is back.
+    OnHold,
+}
+
+impl OrderStatus {
+    pub const ALL: [OrderStatus; 9] = [
+        OrderStatus::Pending,
+        OrderStatus::Paid,
+        OrderStatus::Fulfilling,
+        OrderStatus::Shipped,
+        OrderStatus::Delivered,
+        OrderStatus::Cancelled,
+        OrderStatus::Refunded,
+        OrderStatus::PartiallyRefunded,
+        OrderStatus::OnHold,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Paid => "paid",
+            OrderStatus::Fulfilling => "fulfilling",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Refunded => "refunded",
+            OrderStatus::PartiallyRefunded => "partially_refunded",
+            OrderStatus::OnHold => "on_hold",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|status| status.as_str() == value)
+    }
+
+    /// Whether an order may move from this status to `next`. Unpaid orders
+    /// are cancelled; paid ones are refunded. A payment that can't complete
+    /// its order puts it on hold. How far a partially refunded order may
+    /// still be fulfilled depends on where it stood, see `can_resume`.
+    pub fn can_become(self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Paid | Cancelled | OnHold)
+                | (Cancelled, OnHold)
+                | (OnHold, Paid | Refunded | PartiallyRefunded)
+                | (Paid, Fulfilling | Refunded | PartiallyRefunded)
+                | (Fulfilling, Shipped | Refunded | PartiallyRefunded)
+                | (Shipped, Delivered | Refunded | PartiallyRefunded)
+                | (Delivered, Refunded | PartiallyRefunded)
+                | (PartiallyRefunded, Refunded | PartiallyRefunded)
+        )
+    }
+
+    /// Whether a partially refunded order whose fulfilment had reached
+    /// `stage` may move to `next`: refunded again, or fulfilled further
+    pub fn can_resume(stage: OrderStatus, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        OrderStatus::PartiallyRefunded.can_become(next)
+            || (matches!(next, Fulfilling | Shipped | Delivered) && stage.can_become(next))
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Who changed an order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Actor {
+    /// "customer", "staff", or a system such as "stripe"
+    pub name: String,
+    pub user_id: Option<i32>,
+}
+
+impl Actor {
+    pub fn user(name: &str, user_id: i32) -> Self {
+        Self { name: name.to_string(), user_id: Some(user_id) }
+    }
+
+    pub fn system(name: &str) -> Self {
+        Self { name: name.to_string(), user_id: None }
+    }
+}
+
+/// A status change that has been saved
+#[derive(Debug, Clone)]
+pub struct Transition {
+    pub order: Order,
+    pub change: OrderStatusChange,
+}
+
+/// How far a paid order got in fulfilment, from its history
+fn fulfilment_stage(order_id: i64, conn: &mut PgConnection) -> QueryResult<OrderStatus> {
+    // An order refunded while on hold was never fulfilled
+    let stages = [OrderStatus::OnHold, OrderStatus::Paid, OrderStatus::Fulfilling, OrderStatus::Shipped, OrderStatus::Delivered]
+        .map(|s| s.as_str());
+    let last = order_status_history::table
+        .filter(order_status_history::order_id.eq(order_id))
+        .filter(order_status_history::to_status.eq_any(stages))
+        .order(order_status_history::id.desc())
+        .select(order_status_history::to_status)
+        .first::<String>(conn)
+        .optional()?;
+    Ok(last.as_deref().and_then(OrderStatus::parse).unwrap_or(OrderStatus::Paid))
+}
+
+/// Starts an order's history; call when the order is inserted
+pub fn record_created(order_id: i64, actor: &Actor, conn: &mut PgConnection) -> QueryResult<()> {
+    diesel::insert_into(order_status_history::table)
+        .values(&NewOrderStatusChange {
+            order_id,
+            from_status: None,
+            to_status: OrderStatus::Pending.to_string(),
+            actor: actor.name.clone(),
+            actor_user_id: actor.user_id,
+            reason: None,
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Moves an order to `to` if its lifecycle allows it, and records the
+/// change. Paying takes the order's reserved stock; cancelling gives it back.
+pub fn transition(
+    order_id: i64,
+    to: OrderStatus,
+    actor: &Actor,
+    reason: Option<String>,
+    conn: &mut PgConnection,
+) -> Result<Transition, CustomHttpError> {
+    conn.transaction(|conn| {
+        let order = orders::table
+            .find(order_id)
+            .select(Order::as_select())
+            .for_update()
+            .first::<Order>(conn)
+            .optional()?
+            .ok_or(CustomHttpError::NotFound("Order not found".to_string()))?;
+        let from = OrderStatus::parse(&order.status)
+            .ok_or(CustomHttpError::InternalServerError(format!("Unknown order status {}", order.status)))?;
+        let allowed = match from {
+            OrderStatus::PartiallyRefunded => OrderStatus::can_resume(fulfilment_stage(order_id, conn)?, to),
+            _ => from.can_become(to),
+        };
+        if !allowed {
+            return Err(CustomHttpError::BadRequest(format!("Cannot change order from {} to {}", from, to)));
+        }
+
+        match to {
+            OrderStatus::Paid => {
+                stock_reservation_service::commit(order_id, conn)?;
+            }
+            OrderStatus::Cancelled => {
+                stock_reservation_service::release(order_id, conn)?;
+            }
+            _ => {}
+        }
+
+        let order = diesel::update(orders::table.find(order_id))
+            .set((orders::status.eq(to.as_str()), orders::updated_at.eq(chrono::Utc::now().naive_utc())))
+            .returning(Order::as_returning())
+            .get_result::<Order>(conn)?;
+        let change = diesel::insert_into(order_status_history::table)
+            .values(&NewOrderStatusChange {
+                order_id,
+                from_status: Some(from.to_string()),
+                to_status: to.to_string(),
+                actor: actor.name.clone(),
+                actor_user_id: actor.user_id,
+                reason,
+            })
+            .get_result::<OrderStatusChange>(conn)?;
+        Ok(Transition { order, change })
+    })
+}
+
+/// An order's status changes, oldest first
+pub fn history(order_id: i64, conn: &mut PgConnection) -> QueryResult<Vec<OrderStatusChange>> {
+    order_status_history::table
+        .filter(order_status_history::order_id.eq(order_id))
+        .order((order_status_history::created_at, order_status_history::id))
+        .load::<OrderStatusChange>(conn)
+}
+
+/// Where to email the customer: the checkout email, else the account's one
+fn customer_email(order: &Order, conn: &mut PgConnection) -> QueryResult<Option<String>> {
+    if let Some(email) = order.metadata.as_ref().and_then(|m| m["email"].as_str()) {
+        return Ok(Some(email.to_string()));
+    }
+    let Ok(user_id) = order.user_uuid.parse::<i32>() else {
+        return Ok(None);
+    };
+    Ok(users::table
+        .find(user_id)
+        .select(users::email)
+        .first::<Option<String>>(conn)
+        .optional()?
+        .flatten())
+}
+
+/// Subject and message of the customer email for a status, if it gets one
+fn email_copy(status: OrderStatus) -> Option<(&'static str, &'static str)> {
+    match status {
+        OrderStatus::Shipped => Some(("Shipped", "Your order is on its way.")),
+        OrderStatus::Delivered => Some(("Delivered", "Your order has been delivered. We hope you enjoy it!")),
+        OrderStatus::Cancelled => Some(("Cancelled", "Your order has been cancelled.")),
+        OrderStatus::Refunded => Some(("Refunded", "Your order has been refunded.")),
+        OrderStatus::PartiallyRefunded => Some(("Partially Refunded", "Part of your order has been refunded.")),
+        OrderStatus::OnHold => Some(("On Hold", "We received your payment but can't complete your order right now. We'll be in touch.")),
+        OrderStatus::Pending | OrderStatus::Paid | OrderStatus::Fulfilling => None,
+    }
+}
+
+pub fn format_cents(cents: i64) -> String {
+    format!("{}{}.{:02}", if cents < 0 { "-" } else { "" }, cents.abs() / 100, cents.abs() % 100)
+}
+
+/// Sends the `order.<status>` webhook and the customer email for a
+/// transition. Failures are logged; the transition is already saved.
+pub async fn notify(transition: &Transition, pool: &web::Data<DbPool>, email_service: &EmailService) {
+    let order = &transition.order;
+    let Some(status) = OrderStatus::parse(&transition.change.to_status) else {
+        return;
+    };
+
+    if let Some(tenant_id) = order.tenant_id {
+        WebhookService::new()
+            .trigger(
+                pool,
+                tenant_id,
+                &format!("order.{}", status),
+                "order",
+                &order.uuid,
+                serde_json::json!({
+                    "order": order,
+                    "from_status": transition.change.from_status,
+                    "to_status": transition.change.to_status,
+                    "reason": transition.change.reason,
+                }),
+            )
+            .await;
+    }
+
+    let details = pool.get().map_err(|e| e.to_string()).and_then(|mut conn| {
+        let email = customer_email(order, &mut conn).map_err(|e| e.to_string())?;
+        let items = order_items::table
+            .filter(order_items::order_id.eq(order.id))
+            .order(order_items::id)
+            .select(OrderItem::as_select())
+            .load::<OrderItem>(&mut conn)
+            .map_err(|e| e.to_string())?;
+        Ok((email, items))
+    });
+    let (email, items) = match details {
+        Ok((Some(email), items)) => (email, items),
+        Ok((None, _)) => return,
+        Err(e) => {
+            log::error!("Order {} email skipped: {}", order.uuid, e);
+            return;
+        }
+    };
+
+    let base_url = std::env::var("APP_BASE_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
+    let order_link = format!("{}/orders/{}", base_url, order.uuid);
+    let sent = if status == OrderStatus::Paid {
+        let data = serde_json::json!({
+            "customer_name": "Valued Customer",
+            "order_id": order.id,
+            "items": items.iter().map(|item| serde_json::json!({
+                "quantity": item.quantity,
+                "product_name": match &item.variant_name {
+                    Some(variant) => format!("{} - {}", item.product_name, variant),
+                    None => item.product_name.clone(),
+                },
+                "price": format_cents(item.line_total_cents()),
+            })).collect::<Vec<_>>(),
+            "total_amount": format_cents(order.total_amount_cents),
+            "order_link": order_link,
+        });
+        email_service
+            .send_template_email(&email, &format!("Order #{} confirmed", order.id), "commerce/order_confirmation", &data, order.tenant_id)
+            .await
+    } else if let Some((label, message)) = email_copy(status) {
+        let data = serde_json::json!({
+            "customer_name": "Valued Customer",
+            "order_id": order.id,
+            "status_label": label,
+            "message": message,
+            "reason": transition.change.reason,
+            "order_link": order_link,
+        });
+        email_service
+            .send_template_email(&email, &format!("Order #{} {}", order.id, label.to_lowercase()), "commerce/order_status", &data, order.tenant_id)
+            .await
+    } else {
+        Ok(())
+    };
+    if let Err(e) = sent {
+        log::error!("Order {} email failed: {}", order.uuid, e);
+    }
+}
+
+/// Cancels a pending order's payment with its provider, so it can't be paid
+/// once the order is cancelled. Fails while the provider is processing or
+/// has taken the payment; such an order is refunded once paid instead.
+async fn cancel_payment(order: &Order, registry: &PaymentHandlerRegistry) -> Result<(), CustomHttpError> {
+    let (Some(provider), Some(intent_id)) = (&order.payment_provider, &order.payment_intent_id) else {
+        return Ok(());
+    };
+    if order.status != OrderStatus::Pending.as_str() {
+        return Ok(());
+    }
+    let handler = registry
+        .get(provider)
+        .ok_or(CustomHttpError::BadRequest(format!("Payment provider {} is not configured", provider)))?;
+    let status = match handler.cancel_payment_intent(intent_id).await {
+        Ok(intent) => intent.status,
+        // Already taken, or a provider that can't cancel payments
+        Err(e) => {
+            log::warn!("Order {}: failed to cancel payment {}: {}", order.uuid, intent_id, e);
+            handler
+                .get_payment_intent(intent_id)
+                .await
+                .map_err(|e| {
+                    log::error!("Order {}: failed to fetch payment {}: {}", order.uuid, intent_id, e);
+                    CustomHttpError::InternalServerError("Failed to fetch the order's payment".to_string())
+                })?
+                .status
+        }
+    };
+    match status {
+        PaymentStatus::Processing | PaymentStatus::Succeeded => Err(CustomHttpError::BadRequest(
+            "The order's payment is being processed; refund the order once it is paid instead".to_string(),
+        )),
+        // A payment that still goes through puts the order on hold, see `settle_payment`
+        PaymentStatus::Pending | PaymentStatus::Failed | PaymentStatus::Canceled => Ok(()),
+    }
+}
+
+/// Moves an order to `to` for staff or the customer and announces the change
+/// with `notify`. Cancelling a pending order cancels its payment first.
+pub async fn change_status(
+    order_id: i64,
+    to: OrderStatus,
+    actor: &Actor,
+    reason: Option<String>,
+    registry: &PaymentHandlerRegistry,
+    pool: &web::Data<DbPool>,
+    email_service: &EmailService,
+) -> Result<Transition, CustomHttpError> {
+    let pool_error = |_| CustomHttpError::InternalServerError("Pool connection failed".to_string());
+    if to == OrderStatus::Cancelled {
+        let mut conn = pool.get().map_err(pool_error)?;
+        let order = orders::table
+            .find(order_id)
+            .select(Order::as_select())
+            .first::<Order>(&mut conn)
+            .optional()?
+            .ok_or(CustomHttpError::NotFound("Order not found".to_string()))?;
+        drop(conn);
+        cancel_payment(&order, registry).await?;
+    }
+
+    let mut conn = pool.get().map_err(pool_error)?;
+    let transition = transition(order_id, to, actor, reason, &mut conn)?;
+    drop(conn);
+    notify(&transition, pool, email_service).await;
+    Ok(transition)
+}
+
+/// Records the payment outcome reported by `provider` on the order and moves
+/// it along its lifecycle: a successful payment marks it paid (taking its
+/// reserved stock), a cancelled one cancels it (giving the stock back). After
+/// a failed attempt the customer may still retry, so the order stays pending.
+/// A successful payment for an order that can't be paid, because its stock
+/// sold out after the reservation expired or it was cancelled, puts the order
+/// on hold so staff can refund it.
+/// Returns the order as it now is, or `None` if there is no such order.
+pub async fn settle_payment(
+    pool: &web::Data<DbPool>,
+    email_service: &EmailService,
+    order_uuid: &str,
+    payment_status: &str,
+    provider: &str,
+) -> Result<Option<Order>, String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    // Once an order is past payment, its payment status belongs to refunds
+    // and a late or repeated webhook leaves it alone
+    let updated = diesel::update(
+        orders::table
+            .filter(orders::uuid.eq(order_uuid))
+            .filter(orders::status.eq(OrderStatus::Pending.as_str())),
+    )
+    .set((
+        orders::payment_status.eq(payment_status),
+        orders::updated_at.eq(chrono::Utc::now().naive_utc()),
+    ))
+    .returning(Order::as_returning())
+    .get_result::<Order>(&mut conn)
+    .optional()
+    .map_err(|e| e.to_string())?;
+    let order = match updated {
+        Some(order) => Some(order),
+        None => orders::table
+            .filter(orders::uuid.eq(order_uuid))
+            .select(Order::as_select())
+            .first::<Order>(&mut conn)
+            .optional()
+            .map_err(|e| e.to_string())?,
+    };
+    let Some(order) = order else {
+        log::warn!("Payment for unknown order {}", order_uuid);
+        return Ok(None);
+    };
+
+    let (next, reason) = match payment_status {
+        "succeeded" => (OrderStatus::Paid, "Payment succeeded"),
+        "canceled" => (OrderStatus::Cancelled, "Payment was cancelled"),
+        _ => return Ok(Some(order)),
+    };
+    // Webhooks are retried and payments confirmed more than once; a repeat
+    // finds the order already moved on
+    if order.status == next.as_str() {
+        return Ok(Some(order));
+    }
+    let actor = Actor::system(provider);
+    let transition = match transition(order.id, next, &actor, Some(reason.to_string()), &mut conn) {
+        Ok(transition) => transition,
+        // The customer has paid for an order that can't go ahead
+        Err(CustomHttpError::BadRequest(e)) if next == OrderStatus::Paid => {
+            log::warn!("Order {}: payment succeeded but {}; holding it for review", order_uuid, e);
+            let reason = format!("Payment succeeded but the order can't be completed: {}", e);
+            match transition(order.id, OrderStatus::OnHold, &actor, Some(reason), &mut conn) {
+                // A cancelled order's payment status wasn't updated above
+                Ok(mut held) => {
+                    held.order = diesel::update(orders::table.find(order.id))
+                        .set(orders::payment_status.eq(payment_status))
+                        .returning(Order::as_returning())
+                        .get_result::<Order>(&mut conn)
+                        .map_err(|e| e.to_string())?;
+                    held
+                }
+                Err(CustomHttpError::BadRequest(e)) => {
+                    log::error!("Order {}: paid but not held for review: {}", order_uuid, e);
+                    return Ok(Some(order));
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        // Nothing a retry would fix, e.g. cancelled after the order was paid
+        Err(CustomHttpError::BadRequest(e)) => {
+            log::warn!("Order {}: {}", order_uuid, e);
+            return Ok(Some(order));
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    drop(conn);
+    notify(&transition, pool, email_service).await;
+    Ok(Some(transition.order))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_prefers_variant_details() {
+        let now = chrono::Utc::now().naive_utc();
+        let product = Product {
+            id: 7,
+            uuid: "p".to_string(),
+            name: "T-Shirt".to_string(),
+            description: None,
+            price_cents: 2000,
+            sku: Some("TS".to_string()),
+            inventory_count: None,
+            is_active: true,
+            created_at: now,
+            updated_at: now,
+            stock_quantity: 0,
+            low_stock_threshold: None,
+            stock_status: None,
+            track_inventory: None,
+            allow_backorder: None,
+            backorder_limit: None,
+            tenant_id: None,
+        };
+        let line = OrderLine { product_id: 7, variant_id: Some(3), quantity: 2, unit_price_cents: 2500, tax_cents: 0, discount_cents: 0 };
+        let variant = VariantSnapshot {
+            product_id: 7,
+            sku: Some("TS-L".to_string()),
+            name: "Large".to_string(),
+            attributes: Some(serde_json::json!({ "size": "L" })),
+        };
+
+        let item = snapshot(1, &line, &product, Some(&variant));
+        assert_eq!(item.product_name, "T-Shirt");
+        assert_eq!(item.sku.as_deref(), Some("TS-L"));
+        assert_eq!(item.variant_name.as_deref(), Some("Large"));
+        assert_eq!(item.price_cents, 2500);
+
+        let plain = snapshot(1, &OrderLine { variant_id: None, ..line }, &product, None);
+        assert_eq!(plain.sku.as_deref(), Some("TS"));
+        assert!(plain.variant_attributes.is_none());
+    }
+
+    #[test]
+    fn test_order_transitions() {
+        use OrderStatus::*;
+        assert!(Pending.can_become(Paid));
+        assert!(Pending.can_become(Cancelled));
+        assert!(Paid.can_become(Fulfilling));
+        assert!(Shipped.can_become(Delivered));
+        assert!(PartiallyRefunded.can_become(PartiallyRefunded));
+        assert!(Delivered.can_become(Refunded));
+
+        assert!(!Pending.can_become(Shipped));
+        assert!(!Paid.can_become(Cancelled));
+        assert!(!Delivered.can_become(Shipped));
+        assert!(!Paid.can_become(Paid));
+        assert!(!PartiallyRefunded.can_become(Fulfilling));
+        for status in OrderStatus::ALL {
+            // Only a payment that lands after cancelling reopens the order
+            assert_eq!(Cancelled.can_become(status), status == OnHold);
+            assert!(!Refunded.can_become(status));
+            assert_eq!(OrderStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(OrderStatus::parse("completed"), None);
+    }
+
+    #[test]
+    fn test_unfulfillable_paid_orders_are_held_for_refund() {
+        use OrderStatus::*;
+        // Sold out after the stock reservation expired: still pending
+        assert!(Pending.can_become(OnHold));
+        // Paid after being cancelled
+        assert!(Cancelled.can_become(OnHold));
+        assert!(!Paid.can_become(OnHold));
+        assert!(!OnHold.can_become(OnHold));
+
+        // Refunds go through, and fulfilment only after marking it paid
+        assert!(OnHold.can_become(Refunded));
+        assert!(OnHold.can_become(PartiallyRefunded));
+        assert!(OnHold.can_become(Paid));
+        assert!(!OnHold.can_become(Fulfilling));
+        assert!(!OnHold.can_become(Cancelled));
+        assert!(!OrderStatus::can_resume(OnHold, Fulfilling));
+        assert!(OrderStatus::can_resume(OnHold, Refunded));
+    }
+
+    #[test]
+    fn test_partially_refunded_orders_only_move_forward() {
+        use OrderStatus::*;
+        assert!(OrderStatus::can_resume(Paid, Fulfilling));
+        assert!(OrderStatus::can_resume(Fulfilling, Shipped));
+        assert!(OrderStatus::can_resume(Shipped, Delivered));
+        assert!(OrderStatus::can_resume(Delivered, Refunded));
+        assert!(OrderStatus::can_resume(Shipped, PartiallyRefunded));
+
+        assert!(!OrderStatus::can_resume(Shipped, Fulfilling));
+        assert!(!OrderStatus::can_resume(Delivered, Shipped));
+        assert!(!OrderStatus::can_resume(Delivered, Fulfilling));
+        assert!(!OrderStatus::can_resume(Fulfilling, Fulfilling));
+        assert!(!OrderStatus::can_resume(Paid, Shipped));
+        assert!(!OrderStatus::can_resume(Paid, Cancelled));
+    }
+
+    #[test]
+    fn test_format_cents() {
+        assert_eq!(format_cents(1999), "19.99");
+        assert_eq!(format_cents(5), "0.05");
+        assert_eq!(format_cents(-250), "-2.50");
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use log::info;
 
+use crate::services::payment_service::{PaymentHandler, Refund, RefundRequest};
+
 /// Payment Processing
 /// Handle payment transactions
 pub struct PaymentProcessor;
@@ -59,11 +61,21 @@ impl PaymentProcessor {
         })
     }
 
-    /// Refund payment
-    pub async fn refund(&self, payment_id: &str, amount: f64) -> Result<(), String> {
-        info!("Refunding payment {}: ${:.2}", payment_id, amount);
-        // TODO: Process refund
-        Ok(())
+    /// Refund payment through its provider. Orders should be refunded with
+    /// `refund_service::refund_order`, which also records the refund.
+    pub async fn refund(&self, handler: &dyn PaymentHandler, request: RefundRequest) -> Result<Refund, String> {
+        if request.amount_cents <= 0 {
+            return Err("Invalid amount".to_string());
+        }
+
+        info!(
+            "Refunding {} payment {}: {:.2} {}",
+            handler.provider_name(),
+            request.intent_id,
+            request.amount_cents as f64 / 100.0,
+            request.currency.to_uppercase()
+        );
+        handler.refund_payment(request).await
     }
 }
 
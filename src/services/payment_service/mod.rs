@@ -35,6 +35,101 @@ pub struct CreatePaymentIntentRequest {
     pub metadata: HashMap<String, String>,
 }
 
+/// Request to refund all or part of a captured payment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefundRequest {
+    /// The payment intent being refunded
+    pub intent_id: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    /// Sent to the provider so a retried refund is only made once
+    pub idempotency_key: String,
+    pub reason: Option<String>,
+}
+
+/// Represents a refund result from any provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Refund {
+    pub id: String,
+    pub intent_id: String,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub status: RefundStatus,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RefundStatus {
+    Pending,
+    Succeeded,
+    Failed,
+    Canceled,
+}
+
+impl RefundStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefundStatus::Pending => "pending",
+            RefundStatus::Succeeded => "succeeded",
+            RefundStatus::Failed => "failed",
+            RefundStatus::Canceled => "canceled",
+        }
+    }
+}
+
+/// Currencies whose amounts have no minor unit, as the providers count them
+const ZERO_DECIMAL_CURRENCIES: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "JPY", "KMF", "KRW", "MGA", "PYG", "RWF", "UGX", "VND", "VUV", "XAF", "XOF", "XPF",
+];
+const THREE_DECIMAL_CURRENCIES: &[&str] = &["BHD", "JOD", "KWD", "OMR", "TND"];
+
+/// Decimal places of a currency's minor unit
+pub fn currency_exponent(currency: &str) -> u32 {
+    let currency = currency.to_uppercase();
+    if ZERO_DECIMAL_CURRENCIES.contains(&currency.as_str()) {
+        0
+    } else if THREE_DECIMAL_CURRENCIES.contains(&currency.as_str()) {
+        3
+    } else {
+        2
+    }
+}
+
+/// A decimal amount string, e.g. "19.99" or "500" for JPY, from minor units
+pub fn format_minor_units(amount: i64, currency: &str) -> String {
+    let exponent = currency_exponent(currency);
+    if exponent == 0 {
+        return amount.to_string();
+    }
+    let scale = 10u64.pow(exponent);
+    let sign = if amount < 0 { "-" } else { "" };
+    let amount = amount.unsigned_abs();
+    format!("{}{}.{:0width$}", sign, amount / scale, amount % scale, width = exponent as usize)
+}
+
+/// Minor units of a decimal amount string; `None` if it isn't one or has
+/// more decimals than the currency
+pub fn parse_minor_units(value: &str, currency: &str) -> Option<i64> {
+    let exponent = currency_exponent(currency) as usize;
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if whole.is_empty() || !digits(whole) || !digits(fraction) || fraction.len() > exponent {
+        return None;
+    }
+    let scale = 10i64.checked_pow(exponent as u32)?;
+    let fraction = format!("{:0<width$}", fraction, width = exponent);
+    let amount = whole
+        .parse::<i64>()
+        .ok()?
+        .checked_mul(scale)?
+        .checked_add(if exponent == 0 { 0 } else { fraction.parse::<i64>().ok()? })?;
+    Some(if negative { -amount } else { amount })
+}
+
 /// Trait that all payment providers must implement
 #[async_trait]
 pub trait PaymentHandler: Send + Sync {
@@ -56,6 +151,13 @@ pub trait PaymentHandler: Send + Sync {
     /// Cancel a payment intent
     async fn cancel_payment_intent(&self, intent_id: &str) -> Result<PaymentIntent, String>;
     
+    /// Refund a succeeded payment, in full or in part; `amount_cents` is
+    /// what to give back now
+    async fn refund_payment(&self, request: RefundRequest) -> Result<Refund, String>;
+
+    /// Retrieve a refund, to follow one the provider is still processing
+    async fn get_refund(&self, refund_id: &str) -> Result<Refund, String>;
+    
     /// Verify webhook signature (for payment status updates)
     fn verify_webhook_signature(&self, payload: &[u8], signature: &str) -> Result<bool, String>;
 }
@@ -128,6 +230,14 @@ mod tests {
             unimplemented!()
         }
         
+        async fn refund_payment(&self, _request: RefundRequest) -> Result<Refund, String> {
+            unimplemented!()
+        }
+
+        async fn get_refund(&self, _refund_id: &str) -> Result<Refund, String> {
+            unimplemented!()
+        }
+        
         fn verify_webhook_signature(&self, _payload: &[u8], _signature: &str) -> Result<bool, String> {
             Ok(true)
         }
@@ -141,4 +251,22 @@ mod tests {
         assert!(registry.get("mock").is_some());
         assert_eq!(registry.list_handlers(), vec!["mock"]);
     }
+
+    #[test]
+    fn test_minor_units() {
+        assert_eq!(format_minor_units(1999, "usd"), "19.99");
+        assert_eq!(format_minor_units(5, "EUR"), "0.05");
+        assert_eq!(format_minor_units(500, "JPY"), "500");
+        assert_eq!(format_minor_units(1234, "KWD"), "1.234");
+        assert_eq!(format_minor_units(-250, "usd"), "-2.50");
+
+        assert_eq!(parse_minor_units("19.99", "USD"), Some(1999));
+        assert_eq!(parse_minor_units("19.9", "USD"), Some(1990));
+        assert_eq!(parse_minor_units("20", "USD"), Some(2000));
+        assert_eq!(parse_minor_units("500", "JPY"), Some(500));
+        assert_eq!(parse_minor_units("0.29", "USD"), Some(29));
+        assert_eq!(parse_minor_units("1.5", "JPY"), None);
+        assert_eq!(parse_minor_units("1.999", "USD"), None);
+        assert_eq!(parse_minor_units("abc", "USD"), None);
+    }
 }
@@ -1,4 +1,7 @@
-use super::{PaymentHandler, PaymentIntent, CreatePaymentIntentRequest, PaymentStatus};
+use super::{
+    format_minor_units, parse_minor_units, CreatePaymentIntentRequest, PaymentHandler, PaymentIntent, PaymentStatus, Refund,
+    RefundRequest, RefundStatus,
+};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -41,6 +44,34 @@ struct PayPalOrderResponse {
 #[derive(Deserialize)]
 struct PayPalPurchaseUnitResponse {
     amount: PayPalAmountResponse,
+    #[serde(default)]
+    payments: Option<PayPalPayments>,
+}
+
+#[derive(Deserialize)]
+struct PayPalPayments {
+    #[serde(default)]
+    captures: Vec<PayPalCapture>,
+}
+
+#[derive(Deserialize)]
+struct PayPalCapture {
+    id: String,
+}
+
+#[derive(Serialize)]
+struct PayPalRefundRequest {
+    amount: PayPalAmount,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note_to_payer: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PayPalRefundResponse {
+    id: String,
+    status: String,
+    #[serde(default)]
+    amount: Option<PayPalAmountResponse>,
 }
 
 #[derive(Deserialize)]
@@ -97,6 +128,15 @@ impl PayPalPaymentHandler {
             _ => PaymentStatus::Failed,
         }
     }
+    
+    fn map_refund_status(status: &str) -> RefundStatus {
+        match status {
+            "PENDING" => RefundStatus::Pending,
+            "COMPLETED" => RefundStatus::Succeeded,
+            "CANCELLED" => RefundStatus::Canceled,
+            _ => RefundStatus::Failed,
+        }
+    }
 }
 
 #[async_trait]
@@ -113,7 +153,7 @@ impl PaymentHandler for PayPalPaymentHandler {
         let url = format!("{}/v2/checkout/orders", self.base_url);
         
         // PayPal expects amount in decimal format (e.g., "50.00" for $50)
-        let amount_decimal = format!("{:.2}", request.amount_cents as f64 / 100.0);
+        let amount_decimal = format_minor_units(request.amount_cents, &request.currency);
         
         let custom_id = request.metadata.get("order_id").cloned();
         
@@ -177,11 +217,10 @@ impl PaymentHandler for PayPalPaymentHandler {
             .await
             .map_err(|e| format!("Failed to parse PayPal response: {}", e))?;
         
-        let amount_cents = if let Some(unit) = paypal_order.purchase_units.first() {
-            (unit.amount.value.parse::<f64>().unwrap_or(0.0) * 100.0) as i64
-        } else {
-            0
-        };
+        let amount_cents = paypal_order.purchase_units
+            .first()
+            .and_then(|unit| parse_minor_units(&unit.amount.value, &unit.amount.currency_code))
+            .unwrap_or(0);
         
         let currency = paypal_order.purchase_units
             .first()
@@ -215,11 +254,10 @@ impl PaymentHandler for PayPalPaymentHandler {
             .await
             .map_err(|e| format!("Failed to parse PayPal response: {}", e))?;
         
-        let amount_cents = if let Some(unit) = paypal_order.purchase_units.first() {
-            (unit.amount.value.parse::<f64>().unwrap_or(0.0) * 100.0) as i64
-        } else {
-            0
-        };
+        let amount_cents = paypal_order.purchase_units
+            .first()
+            .and_then(|unit| parse_minor_units(&unit.amount.value, &unit.amount.currency_code))
+            .unwrap_or(0);
         
         Ok(PaymentIntent {
             id: paypal_order.id,
@@ -239,6 +277,112 @@ impl PaymentHandler for PayPalPaymentHandler {
         Err("PayPal orders cannot be manually canceled via API".to_string())
     }
     
+    async fn refund_payment(&self, request: RefundRequest) -> Result<Refund, String> {
+        let token = self.get_access_token().await?;
+        
+        // Refunds are made against the order's capture, not the order itself
+        let url = format!("{}/v2/checkout/orders/{}", self.base_url, request.intent_id);
+        let response = self.client
+            .get(&url)
+            .bearer_auth(&token)
+            .send()
+            .await
+            .map_err(|e| format!("PayPal API error: {}", e))?;
+        
+        if !response.status().is_success() {
+            return Err(format!("PayPal API failed with status: {}", response.status()));
+        }
+        
+        let paypal_order: PayPalOrderResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse PayPal response: {}", e))?;
+        
+        // One capture is the whole payment; splitting a refund across several isn't supported
+        let captures: Vec<&PayPalCapture> = paypal_order.purchase_units
+            .iter()
+            .filter_map(|u| u.payments.as_ref())
+            .flat_map(|p| p.captures.iter())
+            .collect();
+        let capture_id = match captures.as_slice() {
+            [] => return Err("PayPal order has not been captured".to_string()),
+            [capture] => capture.id.clone(),
+            _ => return Err(format!(
+                "PayPal order has {} captures; refund them in PayPal",
+                captures.len()
+            )),
+        };
+        
+        let url = format!("{}/v2/payments/captures/{}/refund", self.base_url, capture_id);
+        let currency = request.currency.to_uppercase();
+        let paypal_request = PayPalRefundRequest {
+            amount: PayPalAmount {
+                currency_code: currency.clone(),
+                value: format_minor_units(request.amount_cents, &currency),
+            },
+            note_to_payer: request.reason.clone(),
+        };
+        
+        let response = self.client
+            .post(&url)
+            .bearer_auth(&token)
+            .header("Content-Type", "application/json")
+            .header("PayPal-Request-Id", &request.idempotency_key)
+            .json(&paypal_request)
+            .send()
+            .await
+            .map_err(|e| format!("PayPal API error: {}", e))?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(format!("PayPal API failed: {}", error_text));
+        }
+        
+        let paypal_refund: PayPalRefundResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse PayPal response: {}", e))?;
+        
+        Ok(Refund {
+            id: paypal_refund.id,
+            intent_id: request.intent_id,
+            amount_cents: request.amount_cents,
+            currency,
+            status: Self::map_refund_status(&paypal_refund.status),
+        })
+    }
+    
+    async fn get_refund(&self, refund_id: &str) -> Result<Refund, String> {
+        let token = self.get_access_token().await?;
+        let url = format!("{}/v2/payments/refunds/{}", self.base_url, refund_id);
+        
+        let response = self.client
+            .get(&url)
+            .bearer_auth(&token)
+            .send()
+            .await
+            .map_err(|e| format!("PayPal API error: {}", e))?;
+        
+        if !response.status().is_success() {
+            return Err(format!("PayPal API failed with status: {}", response.status()));
+        }
+        
+        let paypal_refund: PayPalRefundResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse PayPal response: {}", e))?;
+        let amount = paypal_refund.amount.ok_or("PayPal refund has no amount")?;
+        
+        Ok(Refund {
+            id: paypal_refund.id,
+            intent_id: String::new(),
+            amount_cents: parse_minor_units(&amount.value, &amount.currency_code)
+                .ok_or(format!("Invalid PayPal amount {}", amount.value))?,
+            currency: amount.currency_code,
+            status: Self::map_refund_status(&paypal_refund.status),
+        })
+    }
+    
     fn verify_webhook_signature(&self, _payload: &[u8], signature: &str) -> Result<bool, String> {
         // PayPal webhook verification requires additional metadata
         // For production, implement full webhook verification:
@@ -1,4 +1,4 @@
-use super::{PaymentHandler, PaymentIntent, CreatePaymentIntentRequest, PaymentStatus};
+use super::{PaymentHandler, PaymentIntent, CreatePaymentIntentRequest, PaymentStatus, Refund, RefundRequest, RefundStatus};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -39,6 +39,29 @@ struct SquarePayment {
     reference_id: Option<String>,
 }
 
+#[derive(Serialize)]
+struct SquareRefundRequest {
+    idempotency_key: String,
+    payment_id: String,
+    amount_money: SquareMoney,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SquareRefundResponse {
+    refund: SquareRefund,
+}
+
+#[derive(Deserialize)]
+struct SquareRefund {
+    id: String,
+    status: String,
+    amount_money: SquareMoney,
+    #[serde(default)]
+    payment_id: Option<String>,
+}
+
 impl SquarePaymentHandler {
     pub fn new(access_token: String, sandbox: bool) -> Self {
         let base_url = if sandbox {
@@ -63,6 +86,15 @@ impl SquarePaymentHandler {
         }
     }
     
+    fn map_refund_status(status: &str) -> RefundStatus {
+        match status {
+            "PENDING" => RefundStatus::Pending,
+            "COMPLETED" => RefundStatus::Succeeded,
+            "REJECTED" => RefundStatus::Canceled,
+            _ => RefundStatus::Failed,
+        }
+    }
+    
     fn generate_idempotency_key() -> String {
         uuid::Uuid::new_v4().to_string()
     }
@@ -213,6 +245,77 @@ impl PaymentHandler for SquarePaymentHandler {
         })
     }
     
+    async fn refund_payment(&self, request: RefundRequest) -> Result<Refund, String> {
+        let url = format!("{}/v2/refunds", self.base_url);
+        
+        let square_request = SquareRefundRequest {
+            idempotency_key: request.idempotency_key.clone(),
+            payment_id: request.intent_id.clone(),
+            amount_money: SquareMoney {
+                amount: request.amount_cents,
+                currency: request.currency.to_uppercase(),
+            },
+            reason: request.reason.clone(),
+        };
+        
+        let response = self.client
+            .post(&url)
+            .bearer_auth(&self.access_token)
+            .header("Content-Type", "application/json")
+            .header("Square-Version", "2023-12-13")
+            .json(&square_request)
+            .send()
+            .await
+            .map_err(|e| format!("Square API error: {}", e))?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(format!("Square API failed: {}", error_text));
+        }
+        
+        let square_response: SquareRefundResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Square response: {}", e))?;
+        
+        Ok(Refund {
+            id: square_response.refund.id,
+            intent_id: request.intent_id,
+            amount_cents: square_response.refund.amount_money.amount,
+            currency: square_response.refund.amount_money.currency,
+            status: Self::map_refund_status(&square_response.refund.status),
+        })
+    }
+    
+    async fn get_refund(&self, refund_id: &str) -> Result<Refund, String> {
+        let url = format!("{}/v2/refunds/{}", self.base_url, refund_id);
+        
+        let response = self.client
+            .get(&url)
+            .bearer_auth(&self.access_token)
+            .header("Square-Version", "2023-12-13")
+            .send()
+            .await
+            .map_err(|e| format!("Square API error: {}", e))?;
+        
+        if !response.status().is_success() {
+            return Err(format!("Square API failed with status: {}", response.status()));
+        }
+        
+        let square_response: SquareRefundResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Square response: {}", e))?;
+        
+        Ok(Refund {
+            id: square_response.refund.id,
+            intent_id: square_response.refund.payment_id.unwrap_or_default(),
+            amount_cents: square_response.refund.amount_money.amount,
+            currency: square_response.refund.amount_money.currency,
+            status: Self::map_refund_status(&square_response.refund.status),
+        })
+    }
+    
     fn verify_webhook_signature(&self, payload: &[u8], signature: &str) -> Result<bool, String> {
         // Square webhook verification uses HMAC-SHA256
         use hmac::{Hmac, Mac};
@@ -1,4 +1,4 @@
-use super::{PaymentHandler, PaymentIntent, CreatePaymentIntentRequest, PaymentStatus};
+use super::{PaymentHandler, PaymentIntent, CreatePaymentIntentRequest, PaymentStatus, Refund, RefundRequest, RefundStatus};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -28,6 +28,16 @@ struct StripePaymentIntent {
     metadata: HashMap<String, String>,
 }
 
+#[derive(Deserialize)]
+struct StripeRefund {
+    id: String,
+    amount: i64,
+    currency: String,
+    status: String,
+    #[serde(default)]
+    payment_intent: Option<String>,
+}
+
 impl StripePaymentHandler {
     pub fn new(mut api_key: String) -> Self {
         api_key = api_key.trim().to_string();
@@ -56,6 +66,15 @@ impl StripePaymentHandler {
         }
     }
 
+    fn map_refund_status(status: &str) -> RefundStatus {
+        match status {
+            "pending" | "requires_action" => RefundStatus::Pending,
+            "succeeded" => RefundStatus::Succeeded,
+            "canceled" => RefundStatus::Canceled,
+            _ => RefundStatus::Failed,
+        }
+    }
+
     pub fn verify_webhook_signature(&self, payload: &[u8], signature: &str) -> Result<bool, String> {
         // Stripe webhook verification using HMAC-SHA256
         use hmac::{Hmac, Mac};
@@ -226,6 +245,73 @@ impl PaymentHandler for StripePaymentHandler {
         })
     }
     
+    async fn refund_payment(&self, request: RefundRequest) -> Result<Refund, String> {
+        let url = "https://api.stripe.com/v1/refunds";
+        
+        let mut params = HashMap::new();
+        params.insert("payment_intent".to_string(), request.intent_id.clone());
+        params.insert("amount".to_string(), request.amount_cents.to_string());
+        // Stripe's own `reason` only takes a few fixed values
+        if let Some(reason) = &request.reason {
+            params.insert("metadata[reason]".to_string(), reason.clone());
+        }
+        
+        let response = self.client
+            .post(url)
+            .bearer_auth(&self.api_key)
+            .header("Idempotency-Key", &request.idempotency_key)
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| format!("Stripe API error: {}", e))?;
+        
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(format!("Stripe API failed: {}", error_text));
+        }
+        
+        let stripe_refund: StripeRefund = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Stripe response: {}", e))?;
+        
+        Ok(Refund {
+            id: stripe_refund.id,
+            intent_id: request.intent_id,
+            amount_cents: stripe_refund.amount,
+            currency: stripe_refund.currency,
+            status: Self::map_refund_status(&stripe_refund.status),
+        })
+    }
+    
+    async fn get_refund(&self, refund_id: &str) -> Result<Refund, String> {
+        let url = format!("https://api.stripe.com/v1/refunds/{}", refund_id);
+        
+        let response = self.client
+            .get(&url)
+            .bearer_auth(&self.api_key)
+            .send()
+            .await
+            .map_err(|e| format!("Stripe API error: {}", e))?;
+        
+        if !response.status().is_success() {
+            return Err(format!("Stripe API failed with status: {}", response.status()));
+        }
+        
+        let stripe_refund: StripeRefund = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse Stripe response: {}", e))?;
+        
+        Ok(Refund {
+            id: stripe_refund.id,
+            intent_id: stripe_refund.payment_intent.unwrap_or_default(),
+            amount_cents: stripe_refund.amount,
+            currency: stripe_refund.currency,
+            status: Self::map_refund_status(&stripe_refund.status),
+        })
+    }
+    
     fn verify_webhook_signature(&self, payload: &[u8], signature: &str) -> Result<bool, String> {
         // Stripe webhook verification using HMAC-SHA256
         use hmac::{Hmac, Mac};
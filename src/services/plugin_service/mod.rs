@@ -24,7 +24,7 @@ pub trait Plugin: Send + Sync + Any {
         Ok(())
     }
     
-    /// Called before content is saved to database
+    /// Called after content has been saved to the database
     async fn on_save_content(&self, _content_type: &str, _data: &serde_json::Value) -> Result<(), String> {
         Ok(())
     }
@@ -73,6 +73,31 @@ impl PluginRegistry {
         }
     }
     
+    /// Execute on_save_content hooks; a failing plugin does not block the others
+    pub async fn execute_on_save_content(&self, content_type: &str, data: &serde_json::Value) {
+        let plugins: Vec<Arc<dyn Plugin>> = {
+             let guard = self.plugins.lock().unwrap();
+             guard.clone()
+        };
+
+        for plugin in plugins {
+             if let Err(e) = plugin.on_save_content(content_type, data).await {
+                 log::warn!("Plugin {} on_save_content failed: {}", plugin.name(), e);
+             }
+        }
+    }
+
+    /// Reports a created or updated page to `on_save_content`
+    pub async fn page_saved(&self, page_uuid: &str) {
+        self.execute_on_save_content("page", &serde_json::json!({ "uuid": page_uuid })).await;
+    }
+
+    /// Reports a created or updated module to `on_save_content`
+    pub async fn module_saved(&self, module_uuid: &str, page_uuid: &str) {
+        self.execute_on_save_content("module", &serde_json::json!({ "uuid": module_uuid, "page_uuid": page_uuid }))
+            .await;
+    }
+
     pub fn get_hooks(&self) -> Vec<String> {
         let plugins = self.plugins.lock().unwrap();
         plugins.iter().map(|p| p.name().to_string()).collect()
@@ -0,0 +1,393 @@
+// Redirect Service
+// Per-tenant redirect rules for public pages: matching, hit counting,
+// automatic redirects when a page URL changes, and CSV import
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use diesel::prelude::*;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::redirect_models::{NewRedirect, Redirect};
+use crate::schema::redirects;
+
+/// Regexes are user input; cap their compiled size
+const REGEX_SIZE_LIMIT: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchType {
+    Exact,
+    Prefix,
+    Regex,
+}
+
+impl MatchType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchType::Exact => "exact",
+            MatchType::Prefix => "prefix",
+            MatchType::Regex => "regex",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "exact" => Some(MatchType::Exact),
+            "prefix" => Some(MatchType::Prefix),
+            "regex" => Some(MatchType::Regex),
+            _ => None,
+        }
+    }
+}
+
+fn compile(source: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(source).size_limit(REGEX_SIZE_LIMIT).build()
+}
+
+/// A tenant's compiled regex rules, keyed by source
+type CompiledRules = HashMap<String, Arc<Regex>>;
+
+/// Compiled regex rules per tenant. Rules are still loaded on every lookup,
+/// so edits apply at once; only the compilation is reused.
+fn compiled_rules() -> &'static Mutex<HashMap<i32, CompiledRules>> {
+    static COMPILED: OnceLock<Mutex<HashMap<i32, CompiledRules>>> = OnceLock::new();
+    COMPILED.get_or_init(Default::default)
+}
+
+fn compiled_regex(tenant_id: i32, source: &str) -> Option<Arc<Regex>> {
+    let mut compiled = compiled_rules().lock().unwrap();
+    let tenant = compiled.entry(tenant_id).or_default();
+    if let Some(re) = tenant.get(source) {
+        return Some(re.clone());
+    }
+    let re = Arc::new(compile(source).ok()?);
+    tenant.insert(source.to_string(), re.clone());
+    Some(re)
+}
+
+/// Drops compiled regexes of rules the tenant no longer has
+fn forget_removed_rules(tenant_id: i32, rules: &[Redirect]) {
+    if let Some(tenant) = compiled_rules().lock().unwrap().get_mut(&tenant_id) {
+        tenant.retain(|source, _| rules.iter().any(|r| r.match_type == MatchType::Regex.as_str() && &r.source == source));
+    }
+}
+
+/// Whether a rule of `match_type` from `source` would match `location`, the
+/// path it just redirected to, again
+fn matches_again(match_type: MatchType, source: &str, re: Option<&Regex>, location: &str) -> bool {
+    match match_type {
+        MatchType::Exact => location == source,
+        MatchType::Prefix => location.starts_with(source),
+        MatchType::Regex => re.is_some_and(|re| re.is_match(location)),
+    }
+}
+
+/// Checks a rule before it is stored
+pub fn validate(rule: &NewRedirect) -> Result<(), String> {
+    let match_type = MatchType::parse(&rule.match_type)
+        .ok_or_else(|| format!("match_type must be exact, prefix or regex, got '{}'", rule.match_type))?;
+    if rule.status_code != 301 && rule.status_code != 302 {
+        return Err(format!("status_code must be 301 or 302, got {}", rule.status_code));
+    }
+    if rule.source.trim().is_empty() || rule.target.trim().is_empty() {
+        return Err("source and target are required".to_string());
+    }
+    if !(rule.target.starts_with('/') || rule.target.starts_with("http://") || rule.target.starts_with("https://")) {
+        return Err("target must be a path or an http(s) URL".to_string());
+    }
+
+    let re = match match_type {
+        MatchType::Exact | MatchType::Prefix if !rule.source.starts_with('/') => {
+            return Err("source must be a path starting with '/'".to_string());
+        }
+        MatchType::Regex => Some(compile(&rule.source).map_err(|e| format!("invalid regex: {}", e))?),
+        _ => None,
+    };
+    // A target with captures is only known per request; match_redirect skips those loops
+    if !(match_type == MatchType::Regex && rule.target.contains('$'))
+        && matches_again(match_type, &rule.source, re.as_ref(), &rule.target)
+    {
+        return Err("target would be redirected again by the same rule".to_string());
+    }
+    Ok(())
+}
+
+/// Picks the rule for `path` and the location it redirects to.
+///
+/// Exact rules win, then the longest prefix, then regexes in creation order.
+/// Prefix rules carry the rest of the path over (`/blog/` → `/news/` sends
+/// `/blog/a` to `/news/a`); regex targets may use `$1`-style captures. Rules
+/// whose location they would match again are skipped, so no rule loops.
+pub fn match_redirect<'a>(rules: &'a [Redirect], path: &str) -> Option<(&'a Redirect, String)> {
+    let of_type = |match_type: MatchType| rules.iter().filter(move |r| r.is_active && r.match_type == match_type.as_str());
+
+    let exact = of_type(MatchType::Exact)
+        .filter(|r| r.source == path)
+        .map(|r| (r, r.target.clone()))
+        .filter(|(r, location)| !matches_again(MatchType::Exact, &r.source, None, location));
+
+    let mut prefixes: Vec<&Redirect> = of_type(MatchType::Prefix).filter(|r| path.starts_with(&r.source)).collect();
+    prefixes.sort_by_key(|r| std::cmp::Reverse(r.source.len()));
+    let prefix = prefixes
+        .into_iter()
+        .map(|r| (r, format!("{}{}", r.target, &path[r.source.len()..])))
+        .filter(|(r, location)| !matches_again(MatchType::Prefix, &r.source, None, location));
+
+    let mut regexes: Vec<&Redirect> = of_type(MatchType::Regex).collect();
+    regexes.sort_by_key(|r| r.id);
+    let regex = regexes.into_iter().filter_map(|r| {
+        let re = compiled_regex(r.tenant_id, &r.source)?;
+        if !re.is_match(path) {
+            return None;
+        }
+        let location = re.replace(path, r.target.as_str()).into_owned();
+        (!matches_again(MatchType::Regex, &r.source, Some(&re), &location)).then_some((r, location))
+    });
+
+    exact.chain(prefix).chain(regex).next()
+}
+
+/// The active redirect for `path` in a tenant, if any
+pub fn find_redirect(
+    tenant_id: i32,
+    path: &str,
+    conn: &mut PgConnection,
+) -> QueryResult<Option<(Redirect, String)>> {
+    // Exact rules are looked up by source; prefix and regex rules need evaluating
+    let rules = redirects::table
+        .filter(redirects::tenant_id.eq(tenant_id))
+        .filter(redirects::is_active.eq(true))
+        .filter(
+            redirects::match_type
+                .ne(MatchType::Exact.as_str())
+                .or(redirects::source.eq(path)),
+        )
+        .load::<Redirect>(conn)?;
+    forget_removed_rules(tenant_id, &rules);
+
+    Ok(match_redirect(&rules, path).map(|(rule, location)| (rule.clone(), location)))
+}
+
+pub fn record_hit(redirect_id: i32, conn: &mut PgConnection) -> QueryResult<usize> {
+    diesel::update(redirects::table.find(redirect_id))
+        .set((
+            redirects::hit_count.eq(redirects::hit_count + 1),
+            redirects::last_hit_at.eq(Some(chrono::Utc::now().naive_utc())),
+        ))
+        .execute(conn)
+}
+
+/// Inserts a rule, or replaces the target and status of the rule with the same source
+pub fn upsert(rule: &NewRedirect, conn: &mut PgConnection) -> QueryResult<Redirect> {
+    diesel::insert_into(redirects::table)
+        .values(rule)
+        .on_conflict((redirects::tenant_id, redirects::match_type, redirects::source))
+        .do_update()
+        .set((
+            redirects::target.eq(&rule.target),
+            redirects::status_code.eq(rule.status_code),
+            redirects::is_active.eq(true),
+            redirects::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .get_result(conn)
+}
+
+/// Keeps old links working after a page moves from `old_url` to `new_url`:
+/// adds a 301 from the old URL, points existing redirects at the new URL
+/// instead of chaining, and drops any redirect away from the new URL.
+pub fn redirect_on_url_change(
+    tenant_id: i32,
+    old_url: &str,
+    new_url: &str,
+    conn: &mut PgConnection,
+) -> QueryResult<()> {
+    if old_url == new_url {
+        return Ok(());
+    }
+
+    let tenant_rules = redirects::table.filter(redirects::tenant_id.eq(tenant_id));
+    diesel::delete(
+        tenant_rules
+            .filter(redirects::match_type.eq(MatchType::Exact.as_str()))
+            .filter(redirects::source.eq(new_url)),
+    )
+    .execute(conn)?;
+
+    diesel::update(tenant_rules.filter(redirects::target.eq(old_url)))
+        .set((
+            redirects::target.eq(new_url),
+            redirects::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(conn)?;
+
+    upsert(
+        &NewRedirect {
+            tenant_id,
+            source: old_url.to_string(),
+            target: new_url.to_string(),
+            match_type: MatchType::Exact.as_str().to_string(),
+            status_code: 301,
+        },
+        conn,
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CsvRowError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Parses `source,target[,status_code[,match_type]]` rows. A header row is
+/// skipped; status defaults to 301 and match type to exact. Fields may be
+/// double-quoted.
+pub fn parse_csv(tenant_id: i32, input: &str) -> (Vec<NewRedirect>, Vec<CsvRowError>) {
+    let mut rules = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, line) in input.lines().enumerate() {
+        let line_number = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(line);
+        if index == 0 && fields.first().map(|f| f.eq_ignore_ascii_case("source")).unwrap_or(false) {
+            continue;
+        }
+        if fields.len() < 2 || fields.len() > 4 {
+            errors.push(CsvRowError { line: line_number, message: "expected source,target[,status_code[,match_type]]".to_string() });
+            continue;
+        }
+
+        let status_code = match fields.get(2).map(|s| s.as_str()).filter(|s| !s.is_empty()) {
+            None => 301,
+            Some(code) => match code.parse::<i32>() {
+                Ok(code) => code,
+                Err(_) => {
+                    errors.push(CsvRowError { line: line_number, message: format!("invalid status_code '{}'", code) });
+                    continue;
+                }
+            },
+        };
+        let rule = NewRedirect {
+            tenant_id,
+            source: fields[0].clone(),
+            target: fields[1].clone(),
+            match_type: fields.get(3).filter(|s| !s.is_empty()).map(|s| s.to_lowercase()).unwrap_or_else(|| "exact".to_string()),
+            status_code,
+        };
+        match validate(&rule) {
+            Ok(()) => rules.push(rule),
+            Err(message) => errors.push(CsvRowError { line: line_number, message }),
+        }
+    }
+
+    (rules, errors)
+}
+
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i32, source: &str, target: &str, match_type: &str) -> Redirect {
+        let now = chrono::Utc::now().naive_utc();
+        Redirect {
+            id,
+            tenant_id: 1,
+            source: source.to_string(),
+            target: target.to_string(),
+            match_type: match_type.to_string(),
+            status_code: 301,
+            hit_count: 0,
+            last_hit_at: None,
+            is_active: true,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_match_precedence() {
+        let rules = vec![
+            rule(1, r"^/p/(\d+)$", "/posts/$1", "regex"),
+            rule(2, "/blog/", "/news/", "prefix"),
+            rule(3, "/blog/2020/", "/archive/", "prefix"),
+            rule(4, "/blog/2020/hello", "/hello", "exact"),
+        ];
+
+        let location = |path: &str| match_redirect(&rules, path).map(|(_, location)| location);
+        assert_eq!(location("/blog/2020/hello").as_deref(), Some("/hello"));
+        assert_eq!(location("/blog/2020/other").as_deref(), Some("/archive/other"));
+        assert_eq!(location("/blog/a").as_deref(), Some("/news/a"));
+        assert_eq!(location("/p/42").as_deref(), Some("/posts/42"));
+        assert_eq!(location("/about"), None);
+    }
+
+    #[test]
+    fn test_skips_self_redirects() {
+        let rules = vec![rule(1, "/", "/", "prefix")];
+        assert!(match_redirect(&rules, "/about").is_none());
+
+        let rules = vec![rule(1, "/blog/", "/blog/news/", "prefix"), rule(2, r"/p/(\d+)", "/shop/p/$1", "regex")];
+        assert!(match_redirect(&rules, "/blog/a").is_none());
+        assert!(match_redirect(&rules, "/p/42").is_none());
+    }
+
+    #[test]
+    fn test_validate_rejects_rules_matching_their_target() {
+        let new = |source: &str, target: &str, match_type: &str| NewRedirect {
+            tenant_id: 1,
+            source: source.to_string(),
+            target: target.to_string(),
+            match_type: match_type.to_string(),
+            status_code: 301,
+        };
+        assert!(validate(&new("/old", "/old", "exact")).is_err());
+        assert!(validate(&new("/blog/", "/blog/news/", "prefix")).is_err());
+        assert!(validate(&new("^/docs", "/docs/v2", "regex")).is_err());
+        assert!(validate(&new("/blog/", "/news/", "prefix")).is_ok());
+        assert!(validate(&new(r"^/p/(\d+)$", "/posts/$1", "regex")).is_ok());
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let csv = "source,target,status_code,match_type\n\
+                   /old,/new\n\
+                   \"/a,b\",/c,302\n\
+                   /x,/y,307\n\
+                   ^/(,/z,301,regex\n\
+                   /docs/,https://docs.example.com/,301,prefix\n";
+        let (rules, errors) = parse_csv(7, csv);
+
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].status_code, 301);
+        assert_eq!(rules[1].source, "/a,b");
+        assert_eq!(rules[1].status_code, 302);
+        assert_eq!(rules[2].match_type, "prefix");
+        assert_eq!(errors.iter().map(|e| e.line).collect::<Vec<_>>(), vec![4, 5]);
+    }
+}
@@ -0,0 +1,390 @@
+// Refund Service
+// Gives back all or part of an order's payment through its provider. Each
+// refund is saved before the provider is called, keyed by the caller's
+// idempotency key, so a retried request returns the first refund instead of
+// making another. The refund's UUID is the provider's idempotency key, so a
+// retry after a lost response is deduplicated by the provider as well.
+// A refund the provider accepts but hasn't finished stays pending, and only
+// moves the order once the worker sees the provider complete it.
+
+use actix_web::web;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use diesel::prelude::*;
+
+use crate::models::commerce_models::{NewOrderRefund, Order, OrderRefund};
+use crate::models::DbPool;
+use crate::schema::{order_refunds, orders};
+use crate::services::email_service::EmailService;
+use crate::services::errors_service::CustomHttpError;
+use crate::services::order_service::{self, format_cents, Actor, OrderStatus, Transition};
+use crate::services::payment_service::{
+    format_minor_units, PaymentHandlerRegistry, PaymentStatus, RefundRequest, RefundStatus,
+};
+
+const WORKER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Refunds that hold back part of the order's total
+const OPEN_STATUSES: [&str; 2] = ["pending", "succeeded"];
+/// Refunds the customer has actually been given
+const SETTLED_STATUSES: [&str; 1] = ["succeeded"];
+
+/// A refund and the order status change it caused, if any
+#[derive(Debug, Clone)]
+pub struct RefundOutcome {
+    pub refund: OrderRefund,
+    pub transition: Option<Transition>,
+}
+
+/// What to refund: `amount_cents` of `None` refunds whatever is left
+#[derive(Debug, Clone)]
+pub struct RefundOrder {
+    pub order_id: i64,
+    pub amount_cents: Option<i64>,
+    pub idempotency_key: String,
+    pub reason: Option<String>,
+}
+
+/// Cents to refund out of `total` with `refunded` already given back
+fn refund_amount(total: i64, refunded: i64, requested: Option<i64>) -> Result<i64, CustomHttpError> {
+    let left = total - refunded;
+    let amount = requested.unwrap_or(left);
+    if amount <= 0 {
+        return Err(CustomHttpError::BadRequest(if left <= 0 {
+            "The order has already been refunded in full".to_string()
+        } else {
+            "Refund amount must be positive".to_string()
+        }));
+    }
+    if amount > left {
+        return Err(CustomHttpError::BadRequest(format!("Only {} of the order can still be refunded", format_cents(left))));
+    }
+    Ok(amount)
+}
+
+/// The order status once `refunded` of `total` has been given back
+fn refunded_status(total: i64, refunded: i64) -> OrderStatus {
+    if refunded >= total {
+        OrderStatus::Refunded
+    } else {
+        OrderStatus::PartiallyRefunded
+    }
+}
+
+/// Cents of the order's refunds in one of `statuses`
+fn refunded_cents(order_id: i64, statuses: &[&str], conn: &mut PgConnection) -> QueryResult<i64> {
+    let refunded = order_refunds::table
+        .filter(order_refunds::order_id.eq(order_id))
+        .filter(order_refunds::status.eq_any(statuses))
+        .select(diesel::dsl::sum(order_refunds::amount_cents))
+        .first::<Option<BigDecimal>>(conn)?;
+    Ok(refunded.and_then(|cents| cents.to_i64()).unwrap_or(0))
+}
+
+/// Saves the refund to make, or finds the one already made with this key.
+/// The order is locked so concurrent refunds can't exceed its total.
+fn open_refund(
+    request: &RefundOrder,
+    provider: &str,
+    currency: &str,
+    actor: &Actor,
+    conn: &mut PgConnection,
+) -> Result<OrderRefund, CustomHttpError> {
+    conn.transaction(|conn| {
+        let order = orders::table
+            .find(request.order_id)
+            .select(Order::as_select())
+            .for_update()
+            .first::<Order>(conn)?;
+        let existing = order_refunds::table
+            .filter(order_refunds::order_id.eq(order.id))
+            .filter(order_refunds::idempotency_key.eq(&request.idempotency_key))
+            .first::<OrderRefund>(conn)
+            .optional()?;
+        // Answered by the provider, or still being sent
+        if let Some(refund) = &existing {
+            if refund.provider_refund_id.is_some() || refund.status == "pending" {
+                return Ok(refund.clone());
+            }
+        }
+
+        let status = OrderStatus::parse(&order.status)
+            .ok_or(CustomHttpError::InternalServerError(format!("Unknown order status {}", order.status)))?;
+        if !status.can_become(OrderStatus::PartiallyRefunded) {
+            return Err(CustomHttpError::BadRequest(format!("A {} order can't be refunded", status)));
+        }
+        let refunded = refunded_cents(order.id, &OPEN_STATUSES, conn)?;
+
+        match existing {
+            // Never reached the provider; try again for the same amount
+            Some(refund) => {
+                refund_amount(order.total_amount_cents, refunded, Some(refund.amount_cents))?;
+                Ok(diesel::update(order_refunds::table.find(refund.id))
+                    .set((
+                        order_refunds::status.eq("pending"),
+                        order_refunds::failure_reason.eq(None::<String>),
+                        order_refunds::updated_at.eq(chrono::Utc::now().naive_utc()),
+                    ))
+                    .get_result::<OrderRefund>(conn)?)
+            }
+            None => {
+                let amount_cents = refund_amount(order.total_amount_cents, refunded, request.amount_cents)?;
+                Ok(diesel::insert_into(order_refunds::table)
+                    .values(&NewOrderRefund {
+                        uuid: uuid::Uuid::new_v4().to_string(),
+                        order_id: order.id,
+                        idempotency_key: request.idempotency_key.clone(),
+                        provider: provider.to_string(),
+                        amount_cents,
+                        currency: currency.to_lowercase(),
+                        reason: request.reason.clone(),
+                        created_by: actor.user_id,
+                    })
+                    .get_result::<OrderRefund>(conn)?)
+            }
+        }
+    })
+}
+
+/// Saves the provider's answer and, once the refund has succeeded, moves the
+/// order to refunded or partially refunded. Only a pending refund is closed,
+/// so a refund seen by two callers moves the order once.
+fn close_refund(
+    refund: &OrderRefund,
+    provider_refund_id: &str,
+    status: RefundStatus,
+    actor: &Actor,
+    conn: &mut PgConnection,
+) -> Result<RefundOutcome, CustomHttpError> {
+    conn.transaction(|conn| {
+        let closed = diesel::update(
+            order_refunds::table
+                .find(refund.id)
+                .filter(order_refunds::status.eq(RefundStatus::Pending.as_str())),
+        )
+        .set((
+            order_refunds::provider_refund_id.eq(provider_refund_id),
+            order_refunds::status.eq(status.as_str()),
+            order_refunds::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .get_result::<OrderRefund>(conn)
+        .optional()?;
+        let Some(refund) = closed else {
+            let refund = order_refunds::table.find(refund.id).first::<OrderRefund>(conn)?;
+            return Ok(RefundOutcome { refund, transition: None });
+        };
+        // The provider is still processing it, or gave nothing back
+        if status != RefundStatus::Succeeded {
+            return Ok(RefundOutcome { refund, transition: None });
+        }
+
+        let order = orders::table
+            .find(refund.order_id)
+            .select(Order::as_select())
+            .for_update()
+            .first::<Order>(conn)?;
+        let next = refunded_status(order.total_amount_cents, refunded_cents(order.id, &SETTLED_STATUSES, conn)?);
+        diesel::update(orders::table.find(order.id))
+            .set(orders::payment_status.eq(next.as_str()))
+            .execute(conn)?;
+        // A concurrent refund may have finished the order already
+        if order.status == OrderStatus::Refunded.as_str() {
+            return Ok(RefundOutcome { refund, transition: None });
+        }
+        let mut reason = format!(
+            "Refunded {} {}",
+            format_minor_units(refund.amount_cents, &refund.currency),
+            refund.currency.to_uppercase()
+        );
+        if let Some(note) = &refund.reason {
+            reason = format!("{}: {}", reason, note);
+        }
+        let transition = order_service::transition(order.id, next, actor, Some(reason), conn)?;
+        Ok(RefundOutcome { refund, transition: Some(transition) })
+    })
+}
+
+/// Asks the provider whether a refund it left pending has finished, and
+/// closes it if so. `None` while the provider is still processing it.
+async fn follow_refund(
+    refund: &OrderRefund,
+    registry: &PaymentHandlerRegistry,
+    pool: &DbPool,
+) -> Result<Option<RefundOutcome>, CustomHttpError> {
+    let Some(provider_refund_id) = &refund.provider_refund_id else {
+        return Ok(None);
+    };
+    let handler = registry
+        .get(&refund.provider)
+        .ok_or(CustomHttpError::BadRequest(format!("Payment provider {} is not configured", refund.provider)))?;
+    let made = handler.get_refund(provider_refund_id).await.map_err(|e| {
+        CustomHttpError::InternalServerError(format!("Failed to fetch refund {}: {}", refund.uuid, e))
+    })?;
+    if made.status == RefundStatus::Pending {
+        return Ok(None);
+    }
+    let mut conn = pool
+        .get()
+        .map_err(|_| CustomHttpError::InternalServerError("Pool connection failed".to_string()))?;
+    close_refund(refund, &made.id, made.status, &Actor::system(&refund.provider), &mut conn).map(Some)
+}
+
+/// Follows every refund the provider accepted but hadn't finished, returning
+/// those that have since been closed
+pub async fn poll_pending_refunds(
+    registry: &PaymentHandlerRegistry,
+    pool: &DbPool,
+) -> Result<Vec<RefundOutcome>, CustomHttpError> {
+    let mut conn = pool
+        .get()
+        .map_err(|_| CustomHttpError::InternalServerError("Pool connection failed".to_string()))?;
+    let pending = order_refunds::table
+        .filter(order_refunds::status.eq(RefundStatus::Pending.as_str()))
+        .filter(order_refunds::provider_refund_id.is_not_null())
+        .order(order_refunds::id)
+        .load::<OrderRefund>(&mut conn)?;
+    drop(conn);
+
+    let mut closed = Vec::new();
+    for refund in pending {
+        match follow_refund(&refund, registry, pool).await {
+            Ok(Some(outcome)) => closed.push(outcome),
+            Ok(None) => {}
+            Err(e) => log::warn!("Refund {} is still pending: {}", refund.uuid, e),
+        }
+    }
+    Ok(closed)
+}
+
+/// Follows pending refunds every five minutes, for providers that finish
+/// refunds after answering the request, and announces the orders they move
+pub async fn run_worker(pool: DbPool, registry: web::Data<PaymentHandlerRegistry>, email_service: web::Data<EmailService>) {
+    let pool = web::Data::new(pool);
+    loop {
+        tokio::time::sleep(WORKER_INTERVAL).await;
+        let closed = match poll_pending_refunds(&registry, &pool).await {
+            Ok(closed) => closed,
+            Err(e) => {
+                log::error!("Refund polling failed: {}", e);
+                continue;
+            }
+        };
+        if !closed.is_empty() {
+            log::info!("Closed {} pending refunds", closed.len());
+        }
+        for transition in closed.iter().filter_map(|outcome| outcome.transition.as_ref()) {
+            order_service::notify(transition, &pool, &email_service).await;
+        }
+    }
+}
+
+/// Refunds an order through the provider that took its payment. Repeating a
+/// request with the same idempotency key returns the refund already made,
+/// checking first whether a pending one has finished; repeating one that
+/// failed before reaching the provider retries it.
+pub async fn refund_order(
+    request: RefundOrder,
+    actor: &Actor,
+    registry: &PaymentHandlerRegistry,
+    pool: &DbPool,
+) -> Result<RefundOutcome, CustomHttpError> {
+    if request.idempotency_key.is_empty() || request.idempotency_key.len() > 255 {
+        return Err(CustomHttpError::BadRequest("Idempotency key must be 1 to 255 characters".to_string()));
+    }
+    let pool_error = |_| CustomHttpError::InternalServerError("Pool connection failed".to_string());
+
+    let mut conn = pool.get().map_err(pool_error)?;
+    let order = orders::table
+        .find(request.order_id)
+        .select(Order::as_select())
+        .first::<Order>(&mut conn)
+        .optional()?
+        .ok_or(CustomHttpError::NotFound("Order not found".to_string()))?;
+    let (Some(provider), Some(intent_id)) = (order.payment_provider.clone(), order.payment_intent_id.clone()) else {
+        return Err(CustomHttpError::BadRequest("Order has no payment to refund".to_string()));
+    };
+    drop(conn);
+    let handler = registry
+        .get(&provider)
+        .ok_or(CustomHttpError::BadRequest(format!("Payment provider {} is not configured", provider)))?;
+
+    // The provider knows the currency, and whether there is anything to refund
+    let intent = handler.get_payment_intent(&intent_id).await.map_err(|e| {
+        log::error!("Order {}: failed to fetch payment {}: {}", order.uuid, intent_id, e);
+        CustomHttpError::InternalServerError("Failed to fetch the order's payment".to_string())
+    })?;
+    if intent.status != PaymentStatus::Succeeded {
+        return Err(CustomHttpError::BadRequest("The order's payment has not succeeded".to_string()));
+    }
+
+    let mut conn = pool.get().map_err(pool_error)?;
+    let refund = open_refund(&request, &provider, &intent.currency, actor, &mut conn)?;
+    drop(conn);
+    if refund.provider_refund_id.is_some() {
+        if refund.status == RefundStatus::Pending.as_str() {
+            match follow_refund(&refund, registry, pool).await {
+                Ok(Some(outcome)) => return Ok(outcome),
+                Ok(None) => {}
+                Err(e) => log::warn!("Order {}: refund {} is still pending: {}", order.uuid, refund.uuid, e),
+            }
+        }
+        return Ok(RefundOutcome { refund, transition: None });
+    }
+
+    let sent = handler
+        .refund_payment(RefundRequest {
+            intent_id,
+            amount_cents: refund.amount_cents,
+            currency: refund.currency.clone(),
+            idempotency_key: refund.uuid.clone(),
+            reason: refund.reason.clone(),
+        })
+        .await;
+    let mut conn = pool.get().map_err(pool_error)?;
+    match sent {
+        Ok(made) => close_refund(&refund, &made.id, made.status, actor, &mut conn),
+        Err(e) => {
+            log::error!("Order {}: refund {} failed: {}", order.uuid, refund.uuid, e);
+            // Unless a concurrent retry got the provider's answer first
+            diesel::update(order_refunds::table.find(refund.id).filter(order_refunds::provider_refund_id.is_null()))
+                .set((
+                    order_refunds::status.eq("failed"),
+                    order_refunds::failure_reason.eq(&e),
+                    order_refunds::updated_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .execute(&mut conn)?;
+            Err(CustomHttpError::InternalServerError(format!(
+                "Refund failed at {}; retry with the same idempotency key",
+                provider
+            )))
+        }
+    }
+}
+
+/// An order's refunds, oldest first
+pub fn refunds(order_id: i64, conn: &mut PgConnection) -> QueryResult<Vec<OrderRefund>> {
+    order_refunds::table
+        .filter(order_refunds::order_id.eq(order_id))
+        .order(order_refunds::id)
+        .load::<OrderRefund>(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refund_amount() {
+        assert_eq!(refund_amount(5000, 0, None).unwrap(), 5000);
+        assert_eq!(refund_amount(5000, 1500, None).unwrap(), 3500);
+        assert_eq!(refund_amount(5000, 1500, Some(3500)).unwrap(), 3500);
+        assert!(refund_amount(5000, 1500, Some(3501)).is_err());
+        assert!(refund_amount(5000, 0, Some(0)).is_err());
+        assert!(refund_amount(5000, 5000, None).is_err());
+    }
+
+    #[test]
+    fn test_refunded_status() {
+        assert_eq!(refunded_status(5000, 1000), OrderStatus::PartiallyRefunded);
+        assert_eq!(refunded_status(5000, 5000), OrderStatus::Refunded);
+    }
+}
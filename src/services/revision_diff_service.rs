@@ -0,0 +1,562 @@
+// Revision Diff & Merge Service
+// Field-level diffs between page revisions, word-level diffs for long text
+// and three-way merging of concurrent edits made from the same base.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+
+/// Fields that change on every save and carry no editorial meaning
+pub const IGNORED_PAGE_FIELDS: &[&str] = &["current_revision", "last_modified_by"];
+
+/// Fields diffed word by word instead of as a whole value
+const TEXT_FIELDS: &[&str] = &["content", "page_content"];
+
+/// Above this many token comparisons the word diff degrades to replace-all
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WordOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WordChange {
+    pub op: WordOp,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub word_diff: Option<Vec<WordChange>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModuleChange {
+    pub uuid: String,
+    pub title: Value,
+    pub change: ModuleChangeKind,
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MergeConflict {
+    /// Field name, or `modules.<uuid>.<field>` for module conflicts
+    pub path: String,
+    pub base: Value,
+    pub ours: Value,
+    pub theirs: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MergeResult {
+    pub merged: Value,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Splits text into alternating word / whitespace tokens so that
+/// concatenating the tokens reproduces the input exactly.
+pub fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_space: Option<bool> = None;
+
+    for (idx, ch) in text.char_indices() {
+        let is_space = ch.is_whitespace();
+        match in_space {
+            Some(prev) if prev != is_space => {
+                tokens.push(&text[start..idx]);
+                start = idx;
+            }
+            _ => {}
+        }
+        in_space = Some(is_space);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// A contiguous edit: tokens `base[start..end]` replaced by `replacement`
+#[derive(Debug, Clone, PartialEq)]
+struct Hunk<'a> {
+    start: usize,
+    end: usize,
+    replacement: Vec<&'a str>,
+}
+
+/// Longest-common-subsequence alignment; returns matched (a_index, b_index) pairs
+fn lcs_pairs(a: &[&str], b: &[&str]) -> Option<Vec<(usize, usize)>> {
+    // Trim common prefix/suffix first: most edits touch a small region
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (n, m) = (a_mid.len(), b_mid.len());
+
+    if n.saturating_mul(m) > MAX_LCS_CELLS {
+        return None;
+    }
+
+    let mut table = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = if a_mid[i] == b_mid[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a_mid[i] == b_mid[j] {
+            pairs.push((prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    for k in 0..suffix {
+        pairs.push((a.len() - suffix + k, b.len() - suffix + k));
+    }
+    Some(pairs)
+}
+
+/// Edits that turn `base` into `other`, expressed against base positions
+fn hunks<'a>(base: &[&'a str], other: &[&'a str]) -> Vec<Hunk<'a>> {
+    let pairs = lcs_pairs(base, other).unwrap_or_default();
+
+    let mut result = Vec::new();
+    let (mut bi, mut oi) = (0, 0);
+    for (pb, po) in pairs.into_iter().chain(std::iter::once((base.len(), other.len()))) {
+        if pb > bi || po > oi {
+            result.push(Hunk {
+                start: bi,
+                end: pb,
+                replacement: other[oi..po].to_vec(),
+            });
+        }
+        bi = pb + 1;
+        oi = po + 1;
+    }
+    result
+}
+
+/// Word-level diff of two strings
+pub fn word_diff(before: &str, after: &str) -> Vec<WordChange> {
+    let a = tokenize(before);
+    let b = tokenize(after);
+
+    let mut ops: Vec<WordChange> = Vec::new();
+    let mut push = |op: WordOp, text: &str| {
+        if text.is_empty() {
+            return;
+        }
+        match ops.last_mut() {
+            Some(last) if last.op == op => last.text.push_str(text),
+            _ => ops.push(WordChange { op, text: text.to_string() }),
+        }
+    };
+
+    let mut cursor = 0;
+    for hunk in hunks(&a, &b) {
+        push(WordOp::Equal, &a[cursor..hunk.start].concat());
+        push(WordOp::Delete, &a[hunk.start..hunk.end].concat());
+        push(WordOp::Insert, &hunk.replacement.concat());
+        cursor = hunk.end;
+    }
+    push(WordOp::Equal, &a[cursor..].concat());
+
+    ops
+}
+
+/// Three-way merge of text at word granularity.
+/// Returns `None` when both sides edited the same region differently.
+pub fn merge_text(base: &str, ours: &str, theirs: &str) -> Option<String> {
+    let base_tokens = tokenize(base);
+    let ours_tokens = tokenize(ours);
+    let theirs_tokens = tokenize(theirs);
+
+    let our_hunks = hunks(&base_tokens, &ours_tokens);
+    let their_hunks = hunks(&base_tokens, &theirs_tokens);
+
+    // Tag and sort all edits by base position
+    let mut all: Vec<&Hunk> = our_hunks.iter().chain(their_hunks.iter()).collect();
+    all.sort_by_key(|h| (h.start, h.end));
+
+    let mut output: Vec<&str> = Vec::new();
+    let mut cursor = 0;
+    let mut idx = 0;
+    while idx < all.len() {
+        let hunk = all[idx];
+        // Identical edits from both sides collapse into one
+        if idx + 1 < all.len() && all[idx + 1] == hunk {
+            idx += 1;
+            continue;
+        }
+        if hunk.start < cursor {
+            return None;
+        }
+        if let Some(next) = all.get(idx + 1) {
+            let overlaps = next.start < hunk.end || next.start == hunk.start;
+            if overlaps {
+                return None;
+            }
+        }
+        output.extend_from_slice(&base_tokens[cursor..hunk.start]);
+        output.extend_from_slice(&hunk.replacement);
+        cursor = hunk.end;
+        idx += 1;
+    }
+    output.extend_from_slice(&base_tokens[cursor..]);
+
+    Some(output.concat())
+}
+
+fn as_object(value: &Value) -> Map<String, Value> {
+    value.as_object().cloned().unwrap_or_default()
+}
+
+/// Field-by-field diff of two JSON objects
+pub fn diff_fields(before: &Value, after: &Value, ignore: &[&str]) -> Vec<FieldChange> {
+    let before = as_object(before);
+    let after = as_object(after);
+
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+
+    keys.into_iter()
+        .filter(|key| !ignore.contains(&key.as_str()))
+        .filter_map(|key| {
+            let old = before.get(key).cloned().unwrap_or(Value::Null);
+            let new = after.get(key).cloned().unwrap_or(Value::Null);
+            if old == new {
+                return None;
+            }
+
+            let word_diff = if TEXT_FIELDS.contains(&key.as_str()) {
+                Some(word_diff(old.as_str().unwrap_or(""), new.as_str().unwrap_or("")))
+            } else {
+                None
+            };
+
+            Some(FieldChange {
+                field: key.clone(),
+                before: old,
+                after: new,
+                word_diff,
+            })
+        })
+        .collect()
+}
+
+fn index_by_uuid(modules: &[Value]) -> HashMap<String, &Value> {
+    modules
+        .iter()
+        .filter_map(|m| m.get("uuid").and_then(|u| u.as_str()).map(|u| (u.to_string(), m)))
+        .collect()
+}
+
+/// Diff of two module lists, matched by module UUID.
+/// Modules present in `before` keep their order, additions are appended.
+pub fn diff_modules(before: &[Value], after: &[Value]) -> Vec<ModuleChange> {
+    let old = index_by_uuid(before);
+    let new = index_by_uuid(after);
+
+    let mut changes = Vec::new();
+
+    for module in before {
+        let uuid = match module.get("uuid").and_then(|u| u.as_str()) {
+            Some(u) => u,
+            None => continue,
+        };
+        match new.get(uuid) {
+            None => changes.push(ModuleChange {
+                uuid: uuid.to_string(),
+                title: module.get("title").cloned().unwrap_or(Value::Null),
+                change: ModuleChangeKind::Removed,
+                fields: diff_fields(module, &Value::Null, &["uuid"]),
+            }),
+            Some(updated) => {
+                let fields = diff_fields(module, updated, &["uuid"]);
+                if !fields.is_empty() {
+                    changes.push(ModuleChange {
+                        uuid: uuid.to_string(),
+                        title: updated.get("title").cloned().unwrap_or(Value::Null),
+                        change: ModuleChangeKind::Modified,
+                        fields,
+                    });
+                }
+            }
+        }
+    }
+
+    for module in after {
+        let uuid = match module.get("uuid").and_then(|u| u.as_str()) {
+            Some(u) => u,
+            None => continue,
+        };
+        if !old.contains_key(uuid) {
+            changes.push(ModuleChange {
+                uuid: uuid.to_string(),
+                title: module.get("title").cloned().unwrap_or(Value::Null),
+                change: ModuleChangeKind::Added,
+                fields: diff_fields(&Value::Null, module, &["uuid"]),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Three-way merge of two JSON objects edited from a common base.
+/// Text fields that both sides changed are merged word by word when possible.
+pub fn merge_fields(base: &Value, ours: &Value, theirs: &Value, path_prefix: &str, ignore: &[&str]) -> MergeResult {
+    let base_map = as_object(base);
+    let ours_map = as_object(ours);
+    let theirs_map = as_object(theirs);
+
+    let keys: BTreeSet<&String> = base_map.keys()
+        .chain(ours_map.keys())
+        .chain(theirs_map.keys())
+        .collect();
+
+    let mut merged = Map::new();
+    let mut conflicts = Vec::new();
+
+    for key in keys {
+        let b = base_map.get(key).cloned().unwrap_or(Value::Null);
+        let o = ours_map.get(key).cloned().unwrap_or(Value::Null);
+        let t = theirs_map.get(key).cloned().unwrap_or(Value::Null);
+
+        let value = if ignore.contains(&key.as_str()) || o == t || o == b {
+            t
+        } else if t == b {
+            o
+        } else {
+            let text_merge = match (b.as_str(), o.as_str(), t.as_str()) {
+                (Some(bs), Some(os), Some(ts)) => merge_text(bs, os, ts),
+                _ => None,
+            };
+            match text_merge {
+                Some(text) => Value::String(text),
+                None => {
+                    conflicts.push(MergeConflict {
+                        path: format!("{}{}", path_prefix, key),
+                        base: b,
+                        ours: o,
+                        theirs: t.clone(),
+                    });
+                    // Server state is kept until the conflict is resolved
+                    t
+                }
+            }
+        };
+        merged.insert(key.clone(), value);
+    }
+
+    MergeResult {
+        merged: Value::Object(merged),
+        conflicts,
+    }
+}
+
+/// Three-way merge of module lists keyed by UUID.
+/// Returns the merged list (theirs order first, then our additions) and conflicts.
+pub fn merge_modules(base: &[Value], ours: &[Value], theirs: &[Value]) -> (Vec<Value>, Vec<MergeConflict>) {
+    let base_idx = index_by_uuid(base);
+    let ours_idx = index_by_uuid(ours);
+    let theirs_idx = index_by_uuid(theirs);
+
+    let mut merged = Vec::new();
+    let mut conflicts = Vec::new();
+
+    let mut order: Vec<&str> = Vec::new();
+    for module in theirs.iter().chain(ours.iter()).chain(base.iter()) {
+        if let Some(uuid) = module.get("uuid").and_then(|u| u.as_str()) {
+            if !order.contains(&uuid) {
+                order.push(uuid);
+            }
+        }
+    }
+
+    for uuid in order {
+        let b = base_idx.get(uuid).copied();
+        let o = ours_idx.get(uuid).copied();
+        let t = theirs_idx.get(uuid).copied();
+        let prefix = format!("modules.{}.", uuid);
+
+        match (b, o, t) {
+            // Added on one or both sides
+            (None, Some(o), None) => merged.push(o.clone()),
+            (None, None, Some(t)) => merged.push(t.clone()),
+            (None, Some(o), Some(t)) => {
+                let result = merge_fields(&Value::Null, o, t, &prefix, &[]);
+                conflicts.extend(result.conflicts);
+                merged.push(result.merged);
+            }
+            // Deleted on both sides
+            (Some(_), None, None) | (None, None, None) => {}
+            // Deleted on one side: fine unless the other side edited it
+            (Some(b), None, Some(t)) => {
+                if t != b {
+                    conflicts.push(MergeConflict {
+                        path: format!("modules.{}", uuid),
+                        base: b.clone(),
+                        ours: Value::Null,
+                        theirs: t.clone(),
+                    });
+                    merged.push(t.clone());
+                }
+            }
+            (Some(b), Some(o), None) => {
+                if o != b {
+                    conflicts.push(MergeConflict {
+                        path: format!("modules.{}", uuid),
+                        base: b.clone(),
+                        ours: o.clone(),
+                        theirs: Value::Null,
+                    });
+                }
+            }
+            (Some(b), Some(o), Some(t)) => {
+                let result = merge_fields(b, o, t, &prefix, &[]);
+                conflicts.extend(result.conflicts);
+                merged.push(result.merged);
+            }
+        }
+    }
+
+    (merged, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tokenize_roundtrip() {
+        let text = "  Hello,  world\nagain ";
+        assert_eq!(tokenize(text).concat(), text);
+        assert_eq!(tokenize("a b"), vec!["a", " ", "b"]);
+    }
+
+    #[test]
+    fn test_word_diff() {
+        let ops = word_diff("the quick brown fox", "the slow brown fox");
+        assert_eq!(ops, vec![
+            WordChange { op: WordOp::Equal, text: "the ".to_string() },
+            WordChange { op: WordOp::Delete, text: "quick".to_string() },
+            WordChange { op: WordOp::Insert, text: "slow".to_string() },
+            WordChange { op: WordOp::Equal, text: " brown fox".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_merge_text_non_overlapping() {
+        let merged = merge_text(
+            "one two three four",
+            "ONE two three four",
+            "one two three FOUR",
+        );
+        assert_eq!(merged, Some("ONE two three FOUR".to_string()));
+    }
+
+    #[test]
+    fn test_merge_text_conflict() {
+        assert_eq!(merge_text("one two", "one 2", "one deux"), None);
+        // Same edit on both sides is not a conflict
+        assert_eq!(merge_text("one two", "one 2", "one 2"), Some("one 2".to_string()));
+    }
+
+    #[test]
+    fn test_diff_fields_ignores_bookkeeping() {
+        let before = json!({"page_title": "A", "current_revision": 1, "content": "x y"});
+        let after = json!({"page_title": "B", "current_revision": 2, "content": "x z"});
+        let changes = diff_fields(&before, &after, IGNORED_PAGE_FIELDS);
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].field, "content");
+        assert!(changes[0].word_diff.is_some());
+        assert_eq!(changes[1].field, "page_title");
+        assert!(changes[1].word_diff.is_none());
+    }
+
+    #[test]
+    fn test_merge_fields() {
+        let base = json!({"page_title": "A", "meta_title": "M", "page_url": "/a"});
+        let ours = json!({"page_title": "A2", "meta_title": "M", "page_url": "/a"});
+        let theirs = json!({"page_title": "A", "meta_title": "M2", "page_url": "/b"});
+
+        let result = merge_fields(&base, &ours, &theirs, "", &[]);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.merged, json!({"page_title": "A2", "meta_title": "M2", "page_url": "/b"}));
+
+        let ours = json!({"page_title": "Ours"});
+        let theirs = json!({"page_title": "Theirs"});
+        let result = merge_fields(&json!({"page_title": "A"}), &ours, &theirs, "", &[]);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].path, "page_title");
+    }
+
+    #[test]
+    fn test_diff_and_merge_modules() {
+        let base = vec![
+            json!({"uuid": "m1", "title": "hero", "content": "Hi"}),
+            json!({"uuid": "m2", "title": "footer", "content": "Bye"}),
+        ];
+        let ours = vec![
+            json!({"uuid": "m1", "title": "hero", "content": "Hello"}),
+            json!({"uuid": "m2", "title": "footer", "content": "Bye"}),
+            json!({"uuid": "m3", "title": "cta", "content": "Buy"}),
+        ];
+        let theirs = vec![
+            json!({"uuid": "m1", "title": "hero", "content": "Hi"}),
+        ];
+
+        let changes = diff_modules(&base, &ours);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].change, ModuleChangeKind::Modified);
+        assert_eq!(changes[1].change, ModuleChangeKind::Added);
+
+        let (merged, conflicts) = merge_modules(&base, &ours, &theirs);
+        assert!(conflicts.is_empty());
+        let uuids: Vec<&str> = merged.iter().map(|m| m["uuid"].as_str().unwrap()).collect();
+        assert_eq!(uuids, vec!["m1", "m3"]);
+        assert_eq!(merged[0]["content"], "Hello");
+
+        // Deleted by them while we edited it
+        let theirs = vec![json!({"uuid": "m2", "title": "footer", "content": "Bye"})];
+        let (_, conflicts) = merge_modules(&base, &ours, &theirs);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "modules.m1");
+    }
+}
@@ -1,10 +1,23 @@
 // Helper function to create a page revision - PostgreSQL only
 use diesel::prelude::*;
-use crate::models::page_models::Page;
-use crate::models::revision_models::NewPageRevision;
+use serde::Serialize;
+use crate::models::module_models::{Module, ModuleCategory, MutCategory, MutModule};
+use crate::models::page_models::{MutPage, Page};
+use crate::models::revision_models::{NewPageRevision, PageRevision};
+use crate::models::Model;
+
+/// Page, module and category state captured by a revision (or read live)
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionState {
+    pub page: Page,
+    /// `None` for legacy revisions created before modules were snapshotted
+    pub modules: Option<Vec<Module>>,
+    /// `None` for revisions created before categories were snapshotted
+    pub categories: Option<Vec<ModuleCategory>>,
+}
 
 /// Creates a new revision record for a page
-/// 
+///
 /// # Arguments
 /// * `page_uuid` - UUID of the page being revised
 /// * `user_id` - ID of the user making the change (from Claims)
@@ -21,24 +34,28 @@ pub fn create_page_revision(
 ) -> Result<i32, diesel::result::Error> {
     use crate::schema::pages::dsl::*;
     use crate::schema::page_revisions::dsl::page_revisions as pr;
-    
+
     // Get current page state
     let current_page: Page = pages.filter(uuid.eq(page_uuid)).first::<Page>(conn)?;
-    
+    let current_modules: Vec<Module> = Module::belonging_to(&current_page).load::<Module>(conn)?;
+    let current_categories: Vec<ModuleCategory> = ModuleCategory::belonging_to(&current_page).load::<ModuleCategory>(conn)?;
+
     // Get current revision number (or start at 0)
     let new_revision_number = current_page.current_revision.unwrap_or(0) + 1;
-    
+
     // Serialize full page to JSON
     let full_snapshot = serde_json::to_string(&current_page)
         .unwrap_or_else(|_| "{}".to_string());
-    
+    let modules_snapshot = serde_json::to_string(&current_modules).ok();
+    let categories_snapshot = serde_json::to_string(&current_categories).ok();
+
     // Create revision record
     let new_revision = NewPageRevision {
         page_uuid: page_uuid.to_string(),
         revision_number: new_revision_number,
         page_title: current_page.page_title.clone(),
         page_url: current_page.page_url.clone(),
-        page_content: None,
+        page_content: current_page.content.clone(),
         meta_title: current_page.meta_title.clone(),
         meta_description: current_page.meta_description.clone(),
         meta_keywords: current_page.meta_keywords,
@@ -46,11 +63,13 @@ pub fn create_page_revision(
         full_snapshot,
         change_summary,
         changed_by_user_id: user_id,
+        modules_snapshot,
+        categories_snapshot,
     };
-    
+
     // Insert revision
     diesel::insert_into(pr).values(&new_revision).execute(conn)?;
-    
+
     // Update page's current_revision counter
     diesel::update(pages.filter(uuid.eq(page_uuid)))
         .set((
@@ -58,6 +77,159 @@ pub fn create_page_revision(
             last_modified_by.eq(user_id),
         ))
         .execute(conn)?;
-    
+
     Ok(new_revision_number)
 }
+
+fn state_from_revision(revision: &PageRevision) -> Result<RevisionState, String> {
+    let page: Page = serde_json::from_str(&revision.full_snapshot)
+        .map_err(|e| format!("Failed to deserialize snapshot: {}", e))?;
+
+    let modules = match &revision.modules_snapshot {
+        Some(json) => Some(
+            serde_json::from_str::<Vec<Module>>(json)
+                .map_err(|e| format!("Failed to deserialize modules snapshot: {}", e))?
+        ),
+        None => None,
+    };
+
+    let categories = match &revision.categories_snapshot {
+        Some(json) => Some(
+            serde_json::from_str::<Vec<ModuleCategory>>(json)
+                .map_err(|e| format!("Failed to deserialize categories snapshot: {}", e))?
+        ),
+        None => None,
+    };
+
+    Ok(RevisionState { page, modules, categories })
+}
+
+/// Loads the state stored in revision `revision_number` of a page
+pub fn load_revision_state(
+    page_uuid: &str,
+    revision_number: i32,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<Option<RevisionState>, String> {
+    use crate::schema::page_revisions::dsl as pr;
+
+    let revision = pr::page_revisions
+        .filter(pr::page_uuid.eq(page_uuid))
+        .filter(pr::revision_number.eq(revision_number))
+        .first::<PageRevision>(conn)
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    revision.as_ref().map(state_from_revision).transpose()
+}
+
+/// Loads the live page and its modules
+pub fn load_current_state(
+    page_uuid: &str,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<RevisionState, diesel::result::Error> {
+    use crate::schema::pages::dsl::*;
+
+    let page: Page = pages.filter(uuid.eq(page_uuid)).first::<Page>(conn)?;
+    let modules = Module::belonging_to(&page).load::<Module>(conn)?;
+    let categories = ModuleCategory::belonging_to(&page).load::<ModuleCategory>(conn)?;
+
+    Ok(RevisionState { page, modules: Some(modules), categories: Some(categories) })
+}
+
+/// State of a page as an editor saw it when `pages.current_revision` was `version`.
+///
+/// `create_page_revision` snapshots the page *before* each update, so the state
+/// at version N is the snapshot stored in revision N + 1 — or the live page if
+/// nobody has saved since.
+pub fn load_state_at_version(
+    page_uuid: &str,
+    version: i32,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<Option<RevisionState>, String> {
+    let current = load_current_state(page_uuid, conn).map_err(|e| e.to_string())?;
+    if current.page.current_revision.unwrap_or(0) == version {
+        return Ok(Some(current));
+    }
+    load_revision_state(page_uuid, version + 1, conn)
+}
+
+/// Restores a page, its categories and its modules to `state` in one transaction.
+///
+/// The live state is snapshotted first, so a rollback can itself be rolled back.
+/// Publishing status and schedule are left as they are; parts of the state that
+/// the revision predates (`None` modules or categories) are not touched.
+pub fn restore_state(
+    page_uuid: &str,
+    state: &RevisionState,
+    user_id: Option<i32>,
+    change_summary: Option<String>,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<i32, diesel::result::Error> {
+    use crate::schema::{module_category, modules};
+
+    conn.transaction(|conn| {
+        let live = Page::read_one_for_update(page_uuid.to_string(), conn)?;
+        let rev_num = create_page_revision(page_uuid, user_id, change_summary, conn)?;
+
+        let mut changes: MutPage = state.page.clone().into();
+        changes.uuid = Some(live.uuid.clone());
+        changes.tenant_id = live.tenant_id;
+        changes.status = live.status;
+        changes.publish_at = live.publish_at;
+        changes.unpublish_at = live.unpublish_at;
+        changes.current_revision = Some(rev_num);
+        changes.last_modified_by = user_id;
+        Page::update(page_uuid.to_string(), &changes, conn)?;
+
+        // Categories first: restored modules may reference them
+        if let Some(categories) = &state.categories {
+            for category in categories {
+                let mut category: MutCategory = category.clone().into();
+                category.page_uuid = page_uuid.to_string();
+                let exists = module_category::table
+                    .filter(module_category::uuid.eq(category.uuid.clone().unwrap_or_default()))
+                    .count()
+                    .get_result::<i64>(conn)? > 0;
+                if exists {
+                    ModuleCategory::update(category.uuid.clone().unwrap_or_default(), &category, conn)?;
+                } else {
+                    ModuleCategory::create(&category, conn)?;
+                }
+            }
+        }
+
+        if let Some(snapshot_modules) = &state.modules {
+            let existing: Vec<String> = modules::table
+                .filter(modules::page_uuid.eq(page_uuid))
+                .select(modules::uuid)
+                .load(conn)?;
+            let kept: Vec<&str> = snapshot_modules.iter().map(|m| m.uuid.as_str()).collect();
+
+            for stale in existing.iter().filter(|uuid| !kept.contains(&uuid.as_str())) {
+                Module::delete(stale.clone(), conn)?;
+            }
+            for module in snapshot_modules {
+                let mut module: MutModule = module.clone().into();
+                module.page_uuid = page_uuid.to_string();
+                if existing.iter().any(|uuid| Some(uuid) == module.uuid.as_ref()) {
+                    Module::update(module.uuid.clone().unwrap_or_default(), &module, conn)?;
+                } else {
+                    Module::create(&module, conn)?;
+                }
+            }
+        }
+
+        // Remove categories added after the revision, now that no restored module points at them
+        if let Some(categories) = &state.categories {
+            let kept: Vec<&str> = categories.iter().map(|c| c.uuid.as_str()).collect();
+            diesel::delete(
+                module_category::table
+                    .filter(module_category::page_uuid.eq(page_uuid))
+                    .filter(module_category::uuid.ne_all(kept)),
+            )
+            .execute(conn)?;
+        }
+
+        Ok(rev_num)
+    })
+}
@@ -1,7 +1,30 @@
-// Search Service - PostgreSQL
+// Search Service - PostgreSQL full-text search
+//
+// Backed by the `search_documents` table, which triggers keep in sync with
+// pages, modules, media and their translations (one tsvector per resource and
+// language). Queries are parsed with the text search configuration of the
+// requested language, ranked with ts_rank and highlighted with ts_headline.
+//
+// Hybrid mode fuses that keyword ranking with pgvector similarity over
+// `content_embeddings` using reciprocal rank fusion.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
 
 use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Float4, Integer, Nullable, Text};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Resource types indexed in `search_documents`
+pub const RESOURCE_TYPES: &[&str] = &["pages", "modules", "media"];
+
+const DEFAULT_CONFIG: &str = "simple";
+
+// Control characters mark highlights so the snippet can be HTML-escaped
+// before the markers are turned into <mark> tags.
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_STOP: char = '\u{3}';
 
 #[derive(Debug, Deserialize)]
 pub struct SearchQuery {
@@ -9,29 +32,8 @@ pub struct SearchQuery {
     pub resources: Option<Vec<String>>,
     pub page: Option<i64>,
     pub per_page: Option<i64>,
-}
-
-#[derive(Debug, Serialize, Queryable)]
-pub struct PageSearchResult {
-    pub uuid: String,
-    pub page_title: String,
-    pub page_url: String,
-    pub meta_description: Option<String>,
-}
-
-#[derive(Debug, Serialize, Queryable)]
-pub struct ModuleSearchResult {
-    pub uuid: String,
-    pub title: String,
-    pub content: String,
-}
-
-#[derive(Debug, Serialize, Queryable)]
-pub struct MediaSearchResult {
-    pub uuid: String,
-    pub filename: String,
-    pub original_filename: String,
-    pub alt_text: Option<String>,
+    /// Language code from the `languages` table; the default language when omitted
+    pub lang: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -39,7 +41,10 @@ pub struct SearchResult {
     pub resource_type: String,
     pub id: String,
     pub title: String,
+    /// `ts_headline` excerpt, HTML-escaped with matches wrapped in `<mark>`
     pub snippet: String,
+    pub url: Option<String>,
+    pub rank: f32,
 }
 
 #[derive(Debug, Serialize)]
@@ -48,98 +53,687 @@ pub struct SearchResponse {
     pub total: usize,
     pub page: i64,
     pub per_page: i64,
+    /// Match counts per resource type, independent of the `resources` filter
+    pub facets: BTreeMap<String, i64>,
+    /// `search_history` id to report clicks against, when the search was logged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_id: Option<i64>,
+}
+
+/// How results are ranked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    Keyword,
+    Semantic,
+    Hybrid,
+}
+
+impl SearchMode {
+    pub fn from_str(mode: &str) -> Option<Self> {
+        match mode {
+            "keyword" => Some(SearchMode::Keyword),
+            "semantic" => Some(SearchMode::Semantic),
+            "hybrid" => Some(SearchMode::Hybrid),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchMode::Keyword => "keyword",
+            SearchMode::Semantic => "semantic",
+            SearchMode::Hybrid => "hybrid",
+        }
+    }
 }
 
-/// Simple keyword search
+/// Per-tenant fusion settings, read from `tenants.settings.search`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HybridWeights {
+    /// Weight of the keyword (ts_rank) ranking
+    pub keyword: f64,
+    /// Weight of the embedding similarity ranking
+    pub semantic: f64,
+    /// RRF smoothing constant; larger values flatten the gap between ranks
+    pub rrf_k: f64,
+    /// Semantic candidates below this cosine similarity are ignored
+    pub min_similarity: f32,
+    /// Candidates taken from each ranking before fusion
+    pub candidates: i64,
+}
+
+impl Default for HybridWeights {
+    fn default() -> Self {
+        HybridWeights {
+            keyword: 1.0,
+            semantic: 1.0,
+            rrf_k: 60.0,
+            min_similarity: 0.0,
+            candidates: 100,
+        }
+    }
+}
+
+impl SearchQuery {
+    /// Resource types to return (all types when unspecified)
+    fn resource_types(&self) -> Vec<String> {
+        match &self.resources {
+            Some(list) if !list.is_empty() => list
+                .iter()
+                .filter(|r| RESOURCE_TYPES.contains(&r.as_str()))
+                .cloned()
+                .collect(),
+            _ => RESOURCE_TYPES.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct RankedRow {
+    #[diesel(sql_type = Text)]
+    resource_type: String,
+    #[diesel(sql_type = Text)]
+    resource_id: String,
+    #[diesel(sql_type = Text)]
+    title: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    url: Option<String>,
+    #[diesel(sql_type = Float4)]
+    rank: f32,
+    #[diesel(sql_type = Text)]
+    headline: String,
+}
+
+#[derive(QueryableByName)]
+struct FacetRow {
+    #[diesel(sql_type = Text)]
+    resource_type: String,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Language the query is run in: `language_key` 0 is the default-language source text
+struct SearchLanguage {
+    language_key: i32,
+    config: String,
+    default_config: String,
+}
+
+fn resolve_language(
+    lang: Option<&str>,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<SearchLanguage, diesel::result::Error> {
+    use crate::schema::languages::dsl::*;
+
+    let default_config = languages
+        .filter(is_default.eq(true))
+        .select(search_config)
+        .first::<String>(conn)
+        .optional()?
+        .unwrap_or_else(|| DEFAULT_CONFIG.to_string());
+
+    let requested = match lang {
+        Some(lang_code) => languages
+            .filter(code.eq(lang_code))
+            .filter(enabled.eq(true))
+            .select((id, search_config, is_default))
+            .first::<(i32, String, Option<bool>)>(conn)
+            .optional()?,
+        None => None,
+    };
+
+    Ok(match requested {
+        Some((lang_id, config, Some(false) | None)) => SearchLanguage {
+            language_key: lang_id,
+            config,
+            default_config,
+        },
+        _ => SearchLanguage {
+            language_key: 0,
+            config: default_config.clone(),
+            default_config,
+        },
+    })
+}
+
+/// Converts user input into `to_tsquery` syntax.
+///
+/// Words are ANDed; `"quoted words"` become phrase queries, a trailing `*`
+/// makes a prefix match, a leading `-` excludes a word and a bare `OR`
+/// between terms makes them alternatives. Returns `None` when nothing
+/// searchable remains.
+pub fn build_tsquery(input: &str) -> Option<String> {
+    fn quote(word: &str) -> Option<String> {
+        let (word, prefix) = match word.strip_suffix('*') {
+            Some(stem) => (stem, true),
+            None => (word, false),
+        };
+        let cleaned: String = word.chars().filter(|c| !c.is_control() && *c != '\\').collect();
+        let cleaned = cleaned.trim_matches(|c: char| !c.is_alphanumeric());
+        if cleaned.is_empty() {
+            return None;
+        }
+        let quoted = format!("'{}'", cleaned.replace('\'', "''"));
+        Some(if prefix { format!("{}:*", quoted) } else { quoted })
+    }
+
+    // Split into terms, keeping quoted phrases together
+    let mut terms: Vec<(bool, String)> = Vec::new();
+    let mut rest = input.trim();
+    while !rest.is_empty() {
+        let negated = rest.starts_with('-');
+        let body = if negated { &rest[1..] } else { rest };
+        if let Some(phrase) = body.strip_prefix('"') {
+            let (inner, after) = match phrase.find('"') {
+                Some(end) => (&phrase[..end], &phrase[end + 1..]),
+                None => (phrase, ""),
+            };
+            terms.push((negated, format!("\"{}", inner)));
+            rest = after.trim_start();
+        } else {
+            let end = body.find(char::is_whitespace).unwrap_or(body.len());
+            terms.push((negated, body[..end].to_string()));
+            rest = body[end..].trim_start();
+        }
+    }
+
+    let mut query = String::new();
+    let mut pending_or = false;
+    for (negated, term) in terms {
+        if term == "OR" && !negated {
+            pending_or = !query.is_empty();
+            continue;
+        }
+
+        let expr = match term.strip_prefix('"') {
+            Some(phrase) => {
+                let words: Vec<String> = phrase.split_whitespace().filter_map(quote).collect();
+                match words.len() {
+                    0 => continue,
+                    1 => words[0].clone(),
+                    _ => format!("({})", words.join(" <-> ")),
+                }
+            }
+            None => match quote(&term) {
+                Some(word) => word,
+                None => continue,
+            },
+        };
+        let expr = if negated { format!("!{}", expr) } else { expr };
+
+        if !query.is_empty() {
+            query.push_str(if pending_or { " | " } else { " & " });
+        }
+        query.push_str(&expr);
+        pending_or = false;
+    }
+
+    if query.is_empty() { None } else { Some(query) }
+}
+
+/// HTML-escapes a headline and turns the highlight markers into `<mark>` tags
+fn headline_to_html(headline: &str) -> String {
+    let mut html = String::with_capacity(headline.len() + 16);
+    for c in headline.chars() {
+        match c {
+            HIGHLIGHT_START => html.push_str("<mark>"),
+            HIGHLIGHT_STOP => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            _ => html.push(c),
+        }
+    }
+    html
+}
+
+// Documents matching the query in the requested language, falling back to the
+// default-language source text. A translation wins over its source when both match.
+// $1 language config, $2 default config, $3 tsquery, $4 tenant, $5 language_key
+const MATCHES_CTE: &str = "
+    WITH q AS (
+        SELECT to_tsquery($1::regconfig, $3) AS lang_q,
+               to_tsquery($2::regconfig, $3) AS base_q
+    ),
+    matches AS (
+        SELECT DISTINCT ON (d.resource_type, d.resource_id)
+               d.resource_type, d.resource_id, d.title, d.body, d.url, d.search_config,
+               CASE WHEN d.language_key = 0 THEN q.base_q ELSE q.lang_q END AS tsq,
+               ts_rank(d.document, CASE WHEN d.language_key = 0 THEN q.base_q ELSE q.lang_q END) AS rank
+          FROM search_documents d, q
+         WHERE ($4::integer IS NULL OR d.tenant_id = $4)
+           AND ((d.language_key = $5 AND d.document @@ q.lang_q)
+             OR (d.language_key = 0 AND d.document @@ q.base_q))
+         ORDER BY d.resource_type, d.resource_id, d.language_key DESC
+    )";
+
+/// Parsed keyword query, ready to run against `search_documents`
+struct KeywordSearch {
+    tsquery: String,
+    language: SearchLanguage,
+}
+
+fn prepare_keyword(
+    query: &SearchQuery,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<Option<KeywordSearch>, diesel::result::Error> {
+    let tsquery = match build_tsquery(&query.q) {
+        Some(tsquery) => tsquery,
+        None => return Ok(None),
+    };
+    let language = resolve_language(query.lang.as_deref(), conn)?;
+    Ok(Some(KeywordSearch { tsquery, language }))
+}
+
+fn keyword_facets(
+    search: &KeywordSearch,
+    tenant_id: Option<i32>,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<BTreeMap<String, i64>, diesel::result::Error> {
+    Ok(diesel::sql_query(format!(
+        "{} SELECT resource_type, COUNT(*) AS count FROM matches GROUP BY resource_type",
+        MATCHES_CTE
+    ))
+    .bind::<Text, _>(&search.language.config)
+    .bind::<Text, _>(&search.language.default_config)
+    .bind::<Text, _>(&search.tsquery)
+    .bind::<Nullable<Integer>, _>(tenant_id)
+    .bind::<Integer, _>(search.language.language_key)
+    .load::<FacetRow>(conn)?
+    .into_iter()
+    .map(|row| (row.resource_type, row.count))
+    .collect())
+}
+
+fn keyword_rows(
+    search: &KeywordSearch,
+    tenant_id: Option<i32>,
+    resource_types: &[String],
+    limit: i64,
+    offset: i64,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<Vec<RankedRow>, diesel::result::Error> {
+    let headline_options = format!(
+        "StartSel={}, StopSel={}, MaxWords=35, MinWords=15, MaxFragments=2, FragmentDelimiter=\" … \"",
+        HIGHLIGHT_START, HIGHLIGHT_STOP
+    );
+    diesel::sql_query(format!(
+        "{} SELECT resource_type, resource_id, title, url, rank,
+                   ts_headline(search_config::regconfig,
+                               CASE WHEN body = '' THEN title ELSE body END,
+                               tsq, $6) AS headline
+              FROM matches
+             WHERE resource_type = ANY($7)
+             ORDER BY rank DESC, resource_type, resource_id
+             LIMIT $8 OFFSET $9",
+        MATCHES_CTE
+    ))
+    .bind::<Text, _>(&search.language.config)
+    .bind::<Text, _>(&search.language.default_config)
+    .bind::<Text, _>(&search.tsquery)
+    .bind::<Nullable<Integer>, _>(tenant_id)
+    .bind::<Integer, _>(search.language.language_key)
+    .bind::<Text, _>(&headline_options)
+    .bind::<Array<Text>, _>(resource_types)
+    .bind::<BigInt, _>(limit)
+    .bind::<BigInt, _>(offset)
+    .load(conn)
+}
+
+fn to_result(row: RankedRow) -> SearchResult {
+    SearchResult {
+        resource_type: row.resource_type,
+        id: row.resource_id,
+        title: row.title,
+        snippet: headline_to_html(&row.headline),
+        url: row.url,
+        rank: row.rank,
+    }
+}
+
+fn empty_response(page: i64, per_page: i64) -> SearchResponse {
+    SearchResponse {
+        results: vec![],
+        total: 0,
+        page,
+        per_page,
+        facets: BTreeMap::new(),
+        search_id: None,
+    }
+}
+
+/// Full-text search across pages, modules and media
+///
+/// When `tenant_id` is given, results are restricted to that tenant
+/// (modules are scoped through their parent page).
 pub fn search(
     query: &SearchQuery,
+    tenant_id: Option<i32>,
     conn: &mut crate::models::PooledDatabaseConnection
 ) -> Result<SearchResponse, diesel::result::Error> {
-    use crate::schema::{pages, modules, media};
-    
-    let page = query.page.unwrap_or(1);
-    let per_page = query.per_page.unwrap_or(20).min(100);
-    let search_term = format!("%{}%", query.q);
-    
-    let mut all_results: Vec<SearchResult> = Vec::new();
-    
-    // Search pages
-    let page_results: Vec<PageSearchResult> = pages::table
-        .filter(
-            pages::page_title.like(&search_term)
-            .or(pages::page_name.like(&search_term))
-            .or(pages::meta_description.like(&search_term))
-        )
-        .limit(20)
-        .select((pages::uuid, pages::page_title, pages::page_url, pages::meta_description))
-        .load(conn)?;
-    
-    for p in page_results {
-        all_results.push(SearchResult {
-            resource_type: "pages".to_string(),
-            id: p.uuid,
-            title: p.page_title,
-            snippet: p.meta_description.unwrap_or_default(),
-        });
-    }
-    
-    // Search modules
-    let module_results: Vec<ModuleSearchResult> = modules::table
-        .filter(
-            modules::title.like(&search_term)
-            .or(modules::content.like(&search_term))
-        )
-        .limit(20)
-        .select((modules::uuid, modules::title, modules::content))
-        .load(conn)?;
-    
-    for m in module_results {
-        let snippet = if m.content.len() > 150 {
-            format!("{}...", &m.content[..150])
-        } else {
-            m.content
-        };
-        
-        all_results.push(SearchResult {
-            resource_type: "modules".to_string(),
-            id: m.uuid,
-            title: m.title,
-            snippet,
-        });
-    }
-    
-    // Search media
-    let media_results: Vec<MediaSearchResult> = media::table
-        .filter(
-            media::filename.like(&search_term)
-            .or(media::original_filename.like(&search_term))
-            .or(media::alt_text.like(&search_term))
-        )
-        .limit(20)
-        .select((media::uuid, media::filename, media::original_filename, media::alt_text))
-        .load(conn)?;
-    
-    for m in media_results {
-        all_results.push(SearchResult {
-            resource_type: "media".to_string(),
-            id: m.uuid,
-            title: m.filename.clone(),
-            snippet: m.alt_text.unwrap_or(m.original_filename),
-        });
-    }
-    
-    let total = all_results.len();
-    let offset = ((page - 1) * per_page) as usize;
-    let results: Vec<SearchResult> = all_results
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(20).clamp(1, 100);
+
+    let keyword = match prepare_keyword(query, conn)? {
+        Some(keyword) => keyword,
+        None => return Ok(empty_response(page, per_page)),
+    };
+    let resource_types = query.resource_types();
+
+    let facets = keyword_facets(&keyword, tenant_id, conn)?;
+    let total: i64 = facets
+        .iter()
+        .filter(|(resource, _)| resource_types.contains(resource))
+        .map(|(_, count)| count)
+        .sum();
+
+    let rows = keyword_rows(&keyword, tenant_id, &resource_types, per_page, (page - 1) * per_page, conn)?;
+
+    Ok(SearchResponse {
+        results: rows.into_iter().map(to_result).collect(),
+        total: total as usize,
+        page,
+        per_page,
+        facets,
+        search_id: None,
+    })
+}
+
+/// Fuses ranked lists with weighted reciprocal rank fusion:
+/// `score(d) = Σ weight / (k + rank(d))`, ranks starting at 1.
+/// Ties keep the order in which items were first seen.
+pub fn reciprocal_rank_fusion<K: Clone + Eq + Hash>(lists: &[(f64, Vec<K>)], k: f64) -> Vec<(K, f64)> {
+    let mut scores: HashMap<K, (usize, f64)> = HashMap::new();
+    for (weight, list) in lists {
+        for (idx, key) in list.iter().enumerate() {
+            let first_seen = scores.len();
+            let entry = scores.entry(key.clone()).or_insert((first_seen, 0.0));
+            entry.1 += weight / (k + (idx + 1) as f64);
+        }
+    }
+
+    let mut fused: Vec<(K, usize, f64)> = scores.into_iter().map(|(key, (seen, score))| (key, seen, score)).collect();
+    fused.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal).then(a.1.cmp(&b.1)));
+    fused.into_iter().map(|(key, _, score)| (key, score)).collect()
+}
+
+#[derive(QueryableByName)]
+struct SemanticRow {
+    #[diesel(sql_type = Text)]
+    resource_id: String,
+    #[diesel(sql_type = Text)]
+    title: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    url: Option<String>,
+    #[diesel(sql_type = Text)]
+    snippet: String,
+}
+
+/// Pages closest to `embedding`, best chunk per page
+fn semantic_rows(
+    embedding: &[f32],
+    model_name: &str,
+    tenant_id: Option<i32>,
+    weights: &HybridWeights,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<Vec<SemanticRow>, diesel::result::Error> {
+    let vector = format!(
+        "[{}]",
+        embedding.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+    );
+    diesel::sql_query(
+        "SELECT p.uuid AS resource_id,
+                p.page_title AS title,
+                p.page_url AS url,
+                COALESCE(p.meta_description, '') AS snippet
+           FROM content_embeddings e
+           JOIN pages p ON p.uuid = e.page_uuid
+          WHERE ($2::integer IS NULL OR p.tenant_id = $2)
+            AND e.embedding IS NOT NULL
+            AND e.model_name = $5
+          GROUP BY p.uuid, p.page_title, p.page_url, p.meta_description
+         HAVING MAX(1 - (e.embedding <=> $1::vector)) >= $3
+          ORDER BY MAX(1 - (e.embedding <=> $1::vector)) DESC, p.uuid
+          LIMIT $4",
+    )
+    .bind::<Text, _>(vector)
+    .bind::<Nullable<Integer>, _>(tenant_id)
+    .bind::<Float4, _>(weights.min_similarity)
+    .bind::<BigInt, _>(weights.candidates)
+    .bind::<Text, _>(model_name)
+    .load(conn)
+}
+
+/// Semantic or hybrid search: keyword and embedding rankings fused with RRF.
+///
+/// Only pages carry embeddings, so modules and media come from the keyword
+/// side alone. `embedding` is the query vector and the model that produced
+/// it; only page embeddings from the same model are compared. Without one
+/// (e.g. the provider is unavailable) the result degrades to keyword ranking.
+/// `rank` holds the fused score.
+pub fn hybrid_search(
+    query: &SearchQuery,
+    tenant_id: Option<i32>,
+    mode: SearchMode,
+    embedding: Option<(&[f32], &str)>,
+    weights: &HybridWeights,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<SearchResponse, diesel::result::Error> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(20).clamp(1, 100);
+    let candidates = weights.candidates.clamp(1, 1000);
+
+    let mut details: HashMap<(String, String), SearchResult> = HashMap::new();
+
+    let mut keyword_keys = Vec::new();
+    let keyword = match prepare_keyword(query, conn)? {
+        Some(keyword) if mode == SearchMode::Hybrid || embedding.is_none() => Some(keyword),
+        _ => None,
+    };
+    if let Some(keyword) = &keyword {
+        let all_types: Vec<String> = RESOURCE_TYPES.iter().map(|r| r.to_string()).collect();
+        for row in keyword_rows(keyword, tenant_id, &all_types, candidates, 0, conn)? {
+            let key = (row.resource_type.clone(), row.resource_id.clone());
+            keyword_keys.push(key.clone());
+            details.insert(key, to_result(row));
+        }
+    }
+
+    let mut semantic_keys = Vec::new();
+    if let Some((embedding, model_name)) = embedding {
+        for row in semantic_rows(embedding, model_name, tenant_id, weights, conn)? {
+            let key = ("pages".to_string(), row.resource_id.clone());
+            semantic_keys.push(key.clone());
+            details.entry(key).or_insert_with(|| SearchResult {
+                resource_type: "pages".to_string(),
+                id: row.resource_id,
+                title: row.title,
+                snippet: headline_to_html(&row.snippet),
+                url: row.url,
+                rank: 0.0,
+            });
+        }
+    }
+
+    // A ranking weighted 0 contributes nothing, so its exclusive hits drop out
+    let fused: Vec<((String, String), f64)> = reciprocal_rank_fusion(
+        &[(weights.keyword, keyword_keys), (weights.semantic, semantic_keys)],
+        weights.rrf_k,
+    )
+    .into_iter()
+    .filter(|(_, score)| *score > 0.0)
+    .collect();
+
+    let mut facets: BTreeMap<String, i64> = BTreeMap::new();
+    for ((resource_type, _), _) in &fused {
+        *facets.entry(resource_type.clone()).or_insert(0) += 1;
+    }
+
+    let resource_types = query.resource_types();
+    let matching: Vec<SearchResult> = fused
         .into_iter()
-        .skip(offset)
+        .filter(|((resource_type, _), _)| resource_types.contains(resource_type))
+        .filter_map(|(key, score)| {
+            details.remove(&key).map(|mut result| {
+                result.rank = score as f32;
+                result
+            })
+        })
+        .collect();
+
+    let total = matching.len();
+    let results = matching
+        .into_iter()
+        .skip(((page - 1) * per_page) as usize)
         .take(per_page as usize)
         .collect();
-    
+
     Ok(SearchResponse {
         results,
         total,
         page,
         per_page,
+        facets,
+        search_id: None,
     })
 }
+
+/// Fusion settings for a tenant, falling back to defaults for missing keys
+pub fn load_weights(
+    tenant_id: i32,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<HybridWeights, diesel::result::Error> {
+    use crate::schema::tenants;
+
+    let settings: Option<serde_json::Value> = tenants::table
+        .filter(tenants::id.eq(tenant_id))
+        .select(tenants::settings)
+        .first(conn)
+        .optional()?
+        .flatten();
+
+    Ok(settings
+        .and_then(|s| s.get("search").cloned())
+        .and_then(|s| serde_json::from_value(s).ok())
+        .unwrap_or_default())
+}
+
+/// Records a search in `search_history` and returns its id
+pub fn log_search(
+    tenant_id: i32,
+    user_id: Option<i32>,
+    query: &SearchQuery,
+    mode: SearchMode,
+    response: &SearchResponse,
+    weights: &HybridWeights,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<i64, diesel::result::Error> {
+    use crate::schema::search_history;
+
+    let results: Vec<serde_json::Value> = response
+        .results
+        .iter()
+        .map(|r| json!({ "resource_type": r.resource_type, "id": r.id, "rank": r.rank }))
+        .collect();
+
+    diesel::insert_into(search_history::table)
+        .values((
+            search_history::tenant_id.eq(tenant_id),
+            search_history::user_id.eq(user_id),
+            search_history::query.eq(&query.q),
+            search_history::search_type.eq(mode.as_str()),
+            search_history::results_count.eq(response.total as i32),
+            search_history::results.eq(json!(results)),
+            search_history::weights.eq(serde_json::to_value(weights).ok()),
+        ))
+        .returning(search_history::id)
+        .get_result(conn)
+}
+
+/// Records a click on a result of a logged search.
+/// Returns `false` when the search doesn't exist for the tenant.
+pub fn record_click(
+    tenant_id: i32,
+    search_id: i64,
+    user_id: Option<i32>,
+    resource_type: &str,
+    resource_id: &str,
+    position: Option<i32>,
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<bool, diesel::result::Error> {
+    use crate::schema::{search_clicks, search_history};
+
+    let exists = search_history::table
+        .filter(search_history::id.eq(search_id))
+        .filter(search_history::tenant_id.eq(tenant_id))
+        .count()
+        .get_result::<i64>(conn)? > 0;
+    if !exists {
+        return Ok(false);
+    }
+
+    diesel::insert_into(search_clicks::table)
+        .values((
+            search_clicks::search_id.eq(search_id),
+            search_clicks::resource_type.eq(resource_type),
+            search_clicks::resource_id.eq(resource_id),
+            search_clicks::position.eq(position),
+            search_clicks::user_id.eq(user_id),
+        ))
+        .execute(conn)?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_are_anded() {
+        assert_eq!(build_tsquery("running shoes").as_deref(), Some("'running' & 'shoes'"));
+    }
+
+    #[test]
+    fn phrases_prefixes_and_negation() {
+        assert_eq!(
+            build_tsquery("\"quick brown fox\" jump* -lazy").as_deref(),
+            Some("('quick' <-> 'brown' <-> 'fox') & 'jump':* & !'lazy'")
+        );
+        assert_eq!(build_tsquery("cats OR dogs").as_deref(), Some("'cats' | 'dogs'"));
+        assert_eq!(build_tsquery("\"unterminated phrase").as_deref(), Some("('unterminated' <-> 'phrase')"));
+    }
+
+    #[test]
+    fn operators_and_quotes_cannot_escape() {
+        assert_eq!(build_tsquery("o'neil & (x|y)").as_deref(), Some("'o''neil' & 'x|y'"));
+        assert_eq!(build_tsquery("  !!! ** \"\" OR ").as_deref(), None);
+    }
+
+    #[test]
+    fn rrf_rewards_agreement_between_rankings() {
+        let keyword = vec!["a", "b", "c"];
+        let semantic = vec!["c", "d"];
+        let fused = reciprocal_rank_fusion(&[(1.0, keyword), (1.0, semantic)], 60.0);
+        let order: Vec<&str> = fused.iter().map(|(k, _)| *k).collect();
+        // b and d tie on 1/62; b was seen first
+        assert_eq!(order, vec!["c", "a", "b", "d"]);
+        assert!((fused[0].1 - (1.0 / 63.0 + 1.0 / 61.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rrf_weights_scale_each_ranking() {
+        let fused = reciprocal_rank_fusion(&[(0.0, vec!["a"]), (2.0, vec!["b"])], 60.0);
+        assert_eq!(fused[0], ("b", 2.0 / 61.0));
+        assert_eq!(fused[1], ("a", 0.0));
+    }
+
+    #[test]
+    fn headline_is_escaped_around_marks() {
+        let raw = format!("a <b> & {}match{}", HIGHLIGHT_START, HIGHLIGHT_STOP);
+        assert_eq!(headline_to_html(&raw), "a &lt;b&gt; &amp; <mark>match</mark>");
+    }
+}
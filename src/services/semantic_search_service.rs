@@ -1,15 +1,19 @@
 use actix_web::{web, HttpResponse};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use diesel::prelude::*;
 use sha2::{Sha256, Digest};
  
 
+use crate::models::ai_provider_models::AIProviderConfig;
 use crate::models::DbPool;
 use crate::schema::content_embeddings;
+use crate::services::ai_providers::embeddings::{
+    embed_one, embedding_provider_from_config, EmbeddingProvider, HashingEmbeddings, OpenAICompatibleEmbeddings,
+};
+use crate::services::encryption_service;
 use crate::services::errors_service::CustomHttpError;
 
-/// Vector embedding (1536 dimensions for OpenAI ada-002)
+/// Vector embedding, padded to `EMBEDDING_DIMENSIONS`
 pub type Embedding = Vec<f32>;
 
 /// Request to create embedding
@@ -74,8 +78,9 @@ pub async fn create_embedding(
     let content_hash = generate_content_hash(&content);
 
     // Generate embedding vector
-    let embedding_values = generate_embedding_vector(&content).await?;
-    let model_name = "text-embedding-ada-002".to_string(); 
+    let provider = load_embedding_provider(&mut crate::models::pool_handler(pool.clone())?)?;
+    let embedding_values = generate_embedding_vector(provider.as_ref(), &content).await?;
+    let model_name = provider.model().to_string();
     let preview = content.chars().take(200).collect::<String>();
     
     let _id = web::block(move || -> Result<i64, diesel::result::Error> {
@@ -117,7 +122,9 @@ pub async fn semantic_search(
     let min_similarity = payload.min_similarity.unwrap_or(0.7);
     
     // Generate query embedding
-    let query_embedding = generate_embedding_vector(&query).await?;
+    let provider = load_embedding_provider(&mut crate::models::pool_handler(pool.clone())?)?;
+    let query_embedding = generate_embedding_vector(provider.as_ref(), &query).await?;
+    let model_name = provider.model().to_string();
     
     // Perform vector similarity search
     let results = web::block(move || -> Result<Vec<SearchResult>, diesel::result::Error> {
@@ -139,6 +146,7 @@ pub async fn semantic_search(
                 0::integer as rank
              FROM content_embeddings 
              WHERE 1 - (embedding <=> $1::vector) > $2
+               AND model_name = $4
              ORDER BY embedding <=> $1::vector
              LIMIT $3";
         
@@ -146,6 +154,7 @@ pub async fn semantic_search(
             .bind::<diesel::sql_types::Text, _>(query_formatted)
             .bind::<diesel::sql_types::Float4, _>(min_similarity)
             .bind::<diesel::sql_types::BigInt, _>(limit)
+            .bind::<diesel::sql_types::Text, _>(model_name)
             .load::<SearchResult>(&mut conn)
             .map(|rows| {
                 rows.into_iter()
@@ -177,80 +186,78 @@ fn generate_content_hash(content: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
-/// Generate embedding vector using OpenAI (or Mock)
-async fn generate_embedding_vector(text: &str) -> Result<Embedding, CustomHttpError> {
-    #[derive(Serialize)]
-    struct EmbeddingRequest {
-        input: String,
-        model: String,
-    }
-    
-    #[derive(Deserialize)]
-    struct EmbeddingResponse {
-        data: Vec<EmbeddingData>,
-    }
-    
-    #[derive(Deserialize)]
-    struct EmbeddingData {
-        embedding: Vec<f32>,
+/// Width of the `content_embeddings.embedding` column
+pub(crate) const EMBEDDING_DIMENSIONS: usize = 1536;
+
+/// Loads the embedding backend for this installation.
+///
+/// The first active `ai_provider_configs` row with purpose `embedding` wins.
+/// Without one, OpenAI is used when `OPENAI_API_KEY` holds a real key, and
+/// local hashing embeddings otherwise, so search works without any network access.
+pub(crate) fn load_embedding_provider(
+    conn: &mut crate::models::PooledDatabaseConnection,
+) -> Result<Box<dyn EmbeddingProvider>, CustomHttpError> {
+    use crate::schema::ai_provider_configs;
+
+    let config = ai_provider_configs::table
+        .filter(ai_provider_configs::purpose.eq("embedding"))
+        .filter(ai_provider_configs::is_active.eq(true))
+        .order(ai_provider_configs::id.asc())
+        .first::<AIProviderConfig>(conn)
+        .optional()?;
+
+    if let Some(config) = config {
+        // A key that can't be decrypted is a configuration error; the stored
+        // ciphertext must never be sent to the provider in its place
+        let api_key = encryption_service::decrypt(&config.api_key_encrypted).map_err(|e| {
+            log::error!("Embedding provider {} has an unreadable API key: {}", config.id, e);
+            CustomHttpError::InternalServerError(
+                "Embedding provider API key could not be decrypted; re-enter it in the provider settings".to_string(),
+            )
+        })?;
+        return embedding_provider_from_config(&config, Some(api_key))
+            .map_err(|e| CustomHttpError::InternalServerError(e.to_string()));
     }
-    
-    let client = Client::new();
-    
-    // TODO: Get API key from provider config
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .unwrap_or_else(|_| "sk-test".to_string());
 
-    // Mock Mode for Testing/Development without spending credits
-    if api_key.starts_with("sk-test") || api_key == "mock" {
-        log::warn!("Generating MOCK embedding for: {}", text.chars().take(20).collect::<String>());
-        // Deterministic pseudo-random generation based on text hash
-        // This ensures the same text always gets the same vector (somewhat useful for basic sanity checks)
-        let mut hasher = Sha256::new();
-        hasher.update(text.as_bytes());
-        let hash = hasher.finalize();
-        
-        let mut mock_embedding = Vec::with_capacity(1536);
-        for i in 0..1536 {
-            // cycle through hash bytes to generate float between -0.1 and 0.1
-            let byte = hash[i % 32];
-            let val = ((byte as f32 / 255.0) - 0.5) * 0.2; 
-            mock_embedding.push(val);
+    match std::env::var("OPENAI_API_KEY") {
+        Ok(key) if !key.is_empty() && !key.starts_with("sk-test") && key != "mock" => Ok(Box::new(
+            OpenAICompatibleEmbeddings::new(
+                "https://api.openai.com/v1".to_string(),
+                Some(key),
+                "text-embedding-ada-002".to_string(),
+            ),
+        )),
+        _ => {
+            log::warn!("No embedding provider configured, using local hashing embeddings");
+            Ok(Box::new(HashingEmbeddings::new(EMBEDDING_DIMENSIONS)))
         }
-        return Ok(mock_embedding);
     }
-    
-    let request = EmbeddingRequest {
-        input: text.to_string(),
-        model: "text-embedding-ada-002".to_string(),
-    };
-    
-    let response = client
-        .post("https://api.openai.com/v1/embeddings")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request)
-        .send()
+}
+
+/// Embeds `text` with `provider`, zero-padded to the column width.
+///
+/// Padding leaves cosine similarity unchanged, so smaller models
+/// (e.g. 768-dimension ones served by Ollama) fit the same column.
+pub(crate) async fn generate_embedding_vector(
+    provider: &dyn EmbeddingProvider,
+    text: &str,
+) -> Result<Embedding, CustomHttpError> {
+    let embedding = embed_one(provider, text)
         .await
-        .map_err(|e| CustomHttpError::InternalServerError(format!("Embedding request failed: {}", e)))?;
-    
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        log::error!("OpenAI API Error ({}) : {}", status, body);
-        return Err(CustomHttpError::InternalServerError(format!("Embedding generation failed: {}", body)));
+        .map_err(|e| CustomHttpError::InternalServerError(format!("Embedding generation failed: {}", e)))?;
+    pad_embedding(embedding)
+}
+
+fn pad_embedding(mut embedding: Embedding) -> Result<Embedding, CustomHttpError> {
+    if embedding.len() > EMBEDDING_DIMENSIONS {
+        return Err(CustomHttpError::InternalServerError(format!(
+            "Embedding has {} dimensions, at most {} are supported",
+            embedding.len(),
+            EMBEDDING_DIMENSIONS
+        )));
     }
-    
-    let embedding_response: EmbeddingResponse = response
-        .json()
-        .await
-        .map_err(|e| CustomHttpError::InternalServerError(format!("Parse error: {}", e)))?;
-    
-    embedding_response
-        .data
-        .first()
-        .map(|d| d.embedding.clone())
-        .ok_or_else(|| CustomHttpError::InternalServerError("No embedding in response".to_string()))
+    embedding.resize(EMBEDDING_DIMENSIONS, 0.0);
+    Ok(embedding)
 }
 
 /// Convert vector to PostgreSQL array string
@@ -260,3 +267,16 @@ fn vector_to_string(vec: &[f32]) -> String {
         .collect::<Vec<_>>()
         .join(",")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pad_embedding_fills_column_width() {
+        let padded = pad_embedding(vec![0.6, 0.8]).unwrap();
+        assert_eq!(padded.len(), EMBEDDING_DIMENSIONS);
+        assert_eq!(&padded[..3], &[0.6, 0.8, 0.0]);
+        assert!(pad_embedding(vec![0.0; EMBEDDING_DIMENSIONS + 1]).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+// Stock Reservation Service
+// Holds stock for unpaid orders so two customers can't buy the last unit.
+// Rows are locked with SELECT ... FOR UPDATE; stock is only decremented when
+// the payment succeeds.
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::models::commerce_models::Product;
+use crate::models::inventory_models::{InventoryReservation, NewInventoryAuditLog, NewInventoryReservation};
+use crate::models::DatabasePool;
+use crate::schema::{inventory_audit_log, inventory_reservations, orders, product_variants, products};
+use crate::services::errors_service::CustomHttpError;
+
+/// How long stock is held for a payment to succeed
+pub const RESERVATION_TTL_MINUTES: i64 = 30;
+/// How often expired reservations are released
+const WORKER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Stock rules of a product
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StockPolicy {
+    pub track_inventory: bool,
+    pub allow_backorder: bool,
+    /// Units that may be sold below zero; unlimited when `None`
+    pub backorder_limit: Option<i32>,
+}
+
+impl StockPolicy {
+    pub fn of(product: &Product) -> Self {
+        Self {
+            track_inventory: product.track_inventory.unwrap_or(true),
+            allow_backorder: product.allow_backorder.unwrap_or(false),
+            backorder_limit: product.backorder_limit,
+        }
+    }
+
+    /// Whether `requested` more units can be sold from `stock` with
+    /// `reserved` already held for other orders
+    pub fn allows(&self, stock: i32, reserved: i64, requested: i32) -> bool {
+        if !self.track_inventory {
+            return true;
+        }
+        let left = stock as i64 - reserved - requested as i64;
+        if left >= 0 {
+            return true;
+        }
+        match (self.allow_backorder, self.backorder_limit) {
+            (false, _) => false,
+            (true, None) => true,
+            (true, Some(limit)) => -left <= limit as i64,
+        }
+    }
+}
+
+/// `products.stock_status` for a product-level stock count
+pub fn stock_status(stock: i32, low_stock_threshold: Option<i32>) -> &'static str {
+    if stock <= 0 {
+        "out_of_stock"
+    } else if stock <= low_stock_threshold.unwrap_or(0) {
+        "low_stock"
+    } else {
+        "in_stock"
+    }
+}
+
+/// Units held by unexpired reservations of other orders
+fn reserved_quantity(product_id: i64, variant_id: Option<i32>, now: NaiveDateTime, conn: &mut PgConnection) -> QueryResult<i64> {
+    let held = inventory_reservations::table
+        .filter(inventory_reservations::product_id.eq(product_id))
+        .filter(inventory_reservations::variant_id.is_not_distinct_from(variant_id))
+        .filter(inventory_reservations::status.eq("active"))
+        .filter(inventory_reservations::expires_at.gt(now))
+        .select(diesel::dsl::sum(inventory_reservations::quantity))
+        .first::<Option<i64>>(conn)?;
+    Ok(held.unwrap_or(0))
+}
+
+/// Locks and returns the stock a line draws from: the variant's if given,
+/// otherwise the product's
+fn lock_stock(product: &Product, variant_id: Option<i32>, conn: &mut PgConnection) -> Result<i32, CustomHttpError> {
+    match variant_id {
+        Some(variant_id) => {
+            let (owner, stock) = product_variants::table
+                .find(variant_id)
+                .select((product_variants::product_id, product_variants::stock_quantity))
+                .for_update()
+                .first::<(i64, Option<i32>)>(conn)
+                .optional()?
+                .ok_or(CustomHttpError::BadRequest(format!("Variant {} not found", variant_id)))?;
+            if owner != product.id {
+                return Err(CustomHttpError::BadRequest(format!("Variant {} does not belong to product {}", variant_id, product.id)));
+            }
+            Ok(stock.unwrap_or(0))
+        }
+        None => Ok(product.stock_quantity),
+    }
+}
+
+/// Reserves stock for the lines of `order_id`, as `(product_id, variant_id,
+/// quantity)`. Fails without reserving anything if any line is short.
+/// Products that don't track inventory are not reserved.
+pub fn reserve(order_id: i64, lines: &[(i64, Option<i32>, i32)], conn: &mut PgConnection) -> Result<Vec<InventoryReservation>, CustomHttpError> {
+    // Same product twice counts once; sorted so concurrent orders lock rows in the same order
+    let mut wanted: BTreeMap<(i64, Option<i32>), i32> = BTreeMap::new();
+    for &(product_id, variant_id, quantity) in lines {
+        if quantity <= 0 {
+            return Err(CustomHttpError::BadRequest("Quantity must be at least 1".to_string()));
+        }
+        *wanted.entry((product_id, variant_id)).or_insert(0) += quantity;
+    }
+
+    conn.transaction(|conn| {
+        let now = Utc::now().naive_utc();
+        let expires_at = now + Duration::minutes(RESERVATION_TTL_MINUTES);
+        let mut reservations = Vec::new();
+        for ((product_id, variant_id), quantity) in wanted {
+            let product = products::table
+                .find(product_id)
+                .for_update()
+                .first::<Product>(conn)
+                .optional()?
+                .ok_or(CustomHttpError::BadRequest(format!("Product {} not found", product_id)))?;
+            let policy = StockPolicy::of(&product);
+            if !policy.track_inventory {
+                continue;
+            }
+            let stock = lock_stock(&product, variant_id, conn)?;
+            let reserved = reserved_quantity(product_id, variant_id, now, conn)?;
+            if !policy.allows(stock, reserved, quantity) {
+                return Err(CustomHttpError::BadRequest(format!("Not enough stock for {}", product.name)));
+            }
+            reservations.push(NewInventoryReservation { order_id, product_id, variant_id, quantity, expires_at });
+        }
+        if reservations.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(diesel::insert_into(inventory_reservations::table)
+            .values(&reservations)
+            .get_results::<InventoryReservation>(conn)?)
+    })
+}
+
+/// Decrements stock for the order's reservations once its payment has
+/// succeeded, logging each change against the order. A reservation that
+/// expired before the payment came in no longer holds stock, so it is only
+/// committed if the stock is still there; otherwise nothing is committed and
+/// the order needs a refund.
+pub fn commit(order_id: i64, conn: &mut PgConnection) -> Result<usize, CustomHttpError> {
+    conn.transaction(|conn| {
+        // Sorted by product, the order `reserve` locks them in
+        let held = inventory_reservations::table
+            .filter(inventory_reservations::order_id.eq(order_id))
+            .filter(inventory_reservations::status.eq_any(["active", "expired"]))
+            .order((inventory_reservations::product_id, inventory_reservations::variant_id))
+            .for_update()
+            .load::<InventoryReservation>(conn)?;
+        let order_uuid = orders::table.find(order_id).select(orders::uuid).first::<String>(conn)?;
+        let now = Utc::now().naive_utc();
+
+        for reservation in &held {
+            let product = products::table
+                .find(reservation.product_id)
+                .for_update()
+                .first::<Product>(conn)
+                .optional()?
+                .ok_or(CustomHttpError::BadRequest(format!("Product {} not found", reservation.product_id)))?;
+            let before = lock_stock(&product, reservation.variant_id, conn)?;
+            if reservation.status == "expired" || reservation.expires_at <= now {
+                let reserved = reserved_quantity(reservation.product_id, reservation.variant_id, now, conn)?;
+                if !StockPolicy::of(&product).allows(before, reserved, reservation.quantity) {
+                    return Err(CustomHttpError::BadRequest(format!(
+                        "{} sold out after the stock reservation of order {} expired",
+                        product.name, order_uuid
+                    )));
+                }
+                log::warn!("Committing expired stock reservation {} of order {}", reservation.id, order_uuid);
+            }
+
+            let after = before - reservation.quantity;
+            match reservation.variant_id {
+                Some(variant_id) => {
+                    diesel::update(product_variants::table.find(variant_id))
+                        .set((product_variants::stock_quantity.eq(after), product_variants::updated_at.eq(now)))
+                        .execute(conn)?;
+                }
+                None => {
+                    diesel::update(products::table.find(reservation.product_id))
+                        .set((
+                            products::stock_quantity.eq(after),
+                            products::stock_status.eq(stock_status(after, product.low_stock_threshold)),
+                            products::updated_at.eq(now),
+                        ))
+                        .execute(conn)?;
+                }
+            }
+
+            let mut audit = NewInventoryAuditLog::new(
+                reservation.product_id,
+                "sale",
+                before,
+                after,
+                Some(format!("Order {}", order_uuid)),
+            );
+            audit.variant_id = reservation.variant_id;
+            audit.order_id = Some(order_id);
+            diesel::insert_into(inventory_audit_log::table).values(&audit).execute(conn)?;
+        }
+
+        diesel::update(inventory_reservations::table.filter(inventory_reservations::id.eq_any(held.iter().map(|r| r.id))))
+            .set((inventory_reservations::status.eq("committed"), inventory_reservations::updated_at.eq(now)))
+            .execute(conn)?;
+        Ok(held.len())
+    })
+}
+
+/// Gives back the stock held for an order whose payment was cancelled or
+/// failed, or which was cancelled
+pub fn release(order_id: i64, conn: &mut PgConnection) -> QueryResult<usize> {
+    diesel::update(
+        inventory_reservations::table
+            .filter(inventory_reservations::order_id.eq(order_id))
+            .filter(inventory_reservations::status.eq_any(["active", "expired"])),
+    )
+    .set((inventory_reservations::status.eq("released"), inventory_reservations::updated_at.eq(Utc::now().naive_utc())))
+    .execute(conn)
+}
+
+/// Marks reservations whose payment window has passed as expired
+pub fn expire_stale(conn: &mut PgConnection) -> QueryResult<usize> {
+    let now = Utc::now().naive_utc();
+    diesel::update(
+        inventory_reservations::table
+            .filter(inventory_reservations::status.eq("active"))
+            .filter(inventory_reservations::expires_at.le(now)),
+    )
+    .set((inventory_reservations::status.eq("expired"), inventory_reservations::updated_at.eq(now)))
+    .execute(conn)
+}
+
+/// Expires stale reservations every minute. Reservations past `expires_at`
+/// already stop counting; this keeps their status accurate.
+pub async fn run_worker(pool: DatabasePool) {
+    loop {
+        tokio::time::sleep(WORKER_INTERVAL).await;
+        let expired = pool
+            .get()
+            .map_err(|e| e.to_string())
+            .and_then(|mut conn| expire_stale(&mut conn).map_err(|e| e.to_string()));
+        match expired {
+            Ok(0) => {}
+            Ok(n) => log::info!("Expired {} stock reservations", n),
+            Err(e) => log::error!("Stock reservation expiry failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stock_policy() {
+        let strict = StockPolicy { track_inventory: true, allow_backorder: false, backorder_limit: None };
+        assert!(strict.allows(5, 2, 3));
+        assert!(!strict.allows(5, 3, 3));
+        assert!(!strict.allows(0, 0, 1));
+
+        let limited = StockPolicy { allow_backorder: true, backorder_limit: Some(2), ..strict };
+        assert!(limited.allows(1, 0, 3));
+        assert!(!limited.allows(1, 0, 4));
+
+        let unlimited = StockPolicy { allow_backorder: true, backorder_limit: None, ..strict };
+        assert!(unlimited.allows(0, 10, 100));
+
+        let untracked = StockPolicy { track_inventory: false, ..strict };
+        assert!(untracked.allows(0, 0, 1));
+    }
+
+    #[test]
+    fn test_stock_status() {
+        assert_eq!(stock_status(0, Some(10)), "out_of_stock");
+        assert_eq!(stock_status(-2, None), "out_of_stock");
+        assert_eq!(stock_status(10, Some(10)), "low_stock");
+        assert_eq!(stock_status(11, Some(10)), "in_stock");
+    }
+}
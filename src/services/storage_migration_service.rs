@@ -0,0 +1,401 @@
+// Storage Migration Service
+// Moves every media file, with its variants, from local storage to S3 or back
+// and rewrites the stored paths. Media are processed in id order and progress
+// is saved after each item, so a paused or interrupted run picks up where it
+// stopped. Files already in the target storage are skipped, which also makes
+// a fresh run after a completed one retry only the media that failed.
+
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::db_connection::{DatabasePool, PooledDatabaseConnection};
+use crate::models::media_models::{Media, MediaVariant};
+use crate::models::storage_migration_models::{NewStorageMigration, StorageMigration};
+use crate::schema::{media, media_variants, storage_migrations};
+use crate::services::errors_service::CustomHttpError;
+use crate::services::image_transform_service::OutputFormat;
+use crate::services::media_variant_service;
+use crate::services::storage_service::{is_local_path, storage_filename, StorageBackend, StorageService};
+
+const BATCH_SIZE: i64 = 25;
+
+/// Session advisory lock held by the connection driving a run, so only one
+/// run goes at a time across all instances. Released if the process dies.
+const RUN_LOCK_KEY: i64 = 0x4d45_4449_414d_4947;
+
+define_sql_function!(fn pg_try_advisory_lock(key: diesel::sql_types::BigInt) -> diesel::sql_types::Bool);
+define_sql_function!(fn pg_advisory_unlock(key: diesel::sql_types::BigInt) -> diesel::sql_types::Bool);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    LocalToS3,
+    S3ToLocal,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::LocalToS3 => "local_to_s3",
+            Direction::S3ToLocal => "s3_to_local",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "local_to_s3" => Some(Direction::LocalToS3),
+            "s3_to_local" => Some(Direction::S3ToLocal),
+            _ => None,
+        }
+    }
+
+    /// Whether a file stored at `file_path` is still in the source storage
+    pub fn needs_migration(&self, file_path: &str) -> bool {
+        match self {
+            Direction::LocalToS3 => is_local_path(file_path),
+            Direction::S3ToLocal => !is_local_path(file_path),
+        }
+    }
+}
+
+fn try_lock_run(conn: &mut PgConnection) -> QueryResult<bool> {
+    diesel::select(pg_try_advisory_lock(RUN_LOCK_KEY)).get_result(conn)
+}
+
+fn unlock_run(conn: &mut PgConnection) -> QueryResult<bool> {
+    diesel::select(pg_advisory_unlock(RUN_LOCK_KEY)).get_result(conn)
+}
+
+/// The run lock and the connection holding it. Dropping it releases the
+/// lock, also when a run panics, so the connection goes back to the pool
+/// unlocked.
+struct RunLock {
+    conn: PooledDatabaseConnection,
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        if let Err(e) = unlock_run(&mut self.conn) {
+            log::error!("Storage migration: failed to release the run lock: {}", e);
+        }
+    }
+}
+
+/// Whether a run is in progress on any instance
+pub fn is_running(conn: &mut PgConnection) -> QueryResult<bool> {
+    if !try_lock_run(conn)? {
+        return Ok(true);
+    }
+    unlock_run(conn)?;
+    Ok(false)
+}
+
+pub fn list(conn: &mut PgConnection) -> QueryResult<Vec<StorageMigration>> {
+    storage_migrations::table
+        .order(storage_migrations::id.desc())
+        .load::<StorageMigration>(conn)
+}
+
+pub fn find(id: i32, conn: &mut PgConnection) -> Result<StorageMigration, CustomHttpError> {
+    storage_migrations::table
+        .find(id)
+        .first::<StorageMigration>(conn)
+        .optional()?
+        .ok_or(CustomHttpError::NotFound("Storage migration not found".to_string()))
+}
+
+/// Starts a run, or resumes the unfinished run in the same direction with
+/// the given `delete_source`
+pub fn start(
+    direction: Direction,
+    delete_source: bool,
+    started_by: Option<i32>,
+    conn: &mut PgConnection,
+) -> Result<StorageMigration, CustomHttpError> {
+    if is_running(conn)? {
+        return Err(CustomHttpError::BadRequest("A storage migration is already running".to_string()));
+    }
+
+    let unfinished = storage_migrations::table
+        .filter(storage_migrations::status.eq_any(["running", "paused"]))
+        .order(storage_migrations::id.desc())
+        .first::<StorageMigration>(conn)
+        .optional()?;
+    if let Some(run) = unfinished {
+        if run.direction != direction.as_str() {
+            return Err(CustomHttpError::BadRequest(format!(
+                "Migration {} ({}) is unfinished; resume or complete it first",
+                run.id, run.direction
+            )));
+        }
+        return Ok(diesel::update(storage_migrations::table.find(run.id))
+            .set((
+                storage_migrations::status.eq("running"),
+                storage_migrations::delete_source.eq(delete_source),
+                storage_migrations::updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .get_result::<StorageMigration>(conn)?);
+    }
+
+    // Local paths are `uploads/<name>`, see `is_local_path`
+    let total = match direction {
+        Direction::LocalToS3 => media::table.filter(media::file_path.like("uploads/%")).count().get_result::<i64>(conn)?,
+        Direction::S3ToLocal => media::table.filter(media::file_path.not_like("uploads/%")).count().get_result::<i64>(conn)?,
+    };
+    Ok(diesel::insert_into(storage_migrations::table)
+        .values(&NewStorageMigration {
+            direction: direction.as_str().to_string(),
+            delete_source,
+            total: total as i32,
+            started_by,
+        })
+        .get_result::<StorageMigration>(conn)?)
+}
+
+/// Asks a running migration to stop after the media item in progress
+pub fn pause(id: i32, conn: &mut PgConnection) -> Result<StorageMigration, CustomHttpError> {
+    let run = find(id, conn)?;
+    if run.status != "running" {
+        return Err(CustomHttpError::BadRequest(format!("Migration is {}", run.status)));
+    }
+    Ok(diesel::update(storage_migrations::table.find(id))
+        .set((
+            storage_migrations::status.eq("paused"),
+            storage_migrations::updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .get_result::<StorageMigration>(conn)?)
+}
+
+/// Migrates media until none are left or the run is paused. Meant to be
+/// spawned in the background after `start`.
+pub async fn run(id: i32, source: StorageBackend, target: StorageBackend, pool: DatabasePool) {
+    let mut conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(e) => {
+            log::error!("Storage migration {} not started: {}", id, e);
+            return;
+        }
+    };
+    let mut lock = match try_lock_run(&mut conn) {
+        Ok(true) => RunLock { conn },
+        Ok(false) => {
+            log::warn!("Storage migration {} not started: another run is in progress", id);
+            return;
+        }
+        Err(e) => {
+            log::error!("Storage migration {} not started: {}", id, e);
+            return;
+        }
+    };
+    let conn = &mut lock.conn;
+    if let Err(e) = run_batches(id, &source, &target, conn).await {
+        log::error!("Storage migration {} failed: {}", id, e);
+        let now = chrono::Utc::now().naive_utc();
+        let _ = diesel::update(storage_migrations::table.find(id))
+            .set((
+                storage_migrations::status.eq("failed"),
+                storage_migrations::last_error.eq(&e),
+                storage_migrations::updated_at.eq(now),
+                storage_migrations::finished_at.eq(now),
+            ))
+            .execute(conn);
+    }
+}
+
+async fn run_batches(
+    id: i32,
+    source: &StorageBackend,
+    target: &StorageBackend,
+    conn: &mut PgConnection,
+) -> Result<(), String> {
+    loop {
+        let run = storage_migrations::table
+            .find(id)
+            .first::<StorageMigration>(conn)
+            .map_err(|e| e.to_string())?;
+        if run.status != "running" {
+            log::info!("Storage migration {} stopped: {}", id, run.status);
+            return Ok(());
+        }
+        let direction = Direction::parse(&run.direction).ok_or("Unknown migration direction")?;
+
+        let batch = media::table
+            .filter(media::id.gt(run.last_media_id))
+            .order(media::id.asc())
+            .limit(BATCH_SIZE)
+            .load::<Media>(conn)
+            .map_err(|e| e.to_string())?;
+        if batch.is_empty() {
+            let now = chrono::Utc::now().naive_utc();
+            diesel::update(storage_migrations::table.find(id))
+                .set((
+                    storage_migrations::status.eq("completed"),
+                    storage_migrations::updated_at.eq(now),
+                    storage_migrations::finished_at.eq(now),
+                ))
+                .execute(conn)
+                .map_err(|e| e.to_string())?;
+            log::info!("Storage migration {} completed", id);
+            return Ok(());
+        }
+
+        for item in batch {
+            let status = storage_migrations::table
+                .find(id)
+                .select(storage_migrations::status)
+                .first::<String>(conn)
+                .map_err(|e| e.to_string())?;
+            if status != "running" {
+                log::info!("Storage migration {} stopped: {}", id, status);
+                return Ok(());
+            }
+
+            let outcome = migrate_media(&item, direction, run.delete_source, source, target, conn).await;
+            let now = chrono::Utc::now().naive_utc();
+            let progress = diesel::update(storage_migrations::table.find(id));
+            let saved = match outcome {
+                Ok(true) => progress
+                    .set((
+                        storage_migrations::last_media_id.eq(item.id),
+                        storage_migrations::migrated.eq(storage_migrations::migrated + 1),
+                        storage_migrations::updated_at.eq(now),
+                    ))
+                    .execute(conn),
+                Ok(false) => progress
+                    .set((
+                        storage_migrations::last_media_id.eq(item.id),
+                        storage_migrations::skipped.eq(storage_migrations::skipped + 1),
+                        storage_migrations::updated_at.eq(now),
+                    ))
+                    .execute(conn),
+                Err(e) => {
+                    log::warn!("Storage migration {}: media {} failed: {}", id, item.uuid, e);
+                    progress
+                        .set((
+                            storage_migrations::last_media_id.eq(item.id),
+                            storage_migrations::failed.eq(storage_migrations::failed + 1),
+                            storage_migrations::last_error.eq(format!("{}: {}", item.uuid, e)),
+                            storage_migrations::updated_at.eq(now),
+                        ))
+                        .execute(conn)
+                }
+            };
+            saved.map_err(|e| e.to_string())?;
+        }
+    }
+}
+
+/// Copies a media item's files that are still in the source storage and
+/// points the database at the copies. False if there was nothing to copy.
+async fn migrate_media(
+    item: &Media,
+    direction: Direction,
+    delete_source: bool,
+    source: &StorageBackend,
+    target: &StorageBackend,
+    conn: &mut PgConnection,
+) -> Result<bool, String> {
+    let variants: Vec<MediaVariant> = media_variant_service::variants_for(item.id, conn)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|v| direction.needs_migration(&v.file_path))
+        .collect();
+    let move_original = direction.needs_migration(&item.file_path);
+    if !move_original && variants.is_empty() {
+        return Ok(false);
+    }
+
+    let mut copied: Vec<(String, String)> = Vec::new();
+    let result = copy_files(item, &variants, move_original, source, target, &mut copied).await;
+    let result = match result {
+        Ok(()) => conn
+            .transaction(|conn| {
+                for (old_path, new_path) in &copied {
+                    if *old_path == item.file_path {
+                        diesel::update(media::table.find(item.id))
+                            .set(media::file_path.eq(new_path))
+                            .execute(conn)?;
+                    } else {
+                        diesel::update(
+                            media_variants::table
+                                .filter(media_variants::media_id.eq(item.id))
+                                .filter(media_variants::file_path.eq(old_path)),
+                        )
+                        .set(media_variants::file_path.eq(new_path))
+                        .execute(conn)?;
+                    }
+                }
+                Ok::<_, diesel::result::Error>(())
+            })
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        // Leave the source as it was; drop the partial copies
+        for (_, new_path) in &copied {
+            let _ = target.delete(storage_filename(new_path)).await;
+        }
+        return Err(e);
+    }
+    if delete_source {
+        for (old_path, _) in &copied {
+            if let Err(e) = source.delete(storage_filename(old_path)).await {
+                log::warn!("Failed to delete migrated file {}: {}", old_path, e);
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Copies the files through a temporary file, recording (old path, new path) pairs
+async fn copy_files(
+    item: &Media,
+    variants: &[MediaVariant],
+    move_original: bool,
+    source: &StorageBackend,
+    target: &StorageBackend,
+    copied: &mut Vec<(String, String)>,
+) -> Result<(), String> {
+    let mut files: Vec<(&str, &str)> = Vec::new();
+    if move_original {
+        files.push((&item.file_path, &item.mime_type));
+    }
+    for variant in variants {
+        let mime = OutputFormat::parse(&variant.format)
+            .map(|f| f.mime_type())
+            .unwrap_or("application/octet-stream");
+        files.push((&variant.file_path, mime));
+    }
+
+    for (file_path, mime_type) in files {
+        let filename = storage_filename(file_path);
+        let temp = std::env::temp_dir().join(format!("storage-migration-{}", filename));
+        let result = async {
+            if !source.download(filename, &temp).await? {
+                return Err(format!("{} is missing from the source storage", file_path));
+            }
+            target.save_file(filename, &temp, mime_type).await
+        }
+        .await;
+        let _ = tokio::fs::remove_file(&temp).await;
+        copied.push((file_path.to_string(), result?));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_migration() {
+        assert!(Direction::LocalToS3.needs_migration("uploads/abc.jpg"));
+        assert!(!Direction::LocalToS3.needs_migration("abc.jpg"));
+        assert!(Direction::S3ToLocal.needs_migration("abc.jpg"));
+        assert!(!Direction::S3ToLocal.needs_migration("uploads/abc.jpg"));
+        assert_eq!(Direction::parse("s3_to_local"), Some(Direction::S3ToLocal));
+        assert_eq!(Direction::parse("sideways"), None);
+    }
+}
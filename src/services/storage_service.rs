@@ -2,20 +2,61 @@ use async_trait::async_trait;
 use crate::models::config_models::LocalConfig;
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use aws_sdk_s3::Client;
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_s3::config::Region;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::{ByteStream, Length};
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
 
 const UPLOAD_DIR: &str = "uploads";
 
+/// Files larger than this go to S3 as a multipart upload
+const MULTIPART_THRESHOLD: u64 = 16 * 1024 * 1024;
+/// Size of each multipart part (S3 requires at least 5MB for all but the last)
+const MULTIPART_PART_SIZE: u64 = 8 * 1024 * 1024;
+const MAX_PRESIGN_DURATION: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// The name a stored file is saved under, from the path `save` returned.
+/// Local storage returns `uploads/<name>`, S3 returns the key itself.
+pub fn storage_filename(file_path: &str) -> &str {
+    Path::new(file_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(file_path)
+}
+
+/// Whether a stored `file_path` is on local disk; S3 paths are bare keys
+pub fn is_local_path(file_path: &str) -> bool {
+    file_path.starts_with(&format!("{}/", UPLOAD_DIR))
+}
+
 #[async_trait]
 pub trait StorageService {
     async fn save(&self, filename: &str, data: &[u8], mime_type: &str) -> Result<String, String>;
+    /// Stores a file from disk without reading it into memory
+    async fn save_file(&self, filename: &str, path: &Path, mime_type: &str) -> Result<String, String>;
     async fn delete(&self, filename: &str) -> Result<(), String>;
+    /// Reads a stored file back; `None` if it doesn't exist
+    async fn load(&self, filename: &str) -> Result<Option<Vec<u8>>, String>;
+    /// Size of a stored file; `None` if it doesn't exist
+    async fn head(&self, filename: &str) -> Result<Option<u64>, String>;
+    /// Copies a stored file to `dest` without holding it in memory; false if it doesn't exist
+    async fn download(&self, filename: &str, dest: &Path) -> Result<bool, String>;
+    /// A time-limited URL for reading a file, for private buckets
+    async fn presigned_get_url(&self, filename: &str, expires_in: Duration) -> Result<String, String>;
+    /// A time-limited URL a client can upload `filename` to directly
+    async fn presigned_put_url(&self, filename: &str, mime_type: &str, expires_in: Duration) -> Result<String, String>;
     fn get_url(&self, filename: &str) -> String;
 }
 
+/// S3 presigned URLs are valid for at most a week
+fn presigning_config(expires_in: Duration) -> Result<PresigningConfig, String> {
+    PresigningConfig::expires_in(expires_in.min(MAX_PRESIGN_DURATION)).map_err(|e| e.to_string())
+}
+
 #[derive(Clone)]
 pub struct LocalStorage {
     base_url: String,
@@ -42,6 +83,15 @@ impl StorageService for LocalStorage {
         Ok(storage_path)
     }
 
+    async fn save_file(&self, filename: &str, path: &Path, _mime_type: &str) -> Result<String, String> {
+        fs::create_dir_all(UPLOAD_DIR).map_err(|e| e.to_string())?;
+
+        let file_path = PathBuf::from(UPLOAD_DIR).join(filename);
+        tokio::fs::copy(path, &file_path).await.map_err(|e| e.to_string())?;
+
+        Ok(file_path.to_str().unwrap().to_string())
+    }
+
     async fn delete(&self, filename: &str) -> Result<(), String> {
         let file_path = PathBuf::from(UPLOAD_DIR).join(filename);
         if file_path.exists() {
@@ -50,56 +100,211 @@ impl StorageService for LocalStorage {
         Ok(())
     }
 
+    async fn load(&self, filename: &str) -> Result<Option<Vec<u8>>, String> {
+        match tokio::fs::read(PathBuf::from(UPLOAD_DIR).join(filename)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    async fn head(&self, filename: &str) -> Result<Option<u64>, String> {
+        match tokio::fs::metadata(PathBuf::from(UPLOAD_DIR).join(filename)).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    async fn download(&self, filename: &str, dest: &Path) -> Result<bool, String> {
+        match tokio::fs::copy(PathBuf::from(UPLOAD_DIR).join(filename), dest).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Local files are served publicly, so this is the plain URL
+    async fn presigned_get_url(&self, filename: &str, _expires_in: Duration) -> Result<String, String> {
+        Ok(self.get_url(filename))
+    }
+
+    async fn presigned_put_url(&self, _filename: &str, _mime_type: &str, _expires_in: Duration) -> Result<String, String> {
+        Err("Direct uploads need S3 storage".to_string())
+    }
+
     fn get_url(&self, filename: &str) -> String {
         format!("{}/uploads/{}", self.base_url, filename)
     }
 }
 
+/// Connection settings for S3 or an S3-compatible service such as MinIO
+#[derive(Clone, Debug)]
+pub struct S3Config {
+    pub access_key: String,
+    pub secret_key: String,
+    pub region: String,
+    pub bucket: String,
+    pub cdn_url: Option<String>,
+    /// Custom endpoint, e.g. `http://minio:9000`; AWS when unset
+    pub endpoint: Option<String>,
+    /// Address objects as `{endpoint}/{bucket}/{key}` instead of `{bucket}.{host}/{key}`
+    pub force_path_style: bool,
+}
+
+impl S3Config {
+    /// The S3 settings in the app config, if all required ones are set
+    pub fn from_config(config: &LocalConfig) -> Option<Self> {
+        Some(Self {
+            access_key: config.aws_access_key_id.clone()?,
+            secret_key: config.aws_secret_access_key.clone()?,
+            region: config.aws_region.clone()?,
+            bucket: config.aws_s3_bucket.clone()?,
+            cdn_url: config.cdn_url.clone(),
+            endpoint: config.aws_s3_endpoint.clone().filter(|e| !e.is_empty()),
+            force_path_style: config.aws_s3_force_path_style.unwrap_or(false),
+        })
+    }
+
+    /// Public URL of an object
+    pub fn object_url(&self, key: &str) -> String {
+        if let Some(cdn) = &self.cdn_url {
+            return format!("{}/{}", cdn.trim_end_matches('/'), key);
+        }
+        let endpoint = match &self.endpoint {
+            Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+            None => format!("https://s3.{}.amazonaws.com", self.region),
+        };
+        if self.force_path_style {
+            return format!("{}/{}/{}", endpoint, self.bucket, key);
+        }
+        match endpoint.split_once("://") {
+            Some((scheme, host)) => format!("{}://{}.{}/{}", scheme, self.bucket, host, key),
+            None => format!("https://{}.{}/{}", self.bucket, endpoint, key),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct S3Storage {
     client: Client,
-    bucket: String,
-    region: String,
-    cdn_url: Option<String>,
+    config: S3Config,
 }
 
 impl S3Storage {
-    pub async fn new(access_key: String, secret_key: String, region: String, bucket: String, cdn_url: Option<String>) -> Self {
-        let region_provider = RegionProviderChain::first_try(Region::new(region.clone()));
+    pub async fn new(config: S3Config) -> Self {
+        let region_provider = RegionProviderChain::first_try(Region::new(config.region.clone()));
         
         let creds = aws_sdk_s3::config::Credentials::new(
-            access_key,
-            secret_key,
+            config.access_key.clone(),
+            config.secret_key.clone(),
             None,
             None,
             "static_creds",
         );
 
-        let config = aws_config::from_env()
+        let sdk_config = aws_config::from_env()
             .region(region_provider)
             .credentials_provider(creds)
             .load()
             .await;
 
-        let client = Client::new(&config);
+        let mut s3_config = aws_sdk_s3::config::Builder::from(&sdk_config).force_path_style(config.force_path_style);
+        if let Some(endpoint) = &config.endpoint {
+            s3_config = s3_config.endpoint_url(endpoint);
+        }
+        let client = Client::from_conf(s3_config.build());
+
+        Self { client, config }
+    }
+
+    /// Uploads `path` in parts, aborting the upload if any part fails so
+    /// S3 doesn't keep the orphaned parts
+    async fn multipart_upload(&self, key: &str, path: &Path, size: u64, mime_type: &str) -> Result<(), String> {
+        let upload = self.client
+            .create_multipart_upload()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .content_type(mime_type)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let upload_id = upload.upload_id().ok_or("S3 returned no upload id")?.to_string();
 
-        Self {
-            client,
-            bucket,
-            region,
-            cdn_url,
+        let result = self.upload_parts(key, &upload_id, path, size).await;
+        let parts = match result {
+            Ok(parts) => parts,
+            Err(e) => {
+                let _ = self.client
+                    .abort_multipart_upload()
+                    .bucket(&self.config.bucket)
+                    .key(key)
+                    .upload_id(&upload_id)
+                    .send()
+                    .await;
+                return Err(e);
+            }
+        };
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn upload_parts(&self, key: &str, upload_id: &str, path: &Path, size: u64) -> Result<Vec<CompletedPart>, String> {
+        let mut parts = Vec::new();
+        let mut offset = 0;
+        let mut part_number = 1;
+
+        while offset < size {
+            let length = MULTIPART_PART_SIZE.min(size - offset);
+            let body = ByteStream::read_from()
+                .path(path)
+                .offset(offset)
+                .length(Length::Exact(length))
+                .build()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let part = self.client
+                .upload_part()
+                .bucket(&self.config.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            parts.push(
+                CompletedPart::builder()
+                    .set_e_tag(part.e_tag().map(str::to_string))
+                    .part_number(part_number)
+                    .build(),
+            );
+            offset += length;
+            part_number += 1;
         }
+        Ok(parts)
     }
 }
 
 #[async_trait]
 impl StorageService for S3Storage {
     async fn save(&self, filename: &str, data: &[u8], mime_type: &str) -> Result<String, String> {
-        let body = aws_sdk_s3::primitives::ByteStream::from(data.to_vec());
+        let body = ByteStream::from(data.to_vec());
 
         self.client
             .put_object()
-            .bucket(&self.bucket)
+            .bucket(&self.config.bucket)
             .key(filename)
             .body(body)
             .content_type(mime_type)
@@ -111,10 +316,31 @@ impl StorageService for S3Storage {
         Ok(filename.to_string()) // S3 key is the storage path
     }
 
+    async fn save_file(&self, filename: &str, path: &Path, mime_type: &str) -> Result<String, String> {
+        let size = tokio::fs::metadata(path).await.map_err(|e| e.to_string())?.len();
+
+        if size > MULTIPART_THRESHOLD {
+            self.multipart_upload(filename, path, size, mime_type).await?;
+        } else {
+            let body = ByteStream::from_path(path).await.map_err(|e| e.to_string())?;
+            self.client
+                .put_object()
+                .bucket(&self.config.bucket)
+                .key(filename)
+                .body(body)
+                .content_type(mime_type)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(filename.to_string())
+    }
+
     async fn delete(&self, filename: &str) -> Result<(), String> {
         self.client
             .delete_object()
-            .bucket(&self.bucket)
+            .bucket(&self.config.bucket)
             .key(filename)
             .send()
             .await
@@ -123,39 +349,154 @@ impl StorageService for S3Storage {
         Ok(())
     }
 
-    fn get_url(&self, filename: &str) -> String {
-        match &self.cdn_url {
-            Some(cdn) => format!("{}/{}", cdn.trim_end_matches('/'), filename),
-            None => format!("https://{}.s3.{}.amazonaws.com/{}", self.bucket, self.region, filename),
+    async fn load(&self, filename: &str) -> Result<Option<Vec<u8>>, String> {
+        let output = match self.client.get_object().bucket(&self.config.bucket).key(filename).send().await {
+            Ok(output) => output,
+            Err(e) if e.as_service_error().map(|e| e.is_no_such_key()).unwrap_or(false) => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let data = output.body.collect().await.map_err(|e| e.to_string())?;
+        Ok(Some(data.into_bytes().to_vec()))
+    }
+
+    async fn head(&self, filename: &str) -> Result<Option<u64>, String> {
+        match self.client.head_object().bucket(&self.config.bucket).key(filename).send().await {
+            Ok(output) => Ok(Some(output.content_length().unwrap_or(0).max(0) as u64)),
+            Err(e) if e.as_service_error().map(|e| e.is_not_found()).unwrap_or(false) => Ok(None),
+            Err(e) => Err(e.to_string()),
         }
     }
+
+    async fn download(&self, filename: &str, dest: &Path) -> Result<bool, String> {
+        let output = match self.client.get_object().bucket(&self.config.bucket).key(filename).send().await {
+            Ok(output) => output,
+            Err(e) if e.as_service_error().map(|e| e.is_no_such_key()).unwrap_or(false) => return Ok(false),
+            Err(e) => return Err(e.to_string()),
+        };
+        let mut file = tokio::fs::File::create(dest).await.map_err(|e| e.to_string())?;
+        tokio::io::copy(&mut output.body.into_async_read(), &mut file)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    async fn presigned_get_url(&self, filename: &str, expires_in: Duration) -> Result<String, String> {
+        let request = self.client
+            .get_object()
+            .bucket(&self.config.bucket)
+            .key(filename)
+            .presigned(presigning_config(expires_in)?)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(request.uri().to_string())
+    }
+
+    async fn presigned_put_url(&self, filename: &str, mime_type: &str, expires_in: Duration) -> Result<String, String> {
+        let request = self.client
+            .put_object()
+            .bucket(&self.config.bucket)
+            .key(filename)
+            .content_type(mime_type)
+            .presigned(presigning_config(expires_in)?)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(request.uri().to_string())
+    }
+
+    fn get_url(&self, filename: &str) -> String {
+        self.config.object_url(filename)
+    }
 }
 
+/// Where the app stores files. Stored files are passed by the path `save`
+/// returned and routed by it (see `is_local_path`), so with S3 configured
+/// media still on local disk stay readable during and after a migration.
 #[derive(Clone)]
 pub enum StorageBackend {
     Local(LocalStorage),
     S3(S3Storage),
+    /// Both storages configured; new files go to S3 when `write_to_s3` is set
+    Mixed { local: LocalStorage, s3: S3Storage, write_to_s3: bool },
 }
 
 impl StorageBackend {
+    pub fn local(config: &LocalConfig) -> Self {
+        StorageBackend::Local(LocalStorage::new(config.base_url.clone()))
+    }
+
+    /// The S3 backend, if S3 is configured
+    pub async fn s3(config: &LocalConfig) -> Option<Self> {
+        match S3Config::from_config(config) {
+            Some(s3_config) => Some(StorageBackend::S3(S3Storage::new(s3_config).await)),
+            None => None,
+        }
+    }
+
+    /// New files go to `APP_STORAGE_BACKEND` ("local" or "s3"), which defaults
+    /// to S3 when it is configured
     pub async fn from_config(config: &LocalConfig) -> Self {
-        if let (Some(ak), Some(sk), Some(reg), Some(buck)) = (
-            &config.aws_access_key_id,
-            &config.aws_secret_access_key,
-            &config.aws_region,
-            &config.aws_s3_bucket,
-        ) {
-            log::info!("Using S3 Storage Backend");
-            StorageBackend::S3(S3Storage::new(
-                ak.clone(), 
-                sk.clone(), 
-                reg.clone(), 
-                buck.clone(),
-                config.cdn_url.clone()
-            ).await)
-        } else {
-            log::info!("Using Local Storage Backend");
-            StorageBackend::Local(LocalStorage::new(config.base_url.clone()))
+        let local = LocalStorage::new(config.base_url.clone());
+        let write_to_s3 = match config.storage_backend.as_deref().map(str::trim) {
+            Some(b) if b.eq_ignore_ascii_case("local") => false,
+            Some(b) if b.eq_ignore_ascii_case("s3") => true,
+            Some(other) => {
+                log::warn!("Unknown storage backend {:?}, expected local or s3", other);
+                true
+            }
+            None => true,
+        };
+        match S3Config::from_config(config) {
+            Some(s3_config) => {
+                log::info!("Using {} Storage Backend for new files", if write_to_s3 { "S3" } else { "Local" });
+                StorageBackend::Mixed { local, s3: S3Storage::new(s3_config).await, write_to_s3 }
+            }
+            None => {
+                if config.storage_backend.is_some() && write_to_s3 {
+                    log::warn!("S3 storage backend selected but S3 is not configured");
+                }
+                log::info!("Using Local Storage Backend");
+                StorageBackend::Local(local)
+            }
+        }
+    }
+
+    /// The path a file saved as `filename` gets, to check whether it exists
+    pub fn stored_path(&self, filename: &str) -> String {
+        match self {
+            StorageBackend::Local(_) | StorageBackend::Mixed { write_to_s3: false, .. } => {
+                format!("{}/{}", UPLOAD_DIR, filename)
+            }
+            StorageBackend::S3(_) | StorageBackend::Mixed { write_to_s3: true, .. } => filename.to_string(),
+        }
+    }
+
+    /// The storage holding the file stored at `file_path`
+    fn reader(&self, file_path: &str) -> &(dyn StorageService + Send + Sync) {
+        match self {
+            StorageBackend::Local(s) => s,
+            StorageBackend::S3(s) => s,
+            StorageBackend::Mixed { local, s3, .. } => {
+                if is_local_path(file_path) {
+                    local
+                } else {
+                    s3
+                }
+            }
+        }
+    }
+
+    /// The storage new files are written to
+    fn writer(&self) -> &(dyn StorageService + Send + Sync) {
+        match self {
+            StorageBackend::Local(s) => s,
+            StorageBackend::S3(s) => s,
+            StorageBackend::Mixed { local, s3, write_to_s3 } => {
+                if *write_to_s3 {
+                    s3
+                } else {
+                    local
+                }
+            }
         }
     }
 }
@@ -163,23 +504,67 @@ impl StorageBackend {
 #[async_trait]
 impl StorageService for StorageBackend {
     async fn save(&self, filename: &str, data: &[u8], mime_type: &str) -> Result<String, String> {
-        match self {
-            StorageBackend::Local(s) => s.save(filename, data, mime_type).await,
-            StorageBackend::S3(s) => s.save(filename, data, mime_type).await,
-        }
+        self.writer().save(filename, data, mime_type).await
+    }
+
+    async fn save_file(&self, filename: &str, path: &Path, mime_type: &str) -> Result<String, String> {
+        self.writer().save_file(filename, path, mime_type).await
     }
 
     async fn delete(&self, filename: &str) -> Result<(), String> {
-        match self {
-            StorageBackend::Local(s) => s.delete(filename).await,
-            StorageBackend::S3(s) => s.delete(filename).await,
-        }
+        self.reader(filename).delete(storage_filename(filename)).await
+    }
+
+    async fn load(&self, filename: &str) -> Result<Option<Vec<u8>>, String> {
+        self.reader(filename).load(storage_filename(filename)).await
+    }
+
+    async fn head(&self, filename: &str) -> Result<Option<u64>, String> {
+        self.reader(filename).head(storage_filename(filename)).await
+    }
+
+    async fn download(&self, filename: &str, dest: &Path) -> Result<bool, String> {
+        self.reader(filename).download(storage_filename(filename), dest).await
+    }
+
+    async fn presigned_get_url(&self, filename: &str, expires_in: Duration) -> Result<String, String> {
+        self.reader(filename).presigned_get_url(storage_filename(filename), expires_in).await
+    }
+
+    async fn presigned_put_url(&self, filename: &str, mime_type: &str, expires_in: Duration) -> Result<String, String> {
+        self.writer().presigned_put_url(filename, mime_type, expires_in).await
     }
 
     fn get_url(&self, filename: &str) -> String {
-        match self {
-            StorageBackend::Local(s) => s.get_url(filename),
-            StorageBackend::S3(s) => s.get_url(filename),
+        self.reader(filename).get_url(storage_filename(filename))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s3_config(endpoint: Option<&str>, force_path_style: bool) -> S3Config {
+        S3Config {
+            access_key: "key".to_string(),
+            secret_key: "secret".to_string(),
+            region: "eu-west-1".to_string(),
+            bucket: "media".to_string(),
+            cdn_url: None,
+            endpoint: endpoint.map(str::to_string),
+            force_path_style,
         }
     }
+
+    #[test]
+    fn test_object_urls() {
+        assert_eq!(s3_config(None, false).object_url("a.jpg"), "https://media.s3.eu-west-1.amazonaws.com/a.jpg");
+        assert_eq!(s3_config(None, true).object_url("a.jpg"), "https://s3.eu-west-1.amazonaws.com/media/a.jpg");
+        assert_eq!(s3_config(Some("http://minio:9000/"), true).object_url("a.jpg"), "http://minio:9000/media/a.jpg");
+        assert_eq!(s3_config(Some("https://s3.example.com"), false).object_url("a.jpg"), "https://media.s3.example.com/a.jpg");
+
+        let mut cdn = s3_config(Some("http://minio:9000"), true);
+        cdn.cdn_url = Some("https://cdn.example.com/".to_string());
+        assert_eq!(cdn.object_url("a.jpg"), "https://cdn.example.com/a.jpg");
+    }
 }
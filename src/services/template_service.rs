@@ -1,17 +1,136 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use handlebars::{Handlebars, DirectorySourceOptions};
+use liquid::partials::{EagerCompiler, InMemorySource};
 use liquid::{Parser, ParserBuilder};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
+use crate::helpers::liquid_filters;
+use crate::models::theme_models::Theme;
+use crate::services::cache_service_v2::{tenant_tag, CacheServiceV2};
+use crate::services::theme_package_service::{self, ThemeManifest, TEMPLATES_SUBDIR};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TemplateEngine {
     Handlebars,
     Liquid,
 }
 
+impl TemplateEngine {
+    /// Splits an explicit engine suffix off a template name,
+    /// e.g. "about.liquid" is the Liquid template "about"
+    pub fn split_name(name: &str) -> (&str, Option<TemplateEngine>) {
+        if let Some(base) = name.strip_suffix(".liquid") {
+            (base, Some(TemplateEngine::Liquid))
+        } else if let Some(base) = name.strip_suffix(".hbs") {
+            (base, Some(TemplateEngine::Handlebars))
+        } else {
+            (name, None)
+        }
+    }
+}
+
+/// Renders `name` from one registry pair. The engine is the name's suffix if
+/// it has one, else `preferred`, else whichever engine has the template
+/// (Handlebars first).
+fn render_with(
+    handlebars: &Handlebars<'_>,
+    liquid_templates: &HashMap<String, liquid::Template>,
+    preferred: Option<TemplateEngine>,
+    name: &str,
+    data: &serde_json::Value,
+) -> Result<String, String> {
+    let (base, explicit) = TemplateEngine::split_name(name);
+    let has_liquid = liquid_templates.contains_key(base);
+    let engine = match explicit.or(preferred) {
+        Some(TemplateEngine::Liquid) if has_liquid => TemplateEngine::Liquid,
+        Some(TemplateEngine::Handlebars) if handlebars.has_template(base) => TemplateEngine::Handlebars,
+        Some(engine) if explicit.is_some() => engine,
+        _ if !handlebars.has_template(base) && has_liquid => TemplateEngine::Liquid,
+        _ => TemplateEngine::Handlebars,
+    };
+
+    match engine {
+        TemplateEngine::Liquid => {
+            let template = liquid_templates
+                .get(base)
+                .ok_or_else(|| format!("Liquid template not found: {}", base))?;
+            // Convert serde_json::Value to liquid::Object
+            let globals = liquid::to_object(data).map_err(|e| format!("Liquid data error: {}", e))?;
+            template.render(&globals).map_err(|e| e.to_string())
+        }
+        TemplateEngine::Handlebars => handlebars.render(base, data).map_err(|e| e.to_string()),
+    }
+}
+
+fn has_template(handlebars: &Handlebars<'_>, liquid_templates: &HashMap<String, liquid::Template>, name: &str) -> bool {
+    let (base, _) = TemplateEngine::split_name(name);
+    handlebars.has_template(base) || liquid_templates.contains_key(base)
+}
+
+/// Liquid parser with the FreeRadical filters and the given partials
+fn liquid_parser(partials: InMemorySource) -> Result<Parser, String> {
+    liquid_filters::register_filters(ParserBuilder::with_stdlib())
+        .partials(EagerCompiler::new(partials))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Compiles every `.liquid` file under `dir`, keyed like Handlebars templates
+/// ("layouts/base"). Each can `{% include %}` the others by that name.
+pub fn compile_liquid_dir(dir: &Path) -> Result<HashMap<String, liquid::Template>, String> {
+    let mut partials = InMemorySource::new();
+    let mut sources = Vec::new();
+    for path in theme_package_service::files_with_extension(dir, "liquid").map_err(|e| e.to_string())? {
+        let name = template_name(dir, &path);
+        let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        partials.add(name.clone(), source.clone());
+        sources.push((name, source));
+    }
+
+    let parser = liquid_parser(partials)?;
+    sources
+        .into_iter()
+        .map(|(name, source)| {
+            let template = parser.parse(&source).map_err(|e| format!("{}: {}", name, e))?;
+            Ok((name, template))
+        })
+        .collect()
+}
+
+/// Templates of one unpacked theme package, compiled once at activation
+pub struct ThemeRegistry {
+    pub theme_id: i32,
+    pub dir: PathBuf,
+    pub manifest: ThemeManifest,
+    handlebars: Handlebars<'static>,
+    liquid_templates: HashMap<String, liquid::Template>,
+}
+
+impl ThemeRegistry {
+    fn has_template(&self, name: &str) -> bool {
+        has_template(&self.handlebars, &self.liquid_templates, name)
+    }
+
+    /// Uses the manifest's `engine` when both engines have the template
+    fn render(&self, name: &str, data: &serde_json::Value) -> Result<String, String> {
+        render_with(&self.handlebars, &self.liquid_templates, self.manifest.engine, name, data)
+    }
+
+    /// File under the theme's asset directory, if it exists
+    pub fn asset(&self, path: &str) -> Option<PathBuf> {
+        theme_package_service::asset_path(&self.dir, &self.manifest, path)
+    }
+}
+
 pub struct TemplateService {
     handlebars: Arc<Mutex<Handlebars<'static>>>,
     liquid_templates: Arc<Mutex<HashMap<String, liquid::Template>>>,
     liquid_parser: Parser,
+    /// Active theme per tenant; tenants without one use the global templates
+    themes: Arc<RwLock<HashMap<i32, Arc<ThemeRegistry>>>>,
 }
 
 impl TemplateService {
@@ -19,14 +138,14 @@ impl TemplateService {
         let mut handlebars = Handlebars::new();
         handlebars.set_strict_mode(false); // Legacy support
         
-        let liquid_parser = ParserBuilder::with_stdlib()
-            .build()
+        let liquid_parser = liquid_parser(InMemorySource::new())
             .expect("Failed to build liquid parser");
 
         Self {
             handlebars: Arc::new(Mutex::new(handlebars)),
             liquid_templates: Arc::new(Mutex::new(HashMap::new())),
             liquid_parser,
+            themes: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -45,28 +164,38 @@ impl TemplateService {
                 .map_err(|e| e.to_string())?;
         }
 
-        // Load Liquid (Manual Directory Scan would go here, skipping for now)
-        // Ideally we walk the directory and compile .liquid files
+        // Load Liquid
+        let compiled = compile_liquid_dir(Path::new(dir))?;
+        self.liquid_templates.lock().unwrap().extend(compiled);
+        Ok(())
+    }
+
+    /// Drops all global templates and loads `dir` again, e.g. after an edit.
+    /// Nothing is replaced if the directory fails to compile.
+    pub fn reload_templates(&self, dir: &str) -> Result<(), String> {
+        let compiled = compile_liquid_dir(Path::new(dir))?;
+        // Compiled into a fresh registry so a bad template leaves the live one untouched
+        let mut handlebars = Handlebars::new();
+        handlebars.set_strict_mode(false);
+        crate::helpers::default::register_helpers_on(&mut handlebars);
+        let mut options = DirectorySourceOptions::default();
+        options.tpl_extension = ".hbs".to_string();
+        handlebars
+            .register_templates_directory(dir, options)
+            .map_err(|e| e.to_string())?;
+
+        *self.handlebars.lock().unwrap() = handlebars;
+        *self.liquid_templates.lock().unwrap() = compiled;
         Ok(())
     }
 
+    /// Renders a global template. `template_name` may carry an engine suffix
+    /// ("about.liquid", "about.hbs") to pick the engine; without one Handlebars
+    /// is used when it has the template, Liquid otherwise.
     pub fn render(&self, template_name: &str, data: &serde_json::Value) -> Result<String, String> {
-        // Check extension
-        if template_name.ends_with(".liquid") {
-             let templates = self.liquid_templates.lock().unwrap();
-             if let Some(template) = templates.get(template_name) {
-                 // Convert serde_json::Value to liquid::Object
-                 let globals = liquid::to_object(data)
-                    .map_err(|e| format!("Liquid data error: {}", e))?;
-                 
-                 return template.render(&globals).map_err(|e| e.to_string());
-             }
-             Err(format!("Liquid template not found: {}", template_name))
-        } else {
-            // Default to Handlebars
-            let hb = self.handlebars.lock().unwrap();
-            hb.render(template_name, data).map_err(|e| e.to_string())
-        }
+        let hb = self.handlebars.lock().unwrap();
+        let liquid_templates = self.liquid_templates.lock().unwrap();
+        render_with(&hb, &liquid_templates, None, template_name, data)
     }
     
     // Helper to register liquid template (for testing/loading)
@@ -74,17 +203,153 @@ impl TemplateService {
         let template = self.liquid_parser.parse(content)
             .map_err(|e| e.to_string())?;
             
+        let (base, _) = TemplateEngine::split_name(name);
         let mut templates = self.liquid_templates.lock().unwrap();
-        templates.insert(name.to_string(), template);
+        templates.insert(base.to_string(), template);
+        Ok(())
+    }
+
+    /// Compiles an unpacked theme and makes it the tenant's active registry
+    pub fn load_theme(&self, tenant_id: i32, theme: &Theme) -> Result<(), String> {
+        let registry = self.compile_theme(theme)?;
+        self.set_active_theme(tenant_id, registry);
         Ok(())
     }
 
+    /// Compiles an unpacked theme without activating it
+    pub fn compile_theme(&self, theme: &Theme) -> Result<ThemeRegistry, String> {
+        let dir = PathBuf::from(&theme.file_path);
+        let manifest = theme_package_service::read_manifest(&dir).map_err(|e| e.to_string())?;
+        let templates_dir = dir.join(TEMPLATES_SUBDIR);
+
+        let mut handlebars = Handlebars::new();
+        handlebars.set_strict_mode(false);
+        crate::helpers::default::register_helpers_on(&mut handlebars);
+        let mut options = DirectorySourceOptions::default();
+        options.tpl_extension = ".hbs".to_string();
+        handlebars
+            .register_templates_directory(&templates_dir, options)
+            .map_err(|e| e.to_string())?;
+
+        let liquid_templates = compile_liquid_dir(&templates_dir)?;
+
+        Ok(ThemeRegistry {
+            theme_id: theme.id,
+            dir,
+            manifest,
+            handlebars,
+            liquid_templates,
+        })
+    }
+
+    pub fn set_active_theme(&self, tenant_id: i32, registry: ThemeRegistry) {
+        self.themes.write().unwrap().insert(tenant_id, Arc::new(registry));
+    }
+
+    pub fn unload_theme(&self, tenant_id: i32) {
+        self.themes.write().unwrap().remove(&tenant_id);
+    }
+
+    pub fn active_theme(&self, tenant_id: i32) -> Option<Arc<ThemeRegistry>> {
+        self.themes.read().unwrap().get(&tenant_id).cloned()
+    }
+
+    /// Loads every tenant's active theme, skipping (and logging) broken ones
+    pub fn load_active_themes(&self, conn: &mut diesel::PgConnection) -> Result<usize, diesel::result::Error> {
+        use crate::schema::{tenant_themes, themes};
+        use diesel::prelude::*;
+
+        let active = tenant_themes::table
+            .inner_join(themes::table)
+            .select((tenant_themes::tenant_id, Theme::as_select()))
+            .load::<(i32, Theme)>(conn)?;
+
+        let mut loaded = 0;
+        for (tenant_id, theme) in active {
+            match self.load_theme(tenant_id, &theme) {
+                Ok(()) => loaded += 1,
+                Err(e) => log::error!("Failed to load theme {} for tenant {}: {}", theme.id, tenant_id, e),
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Brings this instance's theme for the tenant in line with the database.
+    /// The active theme id is kept in the shared cache under the tenant's tag,
+    /// so an activation on any instance purges it and every instance reloads.
+    pub async fn sync_active_theme(&self, tenant_id: i32, cache: &CacheServiceV2, conn: &mut diesel::PgConnection) {
+        use crate::schema::{tenant_themes, themes};
+        use diesel::prelude::*;
+
+        let key = active_theme_key(tenant_id);
+        let loaded = self.active_theme(tenant_id).map(|theme| theme.theme_id);
+        if cache.get::<Option<i32>>(&key).await == Some(loaded) {
+            return;
+        }
+
+        let theme = match tenant_themes::table
+            .inner_join(themes::table)
+            .filter(tenant_themes::tenant_id.eq(tenant_id))
+            .select(Theme::as_select())
+            .first::<Theme>(conn)
+            .optional()
+        {
+            Ok(theme) => theme,
+            Err(e) => {
+                log::error!("Failed to read the active theme of tenant {}: {}", tenant_id, e);
+                return;
+            }
+        };
+        let active = match theme {
+            Some(theme) if loaded == Some(theme.id) => Some(theme.id),
+            Some(theme) => match self.load_theme(tenant_id, &theme) {
+                Ok(()) => Some(theme.id),
+                Err(e) => {
+                    log::error!("Failed to load theme {} for tenant {}: {}", theme.id, tenant_id, e);
+                    self.unload_theme(tenant_id);
+                    None
+                }
+            },
+            None => {
+                self.unload_theme(tenant_id);
+                None
+            }
+        };
+        let _ = cache.set_with_tags(&key, &active, None, &[tenant_tag(tenant_id)]).await;
+    }
+
+    /// Renders with the tenant's active theme, falling back to the global
+    /// templates when the tenant has no theme or the theme lacks `template_name`
+    pub fn render_for_tenant(&self, tenant_id: i32, template_name: &str, data: &serde_json::Value) -> Result<String, String> {
+        if let Some(theme) = self.active_theme(tenant_id) {
+            if theme.has_template(template_name) {
+                return theme.render(template_name, data);
+            }
+        }
+        self.render(template_name, data)
+    }
+
     // Getter for legacy main.rs code if needed
     pub fn get_handlebars(&self) -> Arc<Mutex<Handlebars<'static>>> {
         self.handlebars.clone()
     }
 }
 
+/// Shared-cache key holding the id of a tenant's active theme
+fn active_theme_key(tenant_id: i32) -> String {
+    format!("theme:active:{}", tenant_id)
+}
+
+/// Registry name of a template file: its path under `templates/` without extension
+fn template_name(templates_dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(templates_dir).unwrap_or(path).with_extension("");
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,4 +375,83 @@ mod tests {
         let res = service.render("t1.liquid", &json!({"name": "Liquid"})).unwrap();
         assert_eq!(res, "Hello Liquid");
     }
+
+    #[test]
+    fn test_tenant_theme_overrides_global_templates() {
+        let dir = std::env::temp_dir().join(format!("theme-registry-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("templates")).unwrap();
+        std::fs::write(
+            dir.join("theme.json"),
+            r#"{"name": "Dark", "version": "1.0.0", "templates": ["index", "about"]}"#,
+        ).unwrap();
+        std::fs::write(dir.join("templates/index.hbs"), "Dark {{name}}").unwrap();
+        std::fs::write(dir.join("templates/about.liquid"), "About {{ name }}").unwrap();
+
+        let service = TemplateService::new();
+        service.get_handlebars().lock().unwrap()
+            .register_template_string("index", "Default {{name}}")
+            .unwrap();
+        let theme = Theme {
+            id: 1,
+            name: "Dark".to_string(),
+            description: None,
+            version: "1.0.0".to_string(),
+            file_path: dir.to_string_lossy().to_string(),
+            thumbnail_url: None,
+            is_active: Some(true),
+            is_default: Some(false),
+            tenant_id: Some(7),
+            created_at: None,
+            updated_at: None,
+            manifest: None,
+        };
+        service.load_theme(7, &theme).unwrap();
+
+        let data = json!({"name": "Site"});
+        assert_eq!(service.render_for_tenant(7, "index", &data).unwrap(), "Dark Site");
+        assert_eq!(service.render_for_tenant(7, "about", &data).unwrap(), "About Site");
+        assert_eq!(service.render_for_tenant(8, "index", &data).unwrap(), "Default Site");
+
+        service.unload_theme(7);
+        assert_eq!(service.render_for_tenant(7, "index", &data).unwrap(), "Default Site");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_engine_selection_and_liquid_helpers() {
+        let dir = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("layouts")).unwrap();
+        std::fs::write(dir.join("index.hbs"), "hbs {{page_title}}").unwrap();
+        std::fs::write(dir.join("index.liquid"), "liquid {{ page_title }}").unwrap();
+        std::fs::write(dir.join("layouts/base.liquid"), "<h1>{{ page_title }}</h1>").unwrap();
+        std::fs::write(
+            dir.join("about.liquid"),
+            r#"{% include "layouts/base" %}{{ "Body" | get }} {{ price | formatPrice }}"#,
+        ).unwrap();
+
+        let service = TemplateService::new();
+        service.load_templates(dir.to_str().unwrap()).unwrap();
+        let data = json!({"page_title": "Hi", "price": 250, "fields": {"Body": {"content": "Text"}}});
+
+        assert_eq!(service.render("index", &data).unwrap(), "hbs Hi");
+        assert_eq!(service.render("index.liquid", &data).unwrap(), "liquid Hi");
+        assert_eq!(service.render("about", &data).unwrap(), "<h1>Hi</h1>Text $2.50");
+        assert!(service.render("missing.liquid", &data).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_reload_keeps_live_templates() {
+        let dir = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.hbs"), "Hello {{name}}").unwrap();
+
+        let service = TemplateService::new();
+        service.load_templates(dir.to_str().unwrap()).unwrap();
+        std::fs::write(dir.join("index.hbs"), "Hello {{#if name}}").unwrap();
+        assert!(service.reload_templates(dir.to_str().unwrap()).is_err());
+
+        assert_eq!(service.render("index", &json!({"name": "World"})).unwrap(), "Hello World");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
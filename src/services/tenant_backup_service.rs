@@ -0,0 +1,1296 @@
+// Tenant Backup Service
+// Logical backups of a single tenant: content, media files, CRM and commerce
+// rows go into a versioned zip archive with a SHA-256 checksum for every
+// entry, kept on local disk under `BACKUP_DIR`. Rows are exported as JSON straight from
+// Postgres, so the archive follows the schema without a model per table. A
+// restore writes them into the same tenant or a new one, giving rows fresh
+// IDs and remapping the references listed in `TABLES`.
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{Array, BigInt, Bool, Integer, Jsonb, Text};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::CompressionMethod;
+
+use crate::models::backup_models::{Backup, BackupPolicy, NewBackup, NewBackupPolicy};
+use crate::models::db_connection::DatabasePool;
+use crate::models::tenant_models::{NewTenant, NewTenantMember, Tenant};
+use crate::schema::{backup_policies, backups, pages, tenant_members, tenants};
+use crate::services::cache_service_v2::{tenant_tag, CacheServiceV2};
+use crate::services::embedding_refresh_service;
+use crate::services::errors_service::CustomHttpError;
+use crate::services::image_transform_service::OutputFormat;
+use crate::services::media_usage_service::UUID_PATTERN;
+use crate::services::storage_service::{storage_filename, StorageBackend, StorageService};
+
+pub const ARCHIVE_FORMAT: &str = "freeradical-tenant-backup";
+/// Bumped when the archive layout changes; older archives stay restorable
+pub const ARCHIVE_VERSION: u32 = 1;
+pub const BACKUP_TYPE: &str = "tenant";
+const MANIFEST_ENTRY: &str = "manifest.json";
+
+/// How often the scheduler looks for due backup policies
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// A table in the archive. Tables are restored in the order of `TABLES`, so
+/// each one comes after the tables it references.
+struct TableSpec {
+    name: &'static str,
+    /// Selects the tenant's rows; `$1` is the tenant ID
+    scope: &'static str,
+    order_by: &'static str,
+    /// `id` is a serial key, assigned anew on restore
+    serial_id: bool,
+    /// Column holding the row's UUID. Copies get new UUIDs, replaced wherever
+    /// they appear in the restored rows (content, file paths, references).
+    uuid_key: Option<&'static str>,
+    /// Integer columns holding the `id` of a row in an earlier table
+    refs: &'static [(&'static str, &'static str)],
+    /// Columns holding user IDs; users are not part of the archive
+    user_refs: &'static [&'static str],
+    /// Columns left out of the archive
+    skip_columns: &'static [&'static str],
+    /// Rows have a `file_path` in storage that is archived with them
+    files: bool,
+}
+
+impl TableSpec {
+    const DEFAULT: TableSpec = TableSpec {
+        name: "",
+        scope: "tenant_id = $1",
+        order_by: "id",
+        serial_id: true,
+        uuid_key: None,
+        refs: &[],
+        user_refs: &[],
+        skip_columns: &[],
+        files: false,
+    };
+}
+
+const TABLES: &[TableSpec] = &[
+    // Parents sort before their subfolders
+    TableSpec { name: "media_folders", order_by: "path", refs: &[("parent_id", "media_folders")], ..TableSpec::DEFAULT },
+    TableSpec {
+        name: "media",
+        uuid_key: Some("uuid"),
+        refs: &[("folder_id", "media_folders")],
+        user_refs: &["uploaded_by"],
+        files: true,
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "media_variants",
+        scope: "media_id IN (SELECT id FROM media WHERE tenant_id = $1)",
+        refs: &[("media_id", "media")],
+        files: true,
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "pages",
+        order_by: "uuid",
+        serial_id: false,
+        uuid_key: Some("uuid"),
+        user_refs: &["last_modified_by"],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "page_revisions",
+        scope: "page_uuid IN (SELECT uuid FROM pages WHERE tenant_id = $1)",
+        user_refs: &["changed_by_user_id"],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "module_category",
+        scope: "page_uuid IN (SELECT uuid FROM pages WHERE tenant_id = $1)",
+        order_by: "uuid",
+        serial_id: false,
+        uuid_key: Some("uuid"),
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "modules",
+        scope: "tenant_id = $1 OR page_uuid IN (SELECT uuid FROM pages WHERE tenant_id = $1)",
+        order_by: "uuid",
+        serial_id: false,
+        uuid_key: Some("uuid"),
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "page_translations",
+        scope: "page_id IN (SELECT uuid FROM pages WHERE tenant_id = $1)",
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "module_translations",
+        scope: "module_id IN (SELECT uuid FROM modules WHERE tenant_id = $1 \
+                OR page_uuid IN (SELECT uuid FROM pages WHERE tenant_id = $1))",
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "media_usages",
+        scope: "media_id IN (SELECT id FROM media WHERE tenant_id = $1)",
+        refs: &[("media_id", "media")],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec { name: "products", uuid_key: Some("uuid"), ..TableSpec::DEFAULT },
+    TableSpec {
+        name: "product_variants",
+        scope: "product_id IN (SELECT id FROM products WHERE tenant_id = $1)",
+        uuid_key: Some("uuid"),
+        refs: &[("product_id", "products")],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec { name: "orders", uuid_key: Some("uuid"), ..TableSpec::DEFAULT },
+    TableSpec {
+        name: "order_items",
+        scope: "order_id IN (SELECT id FROM orders WHERE tenant_id = $1)",
+        refs: &[("order_id", "orders"), ("product_id", "products"), ("variant_id", "product_variants")],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "order_refunds",
+        scope: "order_id IN (SELECT id FROM orders WHERE tenant_id = $1)",
+        uuid_key: Some("uuid"),
+        refs: &[("order_id", "orders")],
+        user_refs: &["created_by"],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "order_status_history",
+        scope: "order_id IN (SELECT id FROM orders WHERE tenant_id = $1)",
+        refs: &[("order_id", "orders")],
+        user_refs: &["actor_user_id"],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec { name: "crm_segments", user_refs: &["created_by"], ..TableSpec::DEFAULT },
+    TableSpec {
+        name: "crm_customers",
+        refs: &[("primary_segment_id", "crm_segments")],
+        user_refs: &["user_id"],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "crm_segment_members",
+        scope: "segment_id IN (SELECT id FROM crm_segments WHERE tenant_id = $1)",
+        order_by: "segment_id, customer_id",
+        serial_id: false,
+        refs: &[("segment_id", "crm_segments"), ("customer_id", "crm_customers")],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "crm_interactions",
+        refs: &[("customer_id", "crm_customers"), ("order_id", "orders")],
+        user_refs: &["created_by"],
+        // Derived from the other columns
+        skip_columns: &["search_vector"],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "crm_notes",
+        refs: &[("customer_id", "crm_customers")],
+        user_refs: &["created_by"],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "crm_tasks",
+        refs: &[("customer_id", "crm_customers")],
+        user_refs: &["assigned_to", "created_by"],
+        ..TableSpec::DEFAULT
+    },
+    TableSpec {
+        name: "crm_campaigns",
+        refs: &[("segment_id", "crm_segments")],
+        user_refs: &["created_by"],
+        ..TableSpec::DEFAULT
+    },
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    pub created_at: NaiveDateTime,
+    pub tenant: TenantSnapshot,
+    /// Rows per archived table
+    pub tables: BTreeMap<String, usize>,
+    /// Checksum of every entry but the manifest
+    pub entries: BTreeMap<String, EntryChecksum>,
+    /// Files of media rows that were not in storage at backup time
+    pub missing_files: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TenantSnapshot {
+    pub id: i32,
+    pub uuid: String,
+    pub name: String,
+    pub subdomain: String,
+    pub plan: Option<String>,
+    pub settings: Option<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EntryChecksum {
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewTenantTarget {
+    /// Defaults to the name of the backed-up tenant
+    pub name: Option<String>,
+    pub subdomain: String,
+}
+
+pub enum RestoreTarget {
+    /// Restore into an existing tenant, optionally deleting its current content first
+    Existing { tenant_id: i32, replace_existing: bool },
+    /// Create a tenant owned by `owner_id` and restore into it
+    New { target: NewTenantTarget, owner_id: i32 },
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct RestoreReport {
+    pub tenant_id: i32,
+    /// Rows written per table
+    pub restored: BTreeMap<String, usize>,
+    /// Rows left out per table: they conflicted with existing rows, or
+    /// referenced rows that could not be restored
+    pub skipped: BTreeMap<String, usize>,
+    /// Media files written to storage
+    pub files: usize,
+    /// Archived tables this database doesn't have
+    pub missing_tables: Vec<String>,
+}
+
+#[derive(QueryableByName)]
+struct JsonRow {
+    #[diesel(sql_type = Jsonb)]
+    row: Value,
+}
+
+#[derive(QueryableByName)]
+struct IdRow {
+    #[diesel(sql_type = BigInt)]
+    id: i64,
+}
+
+#[derive(QueryableByName)]
+struct TextRow {
+    #[diesel(sql_type = Text)]
+    value: String,
+}
+
+#[derive(QueryableByName)]
+struct BoolRow {
+    #[diesel(sql_type = Bool)]
+    value: bool,
+}
+
+#[derive(QueryableByName)]
+struct ColumnRow {
+    #[diesel(sql_type = Text)]
+    column_name: String,
+    #[diesel(sql_type = Bool)]
+    nullable: bool,
+}
+
+/// Passes writes through while hashing them
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new(), size: 0 }
+    }
+
+    fn finish(self) -> (W, EntryChecksum) {
+        let checksum = EntryChecksum { sha256: hex::encode(self.hasher.finalize()), size: self.size };
+        (self.inner, checksum)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut writer = HashingWriter::new(std::io::sink());
+    std::io::copy(&mut std::fs::File::open(path)?, &mut writer)?;
+    Ok(writer.finish().1.sha256)
+}
+
+fn table_entry(table: &str) -> String {
+    format!("tables/{}.json", table)
+}
+
+fn file_entry(filename: &str) -> String {
+    format!("files/{}", filename)
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Archives hold all of a tenant's data, so they stay out of the media
+/// storage, which is served publicly
+fn archive_dir() -> PathBuf {
+    PathBuf::from(std::env::var("BACKUP_DIR").unwrap_or_else(|_| "./backups".to_string())).join("tenants")
+}
+
+fn table_exists(table: &str, conn: &mut PgConnection) -> QueryResult<bool> {
+    sql_query("SELECT to_regclass($1) IS NOT NULL AS value")
+        .bind::<Text, _>(table)
+        .get_result::<BoolRow>(conn)
+        .map(|row| row.value)
+}
+
+/// Writable columns of a table and whether each is nullable
+fn table_columns(table: &str, conn: &mut PgConnection) -> QueryResult<HashMap<String, bool>> {
+    let columns = sql_query(
+        "SELECT column_name::TEXT AS column_name, is_nullable = 'YES' AS nullable \
+         FROM information_schema.columns \
+         WHERE table_schema = current_schema() AND table_name = $1 AND is_generated = 'NEVER'",
+    )
+    .bind::<Text, _>(table)
+    .load::<ColumnRow>(conn)?;
+    Ok(columns.into_iter().map(|c| (c.column_name, c.nullable)).collect())
+}
+
+fn export_rows(spec: &TableSpec, tenant_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<Value>> {
+    let rows = sql_query(format!(
+        "SELECT to_jsonb(t) AS row FROM {} t WHERE {} ORDER BY {}",
+        spec.name, spec.scope, spec.order_by
+    ))
+    .bind::<Integer, _>(tenant_id)
+    .load::<JsonRow>(conn)?;
+    Ok(rows
+        .into_iter()
+        .map(|JsonRow { mut row }| {
+            if let Some(fields) = row.as_object_mut() {
+                for column in spec.skip_columns {
+                    fields.remove(*column);
+                }
+            }
+            row
+        })
+        .collect())
+}
+
+fn add_entry<R: Read>(
+    zip: &mut zip::ZipWriter<std::fs::File>,
+    manifest: &mut Manifest,
+    name: &str,
+    reader: &mut R,
+    compression: CompressionMethod,
+) -> Result<(), String> {
+    let options = FileOptions::default().compression_method(compression).large_file(true);
+    zip.start_file(name, options).map_err(|e| e.to_string())?;
+    let mut writer = HashingWriter::new(&mut *zip);
+    std::io::copy(reader, &mut writer).map_err(|e| e.to_string())?;
+    manifest.entries.insert(name.to_string(), writer.finish().1);
+    Ok(())
+}
+
+/// Writes the archive of a tenant to `path`
+async fn write_archive(
+    tenant: &Tenant,
+    storage: &StorageBackend,
+    conn: &mut PgConnection,
+    path: &Path,
+) -> Result<Manifest, String> {
+    let mut manifest = Manifest {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        created_at: chrono::Utc::now().naive_utc(),
+        tenant: TenantSnapshot {
+            id: tenant.id,
+            uuid: tenant.uuid.clone(),
+            name: tenant.name.clone(),
+            subdomain: tenant.subdomain.clone(),
+            plan: tenant.plan.clone(),
+            settings: tenant.settings.clone(),
+        },
+        tables: BTreeMap::new(),
+        entries: BTreeMap::new(),
+        missing_files: Vec::new(),
+    };
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path).map_err(|e| e.to_string())?);
+    let mut archived_files = HashSet::new();
+
+    for spec in TABLES {
+        if !table_exists(spec.name, conn).map_err(|e| e.to_string())? {
+            continue;
+        }
+        let rows = export_rows(spec, tenant.id, conn).map_err(|e| e.to_string())?;
+        let data = serde_json::to_vec(&rows).map_err(|e| e.to_string())?;
+        add_entry(&mut zip, &mut manifest, &table_entry(spec.name), &mut data.as_slice(), CompressionMethod::Deflated)?;
+        manifest.tables.insert(spec.name.to_string(), rows.len());
+
+        if !spec.files {
+            continue;
+        }
+        for row in &rows {
+            let Some(file_path) = row.get("file_path").and_then(Value::as_str) else { continue };
+            let filename = storage_filename(file_path).to_string();
+            if !archived_files.insert(filename.clone()) {
+                continue;
+            }
+            // Media are mostly compressed already
+            let temp = std::env::temp_dir().join(format!("tenant-backup-file-{}", Uuid::new_v4()));
+            let archived = match storage.download(file_path, &temp).await {
+                Ok(true) => std::fs::File::open(&temp)
+                    .map_err(|e| e.to_string())
+                    .and_then(|mut file| {
+                        add_entry(&mut zip, &mut manifest, &file_entry(&filename), &mut file, CompressionMethod::Stored)
+                    })
+                    .map(|_| true),
+                Ok(false) => Ok(false),
+                Err(e) => Err(e),
+            };
+            let _ = std::fs::remove_file(&temp);
+            if !archived? {
+                log::warn!("Backup of tenant {}: {} is missing from storage", tenant.id, filename);
+                manifest.missing_files.push(filename);
+            }
+        }
+    }
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    zip.start_file(MANIFEST_ENTRY, FileOptions::default()).map_err(|e| e.to_string())?;
+    zip.write_all(&manifest_json).map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(manifest)
+}
+
+/// Backs up a tenant and stores the archive. The backup row records the
+/// outcome either way.
+pub async fn create_backup(
+    tenant_id: i32,
+    storage: &StorageBackend,
+    conn: &mut PgConnection,
+) -> Result<Backup, CustomHttpError> {
+    let tenant = tenants::table
+        .find(tenant_id)
+        .first::<Tenant>(conn)
+        .optional()?
+        .ok_or(CustomHttpError::NotFound("Tenant not found".to_string()))?;
+    let backup = diesel::insert_into(backups::table)
+        .values(&NewBackup {
+            uuid: Uuid::new_v4().to_string(),
+            type_: BACKUP_TYPE.to_string(),
+            status: Some("running".to_string()),
+            storage_location: Some("local".to_string()),
+            tenant_id: Some(tenant.id),
+        })
+        .get_result::<Backup>(conn)?;
+
+    let dir = archive_dir();
+    let archive_path = dir.join(format!("tenant-backup-{}.zip", backup.uuid));
+    let written = match std::fs::create_dir_all(&dir) {
+        Ok(()) => write_archive(&tenant, storage, conn, &archive_path).await,
+        Err(e) => Err(e.to_string()),
+    };
+    let stored = written.and_then(|manifest| {
+        let checksum = sha256_file(&archive_path).map_err(|e| e.to_string())?;
+        let size = std::fs::metadata(&archive_path).map(|m| m.len()).map_err(|e| e.to_string())?;
+        Ok((manifest, archive_path.to_string_lossy().to_string(), size, checksum))
+    });
+    if stored.is_err() {
+        let _ = std::fs::remove_file(&archive_path);
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    match stored {
+        Ok((manifest, file_path, size, checksum)) => {
+            let metadata = serde_json::json!({
+                "format": manifest.format,
+                "version": manifest.version,
+                "tenant": manifest.tenant,
+                "tables": manifest.tables,
+                "missing_files": manifest.missing_files,
+            });
+            Ok(diesel::update(backups::table.find(backup.id))
+                .set((
+                    backups::status.eq("completed"),
+                    backups::file_path.eq(file_path),
+                    backups::file_size.eq(size as i64),
+                    backups::checksum.eq(checksum),
+                    backups::metadata.eq(metadata),
+                    backups::completed_at.eq(now),
+                ))
+                .get_result::<Backup>(conn)?)
+        }
+        Err(e) => {
+            log::error!("Backup of tenant {} failed: {}", tenant.id, e);
+            diesel::update(backups::table.find(backup.id))
+                .set((
+                    backups::status.eq("failed"),
+                    backups::metadata.eq(serde_json::json!({ "error": e })),
+                    backups::completed_at.eq(now),
+                ))
+                .execute(conn)?;
+            Err(CustomHttpError::InternalServerError(format!("Backup failed: {}", e)))
+        }
+    }
+}
+
+/// Newest first
+pub fn list_backups(tenant_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<Backup>> {
+    backups::table
+        .filter(backups::tenant_id.eq(tenant_id))
+        .filter(backups::type_.eq(BACKUP_TYPE))
+        .order((backups::created_at.desc(), backups::id.desc()))
+        .load::<Backup>(conn)
+}
+
+pub fn find_backup(uuid: &str, conn: &mut PgConnection) -> Result<Backup, CustomHttpError> {
+    backups::table
+        .filter(backups::uuid.eq(uuid))
+        .filter(backups::type_.eq(BACKUP_TYPE))
+        .first::<Backup>(conn)
+        .optional()?
+        .ok_or(CustomHttpError::NotFound("Backup not found".to_string()))
+}
+
+pub fn delete_backup(backup: &Backup, conn: &mut PgConnection) -> Result<(), CustomHttpError> {
+    if let Some(file_path) = &backup.file_path {
+        match std::fs::remove_file(file_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to delete backup archive {}: {}", file_path, e),
+        }
+    }
+    diesel::delete(backups::table.find(backup.id)).execute(conn)?;
+    Ok(())
+}
+
+/// Reads an archive entry, checking it against the manifest
+fn copy_entry<R: Read + Seek, W: Write>(
+    archive: &mut zip::ZipArchive<R>,
+    manifest: &Manifest,
+    name: &str,
+    dest: W,
+) -> Result<W, CustomHttpError> {
+    let expected = manifest
+        .entries
+        .get(name)
+        .ok_or_else(|| CustomHttpError::BadRequest(format!("{} is not listed in the archive manifest", name)))?;
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| CustomHttpError::BadRequest(format!("{} is missing from the archive", name)))?;
+    let mut writer = HashingWriter::new(dest);
+    std::io::copy(&mut entry, &mut writer)
+        .map_err(|e| CustomHttpError::BadRequest(format!("Failed to read {}: {}", name, e)))?;
+    let (dest, checksum) = writer.finish();
+    if checksum.sha256 != expected.sha256 || checksum.size != expected.size {
+        return Err(CustomHttpError::BadRequest(format!("{} is corrupt: checksum mismatch", name)));
+    }
+    Ok(dest)
+}
+
+fn read_manifest<R: Read + Seek>(archive: &mut zip::ZipArchive<R>) -> Result<Manifest, CustomHttpError> {
+    let invalid = |e: String| CustomHttpError::BadRequest(format!("Invalid backup manifest: {}", e));
+    let mut data = Vec::new();
+    archive
+        .by_name(MANIFEST_ENTRY)
+        .map_err(|e| invalid(e.to_string()))?
+        .read_to_end(&mut data)
+        .map_err(|e| invalid(e.to_string()))?;
+    let manifest: Manifest = serde_json::from_slice(&data).map_err(|e| invalid(e.to_string()))?;
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(invalid(format!("unknown format {}", manifest.format)));
+    }
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(invalid(format!(
+            "version {} is newer than this server supports ({})",
+            manifest.version, ARCHIVE_VERSION
+        )));
+    }
+    Ok(manifest)
+}
+
+/// New UUIDs for the rows of tables with a UUID key
+fn new_uuid_map(tables: &[(&TableSpec, Vec<Value>)]) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    for (spec, rows) in tables {
+        let Some(key) = spec.uuid_key else { continue };
+        for row in rows {
+            if let Some(uuid) = row.get(key).and_then(Value::as_str).and_then(|v| Uuid::parse_str(v).ok()) {
+                map.insert(uuid.to_string(), Uuid::new_v4().to_string());
+            }
+        }
+    }
+    map
+}
+
+/// Replaces the UUIDs in `map` wherever they appear in a value
+fn remap_uuids(value: &mut Value, map: &HashMap<String, String>) {
+    match value {
+        Value::String(text) => {
+            let replaced = UUID_PATTERN.replace_all(text, |caps: &regex::Captures| {
+                map.get(&caps[0].to_lowercase()).cloned().unwrap_or_else(|| caps[0].to_string())
+            });
+            if let Cow::Owned(replaced) = replaced {
+                *text = replaced;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| remap_uuids(item, map)),
+        Value::Object(fields) => fields.values_mut().for_each(|field| remap_uuids(field, map)),
+        _ => {}
+    }
+}
+
+struct RestoreContext {
+    tenant_id: i32,
+    /// Old to new `id` per serial-keyed table
+    ids: HashMap<&'static str, HashMap<i64, i64>>,
+    /// Users referenced by the archive that exist here
+    users: HashSet<i64>,
+}
+
+/// Adapts an archived row to the restore target: drops the serial key, moves
+/// it to the tenant, remaps references and keeps only columns the table has.
+/// `None` if the row can't be restored because a required reference can't.
+/// Returns the old `id` alongside.
+fn prepare_row(
+    spec: &TableSpec,
+    row: &Value,
+    ctx: &RestoreContext,
+    columns: &HashMap<String, bool>,
+) -> Option<(Option<i64>, Map<String, Value>)> {
+    let mut row = row.as_object()?.clone();
+    let old_id = if spec.serial_id { row.remove("id").and_then(|id| id.as_i64()) } else { None };
+    if columns.contains_key("tenant_id") {
+        row.insert("tenant_id".to_string(), ctx.tenant_id.into());
+    }
+    let nullable = |column: &str| columns.get(column).copied().unwrap_or(true);
+
+    for (column, table) in spec.refs {
+        let Some(old) = row.get(*column).and_then(Value::as_i64) else { continue };
+        match ctx.ids.get(table).and_then(|ids| ids.get(&old)) {
+            Some(new) => {
+                row.insert(column.to_string(), (*new).into());
+            }
+            None if nullable(column) => {
+                row.insert(column.to_string(), Value::Null);
+            }
+            None => return None,
+        }
+    }
+    for column in spec.user_refs {
+        let Some(user_id) = row.get(*column).and_then(Value::as_i64) else { continue };
+        if !ctx.users.contains(&user_id) {
+            if !nullable(column) {
+                return None;
+            }
+            row.insert(column.to_string(), Value::Null);
+        }
+    }
+
+    row.retain(|column, _| columns.contains_key(column));
+    Some((old_id, row))
+}
+
+/// Inserts a prepared row; the new `id` (0 for tables without a serial key),
+/// or `None` if it conflicts with an existing row
+fn insert_row(spec: &TableSpec, row: Map<String, Value>, conn: &mut PgConnection) -> QueryResult<Option<i64>> {
+    let columns = row.keys().map(|column| quote_ident(column)).collect::<Vec<_>>().join(", ");
+    let returning = if spec.serial_id { "id::BIGINT" } else { "0::BIGINT" };
+    let inserted = sql_query(format!(
+        "INSERT INTO {table} ({columns}) SELECT {columns} FROM jsonb_populate_record(NULL::{table}, $1) \
+         ON CONFLICT DO NOTHING RETURNING {returning} AS id",
+        table = spec.name,
+        columns = columns,
+        returning = returning,
+    ))
+    .bind::<Jsonb, _>(Value::Object(row))
+    .load::<IdRow>(conn)?;
+    Ok(inserted.first().map(|row| row.id))
+}
+
+fn existing_users(tables: &[(&TableSpec, Vec<Value>)], conn: &mut PgConnection) -> QueryResult<HashSet<i64>> {
+    let referenced: Vec<i64> = tables
+        .iter()
+        .flat_map(|(spec, rows)| {
+            rows.iter()
+                .flat_map(move |row| spec.user_refs.iter().filter_map(move |column| row.get(*column)?.as_i64()))
+        })
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if referenced.is_empty() {
+        return Ok(HashSet::new());
+    }
+    Ok(sql_query("SELECT id::BIGINT AS id FROM users WHERE id = ANY($1)")
+        .bind::<Array<BigInt>, _>(referenced)
+        .load::<IdRow>(conn)?
+        .into_iter()
+        .map(|row| row.id)
+        .collect())
+}
+
+/// Tables outside the archive that reference pages without `ON DELETE`, by
+/// referencing column. Their rows are derived from the pages and go with them.
+const PAGE_DEPENDENTS: &[(&str, &str)] = &[("content_embeddings", "page_uuid"), ("ai_generated_content", "page_id")];
+
+/// Deletes a tenant's rows in the archived tables; returns the file paths of its media
+fn clear_tenant(tenant_id: i32, conn: &mut PgConnection) -> QueryResult<Vec<String>> {
+    for (table, column) in PAGE_DEPENDENTS {
+        if table_exists(table, conn)? {
+            sql_query(format!("DELETE FROM {} WHERE {} IN (SELECT uuid FROM pages WHERE tenant_id = $1)", table, column))
+                .bind::<Integer, _>(tenant_id)
+                .execute(conn)?;
+        }
+    }
+    let mut files = Vec::new();
+    for spec in TABLES.iter().filter(|spec| spec.files) {
+        if table_exists(spec.name, conn)? {
+            let paths = sql_query(format!("SELECT file_path AS value FROM {} WHERE {}", spec.name, spec.scope))
+                .bind::<Integer, _>(tenant_id)
+                .load::<TextRow>(conn)?;
+            files.extend(paths.into_iter().map(|row| row.value));
+        }
+    }
+    for spec in TABLES.iter().rev() {
+        if table_exists(spec.name, conn)? {
+            sql_query(format!("DELETE FROM {} WHERE {}", spec.name, spec.scope))
+                .bind::<Integer, _>(tenant_id)
+                .execute(conn)?;
+        }
+    }
+    Ok(files)
+}
+
+fn create_tenant(
+    target: &NewTenantTarget,
+    owner_id: i32,
+    snapshot: &TenantSnapshot,
+    conn: &mut PgConnection,
+) -> Result<i32, CustomHttpError> {
+    let subdomain = target.subdomain.trim().to_lowercase();
+    if subdomain.is_empty() {
+        return Err(CustomHttpError::BadRequest("Subdomain is required".to_string()));
+    }
+    let taken = diesel::select(diesel::dsl::exists(tenants::table.filter(tenants::subdomain.eq(&subdomain))))
+        .get_result::<bool>(conn)?;
+    if taken {
+        return Err(CustomHttpError::BadRequest(format!("Subdomain {} is taken", subdomain)));
+    }
+
+    diesel::insert_into(tenants::table)
+        .values(&NewTenant {
+            uuid: Uuid::new_v4().to_string(),
+            name: target.name.clone().unwrap_or_else(|| snapshot.name.clone()),
+            subdomain: subdomain.clone(),
+            custom_domain: None,
+            plan: snapshot.plan.clone(),
+            is_active: Some(true),
+            settings: snapshot.settings.clone(),
+        })
+        .execute(conn)?;
+    let tenant = tenants::table.filter(tenants::subdomain.eq(&subdomain)).first::<Tenant>(conn)?;
+    diesel::insert_into(tenant_members::table)
+        .values(&NewTenantMember {
+            tenant_id: tenant.id,
+            user_id: owner_id,
+            role: "owner".to_string(),
+            status: "active".to_string(),
+        })
+        .execute(conn)?;
+    Ok(tenant.id)
+}
+
+/// Restores a completed tenant backup. Media files are written to storage
+/// first, never over an existing file, and the rows in one transaction; if
+/// that fails, the files are removed again and nothing changes. Afterwards
+/// the tenant's cached pages are purged and its pages queued for re-embedding.
+pub async fn restore_backup(
+    backup: &Backup,
+    target: RestoreTarget,
+    storage: &StorageBackend,
+    cache: &CacheServiceV2,
+    conn: &mut PgConnection,
+) -> Result<RestoreReport, CustomHttpError> {
+    if backup.type_ != BACKUP_TYPE || backup.status.as_deref() != Some("completed") {
+        return Err(CustomHttpError::BadRequest("Only completed tenant backups can be restored".to_string()));
+    }
+    let file_path = backup
+        .file_path
+        .as_deref()
+        .ok_or(CustomHttpError::BadRequest("Backup has no archive".to_string()))?;
+
+    let archive_path = Path::new(file_path);
+    if !archive_path.is_file() {
+        return Err(CustomHttpError::NotFound("Backup archive is missing".to_string()));
+    }
+
+    let report = restore_archive(backup, archive_path, target, storage, conn).await?;
+    if let Err(e) = cache.purge_tags(&[tenant_tag(report.tenant_id)]).await {
+        log::warn!("Restore of backup {}: failed to purge the tenant cache: {}", backup.uuid, e);
+    }
+    let page_uuids = pages::table
+        .filter(pages::tenant_id.eq(report.tenant_id))
+        .select(pages::uuid)
+        .load::<String>(conn)?;
+    page_uuids.iter().for_each(|uuid| embedding_refresh_service::enqueue_page(uuid));
+    Ok(report)
+}
+
+async fn restore_archive(
+    backup: &Backup,
+    archive_path: &Path,
+    target: RestoreTarget,
+    storage: &StorageBackend,
+    conn: &mut PgConnection,
+) -> Result<RestoreReport, CustomHttpError> {
+    let internal = |e: String| CustomHttpError::InternalServerError(e);
+    if let Some(expected) = &backup.checksum {
+        if sha256_file(archive_path).map_err(|e| internal(e.to_string()))? != *expected {
+            return Err(CustomHttpError::BadRequest("Backup archive is corrupt: checksum mismatch".to_string()));
+        }
+    }
+
+    let file = std::fs::File::open(archive_path).map_err(|e| internal(e.to_string()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| CustomHttpError::BadRequest(format!("Invalid backup archive: {}", e)))?;
+    let manifest = read_manifest(&mut archive)?;
+
+    let mut tables: Vec<(&TableSpec, Vec<Value>)> = Vec::new();
+    for spec in TABLES.iter().filter(|spec| manifest.tables.contains_key(spec.name)) {
+        let data = copy_entry(&mut archive, &manifest, &table_entry(spec.name), Vec::new())?;
+        let rows = serde_json::from_slice::<Vec<Value>>(&data)
+            .map_err(|e| CustomHttpError::BadRequest(format!("Invalid {} in archive: {}", spec.name, e)))?;
+        tables.push((spec, rows));
+    }
+
+    // Rows keep their UUIDs only when they replace themselves
+    let keep_uuids = matches!(
+        target,
+        RestoreTarget::Existing { tenant_id, replace_existing: true } if tenant_id == manifest.tenant.id
+    );
+    let uuid_map = if keep_uuids { HashMap::new() } else { new_uuid_map(&tables) };
+    // Archive names of the files, taken before their paths are remapped
+    let archived_names: Vec<Vec<Option<String>>> = tables
+        .iter()
+        .map(|(spec, rows)| {
+            rows.iter()
+                .map(|row| {
+                    let path = row.get("file_path").and_then(Value::as_str).filter(|_| spec.files)?;
+                    Some(storage_filename(path).to_string())
+                })
+                .collect()
+        })
+        .collect();
+    if !uuid_map.is_empty() {
+        for (_, rows) in tables.iter_mut() {
+            rows.iter_mut().for_each(|row| remap_uuids(row, &uuid_map));
+        }
+    }
+
+    let mut stored_files: HashMap<String, String> = HashMap::new();
+    let result = restore_files(&mut tables, &archived_names, &manifest, &mut archive, storage, &mut stored_files)
+        .await
+        .and_then(|_| restore_rows(&tables, &target, &manifest, conn));
+    let (mut report, old_files) = match result {
+        Ok(restored) => restored,
+        Err(e) => {
+            for path in stored_files.values() {
+                let _ = storage.delete(path).await;
+            }
+            return Err(e);
+        }
+    };
+
+    // Files of replaced media; restored files never share their names
+    let written: HashSet<&str> = stored_files.values().map(String::as_str).collect();
+    for path in old_files {
+        if !written.contains(path.as_str()) {
+            let _ = storage.delete(&path).await;
+        }
+    }
+    report.files = stored_files.len();
+    Ok(report)
+}
+
+/// Storage name for a restored file whose name is taken, e.g. by the live
+/// media a same-tenant restore replaces
+fn fresh_filename(name: &str) -> String {
+    let suffix = &Uuid::new_v4().simple().to_string()[..8];
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}-{}.{}", stem, suffix, ext),
+        _ => format!("{}-{}", name, suffix),
+    }
+}
+
+/// Writes archived media files to storage under their (remapped) names and
+/// points the rows at them. Existing files are left alone: a taken name gets
+/// a fresh one, so a failed restore only removes files it created.
+async fn restore_files<R: Read + Seek>(
+    tables: &mut [(&TableSpec, Vec<Value>)],
+    archived_names: &[Vec<Option<String>>],
+    manifest: &Manifest,
+    archive: &mut zip::ZipArchive<R>,
+    storage: &StorageBackend,
+    stored_files: &mut HashMap<String, String>,
+) -> Result<(), CustomHttpError> {
+    for ((spec, rows), names) in tables.iter_mut().zip(archived_names) {
+        if !spec.files {
+            continue;
+        }
+        for (row, archived_name) in rows.iter_mut().zip(names) {
+            let Some(archived_name) = archived_name else { continue };
+            let entry = file_entry(archived_name);
+            if !manifest.entries.contains_key(&entry) {
+                continue; // Missing when the backup was taken
+            }
+            let Some(fields) = row.as_object_mut() else { continue };
+            let name = fields
+                .get("file_path")
+                .and_then(Value::as_str)
+                .map(|path| storage_filename(path).to_string())
+                .unwrap_or_else(|| archived_name.clone());
+
+            if !stored_files.contains_key(&name) {
+                let mime_type = match fields.get("mime_type").and_then(Value::as_str) {
+                    Some(mime) => mime.to_string(),
+                    None => fields
+                        .get("format")
+                        .and_then(Value::as_str)
+                        .and_then(OutputFormat::parse)
+                        .map(|format| format.mime_type())
+                        .unwrap_or("application/octet-stream")
+                        .to_string(),
+                };
+                let temp = std::env::temp_dir().join(format!("tenant-restore-file-{}", Uuid::new_v4()));
+                let extracted = std::fs::File::create(&temp)
+                    .map_err(|e| CustomHttpError::InternalServerError(e.to_string()))
+                    .and_then(|file| copy_entry(archive, manifest, &entry, file).map(|_| ()));
+                let saved = match extracted {
+                    Ok(()) => match storage.head(&storage.stored_path(&name)).await {
+                        Ok(existing) => {
+                            let stored_name = if existing.is_some() { fresh_filename(&name) } else { name.clone() };
+                            storage.save_file(&stored_name, &temp, &mime_type).await.map_err(CustomHttpError::InternalServerError)
+                        }
+                        Err(e) => Err(CustomHttpError::InternalServerError(e)),
+                    },
+                    Err(e) => Err(e),
+                };
+                let _ = std::fs::remove_file(&temp);
+                stored_files.insert(name.clone(), saved?);
+            }
+            let stored = stored_files[&name].clone();
+            if fields.get("filename").and_then(Value::as_str) == Some(name.as_str()) {
+                fields.insert("filename".to_string(), Value::String(storage_filename(&stored).to_string()));
+            }
+            fields.insert("file_path".to_string(), Value::String(stored));
+        }
+    }
+    Ok(())
+}
+
+fn restore_rows(
+    tables: &[(&TableSpec, Vec<Value>)],
+    target: &RestoreTarget,
+    manifest: &Manifest,
+    conn: &mut PgConnection,
+) -> Result<(RestoreReport, Vec<String>), CustomHttpError> {
+    conn.transaction::<_, CustomHttpError, _>(|conn| {
+        let (tenant_id, old_files) = match target {
+            RestoreTarget::Existing { tenant_id, replace_existing } => {
+                let old_files = if *replace_existing { clear_tenant(*tenant_id, conn)? } else { Vec::new() };
+                (*tenant_id, old_files)
+            }
+            RestoreTarget::New { target, owner_id } => (create_tenant(target, *owner_id, &manifest.tenant, conn)?, Vec::new()),
+        };
+
+        let mut report = RestoreReport { tenant_id, ..Default::default() };
+        let mut ctx = RestoreContext { tenant_id, ids: HashMap::new(), users: existing_users(tables, conn)? };
+        for (spec, rows) in tables {
+            if !table_exists(spec.name, conn)? {
+                report.missing_tables.push(spec.name.to_string());
+                continue;
+            }
+            let columns = table_columns(spec.name, conn)?;
+            let (mut restored, mut skipped) = (0, 0);
+            for row in rows {
+                let Some((old_id, prepared)) = prepare_row(spec, row, &ctx, &columns) else {
+                    skipped += 1;
+                    continue;
+                };
+                match insert_row(spec, prepared, conn)? {
+                    Some(new_id) => {
+                        restored += 1;
+                        if let Some(old_id) = old_id {
+                            ctx.ids.entry(spec.name).or_default().insert(old_id, new_id);
+                        }
+                    }
+                    None => skipped += 1,
+                }
+            }
+            report.restored.insert(spec.name.to_string(), restored);
+            if skipped > 0 {
+                report.skipped.insert(spec.name.to_string(), skipped);
+            }
+        }
+        Ok((report, old_files))
+    })
+}
+
+pub fn policy_for(tenant_id: i32, conn: &mut PgConnection) -> QueryResult<Option<BackupPolicy>> {
+    backup_policies::table
+        .filter(backup_policies::tenant_id.eq(tenant_id))
+        .first::<BackupPolicy>(conn)
+        .optional()
+}
+
+pub fn validate_policy(policy: &NewBackupPolicy) -> Result<(), String> {
+    if !(1..=24 * 30).contains(&policy.interval_hours) {
+        return Err("interval_hours must be between 1 and 720".to_string());
+    }
+    if !(1..=365).contains(&policy.keep_last) {
+        return Err("keep_last must be between 1 and 365".to_string());
+    }
+    if let Some(days) = policy.keep_days {
+        if !(1..=3650).contains(&days) {
+            return Err("keep_days must be between 1 and 3650".to_string());
+        }
+    }
+    Ok(())
+}
+
+pub fn save_policy(policy: &NewBackupPolicy, conn: &mut PgConnection) -> Result<BackupPolicy, CustomHttpError> {
+    validate_policy(policy).map_err(CustomHttpError::BadRequest)?;
+    Ok(diesel::insert_into(backup_policies::table)
+        .values(policy)
+        .on_conflict(backup_policies::tenant_id)
+        .do_update()
+        .set((policy, backup_policies::updated_at.eq(chrono::Utc::now().naive_utc())))
+        .get_result::<BackupPolicy>(conn)?)
+}
+
+pub fn is_due(policy: &BackupPolicy, now: NaiveDateTime) -> bool {
+    policy.is_active
+        && policy
+            .last_run_at
+            .map(|last| last + chrono::Duration::hours(policy.interval_hours as i64) <= now)
+            .unwrap_or(true)
+}
+
+/// IDs of the backups a policy no longer keeps. The newest `keep_last`
+/// completed backups always stay; older ones go once they pass `keep_days`,
+/// or straight away without it. Failed backups go once a later backup
+/// completes. `backups` are newest first.
+pub fn expired_backups(backups: &[Backup], policy: &BackupPolicy, now: NaiveDateTime) -> Vec<i32> {
+    let cutoff = policy.keep_days.map(|days| now - chrono::Duration::days(days as i64));
+    let mut completed = 0;
+    let mut seen_completed = false;
+    let mut expired = Vec::new();
+    for backup in backups {
+        match backup.status.as_deref() {
+            Some("completed") => {
+                completed += 1;
+                seen_completed = true;
+                let beyond_keep_last = completed > policy.keep_last;
+                let old_enough = match (cutoff, backup.created_at) {
+                    (Some(cutoff), Some(created_at)) => created_at < cutoff,
+                    _ => true,
+                };
+                if beyond_keep_last && old_enough {
+                    expired.push(backup.id);
+                }
+            }
+            Some("failed") if seen_completed => expired.push(backup.id),
+            _ => {}
+        }
+    }
+    expired
+}
+
+/// Deletes the tenant's backups its policy no longer keeps
+pub fn apply_retention(policy: &BackupPolicy, conn: &mut PgConnection) -> Result<usize, CustomHttpError> {
+    let backups = list_backups(policy.tenant_id, conn)?;
+    let expired = expired_backups(&backups, policy, chrono::Utc::now().naive_utc());
+    for backup in backups.iter().filter(|b| expired.contains(&b.id)) {
+        delete_backup(backup, conn)?;
+    }
+    Ok(expired.len())
+}
+
+/// Takes the backups of due policies and prunes old ones, every few minutes
+pub async fn run_scheduler(pool: DatabasePool, storage: StorageBackend) {
+    loop {
+        tokio::time::sleep(SCHEDULER_INTERVAL).await;
+        if let Err(e) = run_due_policies(&pool, &storage).await {
+            log::error!("Scheduled backups failed: {}", e);
+        }
+    }
+}
+
+async fn run_due_policies(pool: &DatabasePool, storage: &StorageBackend) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().naive_utc();
+    let due: Vec<BackupPolicy> = backup_policies::table
+        .filter(backup_policies::is_active.eq(true))
+        .load::<BackupPolicy>(&mut conn)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|policy| is_due(policy, now))
+        .collect();
+
+    for policy in due {
+        // Marked first, so a failing backup waits for the next interval
+        diesel::update(backup_policies::table.find(policy.id))
+            .set(backup_policies::last_run_at.eq(now))
+            .execute(&mut conn)
+            .map_err(|e| e.to_string())?;
+        match create_backup(policy.tenant_id, storage, &mut conn).await {
+            Ok(backup) => log::info!("Scheduled backup {} of tenant {} completed", backup.uuid, policy.tenant_id),
+            Err(e) => log::warn!("Scheduled backup of tenant {} failed: {}", policy.tenant_id, e),
+        }
+        match apply_retention(&policy, &mut conn) {
+            Ok(0) => {}
+            Ok(deleted) => log::info!("Deleted {} expired backups of tenant {}", deleted, policy.tenant_id),
+            Err(e) => log::warn!("Backup retention for tenant {} failed: {}", policy.tenant_id, e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn spec(name: &str) -> &'static TableSpec {
+        TABLES.iter().find(|spec| spec.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_tables_follow_their_references() {
+        for (index, spec) in TABLES.iter().enumerate() {
+            for (_, table) in spec.refs {
+                let referenced = TABLES.iter().position(|s| s.name == *table).unwrap();
+                assert!(referenced <= index, "{} is restored before {}", spec.name, table);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fresh_filename_keeps_extension() {
+        let fresh = fresh_filename("abc.webp");
+        assert!(fresh.starts_with("abc-") && fresh.ends_with(".webp"));
+        assert_ne!(fresh, fresh_filename("abc.webp"));
+        assert!(fresh_filename("noext").starts_with("noext-"));
+    }
+
+    #[test]
+    fn test_prepare_row_remaps_ids() {
+        let ctx = RestoreContext {
+            tenant_id: 9,
+            ids: HashMap::from([("media_folders", HashMap::from([(3, 30)]))]),
+            users: HashSet::from([1]),
+        };
+        let columns: HashMap<String, bool> = ["id", "uuid", "tenant_id", "folder_id", "uploaded_by", "file_path"]
+            .iter()
+            .map(|c| (c.to_string(), true))
+            .collect();
+        let row = json!({"id": 5, "uuid": "u", "tenant_id": 1, "folder_id": 3, "uploaded_by": 2, "legacy": "x"});
+
+        let (old_id, prepared) = prepare_row(spec("media"), &row, &ctx, &columns).unwrap();
+        assert_eq!(old_id, Some(5));
+        assert_eq!(
+            Value::Object(prepared),
+            json!({"uuid": "u", "tenant_id": 9, "folder_id": 30, "uploaded_by": null})
+        );
+
+        // A required reference that wasn't restored drops the row
+        let columns: HashMap<String, bool> =
+            [("media_id".to_string(), false), ("variant_name".to_string(), false)].into();
+        assert!(prepare_row(spec("media_variants"), &json!({"id": 1, "media_id": 77}), &ctx, &columns).is_none());
+    }
+
+    #[test]
+    fn test_remap_uuids() {
+        let old = "6ba7b810-9dad-11d1-80b4-00c04fd430c8";
+        let map = HashMap::from([(old.to_string(), "new-uuid".to_string())]);
+        let mut row = json!({
+            "file_path": format!("uploads/{}-768w.webp", old),
+            "content": format!("<img src=\"/media/{}?w=1\">", old.to_uppercase()),
+            "tags": ["keep"],
+        });
+        remap_uuids(&mut row, &map);
+        assert_eq!(row["file_path"], "uploads/new-uuid-768w.webp");
+        assert_eq!(row["content"], "<img src=\"/media/new-uuid?w=1\">");
+    }
+
+    #[test]
+    fn test_expired_backups() {
+        let now = chrono::Utc::now().naive_utc();
+        let backup = |id: i32, status: &str, days_old: i64| Backup {
+            id,
+            uuid: id.to_string(),
+            type_: BACKUP_TYPE.to_string(),
+            status: Some(status.to_string()),
+            file_path: None,
+            file_size: None,
+            storage_location: None,
+            metadata: None,
+            created_at: Some(now - chrono::Duration::days(days_old)),
+            completed_at: None,
+            tenant_id: Some(1),
+            checksum: None,
+        };
+        let backups = vec![
+            backup(6, "failed", 0),
+            backup(5, "completed", 1),
+            backup(4, "failed", 2),
+            backup(3, "completed", 3),
+            backup(2, "completed", 10),
+            backup(1, "completed", 40),
+        ];
+        let mut policy = BackupPolicy {
+            id: 1,
+            tenant_id: 1,
+            interval_hours: 24,
+            keep_last: 2,
+            keep_days: Some(30),
+            is_active: true,
+            last_run_at: None,
+            created_at: now,
+            updated_at: now,
+        };
+        assert_eq!(expired_backups(&backups, &policy, now), vec![4, 1]);
+
+        policy.keep_days = None;
+        assert_eq!(expired_backups(&backups, &policy, now), vec![4, 2, 1]);
+
+        assert!(is_due(&policy, now));
+        policy.last_run_at = Some(now - chrono::Duration::hours(23));
+        assert!(!is_due(&policy, now));
+    }
+}
@@ -0,0 +1,374 @@
+// Theme Package Service
+// Unpacks uploaded theme archives into isolated directories and validates their manifest
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+use crate::services::template_service::{compile_liquid_dir, TemplateEngine};
+
+/// Unpacked themes live in `{THEMES_DIR}/{package id}`
+pub const THEMES_DIR: &str = "./uploads/themes";
+
+/// Manifest file at the root of every theme package
+pub const MANIFEST_FILE: &str = "theme.json";
+
+/// Directory inside a package holding templates and partials
+pub const TEMPLATES_SUBDIR: &str = "templates";
+
+/// Largest archive accepted for upload
+pub const MAX_ARCHIVE_BYTES: usize = 20 * 1024 * 1024;
+
+/// Limits against zip bombs
+const MAX_ENTRIES: usize = 2_000;
+const MAX_UNPACKED_BYTES: u64 = 50 * 1024 * 1024;
+
+const TEMPLATE_EXTENSIONS: &[&str] = &["hbs", "liquid"];
+const ASSET_EXTENSIONS: &[&str] = &[
+    "css", "js", "map", "json", "txt", "png", "jpg", "jpeg", "gif", "webp", "avif", "svg", "ico",
+    "woff", "woff2", "ttf", "otf", "eot",
+];
+
+/// `theme.json`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ThemeManifest {
+    pub name: String,
+    /// Semantic version, e.g. "1.2.0"
+    pub version: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Page templates, by name relative to `templates/` without extension (e.g. "index")
+    pub templates: Vec<String>,
+    /// Partials the templates include (e.g. "layouts/base"); all must be present
+    #[serde(default)]
+    pub partials: Vec<String>,
+    /// Static asset directory, served at `/theme-assets/`
+    #[serde(default = "default_assets_dir")]
+    pub assets: String,
+    /// Engine used when a template exists as both `.hbs` and `.liquid`
+    #[serde(default)]
+    pub engine: Option<TemplateEngine>,
+}
+
+fn default_assets_dir() -> String {
+    "assets".to_string()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ThemePackageError {
+    InvalidArchive(String),
+    UnsafeEntry(String),
+    TooLarge(String),
+    InvalidManifest(String),
+    MissingFile(String),
+    InvalidTemplate(String),
+    Io(String),
+}
+
+impl std::fmt::Display for ThemePackageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThemePackageError::InvalidArchive(msg) => write!(f, "Invalid theme archive: {}", msg),
+            ThemePackageError::UnsafeEntry(name) => write!(f, "Unsafe path in theme archive: {}", name),
+            ThemePackageError::TooLarge(msg) => write!(f, "Theme archive too large: {}", msg),
+            ThemePackageError::InvalidManifest(msg) => write!(f, "Invalid theme manifest: {}", msg),
+            ThemePackageError::MissingFile(name) => write!(f, "Theme is missing {}", name),
+            ThemePackageError::InvalidTemplate(msg) => write!(f, "Invalid theme template: {}", msg),
+            ThemePackageError::Io(msg) => write!(f, "Theme I/O error: {}", msg),
+        }
+    }
+}
+
+impl From<std::io::Error> for ThemePackageError {
+    fn from(e: std::io::Error) -> Self {
+        ThemePackageError::Io(e.to_string())
+    }
+}
+
+/// An unpacked, validated theme
+#[derive(Debug, Clone)]
+pub struct InstalledTheme {
+    pub dir: PathBuf,
+    pub manifest: ThemeManifest,
+}
+
+/// Unpacks `archive` into a fresh directory under `themes_dir` and validates it.
+/// Nothing is left on disk when validation fails.
+pub fn install_package<R: Read + Seek>(archive: R, themes_dir: &Path) -> Result<InstalledTheme, ThemePackageError> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let staging = themes_dir.join(format!(".{}.tmp", id));
+    let dir = themes_dir.join(&id);
+    fs::create_dir_all(&staging)?;
+
+    let result = unpack(archive, &staging)
+        .and_then(|_| read_manifest(&staging))
+        .and_then(|manifest| validate(&staging, &manifest).map(|_| manifest));
+
+    match result {
+        Ok(manifest) => {
+            fs::rename(&staging, &dir)?;
+            Ok(InstalledTheme { dir, manifest })
+        }
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            Err(e)
+        }
+    }
+}
+
+/// Relative path of an archive entry, or `None` if it could escape the target directory
+fn safe_relative_path(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    if clean.as_os_str().is_empty() { None } else { Some(clean) }
+}
+
+fn unpack<R: Read + Seek>(archive: R, target: &Path) -> Result<(), ThemePackageError> {
+    let mut zip = zip::ZipArchive::new(archive).map_err(|e| ThemePackageError::InvalidArchive(e.to_string()))?;
+    if zip.len() > MAX_ENTRIES {
+        return Err(ThemePackageError::TooLarge(format!("{} entries (max {})", zip.len(), MAX_ENTRIES)));
+    }
+
+    // Archives are often built from a folder: accept a single wrapping directory around theme.json
+    let names: Vec<String> = zip.file_names().map(|n| n.to_string()).collect();
+    let prefix = manifest_prefix(&names)
+        .ok_or_else(|| ThemePackageError::MissingFile(MANIFEST_FILE.to_string()))?;
+
+    let mut unpacked: u64 = 0;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(|e| ThemePackageError::InvalidArchive(e.to_string()))?;
+        let name = entry.name().to_string();
+        let Some(relative) = name.strip_prefix(&prefix) else { continue };
+        if relative.is_empty() || relative.starts_with("__MACOSX") {
+            continue;
+        }
+
+        let relative = safe_relative_path(relative).ok_or_else(|| ThemePackageError::UnsafeEntry(name.clone()))?;
+        // Symlinks could point anywhere on the host
+        if entry.unix_mode().map(|mode| mode & 0o170000 == 0o120000).unwrap_or(false) {
+            return Err(ThemePackageError::UnsafeEntry(name));
+        }
+
+        let out_path = target.join(&relative);
+        if entry.is_dir() {
+            fs::create_dir_all(&out_path)?;
+            continue;
+        }
+        check_extension(&relative)?;
+
+        unpacked += entry.size();
+        if unpacked > MAX_UNPACKED_BYTES {
+            return Err(ThemePackageError::TooLarge(format!("more than {} bytes unpacked", MAX_UNPACKED_BYTES)));
+        }
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut out = fs::File::create(&out_path)?;
+        // `size()` comes from the archive header; never trust it for the actual copy
+        let copied = std::io::copy(&mut (&mut entry).take(MAX_UNPACKED_BYTES + 1), &mut out)?;
+        if copied > entry.size() {
+            return Err(ThemePackageError::InvalidArchive(format!("{} is larger than declared", name)));
+        }
+    }
+    Ok(())
+}
+
+/// Directory prefix (with trailing slash, or empty) of the shallowest `theme.json`
+fn manifest_prefix(names: &[String]) -> Option<String> {
+    names
+        .iter()
+        .filter(|name| *name == MANIFEST_FILE || name.ends_with(&format!("/{}", MANIFEST_FILE)))
+        .filter(|name| !name.starts_with("__MACOSX"))
+        .min_by_key(|name| name.matches('/').count())
+        .filter(|name| name.matches('/').count() <= 1)
+        .map(|name| name[..name.len() - MANIFEST_FILE.len()].to_string())
+}
+
+fn check_extension(path: &Path) -> Result<(), ThemePackageError> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    let is_manifest = path == Path::new(MANIFEST_FILE);
+    let is_template = path.starts_with(TEMPLATES_SUBDIR) && (TEMPLATE_EXTENSIONS.contains(&ext.as_str()) || ext == "html");
+    if is_manifest || is_template || ASSET_EXTENSIONS.contains(&ext.as_str()) {
+        Ok(())
+    } else {
+        Err(ThemePackageError::UnsafeEntry(format!("{} (file type not allowed)", path.display())))
+    }
+}
+
+pub fn read_manifest(dir: &Path) -> Result<ThemeManifest, ThemePackageError> {
+    let raw = fs::read_to_string(dir.join(MANIFEST_FILE))
+        .map_err(|_| ThemePackageError::MissingFile(MANIFEST_FILE.to_string()))?;
+    serde_json::from_str(&raw).map_err(|e| ThemePackageError::InvalidManifest(e.to_string()))
+}
+
+/// `major.minor.patch` with an optional `-pre` or `+build` suffix
+pub fn is_semver(version: &str) -> bool {
+    let core = version.split(['-', '+']).next().unwrap_or("");
+    let parts: Vec<&str> = core.split('.').collect();
+    parts.len() == 3 && parts.iter().all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Template file for `name` in an unpacked theme, whichever engine it targets
+pub fn template_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    let relative = safe_relative_path(name)?;
+    TEMPLATE_EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(TEMPLATES_SUBDIR).join(relative.with_extension(ext)))
+        .find(|path| path.is_file())
+}
+
+/// Checks the manifest against the unpacked files and compiles every template
+pub fn validate(dir: &Path, manifest: &ThemeManifest) -> Result<(), ThemePackageError> {
+    if manifest.name.trim().is_empty() {
+        return Err(ThemePackageError::InvalidManifest("name is required".to_string()));
+    }
+    if !is_semver(&manifest.version) {
+        return Err(ThemePackageError::InvalidManifest(format!("version '{}' is not semver (x.y.z)", manifest.version)));
+    }
+    if manifest.templates.is_empty() {
+        return Err(ThemePackageError::InvalidManifest("at least one template is required".to_string()));
+    }
+
+    let mut seen = HashSet::new();
+    for name in manifest.templates.iter().chain(&manifest.partials) {
+        if !seen.insert(name) {
+            return Err(ThemePackageError::InvalidManifest(format!("'{}' is listed twice", name)));
+        }
+        if template_file(dir, name).is_none() {
+            return Err(ThemePackageError::MissingFile(format!("{}/{}", TEMPLATES_SUBDIR, name)));
+        }
+    }
+
+    let assets = safe_relative_path(&manifest.assets)
+        .ok_or_else(|| ThemePackageError::InvalidManifest(format!("assets path '{}' is not allowed", manifest.assets)))?;
+    if dir.join(&assets).exists() && !dir.join(&assets).is_dir() {
+        return Err(ThemePackageError::InvalidManifest(format!("assets '{}' is not a directory", manifest.assets)));
+    }
+
+    compile_templates(&dir.join(TEMPLATES_SUBDIR))
+}
+
+fn compile_templates(templates_dir: &Path) -> Result<(), ThemePackageError> {
+    let mut hb = handlebars::Handlebars::new();
+    let mut options = handlebars::DirectorySourceOptions::default();
+    options.tpl_extension = ".hbs".to_string();
+    hb.register_templates_directory(templates_dir, options)
+        .map_err(|e| ThemePackageError::InvalidTemplate(e.to_string()))?;
+
+    compile_liquid_dir(templates_dir)
+        .map(|_| ())
+        .map_err(ThemePackageError::InvalidTemplate)
+}
+
+pub(crate) fn files_with_extension(dir: &Path, ext: &str) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut found = Vec::new();
+    if !dir.is_dir() {
+        return Ok(found);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            found.extend(files_with_extension(&path, ext)?);
+        } else if path.extension().and_then(|e| e.to_str()) == Some(ext) {
+            found.push(path);
+        }
+    }
+    Ok(found)
+}
+
+/// Resolves a request path under a theme's asset directory, refusing traversal
+pub fn asset_path(theme_dir: &Path, manifest: &ThemeManifest, request_path: &str) -> Option<PathBuf> {
+    let assets = safe_relative_path(&manifest.assets)?;
+    let relative = safe_relative_path(request_path)?;
+    let path = theme_dir.join(assets).join(relative);
+    path.is_file().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn package(files: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut zip = zip::ZipWriter::new(&mut buffer);
+            for (name, content) in files {
+                zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+                zip.write_all(content.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        buffer.set_position(0);
+        buffer
+    }
+
+    fn themes_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("theme-test-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const MANIFEST: &str = r#"{"name": "Clean", "version": "1.0.0", "templates": ["index"], "partials": ["layouts/base"]}"#;
+
+    #[test]
+    fn test_install_valid_package() {
+        let root = themes_dir("valid");
+        let archive = package(&[
+            ("clean/theme.json", MANIFEST),
+            ("clean/templates/index.hbs", "{{> layouts/base}}<h1>{{page_title}}</h1>"),
+            ("clean/templates/layouts/base.hbs", "<header></header>"),
+            ("clean/assets/style.css", "body {}"),
+        ]);
+
+        let installed = install_package(archive, &root).unwrap();
+        assert_eq!(installed.manifest.name, "Clean");
+        assert!(installed.dir.join("templates/index.hbs").is_file());
+        assert!(asset_path(&installed.dir, &installed.manifest, "style.css").is_some());
+        assert!(asset_path(&installed.dir, &installed.manifest, "../theme.json").is_none());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_missing_partial_and_cleans_up() {
+        let root = themes_dir("partial");
+        let archive = package(&[("theme.json", MANIFEST), ("templates/index.hbs", "hi")]);
+
+        let err = install_package(archive, &root).unwrap_err();
+        assert_eq!(err, ThemePackageError::MissingFile("templates/layouts/base".to_string()));
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 0);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_traversal_and_executables() {
+        let root = themes_dir("unsafe");
+        let traversal = package(&[("theme.json", MANIFEST), ("../evil.css", "x")]);
+        assert!(matches!(install_package(traversal, &root), Err(ThemePackageError::UnsafeEntry(_))));
+
+        let binary = package(&[("theme.json", MANIFEST), ("assets/run.sh", "rm -rf /")]);
+        assert!(matches!(install_package(binary, &root), Err(ThemePackageError::UnsafeEntry(_))));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_semver() {
+        assert!(is_semver("1.0.0"));
+        assert!(is_semver("2.10.3-beta.1"));
+        assert!(!is_semver("1.0"));
+        assert!(!is_semver("v1.0.0"));
+    }
+}
@@ -1,32 +1,89 @@
 extern crate notify;
 
 use actix_web::web::Data;
-use handlebars::DirectorySourceOptions;
-use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::channel;
-use std::sync::Mutex;
 use std::time::Duration;
 
-/// Watches the templates directory and refreshes the templates in memory on update.
-pub fn watch(hb: Data<Mutex<handlebars::Handlebars<'_>>>) -> notify::Result<()> {
+use crate::services::cache_service_v2::{template_tag, CacheServiceV2, ALL_PAGES_TAG};
+use crate::services::template_service::TemplateService;
+
+const TEMPLATES_DIR: &str = "./templates";
+
+/// Watches the templates directory, refreshes the templates in memory on update
+/// and purges the cached pages rendered from the changed template.
+pub fn watch(
+    templates: Data<TemplateService>,
+    cache: CacheServiceV2,
+    runtime: tokio::runtime::Handle,
+) -> notify::Result<()> {
     let (tx, rx) = channel();
 
     let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(2))?;
 
-    watcher.watch("./templates", RecursiveMode::Recursive)?;
+    watcher.watch(TEMPLATES_DIR, RecursiveMode::Recursive)?;
 
     loop {
         match rx.recv() {
-            Ok(_) => {
-                hb.lock().unwrap().clear_templates();
-                let mut options = DirectorySourceOptions::default();
-                options.tpl_extension = ".hbs".to_string();
-                hb.lock()
-                    .unwrap()
-                    .register_templates_directory("./templates", options)
-                    .unwrap();
+            Ok(event) => {
+                // A template that doesn't compile keeps the previous set live
+                if let Err(e) = templates.reload_templates(TEMPLATES_DIR) {
+                    log::error!("Failed to reload templates: {}", e);
+                    continue;
+                }
+
+                if let Some(tags) = changed_path(&event).and_then(|path| purge_tags_for(Path::new(TEMPLATES_DIR), &path)) {
+                    if let Err(e) = runtime.block_on(cache.purge_tags(&tags)) {
+                        log::warn!("Failed to purge cached pages after template change: {}", e);
+                    }
+                }
             }
             Err(e) => println!("watch error: {:?}", e),
         }
     }
 }
+
+fn changed_path(event: &DebouncedEvent) -> Option<PathBuf> {
+    match event {
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Remove(path)
+        | DebouncedEvent::Rename(_, path) => Some(path.clone()),
+        _ => None,
+    }
+}
+
+/// Cache tags to purge when `path` changes; `None` for files that are not templates.
+///
+/// Top-level templates are rendered as pages (`page_name`), so only pages using
+/// them are purged. Nested files are partials and layouts any page may include.
+fn purge_tags_for(root: &Path, path: &Path) -> Option<Vec<String>> {
+    if !matches!(path.extension().and_then(|ext| ext.to_str()), Some("hbs") | Some("liquid")) {
+        return None;
+    }
+    // notify reports absolute paths
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+    match path.strip_prefix(&root).ok() {
+        Some(relative) if relative.components().count() == 1 => {
+            let name = relative.file_stem()?.to_str()?;
+            Some(vec![template_tag(name)])
+        }
+        _ => Some(vec![ALL_PAGES_TAG.to_string()]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_purge_tags_for_template_changes() {
+        let root = Path::new("/srv/site/templates");
+        assert_eq!(purge_tags_for(root, Path::new("/srv/site/templates/about.hbs")), Some(vec!["template:about".to_string()]));
+        assert_eq!(purge_tags_for(root, Path::new("/srv/site/templates/layouts/base.hbs")), Some(vec!["pages".to_string()]));
+        assert_eq!(purge_tags_for(root, Path::new("/srv/site/templates/shop.liquid")), Some(vec!["template:shop".to_string()]));
+        assert_eq!(purge_tags_for(root, Path::new("/srv/site/templates/assets/style.css")), None);
+    }
+}